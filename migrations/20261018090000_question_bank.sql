@@ -0,0 +1,27 @@
+-- Questions become a standalone bank: they no longer belong to one section,
+-- but are linked into any number of sections.
+
+CREATE TABLE IF NOT EXISTS section_questions (
+    section_id INTEGER NOT NULL REFERENCES sections(id) ON DELETE CASCADE,
+    question_id INTEGER NOT NULL REFERENCES questions(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (section_id, question_id)
+);
+
+CREATE INDEX IF NOT EXISTS section_questions_question_id_idx
+ON section_questions (question_id);
+
+-- Keep every existing question in its section, in its current order
+INSERT INTO section_questions (section_id, question_id, position)
+SELECT section_id, id, ROW_NUMBER() OVER (PARTITION BY section_id ORDER BY id)
+FROM questions;
+
+-- Deleting a section or exam now only removes the links
+ALTER TABLE questions
+DROP CONSTRAINT IF EXISTS questions_section_id_fkey;
+
+ALTER TABLE questions
+DROP COLUMN section_id;
+
+ALTER TABLE questions
+ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now();
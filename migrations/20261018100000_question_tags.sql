@@ -0,0 +1,34 @@
+-- Hierarchical tags grouped into facets, e.g. quran: Al-Baqarah > 2:255,
+-- or topic: Fiqh > Salah. A child tag always has the facet of its parent.
+
+CREATE TABLE IF NOT EXISTS tags (
+    id SERIAL PRIMARY KEY,
+    facet TEXT NOT NULL CHECK (facet IN ('quran', 'topic', 'skill', 'difficulty', 'distractor_type')),
+    name TEXT NOT NULL,
+    parent_id INTEGER REFERENCES tags(id) ON DELETE CASCADE,
+    UNIQUE NULLS NOT DISTINCT (facet, parent_id, name)
+);
+
+CREATE INDEX IF NOT EXISTS tags_parent_id_idx ON tags (parent_id);
+
+CREATE TABLE IF NOT EXISTS question_tags (
+    question_id INTEGER NOT NULL REFERENCES questions(id) ON DELETE CASCADE,
+    tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    PRIMARY KEY (question_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS question_tags_tag_id_idx ON question_tags (tag_id);
+
+-- Facets with a fixed set of values
+INSERT INTO tags (facet, name) VALUES
+    ('difficulty', 'easy'),
+    ('difficulty', 'medium'),
+    ('difficulty', 'hard'),
+    ('distractor_type', 'collection'),
+    ('distractor_type', 'diacritic'),
+    ('distractor_type', 'phonetic'),
+    ('distractor_type', 'morphological'),
+    ('distractor_type', 'grammatical'),
+    ('distractor_type', 'alternate_verse'),
+    ('distractor_type', 'thematic'),
+    ('distractor_type', 'collocational');
@@ -0,0 +1,26 @@
+-- Diacritic-insensitive search over questions and options. Text is folded
+-- by normalize_arabic and matched with trigram indexes on the folded text.
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- Drops tashkeel, Quranic marks and tatweel, and folds the alef, ya,
+-- ta marbuta, heh and kaf variants of Arabic and Urdu to one letter each.
+-- Mirrors utils::arabic::fold_for_search.
+CREATE OR REPLACE FUNCTION normalize_arabic(input TEXT) RETURNS TEXT
+LANGUAGE sql IMMUTABLE STRICT PARALLEL SAFE
+AS $$
+    SELECT translate(
+        lower(input),
+        'أإآٱىیئؤةۃۀہھکًٌٍَُِّْٰٕٖٜٟٓٔٗ٘ٙٚٛٝٞـۖۗۘۙۚۛۜ۝۞ۣ۟۠ۡۢۤۥۦۧۨ۩۪ۭ۫۬',
+        'اااايييوهههههك'
+    )
+$$;
+
+CREATE INDEX IF NOT EXISTS questions_text_search_idx
+ON questions USING GIN (normalize_arabic(text) gin_trgm_ops);
+
+CREATE INDEX IF NOT EXISTS questions_description_search_idx
+ON questions USING GIN (normalize_arabic(description) gin_trgm_ops);
+
+CREATE INDEX IF NOT EXISTS options_text_search_idx
+ON options USING GIN (normalize_arabic(text) gin_trgm_ops);
@@ -0,0 +1,9 @@
+-- Where generated options came from. Hand-written options have no row.
+CREATE TABLE IF NOT EXISTS option_provenance (
+    option_id INTEGER PRIMARY KEY REFERENCES options(id) ON DELETE CASCADE,
+    distractor_type TEXT NOT NULL,
+    provider TEXT NOT NULL,
+    model TEXT NOT NULL,
+    prompt_version TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,15 @@
+-- Tokens spent on every LLM call, for quotas and the usage report.
+CREATE TABLE IF NOT EXISTS llm_usage (
+    id BIGSERIAL PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    exam_id INTEGER,
+    endpoint TEXT NOT NULL,
+    provider TEXT NOT NULL,
+    model TEXT NOT NULL,
+    prompt_tokens BIGINT NOT NULL,
+    completion_tokens BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS llm_usage_user_created_at_idx ON llm_usage (user_id, created_at);
+CREATE INDEX IF NOT EXISTS llm_usage_created_at_idx ON llm_usage (created_at);
@@ -0,0 +1,23 @@
+-- Versions of the prompt templates, one series per task, language and
+-- distractor type. Versions are never edited, only added.
+CREATE TABLE IF NOT EXISTS prompt_templates (
+    id SERIAL PRIMARY KEY,
+    task TEXT NOT NULL,
+    language TEXT NOT NULL,
+    distractor_type TEXT,
+    version INTEGER NOT NULL,
+    body TEXT NOT NULL,
+    note TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE NULLS NOT DISTINCT (task, language, distractor_type, version)
+);
+
+-- The version in use for each series.
+CREATE TABLE IF NOT EXISTS active_prompt_templates (
+    task TEXT NOT NULL,
+    language TEXT NOT NULL,
+    distractor_type TEXT,
+    template_id INTEGER NOT NULL REFERENCES prompt_templates(id),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE NULLS NOT DISTINCT (task, language, distractor_type)
+);
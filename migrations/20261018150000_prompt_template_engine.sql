@@ -0,0 +1,19 @@
+-- Few-shot examples a template can loop over, as a JSON array of
+-- {question, correct_answer, distractors}.
+ALTER TABLE prompt_templates ADD COLUMN IF NOT EXISTS examples JSONB NOT NULL DEFAULT '[]';
+
+-- Snippets templates include by name, e.g. shared output instructions.
+CREATE TABLE IF NOT EXISTS prompt_partials (
+    name TEXT PRIMARY KEY,
+    body TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Templates are now rendered by a template engine. Move the single-brace
+-- placeholders to its syntax, delimiting the values they stand for.
+UPDATE prompt_templates
+SET body = replace(
+    replace(body, '{question}', '<question>{{ question }}</question>'),
+    '{correct_answer}', '<correct_answer>{{ correct_answer }}</correct_answer>'
+)
+WHERE body LIKE '%{question}%' OR body LIKE '%{correct_answer}%';
@@ -0,0 +1,60 @@
+-- A/B experiments between versions of one prompt template series. At most
+-- one experiment per series runs at a time.
+CREATE TABLE IF NOT EXISTS prompt_experiments (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    task TEXT NOT NULL,
+    language TEXT NOT NULL,
+    distractor_type TEXT,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    stopped_at TIMESTAMPTZ
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS prompt_experiments_running
+    ON prompt_experiments (task, language, distractor_type) NULLS NOT DISTINCT
+    WHERE active;
+
+CREATE TABLE IF NOT EXISTS prompt_experiment_variants (
+    id SERIAL PRIMARY KEY,
+    experiment_id INTEGER NOT NULL REFERENCES prompt_experiments(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    template_id INTEGER NOT NULL REFERENCES prompt_templates(id),
+    weight INTEGER NOT NULL CHECK (weight > 0),
+    UNIQUE (experiment_id, name)
+);
+
+-- Generations made with an experiment variant, with how their output fared.
+CREATE TABLE IF NOT EXISTS prompt_generations (
+    id UUID PRIMARY KEY,
+    variant_id INTEGER NOT NULL REFERENCES prompt_experiment_variants(id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL,
+    endpoint TEXT NOT NULL,
+    -- Whether every candidate parsed without a repair round.
+    parsed BOOLEAN NOT NULL,
+    -- Distractors the model gave, and those that passed the quality gate.
+    distractors INTEGER NOT NULL,
+    passed INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS prompt_generations_variant ON prompt_generations (variant_id);
+
+-- An author's verdict on a generated distractor.
+CREATE TABLE IF NOT EXISTS generation_feedback (
+    generation_id UUID NOT NULL REFERENCES prompt_generations(id) ON DELETE CASCADE,
+    distractor TEXT NOT NULL,
+    accepted BOOLEAN NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (generation_id, distractor)
+);
+
+-- How often students picked a generated distractor, out of the responses
+-- to the question it was shown in.
+CREATE TABLE IF NOT EXISTS generation_selections (
+    generation_id UUID NOT NULL REFERENCES prompt_generations(id) ON DELETE CASCADE,
+    distractor TEXT NOT NULL,
+    responses INTEGER NOT NULL DEFAULT 0,
+    selections INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (generation_id, distractor)
+);
@@ -0,0 +1,14 @@
+-- Rows inserted with explicit IDs do not advance their table's sequence.
+-- Move each sequence whose keys are reserved ahead of inserts past the
+-- highest ID in use, so reserving never hands out a taken one.
+DO $$
+DECLARE
+    name TEXT;
+BEGIN
+    FOREACH name IN ARRAY ARRAY['exams', 'exam_descriptions', 'sections', 'questions', 'options'] LOOP
+        EXECUTE format(
+            'SELECT setval(%L, GREATEST((SELECT COALESCE(MAX(id), 0) FROM %I), (SELECT last_value FROM %I)))',
+            name || '_id_seq', name, name || '_id_seq'
+        );
+    END LOOP;
+END $$;
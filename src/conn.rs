@@ -31,12 +31,22 @@ impl RedisClient {
         let redis_url = utils::env::load_env_var("REDIS_URL")
             .context("Failed to load REDIS_URL environment variable")?;
 
+        let client = Self::from_url(&redis_url)?;
+
+        info!("Successfully connected to Redis");
+        Ok(client)
+    }
+
+    /// Creates a new instance of `RedisClient` from an explicit Redis URL.
+    ///
+    /// Useful when the URL does not come from the environment, e.g. a local
+    /// stand-in server in tests.
+    pub fn from_url(redis_url: &str) -> Result<Self> {
         let cfg = Config::from_url(redis_url);
         let pool = cfg
             .create_pool(Some(Runtime::Tokio1))
             .context("Failed to create Redis connection pool")?;
 
-        info!("Successfully connected to Redis");
         Ok(Self { pool })
     }
 
@@ -64,7 +74,7 @@ impl RedisClient {
 ///
 /// # Example
 ///
-/// ```rust
+/// ```ignore
 /// let db_client = DbClient::new().await?;
 /// ```
 #[derive(Clone)]
@@ -87,7 +97,7 @@ impl DbClient {
     ///
     /// # Example
     ///
-    /// ```rust
+    /// ```ignore
     /// let db_client = DbClient::new().await?;
     /// ```
     pub async fn new() -> Result<Self> {
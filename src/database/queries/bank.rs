@@ -0,0 +1,471 @@
+use crate::database::queries::ids::{reserve_ids, IdTable};
+use crate::database::queries::insert::{insert_options, insert_questions, link_questions};
+use crate::database::queries::options::insert_provenance;
+use crate::database::queries::tags;
+use crate::model::bank::{
+    BankOption, BankQuestionRequest, BankQuestionResponse, BankQuestionSummary, BankSearchResponse,
+    QuestionUsage,
+};
+use crate::model::generation::OptionProvenance;
+use anyhow::{Context, Result};
+use sqlx::PgConnection;
+
+/// Inserts the options of a bank question with freshly reserved IDs.
+async fn insert_bank_options(
+    tx: &mut PgConnection,
+    question_id: i32,
+    options: &[BankOption],
+) -> Result<()> {
+    let option_ids = reserve_ids(&mut *tx, IdTable::Options, options.len()).await?;
+    let question_ids = vec![question_id; options.len()];
+    let texts: Vec<String> = options.iter().map(|o| o.text.clone()).collect();
+    let correct_flags: Vec<bool> = options.iter().map(|o| o.is_correct).collect();
+
+    insert_options(&mut *tx, &option_ids, &question_ids, &texts, &correct_flags).await?;
+
+    let provenance: Vec<_> = option_ids
+        .iter()
+        .zip(options)
+        .filter_map(|(id, option)| option.provenance.as_ref().map(|p| (*id, p)))
+        .collect();
+    insert_provenance(&mut *tx, &provenance).await
+}
+
+/// Creates a question in the bank, not linked to any exam, and returns its ID.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let id = create_question(&pool, &request).await?;
+/// ```
+pub async fn create_question(pool: &sqlx::PgPool, question: &BankQuestionRequest) -> Result<i32> {
+    let mut tx = pool
+        .begin()
+        .await
+        .context("Failed to start DB transaction")?;
+
+    let id = reserve_ids(&mut tx, IdTable::Questions, 1).await?[0];
+    insert_questions(
+        &mut tx,
+        &[id],
+        std::slice::from_ref(&question.text),
+        &[question.description.clone().unwrap_or_default()],
+        &[question.marks],
+    )
+    .await?;
+    insert_bank_options(&mut tx, id, &question.options).await?;
+
+    tx.commit().await.context("Failed to commit transaction")?;
+
+    Ok(id)
+}
+
+/// Lists the exam sections a question is linked into.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let used_in = question_usage(&pool, 10).await?;
+/// ```
+pub async fn question_usage(pool: &sqlx::PgPool, question_id: i32) -> Result<Vec<QuestionUsage>> {
+    sqlx::query_as!(
+        QuestionUsage,
+        r#"
+        SELECT
+            d.exam_id,
+            d.title AS exam_title,
+            s.id AS section_id,
+            s.title AS section_title
+        FROM section_questions sq
+        JOIN sections s ON sq.section_id = s.id
+        JOIN exam_descriptions d ON s.exam_description_id = d.id
+        WHERE sq.question_id = $1
+        ORDER BY d.exam_id, s.id
+        "#,
+        question_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch question usage")
+}
+
+/// Reads a bank question with its options and the exams that use it, or
+/// `None` if it does not exist.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// if let Some(question) = read_question(&pool, 10).await? {
+///     println!("Used in {} sections", question.used_in.len());
+/// }
+/// ```
+pub async fn read_question(
+    pool: &sqlx::PgPool,
+    question_id: i32,
+) -> Result<Option<BankQuestionResponse>> {
+    let Some(question) = sqlx::query!(
+        r#"
+        SELECT id, text, description, marks, created_at
+        FROM questions
+        WHERE id = $1
+        "#,
+        question_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch question")?
+    else {
+        return Ok(None);
+    };
+
+    let options = sqlx::query!(
+        r#"
+        SELECT
+            o.id,
+            o.text,
+            COALESCE(o.is_correct, false) AS "is_correct!",
+            p.distractor_type AS "distractor_type?",
+            p.provider AS "provider?",
+            p.model AS "model?",
+            p.prompt_version AS "prompt_version?"
+        FROM options o
+        LEFT JOIN option_provenance p ON p.option_id = o.id
+        WHERE o.question_id = $1
+        ORDER BY o.id
+        "#,
+        question_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch question options")?
+    .into_iter()
+    .map(|row| BankOption {
+        id: row.id,
+        text: row.text,
+        is_correct: row.is_correct,
+        provenance: match (
+            row.distractor_type,
+            row.provider,
+            row.model,
+            row.prompt_version,
+        ) {
+            (Some(distractor_type), Some(provider), Some(model), Some(prompt_version)) => {
+                Some(OptionProvenance {
+                    distractor_type,
+                    provider,
+                    model,
+                    prompt_version,
+                })
+            }
+            _ => None,
+        },
+    })
+    .collect();
+
+    let tags = tags::question_tags(pool, question_id).await?;
+    let used_in = question_usage(pool, question_id).await?;
+
+    Ok(Some(BankQuestionResponse {
+        id: question.id,
+        text: question.text,
+        description: question.description,
+        marks: question.marks,
+        created_at: question.created_at,
+        options,
+        tags,
+        used_in,
+    }))
+}
+
+/// Replaces the text, description, marks and options of a bank question.
+/// Every exam that uses the question sees the change.
+///
+/// Returns `false` if the question does not exist.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let updated = update_question(&pool, 10, &request).await?;
+/// ```
+pub async fn update_question(
+    pool: &sqlx::PgPool,
+    question_id: i32,
+    question: &BankQuestionRequest,
+) -> Result<bool> {
+    let mut tx = pool
+        .begin()
+        .await
+        .context("Failed to start DB transaction")?;
+
+    let updated = sqlx::query!(
+        r#"
+        UPDATE questions
+        SET text = $2, description = $3, marks = $4
+        WHERE id = $1
+        "#,
+        question_id,
+        question.text,
+        question.description.clone().unwrap_or_default(),
+        question.marks
+    )
+    .execute(&mut *tx)
+    .await
+    .context("Failed to update question")?
+    .rows_affected();
+
+    if updated == 0 {
+        return Ok(false);
+    }
+
+    sqlx::query!(
+        r#"
+        DELETE FROM options
+        WHERE question_id = $1
+        "#,
+        question_id
+    )
+    .execute(&mut *tx)
+    .await
+    .context("Failed to delete old options")?;
+
+    insert_bank_options(&mut tx, question_id, &question.options).await?;
+
+    tx.commit().await.context("Failed to commit transaction")?;
+
+    Ok(true)
+}
+
+/// Deletes a bank question with its options, removing it from every exam
+/// that uses it. Returns `false` if the question does not exist.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let deleted = delete_question(&pool, 10).await?;
+/// ```
+pub async fn delete_question(pool: &sqlx::PgPool, question_id: i32) -> Result<bool> {
+    let deleted = sqlx::query!(
+        r#"
+        DELETE FROM questions
+        WHERE id = $1
+        "#,
+        question_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to delete question")?
+    .rows_affected();
+
+    Ok(deleted > 0)
+}
+
+/// Escapes the `LIKE` wildcards in user input.
+fn like_pattern(term: &str) -> String {
+    let escaped = term
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// Finds the questions matching a search term and tag filter, newest first.
+///
+/// The term is matched case-insensitively against question text,
+/// description and option text. Tags are OR-ed within a facet and AND-ed
+/// across facets, and a tag also matches its descendants. Without a term
+/// or tags every question matches.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let ids = matching_question_ids(&pool, Some("الحمد"), &[3]).await?;
+/// ```
+pub async fn matching_question_ids(
+    pool: &sqlx::PgPool,
+    term: Option<&str>,
+    tag_ids: &[i32],
+) -> Result<Vec<i32>> {
+    let pattern = term
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(like_pattern);
+
+    sqlx::query_scalar!(
+        r#"
+        WITH RECURSIVE selected (id, facet) AS (
+            SELECT id, facet FROM tags WHERE id = ANY($2)
+            UNION
+            SELECT t.id, s.facet
+            FROM tags t
+            JOIN selected s ON t.parent_id = s.id
+        )
+        SELECT q.id
+        FROM questions q
+        WHERE (
+            $1::text IS NULL
+            OR q.text ILIKE $1
+            OR q.description ILIKE $1
+            OR EXISTS (SELECT 1 FROM options o WHERE o.question_id = q.id AND o.text ILIKE $1)
+        )
+        AND NOT EXISTS (
+            SELECT 1
+            FROM selected f
+            WHERE NOT EXISTS (
+                SELECT 1
+                FROM question_tags qt
+                JOIN selected s ON s.id = qt.tag_id
+                WHERE qt.question_id = q.id AND s.facet = f.facet
+            )
+        )
+        ORDER BY q.created_at DESC, q.id DESC
+        "#,
+        pattern,
+        tag_ids
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to search questions")
+}
+
+/// Searches the bank, newest questions first, with tag counts over all
+/// matches. See [`matching_question_ids`] for how questions are matched.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let page = search_questions(&pool, Some("الحمد"), &[], 20, 0).await?;
+/// println!("{} matches", page.total);
+/// ```
+pub async fn search_questions(
+    pool: &sqlx::PgPool,
+    term: Option<&str>,
+    tag_ids: &[i32],
+    limit: i64,
+    offset: i64,
+) -> Result<BankSearchResponse> {
+    let ids = matching_question_ids(pool, term, tag_ids).await?;
+    let page: Vec<i32> = ids
+        .iter()
+        .skip(offset as usize)
+        .take(limit as usize)
+        .copied()
+        .collect();
+
+    let questions = sqlx::query_as!(
+        BankQuestionSummary,
+        r#"
+        SELECT
+            q.id,
+            q.text,
+            q.description,
+            q.marks,
+            q.created_at,
+            (SELECT COUNT(*) FROM section_questions sq WHERE sq.question_id = q.id) AS "usage_count!"
+        FROM questions q
+        WHERE q.id = ANY($1)
+        ORDER BY array_position($1, q.id)
+        "#,
+        &page
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch questions")?;
+
+    let facets = tags::facet_counts(pool, &ids).await?;
+
+    Ok(BankSearchResponse {
+        total: ids.len() as i64,
+        questions,
+        facets,
+    })
+}
+
+/// Checks that `section_id` is a section of the exam `exam_id`.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// if !section_in_exam(&pool, 1, 3).await? { /* 404 */ }
+/// ```
+pub async fn section_in_exam(pool: &sqlx::PgPool, exam_id: i32, section_id: i32) -> Result<bool> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM sections s
+            JOIN exam_descriptions d ON s.exam_description_id = d.id
+            WHERE s.id = $1 AND d.exam_id = $2
+        ) AS "exists!"
+        "#,
+        section_id,
+        exam_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to look up section")
+}
+
+/// Returns the IDs in `question_ids` that are not in the bank.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let missing = missing_questions(&pool, &[10, 11]).await?;
+/// ```
+pub async fn missing_questions(pool: &sqlx::PgPool, question_ids: &[i32]) -> Result<Vec<i32>> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT requested.id AS "id!"
+        FROM UNNEST($1::int[]) AS requested(id)
+        WHERE NOT EXISTS (SELECT 1 FROM questions q WHERE q.id = requested.id)
+        "#,
+        question_ids
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to look up questions")
+}
+
+/// Links bank questions into a section after its existing questions.
+/// Questions already in the section keep their place.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// link_to_section(&pool, 3, &[10, 11]).await?;
+/// ```
+pub async fn link_to_section(
+    pool: &sqlx::PgPool,
+    section_id: i32,
+    question_ids: &[i32],
+) -> Result<()> {
+    let mut tx = pool
+        .begin()
+        .await
+        .context("Failed to start DB transaction")?;
+
+    let section_ids = vec![section_id; question_ids.len()];
+    link_questions(&mut tx, &section_ids, question_ids).await?;
+
+    tx.commit().await.context("Failed to commit transaction")?;
+
+    Ok(())
+}
+
+/// Removes a question from a section; the question stays in the bank.
+/// Returns `false` if the question was not in the section.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let removed = unlink_from_section(&pool, 3, 10).await?;
+/// ```
+pub async fn unlink_from_section(
+    pool: &sqlx::PgPool,
+    section_id: i32,
+    question_id: i32,
+) -> Result<bool> {
+    let removed = sqlx::query!(
+        r#"
+        DELETE FROM section_questions
+        WHERE section_id = $1 AND question_id = $2
+        "#,
+        section_id,
+        question_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to unlink question")?
+    .rows_affected();
+
+    Ok(removed > 0)
+}
@@ -5,7 +5,9 @@ use anyhow::{Context, Result};
 ///
 /// This function starts a database transaction and deletes the exam record
 /// from the `exam` table. If foreign key constraints are set up with `ON DELETE CASCADE`,
-/// related rows in `details`, `sections`, `questions`, and `options` will be deleted automatically.
+/// related rows in `details` and `sections` will be deleted automatically, along
+/// with the links from the sections to their questions. The questions and their
+/// options stay in the question bank.
 ///
 /// # Arguments
 ///
@@ -41,17 +43,23 @@ pub async fn delete_exam(pool: &sqlx::PgPool, exam_id: i32) -> Result<()> {
     Ok(())
 }
 
-/// Deletes specific sections, questions, and options from the database.
+/// Deletes specific sections and options from the database and removes
+/// questions from an exam.
 ///
 /// This function allows you to manually delete related entities from their respective
-/// tables (`sections`, `questions`, and `options`) in a single transaction.
-/// The deletions are performed in the order: `sections`, `questions`, then `options`.
+/// tables (`sections` and `options`) in a single transaction. Everything is scoped to
+/// `exam_id`: sections of other exams are left alone, and questions live in the
+/// question bank, so they are only unlinked from the sections of `exam_id`. Options
+/// are only deleted from questions used by no other exam; those of shared questions
+/// change through the bank.
+/// The deletions are performed in the order: `options`, `sections`, then `questions`.
 ///
-/// **Note:** Ensure the provided IDs are valid and correspond to existing entities.
+/// **Note:** IDs outside the exam are skipped without an error.
 ///
 /// # Arguments
 ///
 /// * `pool` - A reference to the SQLx PostgreSQL connection pool.
+/// * `exam_id` - The ID of the exam being edited.
 /// * `deletion_data` - A `DeleteIdsRequest` containing vectors of IDs for
 ///   sections, questions, and options to delete.
 ///
@@ -67,52 +75,76 @@ pub async fn delete_exam(pool: &sqlx::PgPool, exam_id: i32) -> Result<()> {
 ///     question_ids: vec![10, 11],
 ///     option_ids: vec![100, 101],
 /// };
-/// delete_related_entities(&pool, &deletion_data).await?;
+/// delete_related_entities(&pool, 1, &deletion_data).await?;
 /// println!("Related entities deleted successfully.");
 /// ```
 pub async fn delete_related_entities(
     pool: &sqlx::PgPool,
+    exam_id: i32,
     deletion_data: &model::delete::DeleteIdsRequest,
 ) -> Result<()> {
     let mut tx = pool.begin().await.context("Failed to begin transaction")?;
 
-    let section_ids: Vec<i32> = deletion_data.section_ids.iter().copied().collect();
+    let option_ids: Vec<i32> = deletion_data.option_ids.to_vec();
+
     sqlx::query!(
         r#"
-        DELETE FROM sections
-        WHERE id = ANY($1);
+        DELETE FROM options o
+        USING section_questions sq, sections s, exam_descriptions d
+        WHERE o.question_id = sq.question_id
+          AND sq.section_id = s.id
+          AND s.exam_description_id = d.id
+          AND d.exam_id = $1
+          AND o.id = ANY($2)
+          AND NOT EXISTS (
+              SELECT 1
+              FROM section_questions other
+              JOIN sections os ON os.id = other.section_id
+              JOIN exam_descriptions od ON od.id = os.exam_description_id
+              WHERE other.question_id = o.question_id
+                AND od.exam_id <> $1
+          );
         "#,
-        &section_ids
+        exam_id,
+        &option_ids
     )
     .execute(&mut *tx)
     .await
-    .context("Failed to delete sections")?;
-
-    let question_ids: Vec<i32> = deletion_data.question_ids.iter().copied().collect();
+    .context("Failed to delete options")?;
 
+    let section_ids: Vec<i32> = deletion_data.section_ids.to_vec();
     sqlx::query!(
         r#"
-        DELETE FROM questions
-        WHERE id = ANY($1);
+        DELETE FROM sections s
+        USING exam_descriptions d
+        WHERE s.exam_description_id = d.id
+          AND d.exam_id = $1
+          AND s.id = ANY($2);
         "#,
-        &question_ids
+        exam_id,
+        &section_ids
     )
     .execute(&mut *tx)
     .await
-    .context("Failed to delete questions")?;
+    .context("Failed to delete sections")?;
 
-    let option_ids: Vec<i32> = deletion_data.option_ids.iter().copied().collect();
+    let question_ids: Vec<i32> = deletion_data.question_ids.to_vec();
 
     sqlx::query!(
         r#"
-            DELETE FROM options
-            WHERE id = ANY($1);
-            "#,
-        &option_ids
+        DELETE FROM section_questions sq
+        USING sections s, exam_descriptions d
+        WHERE sq.section_id = s.id
+          AND s.exam_description_id = d.id
+          AND d.exam_id = $1
+          AND sq.question_id = ANY($2);
+        "#,
+        exam_id,
+        &question_ids
     )
     .execute(&mut *tx)
     .await
-    .context("Failed to delete options")?;
+    .context("Failed to remove questions from exam")?;
 
     tx.commit().await.context("Failed to commit transaction")?;
 
@@ -0,0 +1,392 @@
+use crate::database::queries::prompts::parse_name;
+use crate::model::experiment::{
+    CreateExperimentRequest, Experiment, ExperimentVariant, GenerationRecord, VariantReport,
+};
+use crate::model::prompt::TemplateKey;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+struct ExperimentRow {
+    id: i32,
+    name: String,
+    task: String,
+    language: String,
+    distractor_type: Option<String>,
+    active: bool,
+    created_at: DateTime<Utc>,
+    stopped_at: Option<DateTime<Utc>>,
+}
+
+/// Reads experiments with their variants, newest first.
+async fn read_experiments(
+    pool: &sqlx::PgPool,
+    id: Option<i32>,
+    active_only: bool,
+) -> Result<Vec<Experiment>> {
+    let rows = sqlx::query_as!(
+        ExperimentRow,
+        r#"
+        SELECT id, name, task, language, distractor_type, active, created_at, stopped_at
+        FROM prompt_experiments
+        WHERE ($1::INT IS NULL OR id = $1) AND (NOT $2 OR active)
+        ORDER BY created_at DESC, id DESC
+        "#,
+        id,
+        active_only
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch prompt experiments")?;
+
+    let ids: Vec<i32> = rows.iter().map(|row| row.id).collect();
+    let variants = sqlx::query!(
+        r#"
+        SELECT v.id, v.experiment_id, v.name, v.template_id, v.weight, t.version
+        FROM prompt_experiment_variants v
+        JOIN prompt_templates t ON t.id = v.template_id
+        WHERE v.experiment_id = ANY($1)
+        ORDER BY v.id
+        "#,
+        &ids
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch prompt experiment variants")?;
+
+    let mut experiments = Vec::with_capacity(rows.len());
+    let mut by_id: HashMap<i32, usize> = HashMap::new();
+    for row in rows {
+        let key = TemplateKey {
+            task: parse_name(&row.task)?,
+            language: parse_name(&row.language)?,
+            distractor_type: row.distractor_type.as_deref().map(parse_name).transpose()?,
+        };
+        by_id.insert(row.id, experiments.len());
+        experiments.push(Experiment {
+            id: row.id,
+            name: row.name,
+            key,
+            active: row.active,
+            variants: Vec::new(),
+            created_at: row.created_at,
+            stopped_at: row.stopped_at,
+        });
+    }
+    for variant in variants {
+        if let Some(&index) = by_id.get(&variant.experiment_id) {
+            let experiment = &mut experiments[index];
+            experiment.variants.push(ExperimentVariant {
+                id: variant.id,
+                name: variant.name,
+                template_id: variant.template_id,
+                prompt_version: experiment.key.version_label(variant.version),
+                weight: variant.weight,
+            });
+        }
+    }
+    Ok(experiments)
+}
+
+/// Lists experiments, optionally only the running ones.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let running = list_experiments(&pool, true).await?;
+/// ```
+pub async fn list_experiments(pool: &sqlx::PgPool, active_only: bool) -> Result<Vec<Experiment>> {
+    read_experiments(pool, None, active_only).await
+}
+
+/// Fetches one experiment, `None` if it does not exist.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let experiment = get_experiment(&pool, 2).await?;
+/// ```
+pub async fn get_experiment(pool: &sqlx::PgPool, id: i32) -> Result<Option<Experiment>> {
+    Ok(read_experiments(pool, Some(id), false).await?.pop())
+}
+
+/// Starts an experiment and returns its ID, or `None` if the name is taken
+/// or another experiment runs on the series.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let id = create_experiment(&pool, &request).await?;
+/// ```
+pub async fn create_experiment(
+    pool: &sqlx::PgPool,
+    request: &CreateExperimentRequest,
+) -> Result<Option<i32>> {
+    let mut tx = pool.begin().await?;
+
+    let Some(id) = sqlx::query_scalar!(
+        r#"
+        INSERT INTO prompt_experiments (name, task, language, distractor_type)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT DO NOTHING
+        RETURNING id
+        "#,
+        request.name.trim(),
+        request.key.task.as_str(),
+        request.key.language.as_str(),
+        request
+            .key
+            .distractor_type
+            .map(|distractor_type| distractor_type.as_str())
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .context("Failed to insert prompt experiment")?
+    else {
+        return Ok(None);
+    };
+
+    let names: Vec<String> = request
+        .variants
+        .iter()
+        .map(|v| v.name.trim().to_string())
+        .collect();
+    let template_ids: Vec<i32> = request.variants.iter().map(|v| v.template_id).collect();
+    let weights: Vec<i32> = request.variants.iter().map(|v| v.weight).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO prompt_experiment_variants (experiment_id, name, template_id, weight)
+        SELECT $1, * FROM UNNEST($2::text[], $3::int[], $4::int[])
+        "#,
+        id,
+        &names,
+        &template_ids,
+        &weights
+    )
+    .execute(&mut *tx)
+    .await
+    .context("Failed to insert prompt experiment variants")?;
+
+    tx.commit().await?;
+    Ok(Some(id))
+}
+
+/// Stops splitting requests between the variants of an experiment. Returns
+/// `false` if it does not exist.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let found = stop_experiment(&pool, 2).await?;
+/// ```
+pub async fn stop_experiment(pool: &sqlx::PgPool, id: i32) -> Result<bool> {
+    let updated = sqlx::query!(
+        r#"
+        UPDATE prompt_experiments
+        SET active = FALSE, stopped_at = COALESCE(stopped_at, now())
+        WHERE id = $1
+        "#,
+        id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to stop prompt experiment")?
+    .rows_affected();
+
+    Ok(updated > 0)
+}
+
+/// Records a generation made with an experiment variant.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// insert_generation(&pool, &record).await?;
+/// ```
+pub async fn insert_generation(pool: &sqlx::PgPool, record: &GenerationRecord) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO prompt_generations
+            (id, variant_id, user_id, endpoint, parsed, distractors, passed)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        record.id,
+        record.variant_id,
+        record.user_id,
+        record.endpoint,
+        record.stats.parsed,
+        record.stats.distractors as i32,
+        record.stats.passed as i32
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record prompt generation")?;
+    Ok(())
+}
+
+/// Whether a generation was recorded.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let found = generation_exists(&pool, id).await?;
+/// ```
+pub async fn generation_exists(pool: &sqlx::PgPool, id: Uuid) -> Result<bool> {
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM prompt_generations WHERE id = $1) AS "exists!""#,
+        id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to look up prompt generation")
+}
+
+/// Records an author's verdict on distractors of a generation, replacing
+/// earlier verdicts on the same distractors.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// record_feedback(&pool, id, &["الْكَرِيمِ".into()], &[]).await?;
+/// ```
+pub async fn record_feedback(
+    pool: &sqlx::PgPool,
+    id: Uuid,
+    accepted: &[String],
+    rejected: &[String],
+) -> Result<()> {
+    let distractors: Vec<String> = accepted.iter().chain(rejected).cloned().collect();
+    let verdicts: Vec<bool> = accepted
+        .iter()
+        .map(|_| true)
+        .chain(rejected.iter().map(|_| false))
+        .collect();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO generation_feedback (generation_id, distractor, accepted)
+        SELECT $1, * FROM UNNEST($2::text[], $3::bool[])
+        ON CONFLICT (generation_id, distractor)
+        DO UPDATE SET accepted = EXCLUDED.accepted, created_at = now()
+        "#,
+        id,
+        &distractors,
+        &verdicts
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record generation feedback")?;
+    Ok(())
+}
+
+/// Adds student responses and selections of distractors of a generation.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// record_selections(&pool, id, 30, &[("الْكَرِيمِ".into(), 4)]).await?;
+/// ```
+pub async fn record_selections(
+    pool: &sqlx::PgPool,
+    id: Uuid,
+    responses: i32,
+    selections: &[(String, i32)],
+) -> Result<()> {
+    let (distractors, counts): (Vec<String>, Vec<i32>) = selections.iter().cloned().unzip();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO generation_selections (generation_id, distractor, responses, selections)
+        SELECT $1, s.distractor, $2, s.selections
+        FROM UNNEST($3::text[], $4::int[]) AS s (distractor, selections)
+        ON CONFLICT (generation_id, distractor)
+        DO UPDATE SET
+            responses = generation_selections.responses + EXCLUDED.responses,
+            selections = generation_selections.selections + EXCLUDED.selections
+        "#,
+        id,
+        responses,
+        &distractors,
+        &counts
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record generation selections")?;
+    Ok(())
+}
+
+/// Outcomes per variant of an experiment. The rates are `None` without
+/// anything to compute them from.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let variants = variant_reports(&pool, &experiment).await?;
+/// ```
+pub async fn variant_reports(
+    pool: &sqlx::PgPool,
+    experiment: &Experiment,
+) -> Result<Vec<VariantReport>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            v.id,
+            COALESCE(g.generations, 0) AS "generations!",
+            COALESCE(g.parsed, 0) AS "parsed!",
+            COALESCE(g.distractors, 0) AS "distractors!",
+            COALESCE(g.passed, 0) AS "passed!",
+            COALESCE(f.accepted, 0) AS "accepted!",
+            COALESCE(f.rejected, 0) AS "rejected!",
+            COALESCE(s.responses, 0) AS "responses!",
+            COALESCE(s.selections, 0) AS "selections!"
+        FROM prompt_experiment_variants v
+        LEFT JOIN (
+            SELECT variant_id,
+                COUNT(*) AS generations,
+                COUNT(*) FILTER (WHERE parsed) AS parsed,
+                SUM(distractors)::BIGINT AS distractors,
+                SUM(passed)::BIGINT AS passed
+            FROM prompt_generations
+            GROUP BY variant_id
+        ) g ON g.variant_id = v.id
+        LEFT JOIN (
+            SELECT pg.variant_id,
+                COUNT(*) FILTER (WHERE fb.accepted) AS accepted,
+                COUNT(*) FILTER (WHERE NOT fb.accepted) AS rejected
+            FROM generation_feedback fb
+            JOIN prompt_generations pg ON pg.id = fb.generation_id
+            GROUP BY pg.variant_id
+        ) f ON f.variant_id = v.id
+        LEFT JOIN (
+            SELECT pg.variant_id,
+                SUM(gs.responses)::BIGINT AS responses,
+                SUM(gs.selections)::BIGINT AS selections
+            FROM generation_selections gs
+            JOIN prompt_generations pg ON pg.id = gs.generation_id
+            GROUP BY pg.variant_id
+        ) s ON s.variant_id = v.id
+        WHERE v.experiment_id = $1
+        ORDER BY v.id
+        "#,
+        experiment.id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to compute experiment report")?;
+
+    let rate = |part: i64, whole: i64| (whole > 0).then(|| part as f64 / whole as f64);
+    let reports = rows
+        .into_iter()
+        .filter_map(|row| {
+            let variant = experiment.variants.iter().find(|v| v.id == row.id)?;
+            Some(VariantReport {
+                variant_id: variant.id,
+                variant: variant.name.clone(),
+                prompt_version: variant.prompt_version.clone(),
+                weight: variant.weight,
+                generations: row.generations,
+                parse_success_rate: rate(row.parsed, row.generations),
+                gate_pass_rate: rate(row.passed, row.distractors),
+                accepted: row.accepted,
+                rejected: row.rejected,
+                acceptance_rate: rate(row.accepted, row.accepted + row.rejected),
+                responses: row.responses,
+                selections: row.selections,
+                selection_rate: rate(row.selections, row.responses),
+            })
+        })
+        .collect();
+    Ok(reports)
+}
@@ -0,0 +1,172 @@
+use crate::model::{exam, section};
+use anyhow::{Context, Result};
+use sqlx::PgConnection;
+
+/// Tables whose `SERIAL` primary keys can be reserved ahead of an insert.
+#[derive(Debug, Clone, Copy)]
+pub enum IdTable {
+    Exams,
+    ExamDescriptions,
+    Sections,
+    Questions,
+    Options,
+}
+
+impl IdTable {
+    fn name(self) -> &'static str {
+        match self {
+            IdTable::Exams => "exams",
+            IdTable::ExamDescriptions => "exam_descriptions",
+            IdTable::Sections => "sections",
+            IdTable::Questions => "questions",
+            IdTable::Options => "options",
+        }
+    }
+}
+
+/// Reserves `count` fresh primary keys for `table`.
+///
+/// Keys are drawn with `nextval` alone, which never hands the same value out
+/// twice, so they stay unique whichever connection the rows are inserted on.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let mut conn = pool.acquire().await?;
+/// let ids = reserve_ids(&mut conn, IdTable::Questions, 3).await?;
+/// ```
+pub async fn reserve_ids(
+    conn: &mut PgConnection,
+    table: IdTable,
+    count: usize,
+) -> Result<Vec<i32>> {
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+
+    let table = table.name();
+
+    sqlx::query_scalar(&format!(
+        "SELECT nextval('{table}_id_seq')::int FROM generate_series(1, $1)"
+    ))
+    .bind(count as i32)
+    .fetch_all(&mut *conn)
+    .await
+    .with_context(|| format!("Failed to reserve ids for {}", table))
+}
+
+/// Moves the sequence of `table` past `id`, so rows inserted with explicit
+/// IDs are not handed out again by [`reserve_ids`]. The sequence is only
+/// ever moved forward.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// advance_past(&mut tx, IdTable::Exams, exam.exam_id.base.id).await?;
+/// ```
+pub async fn advance_past(conn: &mut PgConnection, table: IdTable, id: i32) -> Result<()> {
+    let table = table.name();
+
+    sqlx::query(&format!(
+        "SELECT setval('{table}_id_seq', $1) FROM {table}_id_seq WHERE last_value < $1"
+    ))
+    .bind(i64::from(id))
+    .execute(&mut *conn)
+    .await
+    .with_context(|| format!("Failed to advance sequence for {}", table))?;
+
+    Ok(())
+}
+
+/// Assigns fresh IDs to new sections, questions and options and points them
+/// at `description_id`. Sections with a non-zero ID already exist and keep it.
+async fn assign_tree_ids(
+    conn: &mut PgConnection,
+    description_id: i32,
+    sections: &mut [section::SectionRequest],
+) -> Result<()> {
+    let new_sections = sections.iter().filter(|s| s.base.id == 0).count();
+    let question_count: usize = sections.iter().map(|s| s.questions.len()).sum();
+    let option_count: usize = sections
+        .iter()
+        .flat_map(|s| &s.questions)
+        .map(|q| q.options.len())
+        .sum();
+
+    let mut section_ids = reserve_ids(conn, IdTable::Sections, new_sections)
+        .await?
+        .into_iter();
+    let mut question_ids = reserve_ids(conn, IdTable::Questions, question_count)
+        .await?
+        .into_iter();
+    let mut option_ids = reserve_ids(conn, IdTable::Options, option_count)
+        .await?
+        .into_iter();
+
+    for section in sections {
+        if section.base.id == 0 {
+            section.base.id = section_ids.next().context("Ran out of section ids")?;
+        }
+        section.base.exam_description_id = description_id;
+
+        for question in &mut section.questions {
+            question.base.id = question_ids.next().context("Ran out of question ids")?;
+            question.base.section_id = section.base.id;
+
+            for option in &mut question.options {
+                option.base.id = option_ids.next().context("Ran out of option ids")?;
+                option.base.question_id = question.base.id;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Assigns fresh IDs to every entity of `exam` and rewires the parent
+/// references of sections, questions and options to match.
+///
+/// Used for exams built from imported content, whose IDs are only placeholders.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let mut exam = qti::import_package(&bytes)?;
+/// assign_exam_ids(&pool, &mut exam).await?;
+/// insert_exam(&pool, &exam).await?;
+/// ```
+pub async fn assign_exam_ids(pool: &sqlx::PgPool, exam: &mut exam::ExamRequest) -> Result<()> {
+    let mut conn = pool
+        .acquire()
+        .await
+        .context("Failed to acquire connection")?;
+
+    let exam_id = reserve_ids(&mut conn, IdTable::Exams, 1).await?[0];
+    let description_id = reserve_ids(&mut conn, IdTable::ExamDescriptions, 1).await?[0];
+
+    exam.exam_id.base.id = exam_id;
+    exam.description.base.id = description_id;
+    exam.description.base.exam_id = exam_id;
+
+    assign_tree_ids(&mut conn, description_id, &mut exam.sections).await
+}
+
+/// Assigns IDs to content being appended to an existing exam description.
+///
+/// Sections with a zero ID are new and get a fresh one; other sections are
+/// existing ones and keep their ID. Every question and option is new.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// assign_section_ids(&pool, description.id, &mut sections).await?;
+/// append_sections(&pool, description.id, &sections).await?;
+/// ```
+pub async fn assign_section_ids(
+    pool: &sqlx::PgPool,
+    description_id: i32,
+    sections: &mut [section::SectionRequest],
+) -> Result<()> {
+    let mut conn = pool
+        .acquire()
+        .await
+        .context("Failed to acquire connection")?;
+
+    assign_tree_ids(&mut conn, description_id, sections).await
+}
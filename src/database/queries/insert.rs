@@ -1,4 +1,6 @@
+use crate::database::queries::ids::{advance_past, IdTable};
 use crate::model::exam;
+use crate::model::section::SectionRequest;
 use anyhow::{Context, Result};
 use sqlx::PgConnection;
 
@@ -83,28 +85,27 @@ async fn insert_sections(
     Ok(())
 }
 
-/// Inserts multiple questions into sections.
+/// Inserts multiple questions into the question bank. Questions whose ID
+/// already exists are left unchanged.
 ///
 /// # Example (non-runnable)
 /// ```ignore
-/// insert_questions(&mut tx, &[1, 2], &[10, 10], &["Q1".into(), "Q2".into()], &["D1".into(), "D2".into()], &[5, 10]).await?;
+/// insert_questions(&mut tx, &[1, 2], &["Q1".into(), "Q2".into()], &["D1".into(), "D2".into()], &[5, 10]).await?;
 /// ```
-async fn insert_questions(
+pub async fn insert_questions(
     tx: &mut PgConnection,
     question_ids: &[i32],
-    section_ids: &[i32],
     texts: &[String],
     descs: &[String],
     marks: &[i32],
 ) -> Result<()> {
     sqlx::query!(
         r#"
-        INSERT INTO questions (id, section_id, text, description, marks)
-        SELECT * FROM UNNEST($1::int[], $2::int[], $3::text[], $4::text[], $5::int[])
+        INSERT INTO questions (id, text, description, marks)
+        SELECT * FROM UNNEST($1::int[], $2::text[], $3::text[], $4::int[])
         ON CONFLICT (id) DO NOTHING
         "#,
         question_ids,
-        section_ids,
         texts,
         descs,
         marks
@@ -116,13 +117,49 @@ async fn insert_questions(
     Ok(())
 }
 
+/// Links questions into sections, after the questions each section already
+/// has and in the order given. Existing links are left unchanged.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// link_questions(&mut tx, &[10, 10], &[1, 2]).await?;
+/// ```
+pub async fn link_questions(
+    tx: &mut PgConnection,
+    section_ids: &[i32],
+    question_ids: &[i32],
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO section_questions (section_id, question_id, position)
+        SELECT
+            link.section_id,
+            link.question_id,
+            (
+                SELECT COALESCE(MAX(sq.position), 0)
+                FROM section_questions sq
+                WHERE sq.section_id = link.section_id
+            ) + link.ordinality::int
+        FROM UNNEST($1::int[], $2::int[]) WITH ORDINALITY AS link(section_id, question_id, ordinality)
+        ON CONFLICT (section_id, question_id) DO NOTHING
+        "#,
+        section_ids,
+        question_ids
+    )
+    .execute(&mut *tx)
+    .await
+    .context("Failed to link questions to sections")?;
+
+    Ok(())
+}
+
 /// Inserts multiple options for questions.
 ///
 /// # Example (non-runnable)
 /// ```ignore
 /// insert_options(&mut tx, &[1, 2], &[10, 10], &["A".into(), "B".into()], &[true, false]).await?;
 /// ```
-async fn insert_options(
+pub async fn insert_options(
     tx: &mut PgConnection,
     option_ids: &[i32],
     question_ids: &[i32],
@@ -147,37 +184,21 @@ async fn insert_options(
     Ok(())
 }
 
-/// Inserts a full exam (exam metadata, details, sections, questions, and options).
-///
-/// This function handles the entire transaction lifecycle and ensures all parts of an exam are inserted atomically.
-///
-/// # Example (non-runnable)
-/// ```ignore
-/// let exam_request = ExamRequest { ... };
-/// insert_exam(&pool, &exam_request).await?;
-/// ```
-///
-/// # Errors
-/// Returns an error if any part of the insert process fails.
-pub async fn insert_exam(pool: &sqlx::PgPool, exam: &exam::ExamRequest) -> Result<()> {
-    let mut tx = pool
-        .begin()
-        .await
-        .context("Failed to start DB transaction")?;
-
-    let _exam_id = insert_exam_id(&mut tx, exam)
-        .await
-        .context("Failed to insert exam")?;
-
-    let _detail_id = insert_details(&mut tx, exam)
-        .await
-        .context("Failed to insert exam details")?;
-
-    let section_ids: Vec<i32> = exam.sections.iter().map(|s| s.base.id).collect();
-    let section_titles: Vec<String> = exam.sections.iter().map(|s| s.base.title.clone()).collect();
-    let detail_ids: Vec<i32> = vec![exam.description.base.id; section_ids.len()];
+/// Inserts sections of the exam description `description_id` with their
+/// questions and options, and links the questions into the sections.
+/// Sections and questions whose ID already exists are left as they are, so
+/// questions can be added to existing sections and bank questions can be
+/// linked by ID.
+async fn insert_section_tree(
+    tx: &mut PgConnection,
+    description_id: i32,
+    sections: &[SectionRequest],
+) -> Result<()> {
+    let section_ids: Vec<i32> = sections.iter().map(|s| s.base.id).collect();
+    let section_titles: Vec<String> = sections.iter().map(|s| s.base.title.clone()).collect();
+    let detail_ids: Vec<i32> = vec![description_id; section_ids.len()];
 
-    insert_sections(&mut tx, &section_ids, &detail_ids, &section_titles)
+    insert_sections(&mut *tx, &section_ids, &detail_ids, &section_titles)
         .await
         .context("Failed to insert sections")?;
 
@@ -192,7 +213,7 @@ pub async fn insert_exam(pool: &sqlx::PgPool, exam: &exam::ExamRequest) -> Resul
     let mut option_texts = Vec::new();
     let mut option_correct_flags = Vec::new();
 
-    for section in &exam.sections {
+    for section in sections {
         for q in &section.questions {
             question_ids.push(q.base.id);
             question_section_ids.push(q.base.section_id);
@@ -210,9 +231,8 @@ pub async fn insert_exam(pool: &sqlx::PgPool, exam: &exam::ExamRequest) -> Resul
     }
 
     insert_questions(
-        &mut tx,
+        &mut *tx,
         &question_ids,
-        &question_section_ids,
         &question_texts,
         &question_descs,
         &question_marks,
@@ -220,8 +240,12 @@ pub async fn insert_exam(pool: &sqlx::PgPool, exam: &exam::ExamRequest) -> Resul
     .await
     .context("Failed to insert questions")?;
 
+    link_questions(&mut *tx, &question_section_ids, &question_ids)
+        .await
+        .context("Failed to link questions")?;
+
     insert_options(
-        &mut tx,
+        &mut *tx,
         &option_ids,
         &option_question_ids,
         &option_texts,
@@ -230,6 +254,100 @@ pub async fn insert_exam(pool: &sqlx::PgPool, exam: &exam::ExamRequest) -> Resul
     .await
     .context("Failed to insert options")?;
 
+    Ok(())
+}
+
+/// Moves the ID sequences past the IDs `exam` was inserted with, which the
+/// client may have chosen itself.
+async fn advance_sequences(tx: &mut PgConnection, exam: &exam::ExamRequest) -> Result<()> {
+    let questions = exam.sections.iter().flat_map(|s| &s.questions);
+    let highest = [
+        (IdTable::Exams, exam.exam_id.base.id),
+        (IdTable::ExamDescriptions, exam.description.base.id),
+        (
+            IdTable::Sections,
+            exam.sections.iter().map(|s| s.base.id).max().unwrap_or(0),
+        ),
+        (
+            IdTable::Questions,
+            questions.clone().map(|q| q.base.id).max().unwrap_or(0),
+        ),
+        (
+            IdTable::Options,
+            questions
+                .flat_map(|q| &q.options)
+                .map(|o| o.base.id)
+                .max()
+                .unwrap_or(0),
+        ),
+    ];
+
+    for (table, id) in highest {
+        advance_past(&mut *tx, table, id).await?;
+    }
+
+    Ok(())
+}
+
+/// Inserts a full exam (exam metadata, details, sections, questions, and options).
+///
+/// This function handles the entire transaction lifecycle and ensures all parts of an exam are inserted atomically.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let exam_request = ExamRequest { ... };
+/// insert_exam(&pool, &exam_request).await?;
+/// ```
+///
+/// # Errors
+/// Returns an error if any part of the insert process fails.
+pub async fn insert_exam(pool: &sqlx::PgPool, exam: &exam::ExamRequest) -> Result<()> {
+    let mut tx = pool
+        .begin()
+        .await
+        .context("Failed to start DB transaction")?;
+
+    let _exam_id = insert_exam_id(&mut tx, exam)
+        .await
+        .context("Failed to insert exam")?;
+
+    let _detail_id = insert_details(&mut tx, exam)
+        .await
+        .context("Failed to insert exam details")?;
+
+    insert_section_tree(&mut tx, exam.description.base.id, &exam.sections).await?;
+
+    advance_sequences(&mut tx, exam).await?;
+
+    tx.commit().await.context("Failed to commit transaction")?;
+
+    Ok(())
+}
+
+/// Appends sections, questions and options to an existing exam in one
+/// transaction. Sections that already exist keep their title and only gain
+/// the new questions.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// assign_section_ids(&pool, description.id, &mut sections).await?;
+/// append_sections(&pool, description.id, &sections).await?;
+/// ```
+///
+/// # Errors
+/// Returns an error if any insert fails; nothing is written in that case.
+pub async fn append_sections(
+    pool: &sqlx::PgPool,
+    description_id: i32,
+    sections: &[SectionRequest],
+) -> Result<()> {
+    let mut tx = pool
+        .begin()
+        .await
+        .context("Failed to start DB transaction")?;
+
+    insert_section_tree(&mut tx, description_id, sections).await?;
+
     tx.commit().await.context("Failed to commit transaction")?;
 
     Ok(())
@@ -1,3 +1,11 @@
+pub mod bank;
 pub mod delete;
+pub mod experiments;
+pub mod ids;
 pub mod insert;
+pub mod options;
+pub mod prompts;
 pub mod read;
+pub mod search;
+pub mod tags;
+pub mod usage;
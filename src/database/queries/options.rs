@@ -0,0 +1,162 @@
+use crate::database::queries::ids::{reserve_ids, IdTable};
+use crate::database::queries::insert::insert_options;
+use crate::model::generation::{OptionProvenance, SourceQuestion};
+use anyhow::{Context, Result};
+use sqlx::PgConnection;
+
+/// Lists the questions of a section in order, with their correct answer
+/// and options.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let questions = section_questions(&pool, 3).await?;
+/// ```
+pub async fn section_questions(
+    pool: &sqlx::PgPool,
+    section_id: i32,
+) -> Result<Vec<SourceQuestion>> {
+    sqlx::query_as!(
+        SourceQuestion,
+        r#"
+        SELECT
+            q.id,
+            q.text,
+            (
+                SELECT o.text
+                FROM options o
+                WHERE o.question_id = q.id AND o.is_correct
+                ORDER BY o.id
+                LIMIT 1
+            ) AS correct_answer,
+            ARRAY(
+                SELECT o.text FROM options o WHERE o.question_id = q.id ORDER BY o.id
+            ) AS "options!"
+        FROM section_questions sq
+        JOIN questions q ON q.id = sq.question_id
+        WHERE sq.section_id = $1
+        ORDER BY sq.position
+        "#,
+        section_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch section questions")
+}
+
+/// Reads a question with its correct answer and options, or `None` if it
+/// does not exist.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let question = source_question(&pool, 10).await?;
+/// ```
+pub async fn source_question(
+    pool: &sqlx::PgPool,
+    question_id: i32,
+) -> Result<Option<SourceQuestion>> {
+    sqlx::query_as!(
+        SourceQuestion,
+        r#"
+        SELECT
+            q.id,
+            q.text,
+            (
+                SELECT o.text
+                FROM options o
+                WHERE o.question_id = q.id AND o.is_correct
+                ORDER BY o.id
+                LIMIT 1
+            ) AS correct_answer,
+            ARRAY(
+                SELECT o.text FROM options o WHERE o.question_id = q.id ORDER BY o.id
+            ) AS "options!"
+        FROM questions q
+        WHERE q.id = $1
+        "#,
+        question_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch question")
+}
+
+/// Records how generated options were made.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// insert_provenance(&mut tx, &[(100, &provenance)]).await?;
+/// ```
+pub async fn insert_provenance(
+    tx: &mut PgConnection,
+    provenance: &[(i32, &OptionProvenance)],
+) -> Result<()> {
+    if provenance.is_empty() {
+        return Ok(());
+    }
+
+    let option_ids: Vec<i32> = provenance.iter().map(|(id, _)| *id).collect();
+    let column = |field: fn(&OptionProvenance) -> &String| -> Vec<String> {
+        provenance.iter().map(|(_, p)| field(p).clone()).collect()
+    };
+
+    sqlx::query!(
+        r#"
+        INSERT INTO option_provenance (option_id, distractor_type, provider, model, prompt_version)
+        SELECT * FROM UNNEST($1::int[], $2::text[], $3::text[], $4::text[], $5::text[])
+        ON CONFLICT (option_id) DO NOTHING
+        "#,
+        &option_ids,
+        &column(|p| &p.distractor_type),
+        &column(|p| &p.provider),
+        &column(|p| &p.model),
+        &column(|p| &p.prompt_version)
+    )
+    .execute(&mut *tx)
+    .await
+    .context("Failed to record option provenance")?;
+
+    Ok(())
+}
+
+/// Adds generated incorrect options to questions, all in one transaction
+/// and with the provenance of their question, and returns their IDs.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// add_distractors(&pool, &[(10, vec!["الْعَالِمِينَ".into()], provenance)]).await?;
+/// ```
+pub async fn add_distractors(
+    pool: &sqlx::PgPool,
+    distractors: &[(i32, Vec<String>, OptionProvenance)],
+) -> Result<Vec<i32>> {
+    let mut question_ids = Vec::new();
+    let mut texts = Vec::new();
+    let mut provenance = Vec::new();
+    for (question_id, options, origin) in distractors {
+        for text in options {
+            question_ids.push(*question_id);
+            texts.push(text.clone());
+            provenance.push(origin);
+        }
+    }
+    if texts.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut tx = pool
+        .begin()
+        .await
+        .context("Failed to start DB transaction")?;
+
+    let option_ids = reserve_ids(&mut tx, IdTable::Options, texts.len()).await?;
+    let correct_flags = vec![false; texts.len()];
+    insert_options(&mut tx, &option_ids, &question_ids, &texts, &correct_flags).await?;
+
+    let records: Vec<(i32, &OptionProvenance)> =
+        option_ids.iter().copied().zip(provenance).collect();
+    insert_provenance(&mut tx, &records).await?;
+
+    tx.commit().await.context("Failed to commit transaction")?;
+
+    Ok(option_ids)
+}
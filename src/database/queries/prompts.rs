@@ -0,0 +1,290 @@
+use crate::model::prompt::{
+    PromptExample, PromptPartial, PromptTemplate, PromptTemplateQuery, TemplateKey,
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use sqlx::PgConnection;
+
+struct TemplateRow {
+    id: i32,
+    task: String,
+    language: String,
+    distractor_type: Option<String>,
+    version: i32,
+    body: String,
+    examples: String,
+    note: Option<String>,
+    active: bool,
+    created_at: DateTime<Utc>,
+}
+
+/// Parses an enum stored under its serde name.
+pub fn parse_name<T: DeserializeOwned>(name: &str) -> Result<T> {
+    serde_json::from_value(serde_json::Value::String(name.to_string()))
+        .with_context(|| format!("Unknown name `{}` in prompt_templates", name))
+}
+
+impl TryFrom<TemplateRow> for PromptTemplate {
+    type Error = anyhow::Error;
+
+    fn try_from(row: TemplateRow) -> Result<Self> {
+        let key = TemplateKey {
+            task: parse_name(&row.task)?,
+            language: parse_name(&row.language)?,
+            distractor_type: row.distractor_type.as_deref().map(parse_name).transpose()?,
+        };
+        Ok(PromptTemplate {
+            id: row.id,
+            label: key.version_label(row.version),
+            key,
+            version: row.version,
+            body: row.body,
+            examples: serde_json::from_str(&row.examples)
+                .context("Invalid examples in prompt_templates")?,
+            note: row.note,
+            active: row.active,
+            created_at: row.created_at,
+        })
+    }
+}
+
+/// Lists template versions matching the filters, newest version first
+/// within each series.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let active = list_templates(&pool, &PromptTemplateQuery { active: true, ..Default::default() }).await?;
+/// ```
+pub async fn list_templates(
+    pool: &sqlx::PgPool,
+    filter: &PromptTemplateQuery,
+) -> Result<Vec<PromptTemplate>> {
+    let rows = sqlx::query_as!(
+        TemplateRow,
+        r#"
+        SELECT t.id, t.task, t.language, t.distractor_type, t.version, t.body,
+            t.examples::TEXT AS "examples!", t.note,
+            (a.template_id IS NOT NULL) AS "active!", t.created_at
+        FROM prompt_templates t
+        LEFT JOIN active_prompt_templates a ON a.template_id = t.id
+        WHERE ($1::TEXT IS NULL OR t.task = $1)
+            AND ($2::TEXT IS NULL OR t.language = $2)
+            AND ($3::TEXT IS NULL OR t.distractor_type = $3)
+            AND (NOT $4 OR a.template_id IS NOT NULL)
+        ORDER BY t.task, t.language, t.distractor_type NULLS FIRST, t.version DESC
+        "#,
+        filter.task.map(|task| task.as_str()),
+        filter.language.map(|language| language.as_str()),
+        filter
+            .distractor_type
+            .map(|distractor_type| distractor_type.as_str()),
+        filter.active
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list prompt templates")?;
+
+    rows.into_iter().map(PromptTemplate::try_from).collect()
+}
+
+/// Fetches one template version, `None` if it does not exist.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let template = get_template(&pool, 4).await?;
+/// ```
+pub async fn get_template(pool: &sqlx::PgPool, id: i32) -> Result<Option<PromptTemplate>> {
+    let row = sqlx::query_as!(
+        TemplateRow,
+        r#"
+        SELECT t.id, t.task, t.language, t.distractor_type, t.version, t.body,
+            t.examples::TEXT AS "examples!", t.note,
+            (a.template_id IS NOT NULL) AS "active!", t.created_at
+        FROM prompt_templates t
+        LEFT JOIN active_prompt_templates a ON a.template_id = t.id
+        WHERE t.id = $1
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch prompt template")?;
+
+    row.map(PromptTemplate::try_from).transpose()
+}
+
+/// Makes a template version the one in use for its series.
+async fn set_active(tx: &mut PgConnection, id: i32) -> Result<bool> {
+    let updated = sqlx::query!(
+        r#"
+        INSERT INTO active_prompt_templates (task, language, distractor_type, template_id)
+        SELECT task, language, distractor_type, id
+        FROM prompt_templates
+        WHERE id = $1
+        ON CONFLICT (task, language, distractor_type)
+        DO UPDATE SET template_id = EXCLUDED.template_id, updated_at = now()
+        "#,
+        id
+    )
+    .execute(&mut *tx)
+    .await
+    .context("Failed to activate prompt template")?
+    .rows_affected();
+
+    Ok(updated > 0)
+}
+
+/// Adds the next version of a series, the first if there is none yet, and
+/// returns its ID.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let id = insert_template(&pool, &key, "{{ question }} {{ correct_answer }}", &[], None, true).await?;
+/// ```
+pub async fn insert_template(
+    pool: &sqlx::PgPool,
+    key: &TemplateKey,
+    body: &str,
+    examples: &[PromptExample],
+    note: Option<&str>,
+    activate: bool,
+) -> Result<i32> {
+    let examples = serde_json::to_string(examples)?;
+    let mut tx = pool.begin().await?;
+
+    // Serializes the versions of a series.
+    sqlx::query!(
+        "SELECT pg_advisory_xact_lock(hashtext($1))",
+        key.to_string()
+    )
+    .execute(&mut *tx)
+    .await
+    .context("Failed to lock prompt template series")?;
+
+    let id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO prompt_templates
+            (task, language, distractor_type, version, body, examples, note)
+        SELECT $1, $2, $3, COALESCE(MAX(version), 0) + 1, $4, $5::TEXT::JSONB, $6
+        FROM prompt_templates
+        WHERE task = $1 AND language = $2 AND distractor_type IS NOT DISTINCT FROM $3
+        RETURNING id
+        "#,
+        key.task.as_str(),
+        key.language.as_str(),
+        key.distractor_type
+            .map(|distractor_type| distractor_type.as_str()),
+        body,
+        examples,
+        note
+    )
+    .fetch_one(&mut *tx)
+    .await
+    .context("Failed to insert prompt template")?;
+
+    if activate {
+        set_active(&mut tx, id).await?;
+    }
+
+    tx.commit().await?;
+    Ok(id)
+}
+
+/// Makes a template version the one in use. Returns `false` if it does not
+/// exist.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let found = activate_template(&pool, 4).await?;
+/// ```
+pub async fn activate_template(pool: &sqlx::PgPool, id: i32) -> Result<bool> {
+    let mut tx = pool.begin().await?;
+    let found = set_active(&mut tx, id).await?;
+    tx.commit().await?;
+    Ok(found)
+}
+
+/// Deletes a template version that is neither in use nor a variant of an
+/// experiment. Returns `false` if no such version exists.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let deleted = delete_template(&pool, 3).await?;
+/// ```
+pub async fn delete_template(pool: &sqlx::PgPool, id: i32) -> Result<bool> {
+    let deleted = sqlx::query!(
+        r#"
+        DELETE FROM prompt_templates t
+        WHERE t.id = $1
+            AND NOT EXISTS (SELECT 1 FROM active_prompt_templates a WHERE a.template_id = t.id)
+            AND NOT EXISTS (SELECT 1 FROM prompt_experiment_variants v WHERE v.template_id = t.id)
+        "#,
+        id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to delete prompt template")?
+    .rows_affected();
+
+    Ok(deleted > 0)
+}
+
+/// Lists the partials templates can include, by name.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let partials = list_partials(&pool).await?;
+/// ```
+pub async fn list_partials(pool: &sqlx::PgPool) -> Result<Vec<PromptPartial>> {
+    sqlx::query_as!(
+        PromptPartial,
+        r#"
+        SELECT name, body, updated_at
+        FROM prompt_partials
+        ORDER BY name
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list prompt partials")
+}
+
+/// Creates or replaces a partial.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let partial = put_partial(&pool, "json_only", "Reply with JSON only.").await?;
+/// ```
+pub async fn put_partial(pool: &sqlx::PgPool, name: &str, body: &str) -> Result<PromptPartial> {
+    sqlx::query_as!(
+        PromptPartial,
+        r#"
+        INSERT INTO prompt_partials (name, body)
+        VALUES ($1, $2)
+        ON CONFLICT (name) DO UPDATE SET body = EXCLUDED.body, updated_at = now()
+        RETURNING name, body, updated_at
+        "#,
+        name,
+        body
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to save prompt partial")
+}
+
+/// Deletes a partial. Returns `false` if it does not exist.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let deleted = delete_partial(&pool, "json_only").await?;
+/// ```
+pub async fn delete_partial(pool: &sqlx::PgPool, name: &str) -> Result<bool> {
+    let deleted = sqlx::query!("DELETE FROM prompt_partials WHERE name = $1", name)
+        .execute(pool)
+        .await
+        .context("Failed to delete prompt partial")?
+        .rows_affected();
+
+    Ok(deleted > 0)
+}
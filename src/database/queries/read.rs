@@ -69,7 +69,8 @@ async fn fetch_exam_description(
 }
 
 /// Fetches all sections, questions, and options related to an exam ID.
-/// This joins the `exam`, `details`, `sections`, `questions`, and `options` tables.
+/// This joins the `exam`, `details` and `sections` tables, then resolves the
+/// questions linked into each section through `section_questions`, in order.
 ///
 /// # Arguments
 ///
@@ -94,19 +95,21 @@ async fn fetch_sections_and_questions(
             s.id AS section_id,
             s.title AS section_title,
             s.exam_description_id AS section_exam_description_id,
-            q.id AS question_id,
-            q.text AS question_text,
+            q.id AS "question_id?",
+            q.text AS "question_text?",
             q.description AS question_description,
-            q.marks AS question_marks,
-            o.id AS option_id,
-            o.text AS option_text,
+            q.marks AS "question_marks?",
+            o.id AS "option_id?",
+            o.text AS "option_text?",
             o.is_correct AS option_is_correct
         FROM exams e
         JOIN exam_descriptions d ON e.id = d.exam_id
         JOIN sections s ON d.id = s.exam_description_id
-        LEFT JOIN questions q ON s.id = q.section_id
+        LEFT JOIN section_questions sq ON s.id = sq.section_id
+        LEFT JOIN questions q ON sq.question_id = q.id
         LEFT JOIN options o ON q.id = o.question_id
         WHERE e.id = $1
+        ORDER BY s.id, sq.position, q.id, o.id
         "#,
         exam_id
     )
@@ -146,7 +149,8 @@ pub async fn read_exam_data(pool: &sqlx::PgPool, exam_id: i32) -> Result<ExamRes
     let exam_description = fetch_exam_description(pool, exam_id).await?;
     let sections = fetch_sections_and_questions(pool, exam_id).await?;
     let sections_map = parse::map_to_section_response(sections)?;
-    let sections = sections_map.into_iter().map(|(_, v)| v).collect::<Vec<_>>();
+    let mut sections = sections_map.into_values().collect::<Vec<_>>();
+    sections.sort_by_key(|section| section.base.id);
 
     Ok(ExamResponse {
         exam_id: exam_model.into(),
@@ -154,3 +158,60 @@ pub async fn read_exam_data(pool: &sqlx::PgPool, exam_id: i32) -> Result<ExamRes
         sections,
     })
 }
+
+/// Looks up the description of an exam, returning `None` if the exam does not exist.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// if let Some(description) = find_exam_description(&pool, 1).await? {
+///     println!("Title: {}", description.title);
+/// }
+/// ```
+pub async fn find_exam_description(
+    pool: &sqlx::PgPool,
+    exam_id: i32,
+) -> Result<Option<schema::ExamDescriptionModel>> {
+    sqlx::query_as!(
+        schema::ExamDescriptionModel,
+        r#"
+        SELECT
+            id,
+            exam_id,
+            title,
+            description,
+            duration,
+            passing_score
+        FROM exam_descriptions
+        WHERE exam_id = $1
+        "#,
+        exam_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up exam description")
+}
+
+/// Fetches the sections of an exam description, without their questions.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let sections = read_sections(&pool, description.id).await?;
+/// ```
+pub async fn read_sections(
+    pool: &sqlx::PgPool,
+    description_id: i32,
+) -> Result<Vec<schema::SectionsModel>> {
+    sqlx::query_as!(
+        schema::SectionsModel,
+        r#"
+        SELECT id, exam_description_id, title
+        FROM sections
+        WHERE exam_description_id = $1
+        ORDER BY id
+        "#,
+        description_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch sections")
+}
@@ -0,0 +1,132 @@
+use crate::database::schema::SearchRow;
+use crate::model::bank::QuestionUsage;
+use crate::model::search::SearchField;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+
+/// Finds the question texts, descriptions and options containing `term`,
+/// ignoring tashkeel and letter variants (see `normalize_arabic`).
+///
+/// With `fuzzy`, fields whose words are similar to the term by trigrams are
+/// returned too. Exact matches score 1, fuzzy ones their word similarity.
+/// Rows are ordered by question, best match first, then text before
+/// description before options.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let rows = search_fields(&pool, "الحمد لله", true).await?;
+/// ```
+pub async fn search_fields(pool: &sqlx::PgPool, term: &str, fuzzy: bool) -> Result<Vec<SearchRow>> {
+    sqlx::query_as!(
+        SearchRow,
+        r#"
+        WITH term AS (
+            SELECT
+                normalize_arabic($1) AS folded,
+                '%' || replace(replace(replace(normalize_arabic($1), '\', '\\'), '%', '\%'), '_', '\_') || '%' AS pattern
+        ),
+        hits AS (
+            SELECT
+                q.id AS question_id,
+                'text' AS field,
+                NULL::int AS option_id,
+                q.text AS content,
+                CASE
+                    WHEN normalize_arabic(q.text) LIKE term.pattern THEN 1
+                    ELSE word_similarity(term.folded, normalize_arabic(q.text))
+                END AS score
+            FROM questions q, term
+            WHERE normalize_arabic(q.text) LIKE term.pattern
+               OR ($2 AND term.folded <% normalize_arabic(q.text))
+            UNION ALL
+            SELECT
+                q.id,
+                'description',
+                NULL,
+                q.description,
+                CASE
+                    WHEN normalize_arabic(q.description) LIKE term.pattern THEN 1
+                    ELSE word_similarity(term.folded, normalize_arabic(q.description))
+                END
+            FROM questions q, term
+            WHERE normalize_arabic(q.description) LIKE term.pattern
+               OR ($2 AND term.folded <% normalize_arabic(q.description))
+            UNION ALL
+            SELECT
+                o.question_id,
+                'option',
+                o.id,
+                o.text,
+                CASE
+                    WHEN normalize_arabic(o.text) LIKE term.pattern THEN 1
+                    ELSE word_similarity(term.folded, normalize_arabic(o.text))
+                END
+            FROM options o, term
+            WHERE normalize_arabic(o.text) LIKE term.pattern
+               OR ($2 AND term.folded <% normalize_arabic(o.text))
+        )
+        SELECT
+            question_id AS "question_id!",
+            field AS "field!: SearchField",
+            option_id,
+            content AS "content!",
+            score::real AS "score!"
+        FROM hits
+        ORDER BY
+            question_id,
+            score DESC,
+            CASE field WHEN 'text' THEN 0 WHEN 'description' THEN 1 ELSE 2 END,
+            option_id
+        "#,
+        term,
+        fuzzy
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to search questions")
+}
+
+/// Lists the exam sections each of `question_ids` is linked into.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let usage = usage_of(&pool, &[10, 11]).await?;
+/// ```
+pub async fn usage_of(
+    pool: &sqlx::PgPool,
+    question_ids: &[i32],
+) -> Result<HashMap<i32, Vec<QuestionUsage>>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            sq.question_id,
+            d.exam_id,
+            d.title AS exam_title,
+            s.id AS section_id,
+            s.title AS section_title
+        FROM section_questions sq
+        JOIN sections s ON sq.section_id = s.id
+        JOIN exam_descriptions d ON s.exam_description_id = d.id
+        WHERE sq.question_id = ANY($1)
+        ORDER BY d.exam_id, s.id
+        "#,
+        question_ids
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch question usage")?;
+
+    let mut usage: HashMap<i32, Vec<QuestionUsage>> = HashMap::new();
+    for row in rows {
+        usage
+            .entry(row.question_id)
+            .or_default()
+            .push(QuestionUsage {
+                exam_id: row.exam_id,
+                exam_title: row.exam_title,
+                section_id: row.section_id,
+                section_title: row.section_title,
+            });
+    }
+    Ok(usage)
+}
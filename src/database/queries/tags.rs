@@ -0,0 +1,264 @@
+use crate::model::tag::{ExamMatch, FacetCount, Tag, TagFacet};
+use anyhow::{Context, Result};
+
+/// Looks up a tag by ID.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let parent = find_tag(&pool, 3).await?;
+/// ```
+pub async fn find_tag(pool: &sqlx::PgPool, tag_id: i32) -> Result<Option<Tag>> {
+    sqlx::query_as!(
+        Tag,
+        r#"
+        SELECT id, facet AS "facet: TagFacet", name, parent_id
+        FROM tags
+        WHERE id = $1
+        "#,
+        tag_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch tag")
+}
+
+/// Creates a tag, returning `None` if its parent already has a child of the
+/// same name.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let surah = create_tag(&pool, TagFacet::Quran, "Al-Baqarah", None).await?;
+/// ```
+pub async fn create_tag(
+    pool: &sqlx::PgPool,
+    facet: TagFacet,
+    name: &str,
+    parent_id: Option<i32>,
+) -> Result<Option<Tag>> {
+    sqlx::query_as!(
+        Tag,
+        r#"
+        INSERT INTO tags (facet, name, parent_id)
+        VALUES ($1, $2, $3)
+        ON CONFLICT DO NOTHING
+        RETURNING id, facet AS "facet: TagFacet", name, parent_id
+        "#,
+        facet as TagFacet,
+        name,
+        parent_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to create tag")
+}
+
+/// Lists every tag, optionally of one facet, parents before children.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let tags = list_tags(&pool, Some(TagFacet::Topic)).await?;
+/// ```
+pub async fn list_tags(pool: &sqlx::PgPool, facet: Option<TagFacet>) -> Result<Vec<Tag>> {
+    sqlx::query_as!(
+        Tag,
+        r#"
+        SELECT id, facet AS "facet: TagFacet", name, parent_id
+        FROM tags
+        WHERE $1::text IS NULL OR facet = $1
+        ORDER BY facet, parent_id NULLS FIRST, name, id
+        "#,
+        facet as Option<TagFacet>
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list tags")
+}
+
+/// Deletes a tag with its descendants, untagging their questions.
+/// Returns `false` if the tag does not exist.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let deleted = delete_tag(&pool, 3).await?;
+/// ```
+pub async fn delete_tag(pool: &sqlx::PgPool, tag_id: i32) -> Result<bool> {
+    let deleted = sqlx::query!(
+        r#"
+        DELETE FROM tags
+        WHERE id = $1
+        "#,
+        tag_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to delete tag")?
+    .rows_affected();
+
+    Ok(deleted > 0)
+}
+
+/// Returns the IDs in `tag_ids` that are not tags.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let missing = missing_tags(&pool, &[3, 4]).await?;
+/// ```
+pub async fn missing_tags(pool: &sqlx::PgPool, tag_ids: &[i32]) -> Result<Vec<i32>> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT requested.id AS "id!"
+        FROM UNNEST($1::int[]) AS requested(id)
+        WHERE NOT EXISTS (SELECT 1 FROM tags t WHERE t.id = requested.id)
+        "#,
+        tag_ids
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to look up tags")
+}
+
+/// Lists the tags of a question.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let tags = question_tags(&pool, 10).await?;
+/// ```
+pub async fn question_tags(pool: &sqlx::PgPool, question_id: i32) -> Result<Vec<Tag>> {
+    sqlx::query_as!(
+        Tag,
+        r#"
+        SELECT t.id, t.facet AS "facet: TagFacet", t.name, t.parent_id
+        FROM question_tags qt
+        JOIN tags t ON qt.tag_id = t.id
+        WHERE qt.question_id = $1
+        ORDER BY t.facet, t.name, t.id
+        "#,
+        question_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch question tags")
+}
+
+/// Adds tags to a question. Tags it already has are left as they are.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// tag_question(&pool, 10, &[3, 4]).await?;
+/// ```
+pub async fn tag_question(pool: &sqlx::PgPool, question_id: i32, tag_ids: &[i32]) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO question_tags (question_id, tag_id)
+        SELECT $1, tag_id FROM UNNEST($2::int[]) AS tag_id
+        ON CONFLICT DO NOTHING
+        "#,
+        question_id,
+        tag_ids
+    )
+    .execute(pool)
+    .await
+    .context("Failed to tag question")?;
+
+    Ok(())
+}
+
+/// Removes a tag from a question. Returns `false` if the question did not
+/// have the tag.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let removed = untag_question(&pool, 10, 3).await?;
+/// ```
+pub async fn untag_question(pool: &sqlx::PgPool, question_id: i32, tag_id: i32) -> Result<bool> {
+    let removed = sqlx::query!(
+        r#"
+        DELETE FROM question_tags
+        WHERE question_id = $1 AND tag_id = $2
+        "#,
+        question_id,
+        tag_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to untag question")?
+    .rows_affected();
+
+    Ok(removed > 0)
+}
+
+/// Counts, for every tag, the questions in `question_ids` carrying the tag
+/// or one of its descendants, and the exams using those questions. Tags
+/// without matching questions are left out.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let facets = facet_counts(&pool, &matching_ids).await?;
+/// ```
+pub async fn facet_counts(pool: &sqlx::PgPool, question_ids: &[i32]) -> Result<Vec<FacetCount>> {
+    sqlx::query_as!(
+        FacetCount,
+        r#"
+        WITH RECURSIVE closure (ancestor, descendant) AS (
+            SELECT id, id FROM tags
+            UNION ALL
+            SELECT c.ancestor, t.id
+            FROM closure c
+            JOIN tags t ON t.parent_id = c.descendant
+        ),
+        matched AS (
+            SELECT qt.question_id, c.ancestor
+            FROM question_tags qt
+            JOIN closure c ON c.descendant = qt.tag_id
+            WHERE qt.question_id = ANY($1)
+        )
+        SELECT
+            t.id AS tag_id,
+            t.facet AS "facet: TagFacet",
+            t.name,
+            t.parent_id,
+            COUNT(DISTINCT m.question_id) AS "questions!",
+            COUNT(DISTINCT d.exam_id) AS "exams!"
+        FROM tags t
+        JOIN matched m ON m.ancestor = t.id
+        LEFT JOIN section_questions sq ON sq.question_id = m.question_id
+        LEFT JOIN sections s ON s.id = sq.section_id
+        LEFT JOIN exam_descriptions d ON d.id = s.exam_description_id
+        GROUP BY t.id
+        ORDER BY t.facet, t.parent_id NULLS FIRST, t.name, t.id
+        "#,
+        question_ids
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to count tags")
+}
+
+/// Lists the exams using any of `question_ids`, with how many of them each
+/// exam uses.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let exams = exams_using(&pool, &matching_ids).await?;
+/// ```
+pub async fn exams_using(pool: &sqlx::PgPool, question_ids: &[i32]) -> Result<Vec<ExamMatch>> {
+    sqlx::query_as!(
+        ExamMatch,
+        r#"
+        SELECT
+            d.exam_id,
+            d.title,
+            COUNT(DISTINCT sq.question_id) AS "questions!"
+        FROM section_questions sq
+        JOIN sections s ON s.id = sq.section_id
+        JOIN exam_descriptions d ON d.id = s.exam_description_id
+        WHERE sq.question_id = ANY($1)
+        GROUP BY d.exam_id, d.title
+        ORDER BY d.exam_id
+        "#,
+        question_ids
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to find exams")
+}
@@ -0,0 +1,104 @@
+use crate::model::usage::{TokenUsage, UsageReportRow, UsageScope};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+
+/// Records the tokens of one LLM call.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// insert_usage(&pool, &scope, "Gemini", "gemini-1.5-flash", &usage).await?;
+/// ```
+pub async fn insert_usage(
+    pool: &sqlx::PgPool,
+    scope: &UsageScope,
+    provider: &str,
+    model: &str,
+    usage: &TokenUsage,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO llm_usage
+            (user_id, exam_id, endpoint, provider, model, prompt_tokens, completion_tokens)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        scope.user_id,
+        scope.exam_id,
+        scope.endpoint,
+        provider,
+        model,
+        usage.prompt_tokens as i64,
+        usage.completion_tokens as i64
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record LLM usage")?;
+    Ok(())
+}
+
+/// Total tokens a user spent since `since`.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let used = user_tokens_since(&pool, "teacher-7", start_of_day).await?;
+/// ```
+pub async fn user_tokens_since(
+    pool: &sqlx::PgPool,
+    user_id: &str,
+    since: DateTime<Utc>,
+) -> Result<u64> {
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(SUM(prompt_tokens + completion_tokens), 0)::BIGINT AS "total!"
+        FROM llm_usage
+        WHERE user_id = $1 AND created_at >= $2
+        "#,
+        user_id,
+        since
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to sum LLM usage")?;
+    Ok(total.max(0) as u64)
+}
+
+/// Sums the usage between `from` and `to` per user, exam and endpoint,
+/// most tokens first, optionally for one user or exam only.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let rows = usage_report(&pool, from, to, None, Some(3)).await?;
+/// ```
+pub async fn usage_report(
+    pool: &sqlx::PgPool,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    user_id: Option<&str>,
+    exam_id: Option<i32>,
+) -> Result<Vec<UsageReportRow>> {
+    sqlx::query_as!(
+        UsageReportRow,
+        r#"
+        SELECT
+            user_id,
+            exam_id,
+            endpoint,
+            COUNT(*) AS "calls!",
+            SUM(prompt_tokens)::BIGINT AS "prompt_tokens!",
+            SUM(completion_tokens)::BIGINT AS "completion_tokens!",
+            SUM(prompt_tokens + completion_tokens)::BIGINT AS "total_tokens!"
+        FROM llm_usage
+        WHERE created_at >= $1 AND created_at < $2
+            AND ($3::TEXT IS NULL OR user_id = $3)
+            AND ($4::INTEGER IS NULL OR exam_id = $4)
+        GROUP BY user_id, exam_id, endpoint
+        ORDER BY SUM(prompt_tokens + completion_tokens) DESC, user_id, exam_id, endpoint
+        "#,
+        from,
+        to,
+        user_id,
+        exam_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to read the LLM usage report")
+}
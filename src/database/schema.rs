@@ -50,11 +50,20 @@ pub struct SectionRow {
     pub section_id: i32,
     pub section_title: String,
     pub section_exam_description_id: i32,
-    pub question_id: i32,
-    pub question_text: String,
+    pub question_id: Option<i32>,
+    pub question_text: Option<String>,
     pub question_description: Option<String>,
-    pub question_marks: i32,
-    pub option_id: i32,
-    pub option_text: String,
+    pub question_marks: Option<i32>,
+    pub option_id: Option<i32>,
+    pub option_text: Option<String>,
     pub option_is_correct: Option<bool>,
 }
+
+#[derive(sqlx::FromRow)]
+pub struct SearchRow {
+    pub question_id: i32,
+    pub field: crate::model::search::SearchField,
+    pub option_id: Option<i32>,
+    pub content: String,
+    pub score: f32,
+}
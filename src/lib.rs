@@ -0,0 +1,6 @@
+pub mod conn;
+pub mod database;
+pub mod model;
+pub mod routes;
+pub mod services;
+pub mod utils;
@@ -1,20 +1,20 @@
-mod database;
-mod conn;
-mod model;
-mod routes;
-mod utils;
-mod services;
-
 use actix_cors::Cors;
 use actix_web::http::header;
 use actix_web::{middleware::Logger, web, App, HttpServer};
 use anyhow::Result;
+use ilmiya::services::cache::LlmCache;
+use ilmiya::services::jobs::JobQueue;
+use ilmiya::services::llm::LlmClient;
+use ilmiya::services::print::PdfRenderer;
+use ilmiya::services::prompts::PromptStore;
+use ilmiya::services::usage::UsageTracker;
+use ilmiya::{conn, model, routes};
 use log::info;
 
 #[actix_web::main]
 async fn main() -> Result<()> {
     std::env::set_var("RUST_LOG", "debug");
-    env_logger::init();    
+    env_logger::init();
 
     let db_client = conn::DbClient::new().await?;
     db_client.run_migrations().await?;
@@ -22,7 +22,33 @@ async fn main() -> Result<()> {
 
     let redis_client = conn::RedisClient::new().await?;
 
-    let app_state = web::Data::new(model::state::AppState { db_client, redis_client });
+    let usage_tracker = UsageTracker::from_env(db_client.pool.clone())?;
+    let llm_client = LlmClient::from_env(usage_tracker.clone())?;
+    info!(
+        "LLM client initialized with the {} provider.",
+        llm_client.provider_name()
+    );
+
+    let llm_cache = LlmCache::from_env(redis_client.clone())?;
+    let job_queue = JobQueue::from_env(redis_client.clone())?;
+    let pdf_renderer = PdfRenderer::from_env()?;
+
+    let prompts = PromptStore::load(db_client.pool.clone()).await?;
+    prompts.spawn_reloader()?;
+    info!("Prompt templates loaded.");
+
+    let app_state = web::Data::new(model::state::AppState {
+        db_client,
+        redis_client,
+        llm_client,
+        llm_cache,
+        job_queue,
+        usage_tracker,
+        prompts,
+        pdf_renderer,
+    });
+
+    routes::jobs::start_workers(app_state.clone());
 
     HttpServer::new(move || {
         App::new()
@@ -31,8 +57,13 @@ async fn main() -> Result<()> {
             .wrap(
                 Cors::default()
                     .allowed_origin("http://localhost:8081")
-                    .allowed_methods(vec!["GET", "POST", "OPTIONS"])
-                    .allowed_headers(vec![header::CONTENT_TYPE])
+                    .allowed_methods(vec!["GET", "POST", "PUT", "DELETE", "OPTIONS"])
+                    .allowed_headers(vec![
+                        header::CONTENT_TYPE,
+                        header::AUTHORIZATION,
+                        header::HeaderName::from_static("x-user-id"),
+                        header::HeaderName::from_static("x-exam-id"),
+                    ])
                     .supports_credentials(),
             )
             .configure(routes::config_routes)
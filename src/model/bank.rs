@@ -0,0 +1,97 @@
+use crate::model::generation::OptionProvenance;
+use crate::model::tag::{FacetCount, Tag};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// An option of a question in the bank.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BankOption {
+    #[serde(default)]
+    pub id: i32,
+    pub text: String,
+    #[serde(default)]
+    pub is_correct: bool,
+    /// Set on generated options. Sent back on update, it is kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<OptionProvenance>,
+}
+
+/// Body for creating or replacing a bank question. IDs are assigned by the
+/// server; option IDs in the body are ignored.
+#[derive(Debug, Deserialize)]
+pub struct BankQuestionRequest {
+    pub text: String,
+    pub description: Option<String>,
+    pub marks: i32,
+    pub options: Vec<BankOption>,
+}
+
+/// A section of an exam that uses a bank question.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct QuestionUsage {
+    pub exam_id: i32,
+    pub exam_title: String,
+    pub section_id: i32,
+    pub section_title: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BankQuestionResponse {
+    pub id: i32,
+    pub text: String,
+    pub description: Option<String>,
+    pub marks: i32,
+    pub created_at: DateTime<Utc>,
+    pub options: Vec<BankOption>,
+    pub tags: Vec<Tag>,
+    pub used_in: Vec<QuestionUsage>,
+}
+
+/// Query parameters of the bank search.
+#[derive(Debug, Deserialize)]
+pub struct BankSearchQuery {
+    /// Matched case-insensitively against question text, description and options.
+    pub q: Option<String>,
+    /// Comma-separated tag IDs, see [`crate::model::tag::parse_tag_ids`].
+    pub tags: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// Default and largest page size of the bank search.
+pub const DEFAULT_SEARCH_LIMIT: i64 = 20;
+pub const MAX_SEARCH_LIMIT: i64 = 100;
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct BankQuestionSummary {
+    pub id: i32,
+    pub text: String,
+    pub description: Option<String>,
+    pub marks: i32,
+    pub created_at: DateTime<Utc>,
+    /// Number of exam sections the question is linked into.
+    pub usage_count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BankSearchResponse {
+    /// Number of matching questions across all pages.
+    pub total: i64,
+    pub questions: Vec<BankQuestionSummary>,
+    /// Tag counts over all matching questions, not just this page.
+    pub facets: Vec<FacetCount>,
+}
+
+/// Query parameters of the bank delete endpoint.
+#[derive(Debug, Deserialize)]
+pub struct DeleteBankQuestionQuery {
+    /// Deletes the question even if exams still use it, removing it from them.
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// Body for linking bank questions into an exam section, in order.
+#[derive(Debug, Deserialize)]
+pub struct LinkQuestionsRequest {
+    pub question_ids: Vec<i32>,
+}
@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+
+/// Spreadsheet formats accepted by the bulk question import.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpreadsheetFormat {
+    Csv,
+    Xlsx,
+}
+
+/// Query parameters of the bulk import endpoints.
+///
+/// `title`, `duration` and `passing_score` are only used when a new exam is
+/// created. With `dry_run` the upload is validated but nothing is written.
+#[derive(Debug, Deserialize)]
+pub struct BulkImportQuery {
+    pub format: SpreadsheetFormat,
+    #[serde(default)]
+    pub dry_run: bool,
+    pub title: Option<String>,
+    pub duration: Option<i32>,
+    pub passing_score: Option<i32>,
+}
+
+/// A problem with one row of the uploaded sheet.
+#[derive(Debug, Serialize)]
+pub struct RowError {
+    /// 1-based row number as shown by spreadsheet programs.
+    pub row: usize,
+    /// Header of the offending column, if the problem is tied to one cell.
+    pub column: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SectionSummary {
+    pub title: String,
+    pub questions: usize,
+    /// Whether the questions go into a section the exam already has.
+    pub existing: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkImportResponse {
+    /// The created or extended exam. Absent when a new exam was not created.
+    pub exam_id: Option<i32>,
+    pub dry_run: bool,
+    /// Number of non-empty rows read, excluding the header.
+    pub rows: usize,
+    /// Number of questions imported, or that would be imported on a dry run.
+    pub questions: usize,
+    pub sections: Vec<SectionSummary>,
+    pub errors: Vec<RowError>,
+}
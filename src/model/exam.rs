@@ -76,3 +76,32 @@ pub struct EditExamRequest {
     pub sections: Vec<SectionRequest>,
     pub delete: DeleteIdsRequest,
 }
+
+impl ExamRequest {
+    /// Creates an exam whose IDs are assigned later, e.g. by
+    /// `database::queries::ids::assign_exam_ids`.
+    pub fn new(
+        title: String,
+        description: Option<String>,
+        duration: i32,
+        passing_score: i32,
+        sections: Vec<SectionRequest>,
+    ) -> Self {
+        Self {
+            exam_id: ExamIdRequestModel {
+                base: schema::ExamModel { id: 0 },
+            },
+            description: ExamDescriptionRequest {
+                base: schema::ExamDescriptionModel {
+                    id: 0,
+                    exam_id: 0,
+                    title,
+                    description,
+                    duration,
+                    passing_score,
+                },
+            },
+            sections,
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::model::prompt::TemplateKey;
+
+/// Response header with the ID of a generation made with an experiment
+/// variant, to report outcomes against.
+pub const GENERATION_ID_HEADER: &str = "X-Generation-Id";
+
+/// One arm of an experiment: a template version and its share of requests.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExperimentVariant {
+    pub id: i32,
+    pub name: String,
+    pub template_id: i32,
+    /// Version label of the template.
+    pub prompt_version: String,
+    pub weight: i32,
+}
+
+/// An A/B experiment between versions of one template series.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Experiment {
+    pub id: i32,
+    pub name: String,
+    #[serde(flatten)]
+    pub key: TemplateKey,
+    /// Whether requests are still split between the variants.
+    pub active: bool,
+    pub variants: Vec<ExperimentVariant>,
+    pub created_at: DateTime<Utc>,
+    pub stopped_at: Option<DateTime<Utc>>,
+}
+
+/// A variant of `POST /prompts/experiments`.
+#[derive(Deserialize, Debug)]
+pub struct CreateVariantRequest {
+    pub name: String,
+    pub template_id: i32,
+    #[serde(default = "default_weight")]
+    pub weight: i32,
+}
+
+fn default_weight() -> i32 {
+    1
+}
+
+/// Body of `POST /prompts/experiments`.
+#[derive(Deserialize, Debug)]
+pub struct CreateExperimentRequest {
+    pub name: String,
+    #[serde(flatten)]
+    pub key: TemplateKey,
+    pub variants: Vec<CreateVariantRequest>,
+}
+
+/// The variant a request was assigned to.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VariantAssignment {
+    pub experiment_id: i32,
+    pub experiment: String,
+    pub variant_id: i32,
+    pub variant: String,
+}
+
+/// How the output of one generation fared before anyone saw it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct GenerationStats {
+    /// Every candidate parsed without a repair round.
+    pub parsed: bool,
+    /// Distractors the model gave, including regenerated ones.
+    pub distractors: usize,
+    /// Distractors that passed the quality gate.
+    pub passed: usize,
+}
+
+/// Body of `POST /generations/{id}/feedback`: an author's verdict on the
+/// distractors of a generation.
+#[derive(Deserialize, Debug, Default)]
+pub struct FeedbackRequest {
+    #[serde(default)]
+    pub accepted: Vec<String>,
+    #[serde(default)]
+    pub rejected: Vec<String>,
+}
+
+/// Body of `POST /generations/{id}/selections`: how students answered a
+/// question with the distractors of a generation. Counts add up over calls.
+#[derive(Deserialize, Debug)]
+pub struct SelectionsRequest {
+    /// Answers given to the question.
+    pub responses: i32,
+    /// Times each distractor shown was picked, zero included; distractors
+    /// left out are not counted.
+    pub selections: HashMap<String, i32>,
+}
+
+/// Outcomes of one variant in `GET /prompts/experiments/{id}/report`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VariantReport {
+    pub variant_id: i32,
+    pub variant: String,
+    pub prompt_version: String,
+    pub weight: i32,
+    pub generations: i64,
+    /// Share of generations whose output parsed without repair.
+    pub parse_success_rate: Option<f64>,
+    /// Share of distractors that passed the quality gate.
+    pub gate_pass_rate: Option<f64>,
+    pub accepted: i64,
+    pub rejected: i64,
+    /// Share of reviewed distractors authors accepted.
+    pub acceptance_rate: Option<f64>,
+    pub responses: i64,
+    pub selections: i64,
+    /// Share of responses that picked a distractor, averaged over the
+    /// distractors shown.
+    pub selection_rate: Option<f64>,
+}
+
+/// Body of `GET /prompts/experiments/{id}/report`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExperimentReport {
+    pub experiment: Experiment,
+    pub variants: Vec<VariantReport>,
+}
+
+/// A generation to record, see [`GenerationStats`].
+#[derive(Debug, Clone)]
+pub struct GenerationRecord {
+    pub id: Uuid,
+    pub variant_id: i32,
+    pub user_id: String,
+    pub endpoint: String,
+    pub stats: GenerationStats,
+}
@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+
+/// Interchange formats exams can be exported to and imported from.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExamFormat {
+    Qti,
+    Moodle,
+    Gift,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FormatQuery {
+    pub format: ExamFormat,
+}
+
+/// Query parameters of the import endpoint.
+///
+/// Question-bank formats (Moodle XML, GIFT) carry no exam metadata, so the
+/// title, duration and passing score can be supplied here. Values found in
+/// the imported file take precedence.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportQuery {
+    pub format: ExamFormat,
+    pub title: Option<String>,
+    pub duration: Option<i32>,
+    pub passing_score: Option<i32>,
+}
+
+/// Something about one imported question that could not be mapped exactly.
+#[derive(Debug, Serialize)]
+pub struct ImportIssue {
+    /// 1-based position of the question in the source file.
+    pub question: usize,
+    pub name: Option<String>,
+    pub question_type: String,
+    /// Whether the question was left out of the import entirely.
+    pub skipped: bool,
+    pub message: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub skipped: usize,
+    pub issues: Vec<ImportIssue>,
+}
+
+impl ImportReport {
+    /// Records a question that was imported with a loss of information.
+    pub fn warn(
+        &mut self,
+        question: usize,
+        name: Option<&str>,
+        question_type: &str,
+        message: impl Into<String>,
+    ) {
+        self.issues.push(ImportIssue {
+            question,
+            name: name.map(str::to_string),
+            question_type: question_type.to_string(),
+            skipped: false,
+            message: message.into(),
+        });
+    }
+
+    /// Records a question that could not be imported at all.
+    pub fn skip(
+        &mut self,
+        question: usize,
+        name: Option<&str>,
+        question_type: &str,
+        message: impl Into<String>,
+    ) {
+        self.skipped += 1;
+        self.issues.push(ImportIssue {
+            question,
+            name: name.map(str::to_string),
+            question_type: question_type.to_string(),
+            skipped: true,
+            message: message.into(),
+        });
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportResponse {
+    /// ID of the created exam, absent when nothing could be imported.
+    pub exam_id: Option<i32>,
+    pub report: ImportReport,
+}
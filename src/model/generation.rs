@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::model::job::JobKind;
+use crate::model::llm::{DistractorType, GenerationControls, Language};
+
+/// Questions of a section generated for at once; the LLM client bounds
+/// the requests in flight further.
+pub const SECTION_FAN_OUT: usize = 4;
+
+/// Body of `POST /exam/{exam_id}/sections/{section_id}/generate-options`.
+#[derive(Debug, Deserialize)]
+pub struct GenerateSectionOptionsRequest {
+    /// Which distractors to generate, as for the job endpoints.
+    pub kind: JobKind,
+    /// Language of the template; required for `context` generation, Arabic
+    /// by default for the others.
+    pub language: Option<Language>,
+    /// Distractors added per question at most, all of them by default.
+    pub max_options: Option<usize>,
+}
+
+/// Query parameters of section generation, next to the `CandidateQuery` ones.
+#[derive(Debug, Default, Deserialize)]
+pub struct GenerateSectionOptionsQuery {
+    /// Return the distractors without saving them.
+    #[serde(default)]
+    pub preview: bool,
+}
+
+/// A question with what distractor generation needs.
+#[derive(Debug, sqlx::FromRow)]
+pub struct SourceQuestion {
+    pub id: i32,
+    pub text: String,
+    /// Text of the first correct option.
+    pub correct_answer: Option<String>,
+    /// Texts of all options, correct or not.
+    pub options: Vec<String>,
+}
+
+/// Body of `POST /bank/questions/{question_id}/distractors`.
+#[derive(Debug, Deserialize)]
+pub struct AttachDistractorsRequest {
+    pub distractor_type: DistractorType,
+    /// Language of the template, Arabic by default.
+    pub language: Option<Language>,
+    /// Distractors added at most, all of them by default.
+    pub max_options: Option<usize>,
+}
+
+/// How a generated option was made. Hand-written options have none.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OptionProvenance {
+    /// The generation that suggested it, e.g. `thematic`.
+    pub distractor_type: String,
+    pub provider: String,
+    pub model: String,
+    /// Version of the template the prompt was rendered from, see
+    /// `TemplateKey::version_label`.
+    pub prompt_version: String,
+}
+
+/// The distractors generated for one question.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GeneratedOptions {
+    pub question_id: i32,
+    /// New options, not counting ones the question already had.
+    pub options: Vec<String>,
+    /// Why nothing was generated for the question.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GenerateSectionOptionsResponse {
+    pub preview: bool,
+    /// Options written, 0 in preview mode.
+    pub inserted: usize,
+    pub questions: Vec<GeneratedOptions>,
+}
+
+/// Body of `POST /mcq/quran`: one question, several types of distractors.
+#[derive(Debug, Deserialize)]
+pub struct QuranDistractorsRequest {
+    pub question: String,
+    pub correct_answer: String,
+    /// Types to generate, each named once.
+    pub distractor_types: Vec<DistractorType>,
+    /// Language of the templates, Arabic by default.
+    pub language: Option<Language>,
+    #[serde(flatten)]
+    pub controls: GenerationControls,
+}
+
+/// The outcome of one type of `POST /mcq/quran`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TypeDistractors {
+    /// Status the route of the type would have answered with.
+    pub status: u16,
+    /// What the route of the type answers with, e.g. `POST /mcq/quran/thematic`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_version: Option<String>,
+    /// Set when an experiment picked the template version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation_id: Option<Uuid>,
+    /// Why nothing was generated for the type.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuranDistractorsResponse {
+    /// Types that failed.
+    pub failed: usize,
+    pub distractors: BTreeMap<DistractorType, TypeDistractors>,
+}
@@ -0,0 +1,213 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::model::llm::{CandidateQuery, DistractorType, GenerationControls, Language};
+use crate::model::prompt::{PromptTask, TemplateKey};
+use crate::model::usage::UsageScope;
+
+/// Most attempts a job may ask for.
+pub const MAX_JOB_ATTEMPTS: u32 = 10;
+
+/// The generation a job runs, one per synchronous `/mcq` route.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    /// `/mcq/options/context`
+    Context,
+    Collection,
+    Diacritic,
+    Phonetic,
+    Morphological,
+    Grammatical,
+    AlternateVerse,
+    Thematic,
+    Collocational,
+}
+
+impl JobKind {
+    pub const ALL: [JobKind; 9] = [
+        Self::Context,
+        Self::Collection,
+        Self::Diacritic,
+        Self::Phonetic,
+        Self::Morphological,
+        Self::Grammatical,
+        Self::AlternateVerse,
+        Self::Thematic,
+        Self::Collocational,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Context => "context",
+            Self::Collection => "collection",
+            Self::Diacritic => "diacritic",
+            Self::Phonetic => "phonetic",
+            Self::Morphological => "morphological",
+            Self::Grammatical => "grammatical",
+            Self::AlternateVerse => "alternate_verse",
+            Self::Thematic => "thematic",
+            Self::Collocational => "collocational",
+        }
+    }
+
+    /// The distractor type of a `/mcq/quran` job, `None` for context jobs.
+    pub fn distractor_type(&self) -> Option<DistractorType> {
+        match self {
+            Self::Context => None,
+            Self::Collection => Some(DistractorType::Collection),
+            Self::Diacritic => Some(DistractorType::Diacritic),
+            Self::Phonetic => Some(DistractorType::Phonetic),
+            Self::Morphological => Some(DistractorType::Morphological),
+            Self::Grammatical => Some(DistractorType::Grammatical),
+            Self::AlternateVerse => Some(DistractorType::AlternateVerse),
+            Self::Thematic => Some(DistractorType::Thematic),
+            Self::Collocational => Some(DistractorType::Collocational),
+        }
+    }
+
+    /// The template series the kind renders in a language.
+    pub fn template_key(&self, language: Language) -> TemplateKey {
+        match self.distractor_type() {
+            Some(distractor_type) => TemplateKey::distractor(distractor_type, language),
+            None => TemplateKey::new(PromptTask::Context, language),
+        }
+    }
+
+    /// The synchronous route of the kind.
+    pub fn endpoint(&self) -> String {
+        match self {
+            Self::Context => "/mcq/options/context".to_string(),
+            _ => format!("/mcq/quran/{}", self.as_str()),
+        }
+    }
+}
+
+impl From<DistractorType> for JobKind {
+    fn from(distractor_type: DistractorType) -> Self {
+        match distractor_type {
+            DistractorType::Collection => Self::Collection,
+            DistractorType::Diacritic => Self::Diacritic,
+            DistractorType::Phonetic => Self::Phonetic,
+            DistractorType::Morphological => Self::Morphological,
+            DistractorType::Grammatical => Self::Grammatical,
+            DistractorType::AlternateVerse => Self::AlternateVerse,
+            DistractorType::Thematic => Self::Thematic,
+            DistractorType::Collocational => Self::Collocational,
+        }
+    }
+}
+
+/// Everything a worker needs to run a job: the body and query of the
+/// equivalent synchronous request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JobRequest {
+    pub kind: JobKind,
+    pub question: String,
+    pub correct_answer: String,
+    /// Language of the template; required for context jobs, Arabic by
+    /// default for the others.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<Language>,
+    #[serde(default)]
+    pub options: CandidateQuery,
+    #[serde(default)]
+    pub controls: GenerationControls,
+    /// Who the LLM calls of the job are accounted to.
+    #[serde(default)]
+    pub usage: UsageScope,
+}
+
+/// Query parameters of the job endpoints, next to the [`CandidateQuery`]
+/// ones of the generation itself.
+#[derive(Deserialize, Debug, Default)]
+pub struct JobQuery {
+    /// Attempts before the job fails, up to [`MAX_JOB_ATTEMPTS`].
+    pub max_attempts: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    /// Failed, and waiting to be run again.
+    Retrying,
+    Succeeded,
+    Failed,
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Succeeded | Self::Failed)
+    }
+}
+
+/// When a failed job is run again.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts in total, including the first.
+    pub max_attempts: u32,
+    /// Wait before the second attempt, doubled for every later one.
+    pub backoff_ms: u64,
+}
+
+impl RetryPolicy {
+    /// Wait before the attempt after `attempt`.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+        Duration::from_millis(self.backoff_ms.saturating_mul(factor))
+    }
+}
+
+/// A generation job as stored in Redis and returned by `GET /jobs/{id}`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Job {
+    pub id: Uuid,
+    pub status: JobStatus,
+    pub request: JobRequest,
+    /// Attempts started so far.
+    pub attempts: u32,
+    pub retry: RetryPolicy,
+    /// The response body the synchronous route would have returned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    /// Version of the prompt template that produced `result`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_version: Option<String>,
+    /// Generation to report outcomes against, when an experiment picked the
+    /// template version, see `POST /generations/{id}/feedback`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation_id: Option<Uuid>,
+    /// Why the last attempt failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// When the job and its result are removed.
+    pub expires_at: DateTime<Utc>,
+}
+
+/// What a successful attempt at a job produced.
+#[derive(Debug, Clone)]
+pub struct JobOutput {
+    /// The response body the synchronous route would have returned.
+    pub result: Value,
+    /// Version of the prompt template the job rendered.
+    pub prompt_version: String,
+    /// The recorded generation, when an experiment picked the version.
+    pub generation_id: Option<Uuid>,
+}
+
+/// Body of the `202 Accepted` response of the job endpoints.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct JobAccepted {
+    pub id: Uuid,
+    pub status: JobStatus,
+    /// Where to poll for the job.
+    pub location: String,
+}
@@ -1,15 +1,58 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::model::usage::TokenUsage;
+
+/// A language MCQs are generated in. Requests name it in full or by its
+/// ISO 639-1 code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Language {
+    #[serde(alias = "ar")]
     Arabic,
+    #[serde(alias = "ur")]
     Urdu,
+    #[serde(alias = "en")]
+    English,
+    #[serde(alias = "id")]
+    Indonesian,
+    #[serde(alias = "tr")]
+    Turkish,
+    #[serde(alias = "fa")]
+    Persian,
 }
 
-pub enum PromptLanguage {
-    Arabic,
-    Urdu,
+impl Language {
+    pub const ALL: [Language; 6] = [
+        Self::Arabic,
+        Self::Urdu,
+        Self::English,
+        Self::Indonesian,
+        Self::Turkish,
+        Self::Persian,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Arabic => "arabic",
+            Self::Urdu => "urdu",
+            Self::English => "english",
+            Self::Indonesian => "indonesian",
+            Self::Turkish => "turkish",
+            Self::Persian => "persian",
+        }
+    }
+
+    /// The ISO 639-1 code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Arabic => "ar",
+            Self::Urdu => "ur",
+            Self::English => "en",
+            Self::Indonesian => "id",
+            Self::Turkish => "tr",
+            Self::Persian => "fa",
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -17,12 +60,23 @@ pub struct ContextFillInThBlankTextGenerationRequest {
     pub question: String,
     pub correct_answer: String,
     pub language: Language,
+    #[serde(flatten)]
+    pub controls: GenerationControls,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct QuranicVerseFillInThBlankTextGenerationRequest {
     pub question: String,
     pub correct_answer: String,
+    /// Language of the template, Arabic unless given.
+    #[serde(default = "quran_language")]
+    pub language: Language,
+    #[serde(flatten)]
+    pub controls: GenerationControls,
+}
+
+fn quran_language() -> Language {
+    Language::Arabic
 }
 
 #[derive(Serialize, Debug)]
@@ -47,6 +101,14 @@ pub struct Part {
 pub struct LLMGenerationConfig {
     pub candidate_count: u32,
     pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_schema: Option<serde_json::Value>,
 }
 
 impl LLMContent {
@@ -64,6 +126,10 @@ impl LLMRequest {
             generation_config: LLMGenerationConfig {
                 candidate_count,
                 temperature: Some(temprature),
+                seed: None,
+                max_output_tokens: None,
+                response_mime_type: None,
+                response_schema: None,
             },
         }
     }
@@ -73,6 +139,26 @@ impl LLMRequest {
 #[serde(rename_all = "camelCase")]
 pub struct LLMResponse {
     pub candidates: Option<Vec<LLMOptionsResponse>>,
+    pub usage_metadata: Option<LLMUsageMetadata>,
+}
+
+/// Tokens Gemini counted for a response; cumulative in streamed ones.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LLMUsageMetadata {
+    #[serde(default)]
+    pub prompt_token_count: u64,
+    #[serde(default)]
+    pub candidates_token_count: u64,
+}
+
+impl From<LLMUsageMetadata> for TokenUsage {
+    fn from(usage: LLMUsageMetadata) -> Self {
+        Self {
+            prompt_tokens: usage.prompt_token_count,
+            completion_tokens: usage.candidates_token_count,
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -151,7 +237,8 @@ pub struct DiacriticDistractorResponse {
     pub diacritic_distractors: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
 pub enum DistractorType {
     Collection,
     Diacritic,
@@ -162,3 +249,321 @@ pub enum DistractorType {
     Thematic,
     Collocational,
 }
+
+impl DistractorType {
+    pub const ALL: [DistractorType; 8] = [
+        Self::Collection,
+        Self::Diacritic,
+        Self::Phonetic,
+        Self::Morphological,
+        Self::Grammatical,
+        Self::AlternateVerse,
+        Self::Thematic,
+        Self::Collocational,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Collection => "collection",
+            Self::Diacritic => "diacritic",
+            Self::Phonetic => "phonetic",
+            Self::Morphological => "morphological",
+            Self::Grammatical => "grammatical",
+            Self::AlternateVerse => "alternate_verse",
+            Self::Thematic => "thematic",
+            Self::Collocational => "collocational",
+        }
+    }
+}
+
+/// How hard the distractors of a generation should make the question.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+/// Fewest and most distractors per list a request may ask for.
+pub const NUM_DISTRACTORS_RANGE: std::ops::RangeInclusive<usize> = 1..=10;
+
+/// Lowest and highest sampling temperature a request may ask for.
+pub const TEMPERATURE_RANGE: std::ops::RangeInclusive<f32> = 0.0..=2.0;
+
+/// Most output tokens a request may ask for.
+pub const MAX_OUTPUT_TOKENS: u32 = 8192;
+
+/// Largest seed, the largest one every provider accepts.
+pub const MAX_SEED: u64 = i32::MAX as u64;
+
+/// Per-request controls of the `/mcq` generation routes, each within the
+/// limits above. The provider defaults apply to those left out.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct GenerationControls {
+    /// Distractors per list; the template is told, and the response has to
+    /// have exactly this many.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_distractors: Option<usize>,
+    /// Passed to the template as `difficulty`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<Difficulty>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Makes sampling repeatable, for providers that support it. Candidates
+    /// sampled one request at a time, with `sampling=sequential` or by
+    /// Ollama, each get their own seed counted up from this one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+}
+
+/// A provider-independent text generation request.
+#[derive(Debug, Clone)]
+pub struct GenerationRequest {
+    pub prompt: String,
+    /// Number of completions to generate.
+    pub candidates: u32,
+    pub temperature: Option<f32>,
+    pub seed: Option<u64>,
+    pub max_output_tokens: Option<u32>,
+    /// JSON schema the output should follow, for providers that support it.
+    pub response_schema: Option<serde_json::Value>,
+}
+
+impl GenerationRequest {
+    pub fn new(prompt: String, candidates: u32, temperature: f32) -> Self {
+        Self {
+            prompt,
+            candidates,
+            temperature: Some(temperature),
+            seed: None,
+            max_output_tokens: None,
+            response_schema: None,
+        }
+    }
+
+    /// Asks for output that follows `schema`.
+    pub fn with_schema(mut self, schema: serde_json::Value) -> Self {
+        self.response_schema = Some(schema);
+        self
+    }
+
+    /// Applies the sampling controls of a request: its temperature, if set,
+    /// replaces the one given to `new`.
+    pub fn with_controls(mut self, controls: &GenerationControls) -> Self {
+        if let Some(temperature) = controls.temperature {
+            self.temperature = Some(temperature);
+        }
+        self.seed = controls.seed;
+        self.max_output_tokens = controls.max_output_tokens;
+        self
+    }
+
+    /// The seed of the `index`-th candidate when candidates are sampled one
+    /// request at a time: `seed + index`, wrapping around past [`MAX_SEED`].
+    /// The same seed for every request would sample the same completion.
+    pub fn candidate_seed(&self, index: u32) -> Option<u64> {
+        self.seed
+            .map(|seed| (seed + u64::from(index)) % (MAX_SEED + 1))
+    }
+}
+
+/// The completions of a [`GenerationRequest`] and the tokens they took.
+#[derive(Debug, Clone, Default)]
+pub struct Completion {
+    pub texts: Vec<String>,
+    pub usage: TokenUsage,
+}
+
+/// A piece of a streamed completion. `usage` counts the whole completion so
+/// far, so the last one reported is the total.
+#[derive(Debug, Clone, Default)]
+pub struct TextDelta {
+    pub text: String,
+    pub usage: Option<TokenUsage>,
+}
+
+/// Body of an OpenAI-compatible `chat/completions` request.
+#[derive(Serialize, Debug)]
+pub struct ChatCompletionRequest {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    pub n: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<serde_json::Value>,
+    /// Send the reply as server-sent events of [`ChatCompletionChunk`]s.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub stream: bool,
+    /// Asks for the usage in a last chunk when streaming.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<serde_json::Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ChatCompletionResponse {
+    pub choices: Vec<ChatChoice>,
+    #[serde(default)]
+    pub usage: Option<ChatUsage>,
+}
+
+/// Tokens an OpenAI-compatible server counted.
+#[derive(Deserialize, Debug)]
+pub struct ChatUsage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+}
+
+impl From<ChatUsage> for TokenUsage {
+    fn from(usage: ChatUsage) -> Self {
+        Self {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ChatChoice {
+    pub message: ChatMessage,
+}
+
+/// One event of a streamed `chat/completions` reply.
+#[derive(Deserialize, Debug)]
+pub struct ChatCompletionChunk {
+    pub choices: Vec<ChatChunkChoice>,
+    /// Only in the last chunk, and only if asked for.
+    #[serde(default)]
+    pub usage: Option<ChatUsage>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ChatChunkChoice {
+    pub delta: ChatDelta,
+}
+
+/// The text a chunk adds to the reply.
+#[derive(Deserialize, Debug)]
+pub struct ChatDelta {
+    pub content: Option<String>,
+}
+
+/// Body of an Ollama `api/generate` request.
+#[derive(Serialize, Debug)]
+pub struct OllamaGenerateRequest {
+    pub model: String,
+    pub prompt: String,
+    pub stream: bool,
+    /// A JSON schema the output must follow.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<serde_json::Value>,
+    pub options: OllamaOptions,
+}
+
+#[derive(Serialize, Debug)]
+pub struct OllamaOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Most tokens to generate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OllamaGenerateResponse {
+    pub response: String,
+    /// Tokens of the prompt and of the response, in the last line of a stream.
+    #[serde(default)]
+    pub prompt_eval_count: Option<u64>,
+    #[serde(default)]
+    pub eval_count: Option<u64>,
+}
+
+impl OllamaGenerateResponse {
+    /// The tokens counted, if this response reports them.
+    pub fn usage(&self) -> Option<TokenUsage> {
+        if self.prompt_eval_count.is_none() && self.eval_count.is_none() {
+            return None;
+        }
+        Some(TokenUsage {
+            prompt_tokens: self.prompt_eval_count.unwrap_or_default(),
+            completion_tokens: self.eval_count.unwrap_or_default(),
+        })
+    }
+}
+
+/// Most candidates one `/mcq` request may ask for.
+pub const MAX_CANDIDATES: u32 = 8;
+
+/// How several candidates are obtained from the provider.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SamplingMode {
+    /// One request asking for all candidates at once.
+    #[default]
+    Batch,
+    /// One request per candidate.
+    Sequential,
+}
+
+/// Query parameters of the `/mcq` routes for merging several candidates.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CandidateQuery {
+    /// Candidates to generate; 1 (the default) returns the plain response.
+    pub candidates: Option<u32>,
+    #[serde(default)]
+    pub sampling: SamplingMode,
+    /// Distractors kept per list, best first; all of them by default.
+    pub top_k: Option<usize>,
+    /// Distractors each list needs to pass the quality gate before the model
+    /// stops being re-asked; see `services::quality`.
+    pub min_distractors: Option<usize>,
+}
+
+/// A distractor dropped by the quality gate.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RejectedDistractor {
+    pub field: String,
+    pub text: String,
+    pub reason: String,
+}
+
+/// A distractor with how many of the parsed candidates suggested it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RankedDistractor {
+    pub text: String,
+    pub count: u32,
+    /// `count` divided by the number of parsed candidates.
+    pub agreement: f64,
+}
+
+/// The merged response: the usual fields with the top distractors, plus the
+/// agreement of each distractor per list.
+#[derive(Serialize, Debug)]
+pub struct RankedResponse {
+    #[serde(flatten)]
+    pub response: serde_json::Map<String, serde_json::Value>,
+    /// Candidates that parsed and were merged.
+    pub candidates: u32,
+    pub scores: std::collections::BTreeMap<String, Vec<RankedDistractor>>,
+    /// Distractors the quality gate dropped.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rejected: Vec<RejectedDistractor>,
+}
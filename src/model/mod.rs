@@ -6,4 +6,15 @@ pub mod question;
 pub mod option;
 pub mod delete;
 pub mod llm;
-pub mod quran;
\ No newline at end of file
+pub mod quran;
+pub mod format;
+pub mod bulk;
+pub mod print;
+pub mod bank;
+pub mod tag;
+pub mod search;
+pub mod job;
+pub mod generation;
+pub mod usage;
+pub mod prompt;
+pub mod experiment;
@@ -14,3 +14,24 @@ pub struct OptionRequestModel {
     #[serde(flatten)]
     pub base: schema::OptionsModel,
 }
+
+impl OptionRequestModel {
+    /// Creates an option whose IDs are assigned later, e.g. by
+    /// `database::queries::ids::assign_exam_ids`.
+    pub fn new(text: String, is_correct: bool) -> Self {
+        Self {
+            base: schema::OptionsModel {
+                id: 0,
+                question_id: 0,
+                text,
+                is_correct: Some(is_correct),
+            },
+        }
+    }
+}
+
+impl From<OptionResponseModel> for OptionRequestModel {
+    fn from(option: OptionResponseModel) -> Self {
+        Self { base: option.base }
+    }
+}
@@ -0,0 +1,53 @@
+use serde::Deserialize;
+
+/// Output formats of the printable exam.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrintFormat {
+    #[default]
+    Html,
+    Pdf,
+}
+
+/// Query parameters of the print endpoint.
+#[derive(Debug, Deserialize)]
+pub struct PrintQuery {
+    #[serde(default)]
+    pub format: PrintFormat,
+    /// Adds an answer-key page for every variant.
+    #[serde(default)]
+    pub answer_key: bool,
+    /// Number of papers (A, B, C, ...) to print, at most 26.
+    pub variants: Option<usize>,
+    /// Shuffles the options of every paper. Defaults to on when more than one
+    /// variant is printed.
+    pub shuffle: Option<bool>,
+}
+
+/// Largest number of variants, one per letter of the alphabet.
+pub const MAX_VARIANTS: usize = 26;
+
+/// Settings for rendering a printable exam.
+#[derive(Debug, Clone, Copy)]
+pub struct PrintOptions {
+    pub answer_key: bool,
+    pub variants: usize,
+    pub shuffle: bool,
+}
+
+impl PrintQuery {
+    /// Resolves the query into rendering settings, or `None` if the number of
+    /// variants is out of range.
+    pub fn options(&self) -> Option<PrintOptions> {
+        let variants = self.variants.unwrap_or(1);
+        if !(1..=MAX_VARIANTS).contains(&variants) {
+            return None;
+        }
+
+        Some(PrintOptions {
+            answer_key: self.answer_key,
+            variants,
+            shuffle: self.shuffle.unwrap_or(variants > 1),
+        })
+    }
+}
@@ -0,0 +1,277 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::model::experiment::VariantAssignment;
+use crate::model::job::JobKind;
+use crate::model::llm::{Difficulty, DistractorType, GenerationControls, Language};
+
+/// What a prompt template asks the model for.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptTask {
+    /// Distractors for a blank in a passage, `/mcq/options/context`.
+    Context,
+    /// The verse a blank was cut from.
+    QuranicVerse,
+    /// Distractors of one type for a blank in a verse, `/mcq/quran/{kind}`.
+    QuranicVerseDistractor,
+}
+
+impl PromptTask {
+    pub const ALL: [PromptTask; 3] = [
+        Self::Context,
+        Self::QuranicVerse,
+        Self::QuranicVerseDistractor,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Context => "context",
+            Self::QuranicVerse => "quranic_verse",
+            Self::QuranicVerseDistractor => "quranic_verse_distractor",
+        }
+    }
+}
+
+/// Identifies a series of template versions, of which one is active.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TemplateKey {
+    pub task: PromptTask,
+    pub language: Language,
+    /// Set for `quranic_verse_distractor` templates only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distractor_type: Option<DistractorType>,
+}
+
+impl TemplateKey {
+    pub fn new(task: PromptTask, language: Language) -> Self {
+        Self {
+            task,
+            language,
+            distractor_type: None,
+        }
+    }
+
+    pub fn distractor(distractor_type: DistractorType, language: Language) -> Self {
+        Self {
+            task: PromptTask::QuranicVerseDistractor,
+            language,
+            distractor_type: Some(distractor_type),
+        }
+    }
+
+    /// Names a version of the series, e.g.
+    /// `quranic_verse_distractor/arabic/thematic@3`.
+    pub fn version_label(&self, version: i32) -> String {
+        format!("{}@{}", self, version)
+    }
+}
+
+impl fmt::Display for TemplateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.task.as_str(), self.language.as_str())?;
+        if let Some(distractor_type) = self.distractor_type {
+            write!(f, "/{}", distractor_type.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses the name a series displays as, e.g. `context/english` or
+/// `quranic_verse_distractor/turkish/thematic`.
+impl FromStr for TemplateKey {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid template series `{}`", name);
+        let mut parts = name.split('/');
+        let (Some(task), Some(language)) = (parts.next(), parts.next()) else {
+            return Err(invalid());
+        };
+        let task = PromptTask::ALL
+            .into_iter()
+            .find(|t| t.as_str() == task)
+            .ok_or_else(invalid)?;
+        let language = Language::ALL
+            .into_iter()
+            .find(|l| l.as_str() == language)
+            .ok_or_else(invalid)?;
+        let distractor_type = match parts.next() {
+            Some(distractor_type) => Some(
+                DistractorType::ALL
+                    .into_iter()
+                    .find(|d| d.as_str() == distractor_type)
+                    .ok_or_else(invalid)?,
+            ),
+            None => None,
+        };
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            task,
+            language,
+            distractor_type,
+        })
+    }
+}
+
+/// A worked example a template can show the model, as `examples`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PromptExample {
+    pub question: String,
+    pub correct_answer: String,
+    #[serde(default)]
+    pub distractors: Vec<String>,
+}
+
+/// A template version as returned by the `/prompts/templates` endpoints.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PromptTemplate {
+    pub id: i32,
+    #[serde(flatten)]
+    pub key: TemplateKey,
+    pub version: i32,
+    /// `{task}/{language}[/{distractor_type}]@{version}`, as recorded with
+    /// what the version generated.
+    pub label: String,
+    pub body: String,
+    pub examples: Vec<PromptExample>,
+    pub note: Option<String>,
+    /// Whether this is the version in use.
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Body of `POST /prompts/templates`: the first or next version of a series.
+#[derive(Deserialize, Debug)]
+pub struct CreatePromptTemplateRequest {
+    #[serde(flatten)]
+    pub key: TemplateKey,
+    pub body: String,
+    #[serde(default)]
+    pub examples: Vec<PromptExample>,
+    pub note: Option<String>,
+    /// Make the new version the one in use.
+    #[serde(default)]
+    pub activate: bool,
+}
+
+/// Body of `PUT /prompts/templates/{id}`: a new version of the series of
+/// `id`. Versions themselves never change.
+#[derive(Deserialize, Debug)]
+pub struct UpdatePromptTemplateRequest {
+    pub body: String,
+    #[serde(default)]
+    pub examples: Vec<PromptExample>,
+    pub note: Option<String>,
+    #[serde(default)]
+    pub activate: bool,
+}
+
+/// Query parameters of `GET /prompts/templates`.
+#[derive(Deserialize, Debug, Default)]
+pub struct PromptTemplateQuery {
+    pub task: Option<PromptTask>,
+    pub language: Option<Language>,
+    pub distractor_type: Option<DistractorType>,
+    /// Only the versions in use.
+    #[serde(default)]
+    pub active: bool,
+}
+
+/// A snippet templates include with `{% include "name" %}`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PromptPartial {
+    pub name: String,
+    pub body: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Body of `PUT /prompts/partials/{name}`.
+#[derive(Deserialize, Debug)]
+pub struct PutPromptPartialRequest {
+    pub body: String,
+}
+
+/// The values a template is rendered with, besides the `examples` of the
+/// template. Strings are escaped when interpolated, see
+/// `utils::prompts::escape_input`. The controls a request leaves out are
+/// `none`.
+#[derive(Serialize, Debug, Clone)]
+pub struct PromptContext<'a> {
+    pub question: &'a str,
+    pub correct_answer: &'a str,
+    pub difficulty: Option<Difficulty>,
+    pub num_distractors: Option<usize>,
+}
+
+impl<'a> PromptContext<'a> {
+    pub fn new(question: &'a str, correct_answer: &'a str) -> Self {
+        Self {
+            question: question.trim(),
+            correct_answer: correct_answer.trim(),
+            difficulty: None,
+            num_distractors: None,
+        }
+    }
+
+    /// Passes the controls of a request the template can use.
+    pub fn with_controls(mut self, controls: &GenerationControls) -> Self {
+        self.difficulty = controls.difficulty;
+        self.num_distractors = controls.num_distractors;
+        self
+    }
+}
+
+/// A prompt filled in from the active template, or from the version an
+/// experiment assigned, with the version label of the template.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderedPrompt {
+    pub text: String,
+    pub version: String,
+    /// Set when a running experiment picked the version.
+    pub variant: Option<VariantAssignment>,
+}
+
+/// A language of `GET /mcq/capabilities`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LanguageCapability {
+    pub language: Language,
+    /// ISO 639-1 code, accepted wherever the language is.
+    pub code: String,
+}
+
+/// A generation route of `GET /mcq/capabilities` and the languages it has
+/// a template in.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TaskCapability {
+    pub kind: JobKind,
+    pub task: PromptTask,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distractor_type: Option<DistractorType>,
+    pub endpoint: String,
+    pub languages: Vec<Language>,
+}
+
+/// Body of `GET /mcq/capabilities`: the language × task matrix.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Capabilities {
+    pub languages: Vec<LanguageCapability>,
+    pub tasks: Vec<TaskCapability>,
+}
+
+/// Body of the `400 Bad Request` of a generation in a language its task
+/// has no template in.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UnsupportedLanguage {
+    pub message: String,
+    pub task: PromptTask,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distractor_type: Option<DistractorType>,
+    pub language: Language,
+    pub supported_languages: Vec<Language>,
+}
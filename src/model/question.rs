@@ -16,3 +16,34 @@ pub struct QuestionRequest {
     pub base: schema::QuestionsModel,
     pub options: Vec<OptionRequestModel>,
 }
+
+impl QuestionRequest {
+    /// Creates a question whose IDs are assigned later, e.g. by
+    /// `database::queries::ids::assign_exam_ids`.
+    pub fn new(
+        text: String,
+        description: Option<String>,
+        marks: i32,
+        options: Vec<OptionRequestModel>,
+    ) -> Self {
+        Self {
+            base: schema::QuestionsModel {
+                id: 0,
+                section_id: 0,
+                text,
+                description,
+                marks,
+            },
+            options,
+        }
+    }
+}
+
+impl From<QuestionResponse> for QuestionRequest {
+    fn from(question: QuestionResponse) -> Self {
+        Self {
+            base: question.base,
+            options: question.options.into_iter().map(Into::into).collect(),
+        }
+    }
+}
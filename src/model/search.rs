@@ -0,0 +1,47 @@
+use crate::model::bank::QuestionUsage;
+use serde::{Deserialize, Serialize};
+
+/// Query parameters of the question search.
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    /// Also returns near matches, e.g. with a misspelt word. On by default.
+    pub fuzzy: Option<bool>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// The part of a question a search term was found in.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum SearchField {
+    Text,
+    Description,
+    Option,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchMatch {
+    pub field: SearchField,
+    /// The matching option, for option matches.
+    pub option_id: Option<i32>,
+    /// HTML-escaped excerpt with the matched words wrapped in `<mark>`.
+    pub snippet: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    pub question_id: i32,
+    /// 1 for exact matches, otherwise the trigram word similarity.
+    pub score: f32,
+    pub matches: Vec<SearchMatch>,
+    pub used_in: Vec<QuestionUsage>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResponse {
+    /// Number of matching questions across all pages.
+    pub total: usize,
+    pub results: Vec<SearchHit>,
+}
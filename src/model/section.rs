@@ -16,3 +16,27 @@ pub struct SectionResponse {
     pub base: schema::SectionsModel,
     pub questions: Vec<QuestionResponse>,
 }
+
+impl SectionRequest {
+    /// Creates a section whose IDs are assigned later, e.g. by
+    /// `database::queries::ids::assign_exam_ids`.
+    pub fn new(title: String, questions: Vec<QuestionRequest>) -> Self {
+        Self {
+            base: schema::SectionsModel {
+                id: 0,
+                exam_description_id: 0,
+                title,
+            },
+            questions,
+        }
+    }
+}
+
+impl From<SectionResponse> for SectionRequest {
+    fn from(section: SectionResponse) -> Self {
+        Self {
+            base: section.base,
+            questions: section.questions.into_iter().map(Into::into).collect(),
+        }
+    }
+}
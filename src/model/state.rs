@@ -1,7 +1,19 @@
 use crate::conn;
+use crate::services::cache::LlmCache;
+use crate::services::jobs::JobQueue;
+use crate::services::llm::LlmClient;
+use crate::services::print::PdfRenderer;
+use crate::services::prompts::PromptStore;
+use crate::services::usage::UsageTracker;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db_client: conn::DbClient,
-    pub redis_client: conn::RedisClient
+    pub redis_client: conn::RedisClient,
+    pub llm_client: LlmClient,
+    pub llm_cache: LlmCache,
+    pub job_queue: JobQueue,
+    pub usage_tracker: UsageTracker,
+    pub prompts: PromptStore,
+    pub pdf_renderer: PdfRenderer,
 }
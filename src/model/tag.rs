@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+
+/// The kinds of tags. Tags of every facet can be nested, e.g. a Quran
+/// reference `Al-Baqarah > 2:255` or a topic `Fiqh > Salah`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum TagFacet {
+    Quran,
+    Topic,
+    Skill,
+    Difficulty,
+    DistractorType,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct Tag {
+    pub id: i32,
+    pub facet: TagFacet,
+    pub name: String,
+    pub parent_id: Option<i32>,
+}
+
+/// A tag with its child tags, as listed by the taxonomy endpoint.
+#[derive(Debug, Serialize)]
+pub struct TagNode {
+    #[serde(flatten)]
+    pub tag: Tag,
+    pub children: Vec<TagNode>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateTagRequest {
+    pub facet: TagFacet,
+    pub name: String,
+    /// Parent tag, which must be of the same facet.
+    pub parent_id: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TagListQuery {
+    pub facet: Option<TagFacet>,
+}
+
+/// Body for tagging a question.
+#[derive(Debug, Deserialize)]
+pub struct TagQuestionRequest {
+    pub tag_ids: Vec<i32>,
+}
+
+/// Number of matching questions, and of exams using them, carrying a tag
+/// or one of its descendants.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct FacetCount {
+    pub tag_id: i32,
+    pub facet: TagFacet,
+    pub name: String,
+    pub parent_id: Option<i32>,
+    pub questions: i64,
+    pub exams: i64,
+}
+
+/// Parses a comma-separated list of tag IDs from a query string.
+///
+/// Questions match when, for every facet in the list, they carry one of the
+/// listed tags of that facet or a descendant of it: tags are OR-ed within a
+/// facet and AND-ed across facets.
+pub fn parse_tag_ids(tags: Option<&str>) -> Result<Vec<i32>, std::num::ParseIntError> {
+    tags.unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// Query parameters of the exam search.
+#[derive(Debug, Deserialize)]
+pub struct ExamSearchQuery {
+    pub q: Option<String>,
+    /// Comma-separated tag IDs, see [`parse_tag_ids`].
+    pub tags: Option<String>,
+}
+
+/// An exam using at least one matching question.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ExamMatch {
+    pub exam_id: i32,
+    pub title: String,
+    /// Number of matching questions in the exam.
+    pub questions: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExamSearchResponse {
+    pub exams: Vec<ExamMatch>,
+    pub facets: Vec<FacetCount>,
+}
@@ -0,0 +1,114 @@
+use std::ops::AddAssign;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// User of calls made without an `X-User-Id` header.
+pub const ANONYMOUS_USER: &str = "anonymous";
+
+/// Tokens a provider reports for one call.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn total(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+impl AddAssign for TokenUsage {
+    fn add_assign(&mut self, other: Self) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+    }
+}
+
+/// Who an LLM call is made for: the user, the exam if the request names
+/// one, and the route pattern it came through.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UsageScope {
+    pub user_id: String,
+    pub exam_id: Option<i32>,
+    pub endpoint: String,
+}
+
+impl Default for UsageScope {
+    /// Calls made outside any request, e.g. by tooling.
+    fn default() -> Self {
+        Self {
+            user_id: ANONYMOUS_USER.to_string(),
+            exam_id: None,
+            endpoint: "unscoped".to_string(),
+        }
+    }
+}
+
+/// Token budget of a user over one quota period.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QuotaBudget {
+    pub limit: u64,
+    pub used: u64,
+    pub remaining: u64,
+    pub resets_at: DateTime<Utc>,
+}
+
+/// Body of the `429 Too Many Requests` answered once a quota is spent.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QuotaExceeded {
+    pub message: String,
+    pub user_id: String,
+    /// `None` when the period has no quota.
+    pub daily: Option<QuotaBudget>,
+    pub monthly: Option<QuotaBudget>,
+}
+
+impl QuotaExceeded {
+    /// When the user may generate again: the reset of the latest spent quota.
+    pub fn resets_at(&self) -> Option<DateTime<Utc>> {
+        [&self.daily, &self.monthly]
+            .into_iter()
+            .flatten()
+            .filter(|budget| budget.remaining == 0)
+            .map(|budget| budget.resets_at)
+            .max()
+    }
+}
+
+/// Query parameters of `GET /admin/usage`.
+#[derive(Deserialize, Debug, Default)]
+pub struct UsageReportQuery {
+    /// Start of the report, 30 days ago by default.
+    pub from: Option<DateTime<Utc>>,
+    /// End of the report, now by default.
+    pub to: Option<DateTime<Utc>>,
+    pub user_id: Option<String>,
+    pub exam_id: Option<i32>,
+}
+
+/// Tokens spent by one user on one endpoint for one exam.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UsageReportRow {
+    pub user_id: String,
+    pub exam_id: Option<i32>,
+    pub endpoint: String,
+    pub calls: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub total_tokens: i64,
+}
+
+/// Body of `GET /admin/usage`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UsageReport {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// The configured quotas, `None` when a period has none.
+    pub daily_quota: Option<u64>,
+    pub monthly_quota: Option<u64>,
+    pub calls: i64,
+    pub total_tokens: i64,
+    pub rows: Vec<UsageReportRow>,
+}
@@ -0,0 +1,311 @@
+use crate::database::queries;
+use crate::model;
+use crate::model::bank::{
+    BankQuestionRequest, BankSearchQuery, DeleteBankQuestionQuery, LinkQuestionsRequest,
+    DEFAULT_SEARCH_LIMIT, MAX_SEARCH_LIMIT,
+};
+use crate::model::tag::TagQuestionRequest;
+use crate::routes::tags::tag_filter;
+use actix_web::{web, HttpResponse};
+use anyhow::Result;
+use serde_json::json;
+
+pub fn parse_question_id(question_id: web::Path<String>) -> Result<i32, actix_web::Error> {
+    question_id.into_inner().parse().map_err(|e| {
+        log::error!("Invalid question id: {:?}", e);
+        actix_web::error::ErrorBadRequest("Invalid question id")
+    })
+}
+
+/// Checks the parts of a bank question the database does not enforce.
+fn validate_question(question: &BankQuestionRequest) -> Result<(), actix_web::Error> {
+    let problem = if question.text.trim().is_empty() {
+        Some("Question text is empty")
+    } else if question.marks < 1 {
+        Some("Marks must be at least 1")
+    } else if question.options.len() < 2 {
+        Some("A question needs at least two options")
+    } else if !question.options.iter().any(|o| o.is_correct) {
+        Some("No correct option is marked")
+    } else {
+        None
+    };
+
+    match problem {
+        Some(message) => Err(actix_web::error::ErrorBadRequest(message)),
+        None => Ok(()),
+    }
+}
+
+pub async fn create_question(
+    app_state: web::Data<model::state::AppState>,
+    req_body: web::Json<BankQuestionRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    validate_question(&req_body)?;
+
+    let pool = &app_state.db_client.pool;
+    let question = async {
+        let id = queries::bank::create_question(pool, &req_body).await?;
+        queries::bank::read_question(pool, id).await
+    }
+    .await
+    .map_err(|e| {
+        log::error!("Failed to create bank question: {:?}", e);
+        actix_web::error::ErrorInternalServerError("Internal server error")
+    })?;
+
+    Ok(HttpResponse::Created().json(question))
+}
+
+pub async fn search_questions(
+    app_state: web::Data<model::state::AppState>,
+    query: web::Query<BankSearchQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .clamp(1, MAX_SEARCH_LIMIT);
+    let offset = query.offset.unwrap_or_default().max(0);
+    let tag_ids = match tag_filter(&app_state, query.tags.as_deref()).await? {
+        Ok(tag_ids) => tag_ids,
+        Err(response) => return Ok(response),
+    };
+
+    let page = queries::bank::search_questions(
+        &app_state.db_client.pool,
+        query.q.as_deref(),
+        &tag_ids,
+        limit,
+        offset,
+    )
+    .await
+    .map_err(|e| {
+        log::error!("Failed to search question bank: {:?}", e);
+        actix_web::error::ErrorInternalServerError("Internal server error")
+    })?;
+
+    Ok(HttpResponse::Ok().json(page))
+}
+
+pub async fn fetch_question(
+    app_state: web::Data<model::state::AppState>,
+    question_id: web::Path<String>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let question_id = parse_question_id(question_id)?;
+
+    let question = queries::bank::read_question(&app_state.db_client.pool, question_id)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to fetch bank question: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Question not found"))?;
+
+    Ok(HttpResponse::Ok().json(question))
+}
+
+/// Replaces a bank question. The change shows in every exam that uses it.
+pub async fn update_question(
+    app_state: web::Data<model::state::AppState>,
+    question_id: web::Path<String>,
+    req_body: web::Json<BankQuestionRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let question_id = parse_question_id(question_id)?;
+    validate_question(&req_body)?;
+
+    let pool = &app_state.db_client.pool;
+    let question = async {
+        if !queries::bank::update_question(pool, question_id, &req_body).await? {
+            return Ok(None);
+        }
+        queries::bank::read_question(pool, question_id).await
+    }
+    .await
+    .map_err(|e: anyhow::Error| {
+        log::error!("Failed to update bank question: {:?}", e);
+        actix_web::error::ErrorInternalServerError("Internal server error")
+    })?
+    .ok_or_else(|| actix_web::error::ErrorNotFound("Question not found"))?;
+
+    Ok(HttpResponse::Ok().json(question))
+}
+
+/// Deletes a bank question. A question still used by exams is only deleted
+/// with `force=true`; otherwise the response is a 409 listing the exams.
+pub async fn delete_question(
+    app_state: web::Data<model::state::AppState>,
+    question_id: web::Path<String>,
+    query: web::Query<DeleteBankQuestionQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let question_id = parse_question_id(question_id)?;
+    let pool = &app_state.db_client.pool;
+
+    if !query.force {
+        let used_in = queries::bank::question_usage(pool, question_id)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to fetch bank question usage: {:?}", e);
+                actix_web::error::ErrorInternalServerError("Internal server error")
+            })?;
+        if !used_in.is_empty() {
+            return Ok(HttpResponse::Conflict().json(json!({
+                "message": "Question is used by exams; pass force=true to delete it anyway",
+                "used_in": used_in,
+            })));
+        }
+    }
+
+    let deleted = queries::bank::delete_question(pool, question_id)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to delete bank question: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?;
+    if !deleted {
+        return Err(actix_web::error::ErrorNotFound("Question not found"));
+    }
+
+    Ok(HttpResponse::Ok().json("Question deleted successfully"))
+}
+
+/// Checks that the section belongs to the exam, answering 404 otherwise.
+async fn check_section(
+    app_state: &model::state::AppState,
+    exam_id: i32,
+    section_id: i32,
+) -> Result<(), actix_web::Error> {
+    let found = queries::bank::section_in_exam(&app_state.db_client.pool, exam_id, section_id)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to look up section: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?;
+
+    if found {
+        Ok(())
+    } else {
+        Err(actix_web::error::ErrorNotFound("Section not found in exam"))
+    }
+}
+
+/// Adds bank questions to the end of an exam section.
+pub async fn link_questions(
+    app_state: web::Data<model::state::AppState>,
+    path: web::Path<(i32, i32)>,
+    req_body: web::Json<LinkQuestionsRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (exam_id, section_id) = path.into_inner();
+    check_section(&app_state, exam_id, section_id).await?;
+
+    let pool = &app_state.db_client.pool;
+    let missing = queries::bank::missing_questions(pool, &req_body.question_ids)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to look up bank questions: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?;
+    if !missing.is_empty() {
+        return Ok(HttpResponse::NotFound().json(json!({
+            "message": "Questions not found in the bank",
+            "question_ids": missing,
+        })));
+    }
+
+    queries::bank::link_to_section(pool, section_id, &req_body.question_ids)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to link bank questions: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Removes a question from an exam section; it stays in the bank.
+pub async fn unlink_question(
+    app_state: web::Data<model::state::AppState>,
+    path: web::Path<(i32, i32, i32)>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (exam_id, section_id, question_id) = path.into_inner();
+    check_section(&app_state, exam_id, section_id).await?;
+
+    let removed =
+        queries::bank::unlink_from_section(&app_state.db_client.pool, section_id, question_id)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to unlink bank question: {:?}", e);
+                actix_web::error::ErrorInternalServerError("Internal server error")
+            })?;
+    if !removed {
+        return Err(actix_web::error::ErrorNotFound("Question not in section"));
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Adds tags to a bank question.
+pub async fn tag_question(
+    app_state: web::Data<model::state::AppState>,
+    question_id: web::Path<String>,
+    req_body: web::Json<TagQuestionRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let question_id = parse_question_id(question_id)?;
+    let pool = &app_state.db_client.pool;
+
+    let missing = queries::bank::missing_questions(pool, &[question_id])
+        .await
+        .map_err(|e| {
+            log::error!("Failed to look up bank question: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?;
+    if !missing.is_empty() {
+        return Err(actix_web::error::ErrorNotFound("Question not found"));
+    }
+
+    let missing = queries::tags::missing_tags(pool, &req_body.tag_ids)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to look up tags: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?;
+    if !missing.is_empty() {
+        return Ok(HttpResponse::NotFound().json(json!({
+            "message": "Tags not found",
+            "tag_ids": missing,
+        })));
+    }
+
+    let tags = async {
+        queries::tags::tag_question(pool, question_id, &req_body.tag_ids).await?;
+        queries::tags::question_tags(pool, question_id).await
+    }
+    .await
+    .map_err(|e| {
+        log::error!("Failed to tag bank question: {:?}", e);
+        actix_web::error::ErrorInternalServerError("Internal server error")
+    })?;
+
+    Ok(HttpResponse::Ok().json(tags))
+}
+
+/// Removes a tag from a bank question.
+pub async fn untag_question(
+    app_state: web::Data<model::state::AppState>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (question_id, tag_id) = path.into_inner();
+
+    let removed = queries::tags::untag_question(&app_state.db_client.pool, question_id, tag_id)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to untag bank question: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?;
+    if !removed {
+        return Err(actix_web::error::ErrorNotFound(
+            "Question does not have the tag",
+        ));
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
@@ -1,4 +1,4 @@
-use crate::{database::queries, conn, model};
+use crate::{conn, database::queries, model};
 use actix_web::{web, HttpResponse};
 use anyhow::{Context, Result};
 
@@ -6,7 +6,7 @@ pub async fn delete(
     db_client: &conn::DbClient,
     exam: &model::exam::EditExamRequest,
 ) -> Result<HttpResponse> {
-    queries::delete::delete_related_entities(&db_client.pool, &exam.delete)
+    queries::delete::delete_related_entities(&db_client.pool, exam.exam_id.base.id, &exam.delete)
         .await
         .context("Failed to delete sections/questions/options")?;
 
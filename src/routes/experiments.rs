@@ -0,0 +1,240 @@
+use crate::database::queries;
+use crate::model;
+use crate::model::experiment::{
+    CreateExperimentRequest, Experiment, ExperimentReport, FeedbackRequest, SelectionsRequest,
+};
+use actix_web::{web, HttpResponse};
+use log::error;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+fn internal_error(context: &str, e: anyhow::Error) -> actix_web::Error {
+    error!("{}: {:?}", context, e);
+    actix_web::error::ErrorInternalServerError("Internal server error")
+}
+
+async fn find_experiment(
+    app_state: &model::state::AppState,
+    id: i32,
+) -> Result<Experiment, actix_web::Error> {
+    queries::experiments::get_experiment(&app_state.db_client.pool, id)
+        .await
+        .map_err(|e| internal_error("Failed to fetch prompt experiment", e))?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Prompt experiment not found"))
+}
+
+/// Makes the prompt store pick up a started or stopped experiment right
+/// away.
+async fn reload(app_state: &model::state::AppState) {
+    if let Err(e) = app_state.prompts.reload().await {
+        log::warn!("Failed to reload prompt templates: {:?}", e);
+    }
+}
+
+/// Fails with 404 unless the generation was recorded.
+async fn require_generation(
+    app_state: &model::state::AppState,
+    id: Uuid,
+) -> Result<(), actix_web::Error> {
+    let found = queries::experiments::generation_exists(&app_state.db_client.pool, id)
+        .await
+        .map_err(|e| internal_error("Failed to look up generation", e))?;
+    if !found {
+        return Err(actix_web::error::ErrorNotFound("Generation not found"));
+    }
+    Ok(())
+}
+
+/// Lists experiments, newest first.
+pub async fn list_experiments(
+    app_state: web::Data<model::state::AppState>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let experiments = queries::experiments::list_experiments(&app_state.db_client.pool, false)
+        .await
+        .map_err(|e| internal_error("Failed to list prompt experiments", e))?;
+
+    Ok(HttpResponse::Ok().json(experiments))
+}
+
+/// Starts splitting the requests of a series between versions of it. At
+/// most one experiment runs per series.
+pub async fn create_experiment(
+    app_state: web::Data<model::state::AppState>,
+    req_body: web::Json<CreateExperimentRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if req_body.name.trim().is_empty() {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Experiment name is required",
+        ));
+    }
+    if req_body.variants.len() < 2 {
+        return Err(actix_web::error::ErrorBadRequest(
+            "An experiment needs at least two variants",
+        ));
+    }
+    let mut names = HashSet::new();
+    for variant in &req_body.variants {
+        let name = variant.name.trim();
+        if name.is_empty() || !names.insert(name) {
+            return Err(actix_web::error::ErrorBadRequest(
+                "Variant names must be unique and not empty",
+            ));
+        }
+        if variant.weight <= 0 {
+            return Err(actix_web::error::ErrorBadRequest(
+                "Variant weights must be positive",
+            ));
+        }
+
+        let template =
+            queries::prompts::get_template(&app_state.db_client.pool, variant.template_id)
+                .await
+                .map_err(|e| internal_error("Failed to fetch prompt template", e))?;
+        match template {
+            Some(template) if template.key == req_body.key => {}
+            Some(template) => {
+                return Err(actix_web::error::ErrorBadRequest(format!(
+                    "Template {} belongs to {}, not {}",
+                    template.id, template.key, req_body.key
+                )))
+            }
+            None => {
+                return Err(actix_web::error::ErrorBadRequest(format!(
+                    "Template {} not found",
+                    variant.template_id
+                )))
+            }
+        }
+    }
+
+    let id = queries::experiments::create_experiment(&app_state.db_client.pool, &req_body)
+        .await
+        .map_err(|e| internal_error("Failed to save prompt experiment", e))?
+        .ok_or_else(|| {
+            actix_web::error::ErrorConflict(
+                "The experiment name is taken or an experiment already runs on the template",
+            )
+        })?;
+    reload(&app_state).await;
+
+    let experiment = find_experiment(&app_state, id).await?;
+    Ok(HttpResponse::Created().json(experiment))
+}
+
+pub async fn fetch_experiment(
+    app_state: web::Data<model::state::AppState>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let experiment = find_experiment(&app_state, id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(experiment))
+}
+
+/// Stops an experiment; the series goes back to its active version.
+/// Recorded outcomes are kept for the report.
+pub async fn stop_experiment(
+    app_state: web::Data<model::state::AppState>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let id = id.into_inner();
+    let found = queries::experiments::stop_experiment(&app_state.db_client.pool, id)
+        .await
+        .map_err(|e| internal_error("Failed to stop prompt experiment", e))?;
+    if !found {
+        return Err(actix_web::error::ErrorNotFound(
+            "Prompt experiment not found",
+        ));
+    }
+    reload(&app_state).await;
+
+    let experiment = find_experiment(&app_state, id).await?;
+    Ok(HttpResponse::Ok().json(experiment))
+}
+
+/// Compares the variants of an experiment by parse success, quality gate
+/// pass rate, author acceptance and student selection.
+pub async fn experiment_report(
+    app_state: web::Data<model::state::AppState>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let experiment = find_experiment(&app_state, id.into_inner()).await?;
+    let variants = queries::experiments::variant_reports(&app_state.db_client.pool, &experiment)
+        .await
+        .map_err(|e| internal_error("Failed to compute experiment report", e))?;
+
+    Ok(HttpResponse::Ok().json(ExperimentReport {
+        experiment,
+        variants,
+    }))
+}
+
+/// Records which distractors of a generation an author kept.
+pub async fn record_feedback(
+    app_state: web::Data<model::state::AppState>,
+    id: web::Path<Uuid>,
+    req_body: web::Json<FeedbackRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let id = id.into_inner();
+    if req_body.accepted.is_empty() && req_body.rejected.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Name at least one accepted or rejected distractor",
+        ));
+    }
+    if req_body
+        .accepted
+        .iter()
+        .any(|distractor| req_body.rejected.contains(distractor))
+    {
+        return Err(actix_web::error::ErrorBadRequest(
+            "A distractor cannot be both accepted and rejected",
+        ));
+    }
+    require_generation(&app_state, id).await?;
+
+    queries::experiments::record_feedback(
+        &app_state.db_client.pool,
+        id,
+        &req_body.accepted,
+        &req_body.rejected,
+    )
+    .await
+    .map_err(|e| internal_error("Failed to record generation feedback", e))?;
+
+    Ok(HttpResponse::Ok().json("Feedback recorded successfully"))
+}
+
+/// Adds how students answered a question with the distractors of a
+/// generation.
+pub async fn record_selections(
+    app_state: web::Data<model::state::AppState>,
+    id: web::Path<Uuid>,
+    req_body: web::Json<SelectionsRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let id = id.into_inner();
+    if req_body.responses <= 0 {
+        return Err(actix_web::error::ErrorBadRequest(
+            "responses must be positive",
+        ));
+    }
+    if req_body
+        .selections
+        .values()
+        .any(|&count| !(0..=req_body.responses).contains(&count))
+    {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Selections must be between 0 and responses",
+        ));
+    }
+    require_generation(&app_state, id).await?;
+
+    let selections: Vec<(String, i32)> = req_body.selections.clone().into_iter().collect();
+    queries::experiments::record_selections(
+        &app_state.db_client.pool,
+        id,
+        req_body.responses,
+        &selections,
+    )
+    .await
+    .map_err(|e| internal_error("Failed to record generation selections", e))?;
+
+    Ok(HttpResponse::Ok().json("Selections recorded successfully"))
+}
@@ -0,0 +1,65 @@
+use crate::database::queries;
+use crate::model::section::SectionRequest;
+use crate::model::{self, format::ExamFormat, format::FormatQuery};
+use crate::services::{gift, moodle, qti};
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{web, HttpResponse};
+use anyhow::Result;
+
+pub async fn export_exam(
+    app_state: web::Data<model::state::AppState>,
+    exam_id: web::Path<String>,
+    query: web::Query<FormatQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let exam_id_int: i32 = exam_id.into_inner().parse().map_err(|e| {
+        log::error!("Failed to export exam: {:?}", e);
+        actix_web::error::ErrorBadRequest("Invalid exam id")
+    })?;
+
+    let exam_data = queries::read::read_exam_data(&app_state.db_client.pool, exam_id_int)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to fetch exam for export: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?;
+
+    let (bytes, content_type, file_name) = match query.format {
+        ExamFormat::Qti => {
+            let bytes = qti::export_package(&exam_data).map_err(|e| {
+                log::error!("Failed to build QTI package: {:?}", e);
+                actix_web::error::ErrorInternalServerError("Internal server error")
+            })?;
+            (
+                bytes,
+                "application/zip",
+                format!("exam-{}-qti.zip", exam_id_int),
+            )
+        }
+        ExamFormat::Moodle => {
+            let sections: Vec<SectionRequest> =
+                exam_data.sections.into_iter().map(Into::into).collect();
+            (
+                moodle::export_sections(&sections).into_bytes(),
+                "application/xml; charset=utf-8",
+                format!("exam-{}-moodle.xml", exam_id_int),
+            )
+        }
+        ExamFormat::Gift => {
+            let sections: Vec<SectionRequest> =
+                exam_data.sections.into_iter().map(Into::into).collect();
+            (
+                gift::export_sections(&sections).into_bytes(),
+                "text/plain; charset=utf-8",
+                format!("exam-{}.gift.txt", exam_id_int),
+            )
+        }
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(file_name)],
+        })
+        .body(bytes))
+}
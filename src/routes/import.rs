@@ -0,0 +1,299 @@
+use crate::database::queries;
+use crate::model::bulk::{BulkImportQuery, BulkImportResponse, SectionSummary};
+use crate::model::exam::ExamRequest;
+use crate::model::format::{ExamFormat, ImportQuery, ImportReport, ImportResponse};
+use crate::model::{self, section::SectionRequest};
+use crate::services::spreadsheet::{self, ParsedSheet};
+use crate::services::{gift, moodle, qti};
+use actix_web::{web, HttpResponse};
+use anyhow::{Context, Result};
+
+/// Largest upload accepted by the import endpoints.
+pub const MAX_IMPORT_SIZE: usize = 20 * 1024 * 1024;
+
+const DEFAULT_IMPORT_TITLE: &str = "Imported exam";
+
+fn body_text(body: &[u8]) -> Result<&str> {
+    let text = std::str::from_utf8(body).context("Upload is not valid UTF-8")?;
+    Ok(text.trim_start_matches('\u{FEFF}'))
+}
+
+/// Wraps imported sections into an exam using the metadata from the query.
+fn exam_from_sections(query: &ImportQuery, sections: Vec<SectionRequest>) -> ExamRequest {
+    ExamRequest::new(
+        query
+            .title
+            .clone()
+            .unwrap_or_else(|| DEFAULT_IMPORT_TITLE.to_string()),
+        None,
+        query.duration.unwrap_or_default(),
+        query.passing_score.unwrap_or_default(),
+        sections,
+    )
+}
+
+/// Parses the upload into an exam and a report of anything that could not be mapped.
+fn parse_upload(query: &ImportQuery, body: &[u8]) -> Result<(ExamRequest, ImportReport)> {
+    match query.format {
+        ExamFormat::Qti => {
+            let exam = qti::import_package(body)?;
+            let report = ImportReport {
+                imported: exam.sections.iter().map(|s| s.questions.len()).sum(),
+                ..Default::default()
+            };
+            Ok((exam, report))
+        }
+        ExamFormat::Moodle => {
+            let (sections, report) = moodle::import_sections(body_text(body)?)?;
+            Ok((exam_from_sections(query, sections), report))
+        }
+        ExamFormat::Gift => {
+            let (sections, report) = gift::import_sections(body_text(body)?)?;
+            Ok((exam_from_sections(query, sections), report))
+        }
+    }
+}
+
+/// Parses the upload on the blocking thread pool, keeping the unzipping and
+/// XML parsing of large packages from stalling the worker.
+async fn parse_upload_blocking(
+    query: &ImportQuery,
+    body: web::Bytes,
+) -> Result<(ExamRequest, ImportReport), actix_web::Error> {
+    let upload_query = query.clone();
+    web::block(move || parse_upload(&upload_query, &body))
+        .await
+        .map_err(|e| {
+            log::error!("Failed to run {:?} import: {:?}", query.format, e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?
+        .map_err(|e| {
+            log::error!("Failed to read {:?} import: {:?}", query.format, e);
+            actix_web::error::ErrorBadRequest(format!("Invalid {:?} upload: {:#}", query.format, e))
+        })
+}
+
+pub async fn import_exam(
+    app_state: web::Data<model::state::AppState>,
+    query: web::Query<ImportQuery>,
+    body: web::Bytes,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (mut exam, report) = parse_upload_blocking(&query, body).await?;
+
+    if report.imported == 0 {
+        return Ok(HttpResponse::BadRequest().json(ImportResponse {
+            exam_id: None,
+            report,
+        }));
+    }
+
+    queries::ids::assign_exam_ids(&app_state.db_client.pool, &mut exam)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to assign ids to imported exam: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?;
+
+    queries::insert::insert_exam(&app_state.db_client.pool, &exam)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to insert imported exam: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?;
+
+    Ok(HttpResponse::Ok().json(ImportResponse {
+        exam_id: Some(exam.exam_id.base.id),
+        report,
+    }))
+}
+
+/// Reads and validates a spreadsheet upload on the blocking thread pool,
+/// keeping large files from stalling the worker.
+async fn parse_spreadsheet(
+    query: &BulkImportQuery,
+    body: web::Bytes,
+) -> Result<ParsedSheet, actix_web::Error> {
+    let format = query.format;
+    web::block(move || {
+        let rows = spreadsheet::read_rows(format, &body)?;
+        Ok(spreadsheet::parse_rows(&rows))
+    })
+    .await
+    .map_err(|e| {
+        log::error!("Failed to run spreadsheet import: {:?}", e);
+        actix_web::error::ErrorInternalServerError("Internal server error")
+    })?
+    .map_err(|e| invalid_spreadsheet(query, e))
+}
+
+fn bulk_response(
+    query: &BulkImportQuery,
+    exam_id: Option<i32>,
+    sheet: ParsedSheet,
+    existing: &[i32],
+) -> BulkImportResponse {
+    BulkImportResponse {
+        exam_id,
+        dry_run: query.dry_run,
+        rows: sheet.rows,
+        questions: sheet.sections.iter().map(|s| s.questions.len()).sum(),
+        sections: sheet
+            .sections
+            .iter()
+            .map(|s| SectionSummary {
+                title: s.base.title.clone(),
+                questions: s.questions.len(),
+                existing: existing.contains(&s.base.id),
+            })
+            .collect(),
+        errors: sheet.errors,
+    }
+}
+
+fn invalid_spreadsheet(query: &BulkImportQuery, e: anyhow::Error) -> actix_web::Error {
+    log::error!("Failed to read {:?} spreadsheet: {:?}", query.format, e);
+    actix_web::error::ErrorBadRequest(format!("Invalid {:?} upload: {:#}", query.format, e))
+}
+
+/// Creates a new exam from a spreadsheet with one question per row.
+///
+/// Nothing is written if any row has errors; the response then lists them
+/// with a 400 status.
+pub async fn import_spreadsheet(
+    app_state: web::Data<model::state::AppState>,
+    query: web::Query<BulkImportQuery>,
+    body: web::Bytes,
+) -> Result<HttpResponse, actix_web::Error> {
+    let sheet = parse_spreadsheet(&query, body).await?;
+
+    if !sheet.errors.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(bulk_response(&query, None, sheet, &[])));
+    }
+    if query.dry_run {
+        return Ok(HttpResponse::Ok().json(bulk_response(&query, None, sheet, &[])));
+    }
+
+    let ParsedSheet {
+        sections,
+        rows,
+        errors,
+    } = sheet;
+    let mut exam = ExamRequest::new(
+        query
+            .title
+            .clone()
+            .unwrap_or_else(|| DEFAULT_IMPORT_TITLE.to_string()),
+        None,
+        query.duration.unwrap_or_default(),
+        query.passing_score.unwrap_or_default(),
+        sections,
+    );
+
+    queries::ids::assign_exam_ids(&app_state.db_client.pool, &mut exam)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to assign ids to imported exam: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?;
+
+    queries::insert::insert_exam(&app_state.db_client.pool, &exam)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to insert imported exam: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?;
+
+    let sheet = ParsedSheet {
+        sections: exam.sections,
+        rows,
+        errors,
+    };
+    Ok(HttpResponse::Ok().json(bulk_response(
+        &query,
+        Some(exam.exam_id.base.id),
+        sheet,
+        &[],
+    )))
+}
+
+/// Appends the questions of a spreadsheet to an existing exam. Rows go into
+/// the section with the same title, or into a new section if there is none.
+///
+/// Nothing is written if any row has errors; the response then lists them
+/// with a 400 status.
+pub async fn append_spreadsheet(
+    app_state: web::Data<model::state::AppState>,
+    exam_id: web::Path<String>,
+    query: web::Query<BulkImportQuery>,
+    body: web::Bytes,
+) -> Result<HttpResponse, actix_web::Error> {
+    let exam_id_int: i32 = exam_id.into_inner().parse().map_err(|e| {
+        log::error!("Failed to import into exam: {:?}", e);
+        actix_web::error::ErrorBadRequest("Invalid exam id")
+    })?;
+
+    let description = queries::read::find_exam_description(&app_state.db_client.pool, exam_id_int)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to look up exam for import: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Exam not found"))?;
+
+    let mut sheet = parse_spreadsheet(&query, body).await?;
+
+    let existing = queries::read::read_sections(&app_state.db_client.pool, description.id)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to fetch sections for import: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?;
+    for section in &mut sheet.sections {
+        if let Some(found) = existing.iter().find(|s| s.title == section.base.title) {
+            section.base.id = found.id;
+        }
+    }
+    let existing_ids: Vec<i32> = existing.iter().map(|s| s.id).collect();
+
+    if !sheet.errors.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(bulk_response(
+            &query,
+            Some(exam_id_int),
+            sheet,
+            &existing_ids,
+        )));
+    }
+    if query.dry_run {
+        return Ok(HttpResponse::Ok().json(bulk_response(
+            &query,
+            Some(exam_id_int),
+            sheet,
+            &existing_ids,
+        )));
+    }
+
+    queries::ids::assign_section_ids(
+        &app_state.db_client.pool,
+        description.id,
+        &mut sheet.sections,
+    )
+    .await
+    .map_err(|e| {
+        log::error!("Failed to assign ids to imported questions: {:?}", e);
+        actix_web::error::ErrorInternalServerError("Internal server error")
+    })?;
+
+    queries::insert::append_sections(&app_state.db_client.pool, description.id, &sheet.sections)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to insert imported questions: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?;
+
+    Ok(HttpResponse::Ok().json(bulk_response(
+        &query,
+        Some(exam_id_int),
+        sheet,
+        &existing_ids,
+    )))
+}
@@ -0,0 +1,136 @@
+use crate::model::job::{JobAccepted, JobKind, JobOutput, JobQuery, JobRequest};
+use crate::model::llm::{
+    CandidateQuery, ContextFillInThBlankTextGenerationRequest,
+    QuranicVerseFillInThBlankTextGenerationRequest,
+};
+use crate::model::{self, state::AppState};
+use crate::routes::mcq;
+use crate::routes::usage::usage_scope;
+use crate::services::jobs::{spawn_workers, JobFailure};
+use crate::services::usage;
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use log::error;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+/// Validates a job like the synchronous route would validate the request,
+/// then queues it and answers `202 Accepted` with where to poll.
+async fn enqueue(
+    app_state: &AppState,
+    request: JobRequest,
+    job_query: &JobQuery,
+) -> Result<HttpResponse, actix_web::Error> {
+    mcq::job_prompt(&app_state.prompts, &request)?;
+    mcq::validate_candidates(&request.options)?;
+    mcq::validate_controls(&request.controls)?;
+
+    let job = app_state
+        .job_queue
+        .enqueue(request, job_query.max_attempts)
+        .await
+        .map_err(|e| {
+            error!("Failed to queue generation job: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?;
+
+    let location = format!("/jobs/{}", job.id);
+    Ok(HttpResponse::Accepted()
+        .insert_header((header::LOCATION, location.clone()))
+        .json(JobAccepted {
+            id: job.id,
+            status: job.status,
+            location,
+        }))
+}
+
+/// Queues the generation of a `/mcq/quran/{kind}` route.
+pub async fn enqueue_quran_job(
+    app_state: web::Data<model::state::AppState>,
+    http_req: HttpRequest,
+    kind: web::Path<JobKind>,
+    req_body: web::Json<QuranicVerseFillInThBlankTextGenerationRequest>,
+    query: web::Query<CandidateQuery>,
+    job_query: web::Query<JobQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let kind = kind.into_inner();
+    if kind == JobKind::Context {
+        return Err(actix_web::error::ErrorNotFound("Unknown distractor type"));
+    }
+
+    let req_body = req_body.into_inner();
+    let request = JobRequest {
+        kind,
+        question: req_body.question,
+        correct_answer: req_body.correct_answer,
+        language: Some(req_body.language),
+        options: query.into_inner(),
+        controls: req_body.controls,
+        usage: usage_scope(&http_req)?,
+    };
+    enqueue(&app_state, request, &job_query).await
+}
+
+/// Queues the generation of `/mcq/options/context`.
+pub async fn enqueue_context_job(
+    app_state: web::Data<model::state::AppState>,
+    http_req: HttpRequest,
+    req_body: web::Json<ContextFillInThBlankTextGenerationRequest>,
+    query: web::Query<CandidateQuery>,
+    job_query: web::Query<JobQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let req_body = req_body.into_inner();
+    let request = JobRequest {
+        kind: JobKind::Context,
+        question: req_body.question,
+        correct_answer: req_body.correct_answer,
+        language: Some(req_body.language),
+        options: query.into_inner(),
+        controls: req_body.controls,
+        usage: usage_scope(&http_req)?,
+    };
+    enqueue(&app_state, request, &job_query).await
+}
+
+/// Returns the status of a job, with its result once it succeeded or its
+/// error once it failed.
+pub async fn fetch_job(
+    app_state: web::Data<model::state::AppState>,
+    job_id: web::Path<Uuid>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let job = app_state
+        .job_queue
+        .get(job_id.into_inner())
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch generation job: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Job not found"))?;
+
+    Ok(HttpResponse::Ok().json(job))
+}
+
+/// Runs one attempt of a job, accounted to whoever queued it. Server
+/// errors, including an unavailable LLM, are worth retrying; client errors,
+/// including a spent quota, are not.
+pub async fn run_job(
+    app_state: web::Data<model::state::AppState>,
+    request: JobRequest,
+) -> Result<JobOutput, JobFailure> {
+    usage::scoped(
+        request.usage.clone(),
+        mcq::generate_job(&app_state, &request),
+    )
+    .await
+    .map_err(|e| JobFailure {
+        retryable: e.as_response_error().status_code().is_server_error(),
+        message: e.to_string(),
+    })
+}
+
+/// Starts the background workers of the job queue in `app_state`.
+pub fn start_workers(app_state: web::Data<model::state::AppState>) -> Vec<JoinHandle<()>> {
+    let queue = app_state.job_queue.clone();
+    spawn_workers(queue, move |request| run_job(app_state.clone(), request))
+}
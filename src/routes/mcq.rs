@@ -1,193 +1,1319 @@
+use crate::database::queries;
+use crate::model::experiment::{GenerationRecord, GenerationStats, GENERATION_ID_HEADER};
+use crate::model::generation::{
+    AttachDistractorsRequest, GenerateSectionOptionsQuery, GenerateSectionOptionsRequest,
+    GenerateSectionOptionsResponse, GeneratedOptions, OptionProvenance, QuranDistractorsRequest,
+    QuranDistractorsResponse, SourceQuestion, TypeDistractors, SECTION_FAN_OUT,
+};
+use crate::model::job::{JobKind, JobOutput, JobRequest};
 use crate::model::llm::{
-    AlternateVerseDistractorResponse, CollocationalDistractorResponse, DiacriticDistractorResponse,
-    DistractorType, GrammaticalDistractorResponse,
-    GuessFillInTheBlankQuranDistractorCollectionResponse, GuessFillInTheBlankResponse,
-    MorphologicalDistractorResponse, PhoneticOrthographicDistractorResponse,
-    ThematicDistractorResponse,
+    AlternateVerseDistractorResponse, CandidateQuery, CollocationalDistractorResponse,
+    DiacriticDistractorResponse, DistractorType, GenerationControls, GenerationRequest,
+    GrammaticalDistractorResponse, GuessFillInTheBlankQuranDistractorCollectionResponse,
+    GuessFillInTheBlankResponse, Language, MorphologicalDistractorResponse,
+    PhoneticOrthographicDistractorResponse, RejectedDistractor, SamplingMode,
+    ThematicDistractorResponse, MAX_CANDIDATES, MAX_OUTPUT_TOKENS, MAX_SEED, NUM_DISTRACTORS_RANGE,
+    TEMPERATURE_RANGE,
 };
-use crate::utils;
-use crate::{
-    model::{self, llm::PromptLanguage},
-    services::llm::send_prompt_to_llm,
+use crate::model::prompt::{
+    Capabilities, LanguageCapability, PromptContext, PromptTask, RenderedPrompt, TaskCapability,
+    TemplateKey, UnsupportedLanguage,
+};
+use crate::model::usage::UsageScope;
+use crate::routes::bank::parse_question_id;
+use crate::routes::usage::usage_scope;
+use crate::services::cache::{cache_key, CacheDirectives, CacheStatus};
+use crate::services::distractors::{
+    append_regenerated, distractor_texts, merge_candidates, truncate_lists,
 };
-use actix_web::{web, HttpResponse};
+use crate::services::interchange::BLANK;
+use crate::services::llm::schema::response_schema;
+use crate::services::prompts::{PromptError, PromptStore};
+use crate::services::quality::{
+    apply_gate, shortfall, DEFAULT_MIN_DISTRACTORS, REGENERATION_BUDGET,
+};
+use crate::services::usage::{self, current_scope};
+use crate::utils;
+use crate::utils::json::{parse_llm_json, FieldStream};
+use crate::{model, services::llm::LlmError};
+use actix_web::http::{header, StatusCode};
+use actix_web::web::Bytes;
+use actix_web::{web, HttpRequest, HttpResponse};
 use anyhow::Result;
+use futures_util::future::join_all;
+use futures_util::stream::{self, StreamExt};
 use log::error;
 use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::convert::Infallible;
+use tokio::sync::mpsc;
+use uuid::Uuid;
 
 use serde::de::DeserializeOwned;
 
+/// Sampling temperature of MCQ generation, unless a request sets one.
+const TEMPERATURE: f32 = 0.7;
+
+/// Response header naming the version of the prompt template a generation
+/// was rendered from.
+pub const PROMPT_VERSION_HEADER: &str = "X-Prompt-Version";
+
 pub trait QuranDistractorResponse: DeserializeOwned + Send + 'static {}
 impl<T: DeserializeOwned + Send + 'static> QuranDistractorResponse for T {}
 
-pub fn build_contextual_mcq_prompt(
-    question: &String,
-    correct_answer: &String,
-    language: PromptLanguage,
-) -> Result<String, actix_web::Error> {
-    match language {
-        PromptLanguage::Arabic => Err(actix_web::error::ErrorBadRequest(
-            "Arabic language is not supported for this endpoint",
-        )),
-        PromptLanguage::Urdu => Ok(utils::prompts::urdu_prompt_template_context_mcq(
-            question,
-            correct_answer,
-        )),
+/// Calls the generic function `$f` with the response type of a job kind.
+macro_rules! for_job_kind {
+    ($kind:expr, $f:ident($($arg:expr),* $(,)?)) => {
+        match $kind {
+            JobKind::Context => $f::<GuessFillInTheBlankResponse>($($arg),*).await,
+            JobKind::Collection => {
+                $f::<GuessFillInTheBlankQuranDistractorCollectionResponse>($($arg),*).await
+            }
+            JobKind::Diacritic => $f::<DiacriticDistractorResponse>($($arg),*).await,
+            JobKind::Phonetic => $f::<PhoneticOrthographicDistractorResponse>($($arg),*).await,
+            JobKind::Morphological => $f::<MorphologicalDistractorResponse>($($arg),*).await,
+            JobKind::Grammatical => $f::<GrammaticalDistractorResponse>($($arg),*).await,
+            JobKind::AlternateVerse => $f::<AlternateVerseDistractorResponse>($($arg),*).await,
+            JobKind::Thematic => $f::<ThematicDistractorResponse>($($arg),*).await,
+            JobKind::Collocational => $f::<CollocationalDistractorResponse>($($arg),*).await,
+        }
+    };
+}
+
+/// Maps a failed LLM call to a response: 429 with the remaining budget
+/// when the user spent a quota, 503 with `Retry-After` when the provider is
+/// unavailable for now, 500 otherwise.
+pub fn llm_failure(e: anyhow::Error, message: &str) -> actix_web::Error {
+    error!("LLM API failure: {:?}", e);
+
+    match e.downcast_ref::<LlmError>() {
+        Some(LlmError::QuotaExceeded(exceeded)) => {
+            let mut response = HttpResponse::TooManyRequests();
+            if let Some(resets_at) = exceeded.resets_at() {
+                let wait = (resets_at - chrono::Utc::now()).num_seconds().max(1);
+                response.insert_header(("Retry-After", wait.to_string()));
+            }
+            actix_web::error::InternalError::from_response(e.to_string(), response.json(exceeded))
+                .into()
+        }
+        Some(LlmError::Unavailable { retry_after, .. }) => {
+            let mut response = HttpResponse::ServiceUnavailable();
+            if let Some(wait) = retry_after {
+                response.insert_header(("Retry-After", wait.as_secs().max(1).to_string()));
+            }
+            actix_web::error::InternalError::from_response(
+                e.to_string(),
+                response.json("The text generation service is unavailable, try again later"),
+            )
+            .into()
+        }
+        _ => actix_web::error::ErrorInternalServerError(format!("{}: {}", message, e)),
     }
 }
 
+/// Renders a prompt from the active template of a series, or the variant
+/// of a running experiment `user_id` is assigned to. A series without a
+/// template is rejected with the languages its task is supported in, see
+/// `GET /mcq/capabilities`.
+fn render_prompt(
+    prompts: &PromptStore,
+    key: TemplateKey,
+    context: &PromptContext,
+    user_id: &str,
+) -> Result<RenderedPrompt, actix_web::Error> {
+    prompts.render(&key, context, user_id).map_err(|e| match e {
+        PromptError::Missing(key) => {
+            let body = UnsupportedLanguage {
+                message: format!(
+                    "{} is not supported for this endpoint, see GET /mcq/capabilities",
+                    key.language.as_str()
+                ),
+                task: key.task,
+                distractor_type: key.distractor_type,
+                language: key.language,
+                supported_languages: prompts.languages(&key),
+            };
+            actix_web::error::InternalError::from_response(e, HttpResponse::BadRequest().json(body))
+                .into()
+        }
+        PromptError::Render(..) => {
+            error!("{:?}", e);
+            actix_web::error::ErrorInternalServerError("Failed to render the prompt")
+        }
+    })
+}
+
+pub fn build_contextual_mcq_prompt(
+    prompts: &PromptStore,
+    question: &str,
+    correct_answer: &str,
+    language: Language,
+    controls: &GenerationControls,
+    user_id: &str,
+) -> Result<RenderedPrompt, actix_web::Error> {
+    let key = TemplateKey::new(PromptTask::Context, language);
+    let context = PromptContext::new(question, correct_answer).with_controls(controls);
+    render_prompt(prompts, key, &context, user_id)
+}
+
 pub fn get_quranic_verse_distractor_prompt(
-    question: &String,
-    correct_answer: &String,
-    language: PromptLanguage,
+    prompts: &PromptStore,
+    question: &str,
+    correct_answer: &str,
     distractor_type: DistractorType,
-) -> Result<String, actix_web::Error> {
-    match language {
-        PromptLanguage::Urdu => Err(actix_web::error::ErrorBadRequest(
-            "Urdu language is not supported for this endpoint",
-        )),
-        PromptLanguage::Arabic => Ok(
-            utils::prompts::arabic_prompt_template_quranic_verse_distractor_mcq(
-                question,
-                correct_answer,
-                distractor_type,
-            ),
+    language: Language,
+    controls: &GenerationControls,
+    user_id: &str,
+) -> Result<RenderedPrompt, actix_web::Error> {
+    let key = TemplateKey::distractor(distractor_type, language);
+    let context = PromptContext::new(question, correct_answer).with_controls(controls);
+    render_prompt(prompts, key, &context, user_id)
+}
+
+/// Records a generation made with an experiment variant and returns its ID,
+/// or `None` without a variant. A failure to record is logged rather than
+/// failing the generation.
+async fn record_generation(
+    app_state: &model::state::AppState,
+    prompt: &RenderedPrompt,
+    id: Option<Uuid>,
+    scope: &UsageScope,
+    stats: GenerationStats,
+) -> Option<Uuid> {
+    let variant = prompt.variant.as_ref()?;
+    let record = GenerationRecord {
+        id: id.unwrap_or_else(Uuid::new_v4),
+        variant_id: variant.variant_id,
+        user_id: scope.user_id.clone(),
+        endpoint: scope.endpoint.clone(),
+        stats,
+    };
+    match queries::experiments::insert_generation(&app_state.db_client.pool, &record).await {
+        Ok(()) => Some(record.id),
+        Err(e) => {
+            log::warn!("Failed to record generation of {}: {:?}", prompt.version, e);
+            None
+        }
+    }
+}
+
+/// Asks the model once more for a reply that failed to parse, quoting the
+/// reply and the parse error.
+async fn reprompt<T: DeserializeOwned>(
+    app_state: &model::state::AppState,
+    prompt: &str,
+    reply: &str,
+    error: &anyhow::Error,
+    schema: &serde_json::Value,
+    controls: &GenerationControls,
+    llm_error: &str,
+) -> Result<T, actix_web::Error> {
+    log::warn!("Re-prompting after unparseable LLM output: {}", error);
+
+    let repair = utils::prompts::json_repair_prompt(prompt, reply, &error.to_string());
+    let request = GenerationRequest::new(repair, 1, TEMPERATURE)
+        .with_controls(controls)
+        .with_schema(schema.clone());
+    let outputs = app_state
+        .llm_client
+        .generate(&request)
+        .await
+        .map_err(|e| llm_failure(e, llm_error))?;
+
+    let reply = outputs.first().map(String::as_str).unwrap_or_default();
+    parse_llm_json(reply).map_err(|e| {
+        error!("Failed to parse MCQ options from LLM output: {:?}", e);
+        actix_web::error::ErrorInternalServerError(format!("Parsing error: {}", e))
+    })
+}
+
+/// Returns the requested number of candidates, rejecting one outside
+/// `1..=MAX_CANDIDATES`.
+pub fn validate_candidates(query: &CandidateQuery) -> Result<u32, actix_web::Error> {
+    let candidates = query.candidates.unwrap_or(1);
+    if !(1..=MAX_CANDIDATES).contains(&candidates) {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "candidates must be between 1 and {}",
+            MAX_CANDIDATES
+        )));
+    }
+    Ok(candidates)
+}
+
+/// Rejects generation controls outside the server's limits.
+pub fn validate_controls(controls: &GenerationControls) -> Result<(), actix_web::Error> {
+    let bad_request = |message: String| Err(actix_web::error::ErrorBadRequest(message));
+    if let Some(num) = controls.num_distractors {
+        if !NUM_DISTRACTORS_RANGE.contains(&num) {
+            return bad_request(format!(
+                "num_distractors must be between {} and {}",
+                NUM_DISTRACTORS_RANGE.start(),
+                NUM_DISTRACTORS_RANGE.end()
+            ));
+        }
+    }
+    if let Some(temperature) = controls.temperature {
+        if !TEMPERATURE_RANGE.contains(&temperature) {
+            return bad_request(format!(
+                "temperature must be between {} and {}",
+                TEMPERATURE_RANGE.start(),
+                TEMPERATURE_RANGE.end()
+            ));
+        }
+    }
+    if controls.seed.is_some_and(|seed| seed > MAX_SEED) {
+        return bad_request(format!("seed must be at most {}", MAX_SEED));
+    }
+    if let Some(tokens) = controls.max_output_tokens {
+        if !(1..=MAX_OUTPUT_TOKENS).contains(&tokens) {
+            return bad_request(format!(
+                "max_output_tokens must be between 1 and {}",
+                MAX_OUTPUT_TOKENS
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Sends the prompt, constrained to the schema of `T`, and returns the parsed
+/// response body. Output that does not parse even after repair is sent back
+/// to the model once. Distractors then pass the quality gate, and the model
+/// is asked for more while a list is short (see `services::quality`). With
+/// more than one candidate requested, the candidates are merged into a
+/// ranked, de-duplicated set (see `services::distractors`). Returns the body
+/// with how the output fared.
+async fn generate_response<T>(
+    app_state: &model::state::AppState,
+    prompt: String,
+    answer: &str,
+    query: &CandidateQuery,
+    controls: &GenerationControls,
+    llm_error: &str,
+) -> Result<(Value, GenerationStats), actix_web::Error>
+where
+    T: DeserializeOwned + Serialize,
+{
+    let candidates = validate_candidates(query)?;
+    validate_controls(controls)?;
+    let schema = response_schema::<T>();
+    let outputs = async {
+        match (candidates, query.sampling) {
+            (1, _) | (_, SamplingMode::Batch) => {
+                let request = GenerationRequest::new(prompt.clone(), candidates, TEMPERATURE)
+                    .with_controls(controls)
+                    .with_schema(schema.clone());
+                app_state.llm_client.generate(&request).await
+            }
+            (_, SamplingMode::Sequential) => {
+                let mut request = GenerationRequest::new(prompt.clone(), 1, TEMPERATURE)
+                    .with_controls(controls)
+                    .with_schema(schema.clone());
+                let seeds: Vec<_> = (0..candidates)
+                    .map(|candidate| request.candidate_seed(candidate))
+                    .collect();
+                let mut outputs = Vec::new();
+                for seed in seeds {
+                    request.seed = seed;
+                    outputs.extend(app_state.llm_client.generate(&request).await?);
+                }
+                Ok(outputs)
+            }
+        }
+    }
+    .await
+    .map_err(|e| llm_failure(e, llm_error))?;
+
+    finish_response::<T>(
+        app_state, &prompt, answer, query, controls, &schema, candidates, outputs, llm_error,
+    )
+    .await
+}
+
+/// Parses, gates and merges the raw outputs of a generation, see
+/// `generate_response`. With `num_distractors` set, every list is cut to
+/// that many and a shorter one fails the generation.
+#[allow(clippy::too_many_arguments)]
+async fn finish_response<T>(
+    app_state: &model::state::AppState,
+    prompt: &str,
+    answer: &str,
+    query: &CandidateQuery,
+    controls: &GenerationControls,
+    schema: &Value,
+    candidates: u32,
+    outputs: Vec<String>,
+    llm_error: &str,
+) -> Result<(Value, GenerationStats), actix_web::Error>
+where
+    T: DeserializeOwned + Serialize,
+{
+    // A candidate that does not parse is skipped rather than failing the rest.
+    let mut first_failure = None;
+    let mut parsed: Vec<T> = Vec::new();
+    for output in &outputs {
+        match parse_llm_json(output) {
+            Ok(response) => parsed.push(response),
+            Err(e) => {
+                first_failure.get_or_insert((output.as_str(), e));
+            }
+        }
+    }
+    let mut stats = GenerationStats {
+        parsed: first_failure.is_none(),
+        ..Default::default()
+    };
+    if parsed.is_empty() {
+        let (reply, error) =
+            first_failure.unwrap_or(("", anyhow::anyhow!("No valid text in LLM response")));
+        let repaired = reprompt(
+            app_state, prompt, reply, &error, schema, controls, llm_error,
+        )
+        .await?;
+        parsed.push(repaired);
+    }
+
+    let min_distractors = controls
+        .num_distractors
+        .or(query.min_distractors)
+        .unwrap_or(DEFAULT_MIN_DISTRACTORS);
+    let mut pool: Vec<Map<String, Value>> = Vec::new();
+    let mut rejected = Vec::new();
+    stats.passed += gate_into(&parsed, answer, &mut pool, &mut rejected);
+
+    // Distractors regenerated for short lists, kept apart from the
+    // candidates so they neither add votes nor count as candidates.
+    let mut regenerated: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let merge = |regenerated: &BTreeMap<String, Vec<String>>, top_k: Option<usize>| {
+        let mut merged = merge_candidates(&pool, None);
+        for (field, texts) in regenerated {
+            append_regenerated(&mut merged, field, texts);
+        }
+        if let Some(top_k) = top_k {
+            truncate_lists(&mut merged, top_k);
+        }
+        merged
+    };
+
+    let mut last_rejected = rejected.clone();
+    for _ in 0..REGENERATION_BUDGET {
+        let missing = shortfall(&merge(&regenerated, None).response, min_distractors);
+        if missing.is_empty() {
+            break;
+        }
+
+        let retry = utils::prompts::regeneration_prompt(prompt, &last_rejected, &missing);
+        let request = GenerationRequest::new(retry, 1, TEMPERATURE)
+            .with_controls(controls)
+            .with_schema(schema.clone());
+        let outputs = match app_state.llm_client.generate(&request).await {
+            Ok(outputs) => outputs,
+            Err(e) => {
+                // Answer with what passed so far.
+                log::warn!("Failed to regenerate distractors: {:?}", e);
+                break;
+            }
+        };
+
+        // Only the lists that were short are taken from the reply.
+        let parsed: Vec<Map<String, Value>> = outputs
+            .iter()
+            .filter_map(|output| parse_llm_json::<T>(output).ok())
+            .filter_map(|response| match serde_json::to_value(response) {
+                Ok(Value::Object(mut object)) => {
+                    object.retain(|field, _| missing.iter().any(|(short, _)| short == field));
+                    Some(object)
+                }
+                _ => None,
+            })
+            .collect();
+        let mut gated = Vec::new();
+        last_rejected.clear();
+        stats.passed += gate_into(&parsed, answer, &mut gated, &mut last_rejected);
+        rejected.extend(last_rejected.iter().cloned());
+        for (field, value) in gated.into_iter().flatten() {
+            let texts = value
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|text| text.as_str().map(str::to_string));
+            regenerated.entry(field).or_default().extend(texts);
+        }
+    }
+    stats.distractors = stats.passed + rejected.len();
+
+    let top_k = match (query.top_k, controls.num_distractors) {
+        (Some(top_k), Some(num)) => Some(top_k.min(num)),
+        (top_k, num) => top_k.or(num),
+    };
+    let mut merged = merge(&regenerated, top_k);
+    if let Some(expected) = top_k.filter(|_| controls.num_distractors.is_some()) {
+        if let Some((field, missing)) = shortfall(&merged.response, expected).first() {
+            error!("Too few distractors in {} after regeneration", field);
+            return Err(actix_web::error::ErrorBadGateway(format!(
+                "The model returned {} of the {} {} asked for",
+                expected - missing,
+                expected,
+                field
+            )));
+        }
+    }
+    if candidates == 1 {
+        return Ok((Value::Object(merged.response), stats));
+    }
+    merged.rejected = rejected;
+    let body = serde_json::to_value(merged).map_err(|e| {
+        error!("Failed to serialize merged MCQ options: {:?}", e);
+        actix_web::error::ErrorInternalServerError("Internal server error")
+    })?;
+    Ok((body, stats))
+}
+
+/// Answers with the generated response, served from the Redis cache when an
+/// identical request was answered before. `Cache-Control: no-cache` skips the
+/// lookup and `no-store` also skips the write; `X-Cache` tells which
+/// happened. Cache failures are logged and treated as misses. A generation
+/// made with an experiment variant is recorded and named by
+/// `X-Generation-Id`; cached answers are not.
+async fn respond_cached<T>(
+    app_state: &model::state::AppState,
+    http_req: &HttpRequest,
+    prompt: RenderedPrompt,
+    answer: &str,
+    query: &CandidateQuery,
+    controls: &GenerationControls,
+    llm_error: &str,
+) -> Result<HttpResponse, actix_web::Error>
+where
+    T: DeserializeOwned + Serialize,
+{
+    let cache = &app_state.llm_cache;
+    let directives = CacheDirectives::parse(
+        http_req
+            .headers()
+            .get_all(header::CACHE_CONTROL)
+            .filter_map(|value| value.to_str().ok()),
+    );
+
+    let options = serde_json::to_string(query).unwrap_or_default();
+    let sampling = serde_json::to_string(controls).unwrap_or_default();
+    let temperature = controls.temperature.unwrap_or(TEMPERATURE).to_string();
+    let key = cache_key(&[
+        &prompt.text,
+        answer,
+        app_state.llm_client.provider_name(),
+        app_state.llm_client.model_name(),
+        &temperature,
+        std::any::type_name::<T>(),
+        &options,
+        &sampling,
+    ]);
+
+    let use_cache = cache.enabled() && !directives.no_store;
+    let status = if !use_cache || directives.no_cache {
+        CacheStatus::Bypass
+    } else {
+        match cache.get(&key).await {
+            Ok(Some(body)) => {
+                return Ok(HttpResponse::Ok()
+                    .content_type("application/json")
+                    .insert_header(("X-Cache", CacheStatus::Hit.as_str()))
+                    .insert_header((PROMPT_VERSION_HEADER, prompt.version))
+                    .body(body));
+            }
+            Ok(None) => CacheStatus::Miss,
+            Err(e) => {
+                log::warn!("LLM cache lookup failed: {:?}", e);
+                CacheStatus::Miss
+            }
+        }
+    };
+
+    let scope = usage_scope(http_req)?;
+    let (body, stats) = usage::scoped(
+        scope.clone(),
+        generate_response::<T>(
+            app_state,
+            prompt.text.clone(),
+            answer,
+            query,
+            controls,
+            llm_error,
         ),
+    )
+    .await?;
+    let body = body.to_string();
+    if use_cache {
+        if let Err(e) = cache.put(&key, &body).await {
+            log::warn!("Failed to cache LLM response: {:?}", e);
+        }
     }
+
+    let mut response = HttpResponse::Ok();
+    response
+        .content_type("application/json")
+        .insert_header(("X-Cache", status.as_str()))
+        .insert_header((PROMPT_VERSION_HEADER, prompt.version.clone()));
+    if let Some(id) = record_generation(app_state, &prompt, None, &scope, stats).await {
+        response.insert_header((GENERATION_ID_HEADER, id.to_string()));
+    }
+    Ok(response.body(body))
 }
 
-pub async fn generate_mcq_options_from_context(
-    req_body: web::Json<model::llm::ContextFillInThBlankTextGenerationRequest>,
+/// Renders the prompt of a job, failing the way the synchronous route would.
+pub fn job_prompt(
+    prompts: &PromptStore,
+    request: &JobRequest,
+) -> Result<RenderedPrompt, actix_web::Error> {
+    let language = match (request.kind, request.language) {
+        (_, Some(language)) => language,
+        (JobKind::Context, None) => {
+            return Err(actix_web::error::ErrorBadRequest(
+                "language is required for context jobs",
+            ))
+        }
+        // The language the Quran routes default to.
+        (_, None) => Language::Arabic,
+    };
+    let context = PromptContext::new(&request.question, &request.correct_answer)
+        .with_controls(&request.controls);
+    render_prompt(
+        prompts,
+        request.kind.template_key(language),
+        &context,
+        &request.usage.user_id,
+    )
+}
+
+/// Lists the languages, and for every generation route the languages it
+/// has a template in.
+pub async fn capabilities(
+    app_state: web::Data<model::state::AppState>,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let language = utils::parse::map_to_prompt_language(&req_body.language);
+    let languages = Language::ALL
+        .into_iter()
+        .map(|language| LanguageCapability {
+            language,
+            code: language.code().to_string(),
+        })
+        .collect();
+    let tasks = JobKind::ALL
+        .into_iter()
+        .map(|kind| {
+            let key = kind.template_key(Language::Arabic);
+            TaskCapability {
+                kind,
+                task: key.task,
+                distractor_type: key.distractor_type,
+                endpoint: kind.endpoint(),
+                languages: app_state.prompts.languages(&key),
+            }
+        })
+        .collect();
 
-    let prompt =
-        build_contextual_mcq_prompt(&req_body.question, &req_body.correct_answer, language)?;
+    Ok(HttpResponse::Ok().json(Capabilities { languages, tasks }))
+}
 
-    let raw_output = send_prompt_to_llm(prompt, 1).await.map_err(|e| {
-        error!("LLM API failure: {:?}", e);
-        actix_web::error::ErrorInternalServerError(format!("LLM API Error: {}", e))
-    })?;
+/// Runs a job through the pipeline of the synchronous routes, bypassing the
+/// response cache, and returns the body they would have answered with and
+/// the version of the template it rendered, recording the generation when
+/// an experiment picked the version.
+pub async fn generate_job(
+    app_state: &model::state::AppState,
+    request: &JobRequest,
+) -> Result<JobOutput, actix_web::Error> {
+    let prompt = job_prompt(&app_state.prompts, request)?;
+    let text = prompt.text.clone();
+    let answer = &request.correct_answer;
+    let (query, controls) = (&request.options, &request.controls);
+    let llm_error = "LLM API error";
 
-    let clean_text = utils::parse::clean_llm_json_output(&raw_output).map_err(|e| {
-        error!("Failed to clean LLM output: {:?}", e);
-        actix_web::error::ErrorInternalServerError(format!("Cleaning error: {}", e))
-    })?;
+    let (result, stats) = for_job_kind!(
+        request.kind,
+        generate_response(app_state, text, answer, query, controls, llm_error)
+    )?;
+    let generation_id = record_generation(app_state, &prompt, None, &request.usage, stats).await;
+    Ok(JobOutput {
+        result,
+        prompt_version: prompt.version,
+        generation_id,
+    })
+}
 
-    let response: GuessFillInTheBlankResponse = serde_json::from_str(&clean_text).map_err(|e| {
-        error!("Failed to parse MCQ options from cleaned text: {:?}", e);
-        actix_web::error::ErrorInternalServerError(format!("Parsing error: {}", e))
+/// The provenance of options generated by a kind of job from a version of
+/// its template.
+fn provenance(
+    app_state: &model::state::AppState,
+    kind: JobKind,
+    prompt_version: String,
+) -> OptionProvenance {
+    OptionProvenance {
+        distractor_type: kind.as_str().to_string(),
+        provider: app_state.llm_client.provider_name().to_string(),
+        model: app_state.llm_client.model_name().to_string(),
+        prompt_version,
+    }
+}
+
+/// New distractors of a question and the version of the template that
+/// produced them.
+type QuestionDistractors = (Vec<String>, String);
+
+/// Generates the new distractors of a question: those it does not have
+/// yet, at most `max_options` of them, with the version of the template.
+async fn question_distractors(
+    app_state: &model::state::AppState,
+    question: &SourceQuestion,
+    kind: JobKind,
+    language: Option<Language>,
+    max_options: Option<usize>,
+    query: &CandidateQuery,
+) -> Result<QuestionDistractors, actix_web::Error> {
+    let Some(answer) = &question.correct_answer else {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Question has no correct option",
+        ));
+    };
+    if !question.text.contains(BLANK) {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "Question has no {} blank",
+            BLANK
+        )));
+    }
+
+    let job = JobRequest {
+        kind,
+        question: question.text.clone(),
+        correct_answer: answer.clone(),
+        language,
+        options: query.clone(),
+        controls: GenerationControls::default(),
+        usage: current_scope(),
+    };
+    let output = generate_job(app_state, &job).await?;
+
+    let mut texts = distractor_texts(&output.result, &question.options);
+    if let Some(max_options) = max_options {
+        texts.truncate(max_options);
+    }
+    Ok((texts, output.prompt_version))
+}
+
+/// Whether a generation failed on the LLM or a spent quota rather than on
+/// the request.
+fn is_generation_failure(e: &actix_web::Error) -> bool {
+    let status = e.as_response_error().status_code();
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Generates distractors for every question of an exam section and adds
+/// them as incorrect options, in one transaction, or only returns them with
+/// `?preview=true`. At most `SECTION_FAN_OUT` questions are generated for at
+/// a time. A question that fails is reported without failing the others,
+/// unless none succeeded.
+pub async fn generate_section_options(
+    app_state: web::Data<model::state::AppState>,
+    http_req: HttpRequest,
+    path: web::Path<(i32, i32)>,
+    req_body: web::Json<GenerateSectionOptionsRequest>,
+    query: web::Query<CandidateQuery>,
+    section_query: web::Query<GenerateSectionOptionsQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (exam_id, section_id) = path.into_inner();
+    validate_candidates(&query)?;
+    if req_body.kind == JobKind::Context && req_body.language.is_none() {
+        return Err(actix_web::error::ErrorBadRequest(
+            "language is required for context generation",
+        ));
+    }
+
+    let pool = &app_state.db_client.pool;
+    let found = queries::bank::section_in_exam(pool, exam_id, section_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to look up section: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?;
+    if !found {
+        return Err(actix_web::error::ErrorNotFound("Section not found in exam"));
+    }
+
+    let questions = queries::options::section_questions(pool, section_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch section questions: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?;
+
+    let generation = stream::iter(&questions)
+        .map(|question| async {
+            let outcome = question_distractors(
+                &app_state,
+                question,
+                req_body.kind,
+                req_body.language,
+                req_body.max_options,
+                &query,
+            )
+            .await;
+            (question.id, outcome)
+        })
+        .buffered(SECTION_FAN_OUT)
+        .collect();
+    let mut outcomes: Vec<(i32, Result<QuestionDistractors, actix_web::Error>)> =
+        usage::scoped(usage_scope(&http_req)?, generation).await;
+
+    // With nothing generated, a failing LLM or a spent quota fails the request.
+    if outcomes.iter().all(|(_, outcome)| outcome.is_err()) {
+        let server_error = outcomes
+            .iter()
+            .position(|(_, outcome)| matches!(outcome, Err(e) if is_generation_failure(e)));
+        if let Some((_, Err(e))) = server_error.map(|index| outcomes.swap_remove(index)) {
+            return Err(e);
+        }
+    }
+
+    let mut generated = Vec::new();
+    let mut results = Vec::new();
+    for (question_id, outcome) in outcomes {
+        match outcome {
+            Ok((options, prompt_version)) => {
+                let provenance = provenance(&app_state, req_body.kind, prompt_version);
+                generated.push((question_id, options.clone(), provenance));
+                results.push(GeneratedOptions {
+                    question_id,
+                    options,
+                    error: None,
+                });
+            }
+            Err(e) => results.push(GeneratedOptions {
+                question_id,
+                options: Vec::new(),
+                error: Some(e.to_string()),
+            }),
+        }
+    }
+
+    let inserted = if section_query.preview {
+        0
+    } else {
+        queries::options::add_distractors(pool, &generated)
+            .await
+            .map_err(|e| {
+                error!("Failed to save generated options: {:?}", e);
+                actix_web::error::ErrorInternalServerError("Internal server error")
+            })?
+            .len()
+    };
+
+    Ok(HttpResponse::Ok().json(GenerateSectionOptionsResponse {
+        preview: section_query.preview,
+        inserted,
+        questions: results,
+    }))
+}
+
+/// Generates distractors of one type for a bank question from its text and
+/// correct option, and adds them as incorrect options that record how they
+/// were made. Answers with the question as the bank returns it.
+pub async fn attach_distractors(
+    app_state: web::Data<model::state::AppState>,
+    http_req: HttpRequest,
+    question_id: web::Path<String>,
+    req_body: web::Json<AttachDistractorsRequest>,
+    query: web::Query<CandidateQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let question_id = parse_question_id(question_id)?;
+    validate_candidates(&query)?;
+
+    let pool = &app_state.db_client.pool;
+    let question = queries::options::source_question(pool, question_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to fetch question: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Question not found"))?;
+
+    let kind = JobKind::from(req_body.distractor_type);
+    let (options, prompt_version) = usage::scoped(
+        usage_scope(&http_req)?,
+        question_distractors(
+            &app_state,
+            &question,
+            kind,
+            req_body.language,
+            req_body.max_options,
+            &query,
+        ),
+    )
+    .await?;
+
+    let provenance = provenance(&app_state, kind, prompt_version);
+    let question = async {
+        queries::options::add_distractors(pool, &[(question_id, options, provenance)]).await?;
+        queries::bank::read_question(pool, question_id).await
+    }
+    .await
+    .map_err(|e| {
+        error!("Failed to attach generated options: {:?}", e);
+        actix_web::error::ErrorInternalServerError("Internal server error")
     })?;
 
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Created().json(question))
+}
+
+/// Formats one server-sent event.
+fn sse_event(event: &str, data: &Value) -> Bytes {
+    Bytes::from(format!("event: {}\ndata: {}\n\n", event, data))
+}
+
+/// Streams a generation as server-sent events: a `distractors` event with
+/// the gated list each time the model completes one, then a `result` event
+/// with the body the synchronous route would answer, or an `error` event.
+/// With an experiment variant, the generation is recorded once the result
+/// is sent, under the ID of the `X-Generation-Id` header.
+async fn stream_response<T>(
+    app_state: web::Data<model::state::AppState>,
+    request: JobRequest,
+) -> Result<HttpResponse, actix_web::Error>
+where
+    T: DeserializeOwned + Serialize + Send + 'static,
+{
+    let rendered = job_prompt(&app_state.prompts, &request)?;
+    let (prompt, version) = (rendered.text.clone(), rendered.version.clone());
+    let generation_id = rendered.variant.as_ref().map(|_| Uuid::new_v4());
+    if validate_candidates(&request.options)? != 1 {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Streaming generates a single candidate",
+        ));
+    }
+    validate_controls(&request.controls)?;
+
+    let llm_error = "LLM API error";
+    let schema = response_schema::<T>();
+    let generation = GenerationRequest::new(prompt.clone(), 1, TEMPERATURE)
+        .with_controls(&request.controls)
+        .with_schema(schema.clone());
+    // Opened before answering, so an unavailable provider or a spent quota
+    // still fails the request.
+    let scope = request.usage.clone();
+    let mut chunks = usage::scoped(
+        scope.clone(),
+        app_state.llm_client.generate_stream(&generation),
+    )
+    .await
+    .map_err(|e| llm_failure(e, llm_error))?;
+
+    let (tx, rx) = mpsc::channel::<Bytes>(16);
+    tokio::spawn(usage::scoped(scope, async move {
+        let answer = &request.correct_answer;
+        let mut fields = FieldStream::default();
+        let mut text = String::new();
+        while let Some(chunk) = chunks.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    error!("LLM stream failed: {:?}", e);
+                    let message = json!({ "message": format!("{}: {}", llm_error, e) });
+                    let _ = tx.send(sse_event("error", &message)).await;
+                    return;
+                }
+            };
+            text.push_str(&chunk);
+
+            for (field, value) in fields.push(&chunk) {
+                if field == "correct_answer" || !value.is_array() {
+                    continue;
+                }
+                let mut object = Map::new();
+                object.insert(field.clone(), value);
+                apply_gate(&mut object, answer);
+                let event = json!({ "field": field, "distractors": object.remove(&field) });
+                if tx.send(sse_event("distractors", &event)).await.is_err() {
+                    // The client went away.
+                    return;
+                }
+            }
+        }
+
+        // The error is not `Send`, so it is turned into its message before
+        // the generation is recorded.
+        let outcome = finish_response::<T>(
+            &app_state,
+            &prompt,
+            answer,
+            &request.options,
+            &request.controls,
+            &schema,
+            1,
+            vec![text],
+            llm_error,
+        )
+        .await
+        .map_err(|e| e.to_string());
+        let event = match outcome {
+            Ok((body, stats)) => {
+                record_generation(&app_state, &rendered, generation_id, &request.usage, stats)
+                    .await;
+                sse_event("result", &body)
+            }
+            Err(message) => sse_event("error", &json!({ "message": message })),
+        };
+        let _ = tx.send(event).await;
+    }));
+
+    let events = stream::unfold(rx, |mut rx| async move {
+        rx.recv()
+            .await
+            .map(|event| (Ok::<_, Infallible>(event), rx))
+    });
+    let mut response = HttpResponse::Ok();
+    response
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .insert_header((PROMPT_VERSION_HEADER, version));
+    if let Some(id) = generation_id {
+        response.insert_header((GENERATION_ID_HEADER, id.to_string()));
+    }
+    Ok(response.streaming(events))
+}
+
+/// Streams the generation of a `/mcq/quran/{kind}` route.
+pub async fn stream_quran(
+    app_state: web::Data<model::state::AppState>,
+    http_req: HttpRequest,
+    kind: web::Path<JobKind>,
+    req_body: web::Json<model::llm::QuranicVerseFillInThBlankTextGenerationRequest>,
+    query: web::Query<CandidateQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let kind = kind.into_inner();
+    if kind == JobKind::Context {
+        return Err(actix_web::error::ErrorNotFound("Unknown distractor type"));
+    }
+
+    let req_body = req_body.into_inner();
+    let request = JobRequest {
+        kind,
+        question: req_body.question,
+        correct_answer: req_body.correct_answer,
+        language: Some(req_body.language),
+        options: query.into_inner(),
+        controls: req_body.controls,
+        usage: usage_scope(&http_req)?,
+    };
+    for_job_kind!(kind, stream_response(app_state, request))
+}
+
+/// Streams the generation of `/mcq/options/context`.
+pub async fn stream_context(
+    app_state: web::Data<model::state::AppState>,
+    http_req: HttpRequest,
+    req_body: web::Json<model::llm::ContextFillInThBlankTextGenerationRequest>,
+    query: web::Query<CandidateQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let req_body = req_body.into_inner();
+    let request = JobRequest {
+        kind: JobKind::Context,
+        question: req_body.question,
+        correct_answer: req_body.correct_answer,
+        language: Some(req_body.language),
+        options: query.into_inner(),
+        controls: req_body.controls,
+        usage: usage_scope(&http_req)?,
+    };
+    stream_response::<GuessFillInTheBlankResponse>(app_state, request).await
+}
+
+/// Runs parsed responses through the quality gate, adding them to `pool`
+/// and what they lose to `rejected`. Returns how many distractors passed.
+fn gate_into<T: Serialize>(
+    parsed: &[T],
+    answer: &str,
+    pool: &mut Vec<Map<String, Value>>,
+    rejected: &mut Vec<RejectedDistractor>,
+) -> usize {
+    let mut passed = 0;
+    for response in parsed {
+        if let Ok(Value::Object(mut object)) = serde_json::to_value(response) {
+            rejected.extend(apply_gate(&mut object, answer));
+            passed += object
+                .iter()
+                .filter(|(field, _)| *field != "correct_answer")
+                .filter_map(|(_, value)| value.as_array())
+                .map(Vec::len)
+                .sum::<usize>();
+            pool.push(object);
+        }
+    }
+    passed
+}
+
+pub async fn generate_mcq_options_from_context(
+    app_state: web::Data<model::state::AppState>,
+    http_req: HttpRequest,
+    req_body: web::Json<model::llm::ContextFillInThBlankTextGenerationRequest>,
+    query: web::Query<CandidateQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let prompt = build_contextual_mcq_prompt(
+        &app_state.prompts,
+        &req_body.question,
+        &req_body.correct_answer,
+        req_body.language,
+        &req_body.controls,
+        &usage_scope(&http_req)?.user_id,
+    )?;
+
+    respond_cached::<GuessFillInTheBlankResponse>(
+        &app_state,
+        &http_req,
+        prompt,
+        &req_body.correct_answer,
+        &query,
+        &req_body.controls,
+        "LLM API Error",
+    )
+    .await
 }
 
 pub async fn generate_quranic_verse_distractor_response<T>(
+    app_state: web::Data<model::state::AppState>,
+    http_req: HttpRequest,
     req_body: web::Json<model::llm::QuranicVerseFillInThBlankTextGenerationRequest>,
+    query: web::Query<CandidateQuery>,
     distractor_type: DistractorType,
 ) -> Result<HttpResponse, actix_web::Error>
 where
     T: QuranDistractorResponse + Serialize,
 {
     let prompt = get_quranic_verse_distractor_prompt(
+        &app_state.prompts,
         &req_body.question,
         &req_body.correct_answer,
-        PromptLanguage::Arabic,
         distractor_type,
+        req_body.language,
+        &req_body.controls,
+        &usage_scope(&http_req)?.user_id,
     )?;
 
-    let raw_output = send_prompt_to_llm(prompt, 1).await.map_err(|e| {
-        error!("LLM API failure: {:?}", e);
-        actix_web::error::ErrorInternalServerError(format!("LLM API error: {}", e))
-    })?;
+    respond_cached::<T>(
+        &app_state,
+        &http_req,
+        prompt,
+        &req_body.correct_answer,
+        &query,
+        &req_body.controls,
+        "LLM API error",
+    )
+    .await
+}
 
-    let clean_text = utils::parse::clean_llm_json_output(&raw_output).map_err(|e| {
-        error!("Failed to clean LLM output: {:?}", e);
-        actix_web::error::ErrorInternalServerError(format!("Cleaning error: {}", e))
-    })?;
+/// Generates several types of distractors for one question at once, each
+/// as its route would, and answers with them keyed by type. A type that
+/// fails is reported without failing the others, unless none succeeded.
+/// Like jobs, bypasses the response cache.
+pub async fn generate_quran_distractors(
+    app_state: web::Data<model::state::AppState>,
+    http_req: HttpRequest,
+    req_body: web::Json<QuranDistractorsRequest>,
+    query: web::Query<CandidateQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    validate_candidates(&query)?;
+    validate_controls(&req_body.controls)?;
+    if req_body.distractor_types.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Name at least one distractor type",
+        ));
+    }
+    let mut seen = HashSet::new();
+    if !req_body.distractor_types.iter().all(|dt| seen.insert(*dt)) {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Distractor types must be unique",
+        ));
+    }
 
-    let response: T = serde_json::from_str(&clean_text).map_err(|e| {
-        error!("Failed to parse MCQ options from cleaned text: {:?}", e);
-        actix_web::error::ErrorInternalServerError(format!("Parsing error: {}", e))
-    })?;
+    let scope = usage_scope(&http_req)?;
+    let app_state = &app_state;
+    let generation = req_body.distractor_types.iter().map(|&distractor_type| {
+        let job = JobRequest {
+            kind: JobKind::from(distractor_type),
+            question: req_body.question.clone(),
+            correct_answer: req_body.correct_answer.clone(),
+            language: req_body.language,
+            options: query.0.clone(),
+            controls: req_body.controls.clone(),
+            usage: scope.clone(),
+        };
+        async move { (distractor_type, generate_job(app_state, &job).await) }
+    });
+    let mut outcomes: Vec<(DistractorType, Result<JobOutput, actix_web::Error>)> =
+        usage::scoped(scope.clone(), join_all(generation)).await;
+
+    // With nothing generated, a failing LLM or a spent quota fails the request.
+    if outcomes.iter().all(|(_, outcome)| outcome.is_err()) {
+        let server_error = outcomes
+            .iter()
+            .position(|(_, outcome)| matches!(outcome, Err(e) if is_generation_failure(e)));
+        if let Some((_, Err(e))) = server_error.map(|index| outcomes.swap_remove(index)) {
+            return Err(e);
+        }
+    }
+
+    let mut failed = 0;
+    let distractors = outcomes
+        .into_iter()
+        .map(|(distractor_type, outcome)| {
+            let distractors = match outcome {
+                Ok(output) => TypeDistractors {
+                    status: StatusCode::OK.as_u16(),
+                    result: Some(output.result),
+                    prompt_version: Some(output.prompt_version),
+                    generation_id: output.generation_id,
+                    error: None,
+                },
+                Err(e) => {
+                    failed += 1;
+                    TypeDistractors {
+                        status: e.as_response_error().status_code().as_u16(),
+                        result: None,
+                        prompt_version: None,
+                        generation_id: None,
+                        error: Some(e.to_string()),
+                    }
+                }
+            };
+            (distractor_type, distractors)
+        })
+        .collect();
 
-    Ok(HttpResponse::Ok().json(response))
+    Ok(HttpResponse::Ok().json(QuranDistractorsResponse {
+        failed,
+        distractors,
+    }))
 }
 
 pub async fn generate_collection(
+    app_state: web::Data<model::state::AppState>,
+    http_req: HttpRequest,
     req_body: web::Json<model::llm::QuranicVerseFillInThBlankTextGenerationRequest>,
+    query: web::Query<CandidateQuery>,
 ) -> Result<HttpResponse, actix_web::Error> {
     generate_quranic_verse_distractor_response::<GuessFillInTheBlankQuranDistractorCollectionResponse>(
+        app_state,
+        http_req,
         req_body,
+        query,
         DistractorType::Collection,
     ).await
 }
 
 pub async fn generate_morphological(
+    app_state: web::Data<model::state::AppState>,
+    http_req: HttpRequest,
     req_body: web::Json<model::llm::QuranicVerseFillInThBlankTextGenerationRequest>,
+    query: web::Query<CandidateQuery>,
 ) -> Result<HttpResponse, actix_web::Error> {
     generate_quranic_verse_distractor_response::<MorphologicalDistractorResponse>(
+        app_state,
+        http_req,
         req_body,
+        query,
         DistractorType::Morphological,
     )
     .await
 }
 
 pub async fn generate_diacritic(
+    app_state: web::Data<model::state::AppState>,
+    http_req: HttpRequest,
     req_body: web::Json<model::llm::QuranicVerseFillInThBlankTextGenerationRequest>,
+    query: web::Query<CandidateQuery>,
 ) -> Result<HttpResponse, actix_web::Error> {
     generate_quranic_verse_distractor_response::<DiacriticDistractorResponse>(
+        app_state,
+        http_req,
         req_body,
+        query,
         DistractorType::Diacritic,
     )
     .await
 }
 
 pub async fn generate_phonetic(
+    app_state: web::Data<model::state::AppState>,
+    http_req: HttpRequest,
     req_body: web::Json<model::llm::QuranicVerseFillInThBlankTextGenerationRequest>,
+    query: web::Query<CandidateQuery>,
 ) -> Result<HttpResponse, actix_web::Error> {
     generate_quranic_verse_distractor_response::<PhoneticOrthographicDistractorResponse>(
+        app_state,
+        http_req,
         req_body,
+        query,
         DistractorType::Phonetic,
     )
     .await
 }
 
 pub async fn generate_grammatical(
+    app_state: web::Data<model::state::AppState>,
+    http_req: HttpRequest,
     req_body: web::Json<model::llm::QuranicVerseFillInThBlankTextGenerationRequest>,
+    query: web::Query<CandidateQuery>,
 ) -> Result<HttpResponse, actix_web::Error> {
     generate_quranic_verse_distractor_response::<GrammaticalDistractorResponse>(
+        app_state,
+        http_req,
         req_body,
+        query,
         DistractorType::Grammatical,
     )
     .await
 }
 
 pub async fn generate_alternate_verse(
+    app_state: web::Data<model::state::AppState>,
+    http_req: HttpRequest,
     req_body: web::Json<model::llm::QuranicVerseFillInThBlankTextGenerationRequest>,
+    query: web::Query<CandidateQuery>,
 ) -> Result<HttpResponse, actix_web::Error> {
     generate_quranic_verse_distractor_response::<AlternateVerseDistractorResponse>(
+        app_state,
+        http_req,
         req_body,
+        query,
         DistractorType::AlternateVerse,
     )
     .await
 }
 
 pub async fn generate_thematic(
+    app_state: web::Data<model::state::AppState>,
+    http_req: HttpRequest,
     req_body: web::Json<model::llm::QuranicVerseFillInThBlankTextGenerationRequest>,
+    query: web::Query<CandidateQuery>,
 ) -> Result<HttpResponse, actix_web::Error> {
     generate_quranic_verse_distractor_response::<ThematicDistractorResponse>(
+        app_state,
+        http_req,
         req_body,
+        query,
         DistractorType::Thematic,
     )
     .await
 }
 
 pub async fn generate_collocational(
+    app_state: web::Data<model::state::AppState>,
+    http_req: HttpRequest,
     req_body: web::Json<model::llm::QuranicVerseFillInThBlankTextGenerationRequest>,
+    query: web::Query<CandidateQuery>,
 ) -> Result<HttpResponse, actix_web::Error> {
     generate_quranic_verse_distractor_response::<CollocationalDistractorResponse>(
+        app_state,
+        http_req,
         req_body,
+        query,
         DistractorType::Collocational,
     )
     .await
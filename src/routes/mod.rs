@@ -1,21 +1,101 @@
+pub mod bank;
 pub mod create;
 pub mod delete;
 pub mod edit;
+pub mod experiments;
+pub mod export;
 pub mod fetch;
+pub mod import;
+pub mod jobs;
 pub mod mcq;
+pub mod print;
+pub mod prompts;
 pub mod quran;
+pub mod search;
+pub mod tags;
+pub mod usage;
 use actix_web::{web, Scope};
 
 pub fn exam_routes() -> Scope {
     web::scope("/exam")
         .service(web::resource("/create").route(web::post().to(create::create_exam)))
         .service(web::resource("/edit").route(web::put().to(edit::edit_exam)))
+        .service(
+            web::resource("/import")
+                .app_data(web::PayloadConfig::new(import::MAX_IMPORT_SIZE))
+                .route(web::post().to(import::import_exam)),
+        )
+        .service(
+            web::resource("/import/spreadsheet")
+                .app_data(web::PayloadConfig::new(import::MAX_IMPORT_SIZE))
+                .route(web::post().to(import::import_spreadsheet)),
+        )
+        .service(web::resource("/search").route(web::get().to(tags::search_exams)))
         .service(web::resource("/{exam_id}").route(web::get().to(fetch::fetch_exam)))
+        .service(web::resource("/{exam_id}/export").route(web::get().to(export::export_exam)))
+        .service(web::resource("/{exam_id}/print").route(web::get().to(print::print_exam)))
+        .service(
+            web::resource("/{exam_id}/import/spreadsheet")
+                .app_data(web::PayloadConfig::new(import::MAX_IMPORT_SIZE))
+                .route(web::post().to(import::append_spreadsheet)),
+        )
+        .service(
+            web::resource("/{exam_id}/sections/{section_id}/questions")
+                .route(web::post().to(bank::link_questions)),
+        )
+        .service(
+            web::resource("/{exam_id}/sections/{section_id}/generate-options")
+                .route(web::post().to(mcq::generate_section_options)),
+        )
+        .service(
+            web::resource("/{exam_id}/sections/{section_id}/questions/{question_id}")
+                .route(web::delete().to(bank::unlink_question)),
+        )
         .service(web::resource("/delete/{exam_id}").route(web::delete().to(delete::delete_exam)))
 }
 
+pub fn bank_routes() -> Scope {
+    web::scope("/bank")
+        .service(
+            web::resource("/questions")
+                .route(web::post().to(bank::create_question))
+                .route(web::get().to(bank::search_questions)),
+        )
+        .service(
+            web::resource("/questions/{question_id}")
+                .route(web::get().to(bank::fetch_question))
+                .route(web::put().to(bank::update_question))
+                .route(web::delete().to(bank::delete_question)),
+        )
+        .service(
+            web::resource("/questions/{question_id}/tags")
+                .route(web::post().to(bank::tag_question)),
+        )
+        .service(
+            web::resource("/questions/{question_id}/distractors")
+                .route(web::post().to(mcq::attach_distractors)),
+        )
+        .service(
+            web::resource("/questions/{question_id}/tags/{tag_id}")
+                .route(web::delete().to(bank::untag_question)),
+        )
+}
+
+pub fn tag_routes() -> Scope {
+    web::scope("/tags")
+        .service(
+            web::resource("")
+                .route(web::post().to(tags::create_tag))
+                .route(web::get().to(tags::list_tags)),
+        )
+        .service(web::resource("/{tag_id}").route(web::delete().to(tags::delete_tag)))
+}
+
 pub fn mcq_routes() -> Scope {
     web::scope("/mcq")
+        .service(web::resource("/capabilities").route(web::get().to(mcq::capabilities)))
+        .service(web::resource("/quran").route(web::post().to(mcq::generate_quran_distractors)))
+        .service(web::resource("/quran/{kind}/stream").route(web::post().to(mcq::stream_quran)))
         .service(web::resource("/quran/collection").route(web::post().to(mcq::generate_collection)))
         .service(web::resource("/quran/diacritic").route(web::post().to(mcq::generate_diacritic)))
         .service(web::resource("/quran/phonetic").route(web::post().to(mcq::generate_phonetic)))
@@ -39,6 +119,74 @@ pub fn mcq_routes() -> Scope {
             web::resource("/options/context")
                 .route(web::post().to(mcq::generate_mcq_options_from_context)),
         )
+        .service(
+            web::resource("/options/context/stream").route(web::post().to(mcq::stream_context)),
+        )
+}
+
+pub fn job_routes() -> Scope {
+    web::scope("/jobs")
+        .service(web::resource("/mcq/quran/{kind}").route(web::post().to(jobs::enqueue_quran_job)))
+        .service(
+            web::resource("/mcq/options/context").route(web::post().to(jobs::enqueue_context_job)),
+        )
+        .service(web::resource("/{job_id}").route(web::get().to(jobs::fetch_job)))
+}
+
+pub fn prompt_routes() -> Scope {
+    web::scope("/prompts")
+        .service(
+            web::resource("/templates")
+                .route(web::get().to(prompts::list_templates))
+                .route(web::post().to(prompts::create_template)),
+        )
+        .service(
+            web::resource("/templates/{id}")
+                .route(web::get().to(prompts::fetch_template))
+                .route(web::put().to(prompts::update_template))
+                .route(web::delete().to(prompts::delete_template)),
+        )
+        .service(
+            web::resource("/templates/{id}/activate")
+                .route(web::put().to(prompts::activate_template)),
+        )
+        .service(web::resource("/active").route(web::get().to(prompts::active_templates)))
+        .service(web::resource("/partials").route(web::get().to(prompts::list_partials)))
+        .service(
+            web::resource("/partials/{name}")
+                .route(web::put().to(prompts::put_partial))
+                .route(web::delete().to(prompts::delete_partial)),
+        )
+        .service(
+            web::resource("/experiments")
+                .route(web::get().to(experiments::list_experiments))
+                .route(web::post().to(experiments::create_experiment)),
+        )
+        .service(
+            web::resource("/experiments/{id}").route(web::get().to(experiments::fetch_experiment)),
+        )
+        .service(
+            web::resource("/experiments/{id}/stop")
+                .route(web::put().to(experiments::stop_experiment)),
+        )
+        .service(
+            web::resource("/experiments/{id}/report")
+                .route(web::get().to(experiments::experiment_report)),
+        )
+}
+
+pub fn generation_routes() -> Scope {
+    web::scope("/generations")
+        .service(
+            web::resource("/{id}/feedback").route(web::post().to(experiments::record_feedback)),
+        )
+        .service(
+            web::resource("/{id}/selections").route(web::post().to(experiments::record_selections)),
+        )
+}
+
+pub fn admin_routes() -> Scope {
+    web::scope("/admin").service(web::resource("/usage").route(web::get().to(usage::usage_report)))
 }
 
 pub fn quran_routes() -> Scope {
@@ -47,8 +195,19 @@ pub fn quran_routes() -> Scope {
     )
 }
 
+pub fn search_routes() -> Scope {
+    web::scope("/search").service(web::resource("").route(web::get().to(search::search_questions)))
+}
+
 pub fn config_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(exam_routes());
+    cfg.service(bank_routes());
+    cfg.service(tag_routes());
+    cfg.service(search_routes());
     cfg.service(mcq_routes());
+    cfg.service(job_routes());
+    cfg.service(prompt_routes());
+    cfg.service(generation_routes());
+    cfg.service(admin_routes());
     cfg.service(quran_routes());
 }
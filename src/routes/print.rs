@@ -0,0 +1,62 @@
+use crate::database::queries;
+use crate::model::{self, print::PrintFormat, print::PrintQuery};
+use crate::services::print;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{web, HttpResponse};
+use anyhow::Result;
+
+pub async fn print_exam(
+    app_state: web::Data<model::state::AppState>,
+    exam_id: web::Path<String>,
+    query: web::Query<PrintQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let exam_id_int: i32 = exam_id.into_inner().parse().map_err(|e| {
+        log::error!("Failed to print exam: {:?}", e);
+        actix_web::error::ErrorBadRequest("Invalid exam id")
+    })?;
+
+    let options = query.options().ok_or_else(|| {
+        actix_web::error::ErrorBadRequest(format!(
+            "variants must be between 1 and {}",
+            model::print::MAX_VARIANTS
+        ))
+    })?;
+
+    let exam_data = queries::read::read_exam_data(&app_state.db_client.pool, exam_id_int)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to fetch exam for printing: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?;
+
+    let html = print::render_html(&exam_data, &options);
+
+    if query.format == PrintFormat::Html {
+        return Ok(HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .body(html));
+    }
+
+    if !app_state.pdf_renderer.enabled() {
+        log::error!("PDF requested but PDF_RENDER_URL is not configured");
+        return Err(actix_web::error::ErrorServiceUnavailable(
+            "PDF rendering is not configured",
+        ));
+    }
+
+    let pdf = app_state.pdf_renderer.render(html).await.map_err(|e| {
+        log::error!("Failed to render exam PDF: {:?}", e);
+        actix_web::error::ErrorBadGateway("Failed to render PDF")
+    })?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/pdf")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "exam-{}.pdf",
+                exam_id_int
+            ))],
+        })
+        .body(pdf))
+}
@@ -0,0 +1,232 @@
+use crate::database::queries;
+use crate::model;
+use crate::model::prompt::{
+    CreatePromptTemplateRequest, PromptExample, PromptTemplate, PromptTemplateQuery,
+    PutPromptPartialRequest, TemplateKey, UpdatePromptTemplateRequest,
+};
+use crate::utils;
+use actix_web::{web, HttpResponse};
+use log::error;
+
+fn internal_error(context: &str, e: anyhow::Error) -> actix_web::Error {
+    error!("{}: {:?}", context, e);
+    actix_web::error::ErrorInternalServerError("Internal server error")
+}
+
+async fn find_template(
+    app_state: &model::state::AppState,
+    id: i32,
+) -> Result<PromptTemplate, actix_web::Error> {
+    queries::prompts::get_template(&app_state.db_client.pool, id)
+        .await
+        .map_err(|e| internal_error("Failed to fetch prompt template", e))?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Prompt template not found"))
+}
+
+/// Makes the store pick up a change right away rather than at the next
+/// periodic reload.
+async fn reload(app_state: &model::state::AppState) {
+    if let Err(e) = app_state.prompts.reload().await {
+        log::warn!("Failed to reload prompt templates: {:?}", e);
+    }
+}
+
+/// Validates and stores the next version of a series, and answers with it.
+async fn add_version(
+    app_state: &model::state::AppState,
+    key: &TemplateKey,
+    body: &str,
+    examples: &[PromptExample],
+    note: Option<&str>,
+    activate: bool,
+) -> Result<HttpResponse, actix_web::Error> {
+    app_state
+        .prompts
+        .validate(key, body, examples)
+        .map_err(actix_web::error::ErrorBadRequest)?;
+
+    let id = queries::prompts::insert_template(
+        &app_state.db_client.pool,
+        key,
+        body,
+        examples,
+        note,
+        activate,
+    )
+    .await
+    .map_err(|e| internal_error("Failed to save prompt template", e))?;
+    if activate {
+        reload(app_state).await;
+    }
+
+    let template = find_template(app_state, id).await?;
+    Ok(HttpResponse::Created().json(template))
+}
+
+/// Lists template versions, optionally of one task, language or
+/// distractor type, or only the active ones.
+pub async fn list_templates(
+    app_state: web::Data<model::state::AppState>,
+    query: web::Query<PromptTemplateQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let templates = queries::prompts::list_templates(&app_state.db_client.pool, &query)
+        .await
+        .map_err(|e| internal_error("Failed to list prompt templates", e))?;
+
+    Ok(HttpResponse::Ok().json(templates))
+}
+
+/// Lists the versions in use.
+pub async fn active_templates(
+    app_state: web::Data<model::state::AppState>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let filter = PromptTemplateQuery {
+        active: true,
+        ..Default::default()
+    };
+    list_templates(app_state, web::Query(filter)).await
+}
+
+/// Adds the first or next version of a series.
+pub async fn create_template(
+    app_state: web::Data<model::state::AppState>,
+    req_body: web::Json<CreatePromptTemplateRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    add_version(
+        &app_state,
+        &req_body.key,
+        &req_body.body,
+        &req_body.examples,
+        req_body.note.as_deref(),
+        req_body.activate,
+    )
+    .await
+}
+
+pub async fn fetch_template(
+    app_state: web::Data<model::state::AppState>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let template = find_template(&app_state, id.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(template))
+}
+
+/// Adds a new version to the series of a template. Stored versions are
+/// never changed, so that results keep pointing at what produced them.
+pub async fn update_template(
+    app_state: web::Data<model::state::AppState>,
+    id: web::Path<i32>,
+    req_body: web::Json<UpdatePromptTemplateRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let template = find_template(&app_state, id.into_inner()).await?;
+    add_version(
+        &app_state,
+        &template.key,
+        &req_body.body,
+        &req_body.examples,
+        req_body.note.as_deref(),
+        req_body.activate,
+    )
+    .await
+}
+
+/// Makes a version the one in use for its series, e.g. to roll back.
+pub async fn activate_template(
+    app_state: web::Data<model::state::AppState>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let id = id.into_inner();
+    let found = queries::prompts::activate_template(&app_state.db_client.pool, id)
+        .await
+        .map_err(|e| internal_error("Failed to activate prompt template", e))?;
+    if !found {
+        return Err(actix_web::error::ErrorNotFound("Prompt template not found"));
+    }
+    reload(&app_state).await;
+
+    let template = find_template(&app_state, id).await?;
+    Ok(HttpResponse::Ok().json(template))
+}
+
+/// Deletes a version that is neither in use nor part of an experiment.
+pub async fn delete_template(
+    app_state: web::Data<model::state::AppState>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let template = find_template(&app_state, id.into_inner()).await?;
+    if template.active {
+        return Err(actix_web::error::ErrorConflict(
+            "The template version is active; activate another version first",
+        ));
+    }
+
+    let deleted = queries::prompts::delete_template(&app_state.db_client.pool, template.id)
+        .await
+        .map_err(|e| internal_error("Failed to delete prompt template", e))?;
+    if !deleted {
+        return Err(actix_web::error::ErrorConflict(
+            "The template version is a variant of an experiment",
+        ));
+    }
+
+    Ok(HttpResponse::Ok().json("Prompt template deleted successfully"))
+}
+
+/// Lists the partials templates can include.
+pub async fn list_partials(
+    app_state: web::Data<model::state::AppState>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let partials = queries::prompts::list_partials(&app_state.db_client.pool)
+        .await
+        .map_err(|e| internal_error("Failed to list prompt partials", e))?;
+
+    Ok(HttpResponse::Ok().json(partials))
+}
+
+/// Creates or replaces a partial, unless an active template would no
+/// longer render with it.
+pub async fn put_partial(
+    app_state: web::Data<model::state::AppState>,
+    name: web::Path<String>,
+    req_body: web::Json<PutPromptPartialRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let name = name.into_inner();
+    if !utils::prompts::is_partial_name(&name) {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Partial names are lowercase letters, digits and underscores",
+        ));
+    }
+    app_state
+        .prompts
+        .validate_partial(&name, Some(&req_body.body))
+        .map_err(actix_web::error::ErrorBadRequest)?;
+
+    let partial = queries::prompts::put_partial(&app_state.db_client.pool, &name, &req_body.body)
+        .await
+        .map_err(|e| internal_error("Failed to save prompt partial", e))?;
+    reload(&app_state).await;
+
+    Ok(HttpResponse::Ok().json(partial))
+}
+
+/// Deletes a partial no active template includes.
+pub async fn delete_partial(
+    app_state: web::Data<model::state::AppState>,
+    name: web::Path<String>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let name = name.into_inner();
+    app_state
+        .prompts
+        .validate_partial(&name, None)
+        .map_err(actix_web::error::ErrorConflict)?;
+
+    let deleted = queries::prompts::delete_partial(&app_state.db_client.pool, &name)
+        .await
+        .map_err(|e| internal_error("Failed to delete prompt partial", e))?;
+    if !deleted {
+        return Err(actix_web::error::ErrorNotFound("Prompt partial not found"));
+    }
+    reload(&app_state).await;
+
+    Ok(HttpResponse::Ok().json("Prompt partial deleted successfully"))
+}
@@ -0,0 +1,48 @@
+use crate::database::queries;
+use crate::model;
+use crate::model::bank::{DEFAULT_SEARCH_LIMIT, MAX_SEARCH_LIMIT};
+use crate::model::search::{SearchQuery, SearchResponse};
+use crate::services::search;
+use actix_web::{web, HttpResponse};
+use anyhow::Result;
+
+/// Searches question texts, descriptions and options, ignoring tashkeel and
+/// Arabic/Urdu letter variants. Results come with highlighted snippets and
+/// the exams and sections that use each question.
+pub async fn search_questions(
+    app_state: web::Data<model::state::AppState>,
+    query: web::Query<SearchQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let term = query.q.trim();
+    if term.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest("Search term is empty"));
+    }
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .clamp(1, MAX_SEARCH_LIMIT) as usize;
+    let offset = query.offset.unwrap_or_default().max(0) as usize;
+
+    let pool = &app_state.db_client.pool;
+    let response = async {
+        let rows = queries::search::search_fields(pool, term, query.fuzzy.unwrap_or(true)).await?;
+        let hits = search::rank(rows, term);
+        let total = hits.len();
+
+        let mut results: Vec<_> = hits.into_iter().skip(offset).take(limit).collect();
+        let ids: Vec<i32> = results.iter().map(|h| h.question_id).collect();
+        let mut usage = queries::search::usage_of(pool, &ids).await?;
+        for hit in &mut results {
+            hit.used_in = usage.remove(&hit.question_id).unwrap_or_default();
+        }
+
+        anyhow::Ok(SearchResponse { total, results })
+    }
+    .await
+    .map_err(|e| {
+        log::error!("Failed to search questions: {:?}", e);
+        actix_web::error::ErrorInternalServerError("Internal server error")
+    })?;
+
+    Ok(HttpResponse::Ok().json(response))
+}
@@ -0,0 +1,132 @@
+use crate::database::queries;
+use crate::model;
+use crate::model::tag::{
+    parse_tag_ids, CreateTagRequest, ExamSearchQuery, ExamSearchResponse, TagListQuery,
+};
+use crate::utils::parse;
+use actix_web::{web, HttpResponse};
+use anyhow::Result;
+use serde_json::json;
+
+/// Parses the `tags` query parameter and checks that every tag exists.
+///
+/// Returns the tag IDs, or the response to send if the parameter is invalid.
+pub async fn tag_filter(
+    app_state: &model::state::AppState,
+    tags: Option<&str>,
+) -> Result<Result<Vec<i32>, HttpResponse>, actix_web::Error> {
+    let Ok(tag_ids) = parse_tag_ids(tags) else {
+        return Ok(Err(HttpResponse::BadRequest().json("Invalid tag ids")));
+    };
+
+    let missing = queries::tags::missing_tags(&app_state.db_client.pool, &tag_ids)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to look up tags: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?;
+    if !missing.is_empty() {
+        return Ok(Err(HttpResponse::NotFound().json(json!({
+            "message": "Tags not found",
+            "tag_ids": missing,
+        }))));
+    }
+
+    Ok(Ok(tag_ids))
+}
+
+pub async fn create_tag(
+    app_state: web::Data<model::state::AppState>,
+    req_body: web::Json<CreateTagRequest>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let name = req_body.name.trim();
+    if name.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest("Tag name is empty"));
+    }
+
+    let pool = &app_state.db_client.pool;
+    if let Some(parent_id) = req_body.parent_id {
+        let parent = queries::tags::find_tag(pool, parent_id)
+            .await
+            .map_err(|e| {
+                log::error!("Failed to look up parent tag: {:?}", e);
+                actix_web::error::ErrorInternalServerError("Internal server error")
+            })?
+            .ok_or_else(|| actix_web::error::ErrorNotFound("Parent tag not found"))?;
+        if parent.facet != req_body.facet {
+            return Err(actix_web::error::ErrorBadRequest(
+                "A tag must have the facet of its parent",
+            ));
+        }
+    }
+
+    let tag = queries::tags::create_tag(pool, req_body.facet, name, req_body.parent_id)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to create tag: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?
+        .ok_or_else(|| actix_web::error::ErrorConflict("Tag already exists"))?;
+
+    Ok(HttpResponse::Created().json(tag))
+}
+
+/// Lists the taxonomy as trees of tags, optionally for one facet.
+pub async fn list_tags(
+    app_state: web::Data<model::state::AppState>,
+    query: web::Query<TagListQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let tags = queries::tags::list_tags(&app_state.db_client.pool, query.facet)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to list tags: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?;
+
+    Ok(HttpResponse::Ok().json(parse::map_to_tag_tree(tags)))
+}
+
+/// Deletes a tag and the tags nested under it.
+pub async fn delete_tag(
+    app_state: web::Data<model::state::AppState>,
+    tag_id: web::Path<i32>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let deleted = queries::tags::delete_tag(&app_state.db_client.pool, tag_id.into_inner())
+        .await
+        .map_err(|e| {
+            log::error!("Failed to delete tag: {:?}", e);
+            actix_web::error::ErrorInternalServerError("Internal server error")
+        })?;
+    if !deleted {
+        return Err(actix_web::error::ErrorNotFound("Tag not found"));
+    }
+
+    Ok(HttpResponse::Ok().json("Tag deleted successfully"))
+}
+
+/// Finds the exams using questions that match a search term and tag filter,
+/// with tag counts over the matching questions.
+pub async fn search_exams(
+    app_state: web::Data<model::state::AppState>,
+    query: web::Query<ExamSearchQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let tag_ids = match tag_filter(&app_state, query.tags.as_deref()).await? {
+        Ok(tag_ids) => tag_ids,
+        Err(response) => return Ok(response),
+    };
+
+    let pool = &app_state.db_client.pool;
+    let response = async {
+        let ids = queries::bank::matching_question_ids(pool, query.q.as_deref(), &tag_ids).await?;
+        let exams = queries::tags::exams_using(pool, &ids).await?;
+        let facets = queries::tags::facet_counts(pool, &ids).await?;
+        anyhow::Ok(ExamSearchResponse { exams, facets })
+    }
+    .await
+    .map_err(|e| {
+        log::error!("Failed to search exams: {:?}", e);
+        actix_web::error::ErrorInternalServerError("Internal server error")
+    })?;
+
+    Ok(HttpResponse::Ok().json(response))
+}
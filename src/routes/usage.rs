@@ -0,0 +1,119 @@
+use crate::database::queries;
+use crate::model;
+use crate::model::usage::{UsageReport, UsageReportQuery, UsageScope, ANONYMOUS_USER};
+use crate::utils;
+use actix_web::http::header;
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::{Duration, Utc};
+use log::error;
+
+/// Header naming the user a request is made for. Nothing here checks it, so
+/// it must be set by a trusted gateway that strips any value the client
+/// sent; otherwise anyone can spend, or dodge, another user's quota.
+pub const USER_HEADER: &str = "X-User-Id";
+
+/// Header naming the exam a request is made for, where the path does not.
+pub const EXAM_HEADER: &str = "X-Exam-Id";
+
+/// Who LLM calls made for a request are accounted to: the `X-User-Id`
+/// user, the `{exam_id}` of the path or else the `X-Exam-Id` exam, and the
+/// route pattern.
+///
+/// Without `X-User-Id`, calls are accounted to the anonymous user, unless
+/// quotas are set: then the request is refused, so leaving the header out
+/// cannot get around them.
+pub fn usage_scope(http_req: &HttpRequest) -> Result<UsageScope, actix_web::Error> {
+    let header = |name: &str| {
+        http_req
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    };
+
+    let quotas_set = http_req
+        .app_data::<web::Data<model::state::AppState>>()
+        .is_some_and(|app_state| app_state.usage_tracker.quotas().is_set());
+    let user_id = match header(USER_HEADER) {
+        Some(user_id) => user_id,
+        None if quotas_set => {
+            return Err(actix_web::error::ErrorUnauthorized(format!(
+                "The {} header is required",
+                USER_HEADER
+            )))
+        }
+        None => ANONYMOUS_USER,
+    };
+
+    Ok(UsageScope {
+        user_id: user_id.to_string(),
+        exam_id: http_req
+            .match_info()
+            .get("exam_id")
+            .or_else(|| header(EXAM_HEADER))
+            .and_then(|exam_id| exam_id.parse().ok()),
+        endpoint: http_req
+            .match_pattern()
+            .unwrap_or_else(|| http_req.path().to_string()),
+    })
+}
+
+/// Checks the `Authorization: Bearer` token against `ADMIN_API_TOKEN`.
+/// Admin routes are off while it is not set.
+fn authorize_admin(http_req: &HttpRequest) -> Result<(), actix_web::Error> {
+    let token = utils::env::load_env_var("ADMIN_API_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| actix_web::error::ErrorForbidden("Admin routes are disabled"))?;
+
+    let given = http_req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if given != Some(token.as_str()) {
+        return Err(actix_web::error::ErrorUnauthorized("Invalid admin token"));
+    }
+    Ok(())
+}
+
+/// Reports the LLM tokens spent per user, exam and endpoint between `from`
+/// (30 days ago by default) and `to` (now), with the configured quotas.
+pub async fn usage_report(
+    app_state: web::Data<model::state::AppState>,
+    http_req: HttpRequest,
+    query: web::Query<UsageReportQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    authorize_admin(&http_req)?;
+
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query.from.unwrap_or(to - Duration::days(30));
+    if from >= to {
+        return Err(actix_web::error::ErrorBadRequest("from must be before to"));
+    }
+
+    let rows = queries::usage::usage_report(
+        &app_state.db_client.pool,
+        from,
+        to,
+        query.user_id.as_deref(),
+        query.exam_id,
+    )
+    .await
+    .map_err(|e| {
+        error!("Failed to read LLM usage: {:?}", e);
+        actix_web::error::ErrorInternalServerError("Internal server error")
+    })?;
+
+    let quotas = app_state.usage_tracker.quotas();
+    Ok(HttpResponse::Ok().json(UsageReport {
+        from,
+        to,
+        daily_quota: quotas.daily,
+        monthly_quota: quotas.monthly,
+        calls: rows.iter().map(|row| row.calls).sum(),
+        total_tokens: rows.iter().map(|row| row.total_tokens).sum(),
+        rows,
+    }))
+}
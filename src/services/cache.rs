@@ -0,0 +1,138 @@
+//! Redis cache of generated MCQ responses.
+//!
+//! Responses are stored under a SHA-256 fingerprint of everything that
+//! shapes them: the rendered prompt, provider, model, temperature, response
+//! type and generation options. `LLM_CACHE_TTL_SECS` sets how long they are
+//! kept (a day by default); `0` turns the cache off.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use deadpool_redis::redis::AsyncCommands;
+use sha2::{Digest, Sha256};
+
+use crate::conn::RedisClient;
+use crate::utils;
+
+/// How long responses are cached unless `LLM_CACHE_TTL_SECS` says otherwise.
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Prefix of the cache keys in Redis.
+const KEY_PREFIX: &str = "llm:response:";
+
+/// Value of the `X-Cache` response header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CacheStatus {
+    Hit,
+    Miss,
+    /// The request asked not to be served from the cache.
+    Bypass,
+}
+
+impl CacheStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Hit => "HIT",
+            Self::Miss => "MISS",
+            Self::Bypass => "BYPASS",
+        }
+    }
+}
+
+/// The parts of a `Cache-Control` request header the cache honours.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheDirectives {
+    /// Do not answer from the cache, but store the fresh response.
+    pub no_cache: bool,
+    /// Neither answer from nor write to the cache.
+    pub no_store: bool,
+}
+
+impl CacheDirectives {
+    /// Parses the values of every `Cache-Control` header of a request.
+    pub fn parse<'a>(headers: impl Iterator<Item = &'a str>) -> Self {
+        headers
+            .flat_map(|value| value.split(','))
+            .map(|directive| directive.trim().to_ascii_lowercase())
+            .fold(Self::default(), |directives, directive| {
+                match directive.as_str() {
+                    "no-cache" => Self {
+                        no_cache: true,
+                        ..directives
+                    },
+                    "no-store" => Self {
+                        no_store: true,
+                        ..directives
+                    },
+                    _ => directives,
+                }
+            })
+    }
+}
+
+/// Builds the cache key of a response from the parts that shape it.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let key = cache_key(&[&prompt, "Gemini", "gemini-1.5-flash", "0.7", type_name]);
+/// ```
+pub fn cache_key(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        // Length-prefixed so that ("ab", "c") and ("a", "bc") differ.
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+    let digest: String = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("{}{}", KEY_PREFIX, digest)
+}
+
+/// The response cache shared through `AppState`.
+#[derive(Clone)]
+pub struct LlmCache {
+    redis: RedisClient,
+    ttl: Duration,
+}
+
+impl LlmCache {
+    pub fn new(redis: RedisClient, ttl: Duration) -> Self {
+        Self { redis, ttl }
+    }
+
+    /// Reads the TTL from `LLM_CACHE_TTL_SECS`, see the module docs.
+    pub fn from_env(redis: RedisClient) -> Result<Self> {
+        let ttl = match utils::env::load_env_var("LLM_CACHE_TTL_SECS") {
+            Ok(secs) => Duration::from_secs(
+                secs.trim()
+                    .parse()
+                    .with_context(|| format!("Invalid LLM_CACHE_TTL_SECS `{}`", secs))?,
+            ),
+            Err(_) => DEFAULT_CACHE_TTL,
+        };
+        Ok(Self::new(redis, ttl))
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.ttl.is_zero()
+    }
+
+    /// Returns the cached response body, if any.
+    pub async fn get(&self, key: &str) -> Result<Option<String>> {
+        let mut conn = self.redis.get_connection().await?;
+        conn.get(key)
+            .await
+            .context("Failed to read cached LLM response")
+    }
+
+    /// Stores a response body for the configured TTL.
+    pub async fn put(&self, key: &str, body: &str) -> Result<()> {
+        let mut conn = self.redis.get_connection().await?;
+        conn.set_ex::<_, _, ()>(key, body, self.ttl.as_secs())
+            .await
+            .context("Failed to cache LLM response")
+    }
+}
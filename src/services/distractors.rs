@@ -0,0 +1,246 @@
+//! Merging of several LLM candidates into one ranked distractor set.
+//!
+//! Each candidate is a typed MCQ response. Its distractor lists are pooled
+//! by field, duplicates are folded together, and the distractors are ranked
+//! by how many candidates suggested them.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::model::llm::{RankedDistractor, RankedResponse};
+use crate::utils::arabic::fold_for_search;
+
+/// Field holding the answer rather than distractors.
+const ANSWER_FIELD: &str = "correct_answer";
+
+/// How much two distractors may differ and still count as one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Folding {
+    /// Only spacing and tatweel. Diacritic, grammatical and morphological
+    /// distractors often differ from each other and the answer only in tashkeel.
+    Spacing,
+    /// Also tashkeel, keeping the letter variants orthographic distractors use.
+    Marks,
+    /// Everything search folds, see [`fold_for_search`].
+    Letters,
+}
+
+impl Folding {
+    /// The folding suited to a distractor list of a response.
+    pub fn for_field(field: &str) -> Self {
+        match field {
+            "diacritic_distractors" | "grammatical_distractors" | "morphological_distractors" => {
+                Self::Spacing
+            }
+            "phonetic_orthographic_distractors" => Self::Marks,
+            _ => Self::Letters,
+        }
+    }
+}
+
+/// The key under which a distractor is de-duplicated.
+pub fn dedup_key(text: &str, folding: Folding) -> String {
+    let folded: String = text
+        .chars()
+        .filter_map(|c| match folding {
+            Folding::Spacing => (c != '\u{0640}').then_some(c),
+            Folding::Marks => fold_for_search(c).map(|_| c),
+            Folding::Letters => fold_for_search(c),
+        })
+        .flat_map(char::to_lowercase)
+        .collect();
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+struct Tally {
+    text: String,
+    count: u32,
+    first_seen: usize,
+}
+
+/// Ranks the distractors of one field across candidates. A distractor counts
+/// once per candidate; ties keep the order they were first suggested in.
+/// Distractors equal to an answer are dropped.
+pub fn rank_distractors(
+    lists: &[Vec<String>],
+    answers: &[String],
+    folding: Folding,
+    parsed: u32,
+) -> Vec<RankedDistractor> {
+    let answers: Vec<String> = answers.iter().map(|a| dedup_key(a, folding)).collect();
+    let mut tallies: HashMap<String, Tally> = HashMap::new();
+    let mut seen = 0;
+
+    for list in lists {
+        let mut in_candidate = Vec::new();
+        for text in list {
+            let key = dedup_key(text, folding);
+            if key.is_empty() || answers.contains(&key) || in_candidate.contains(&key) {
+                continue;
+            }
+            in_candidate.push(key.clone());
+
+            let tally = tallies.entry(key).or_insert_with(|| {
+                seen += 1;
+                Tally {
+                    text: text.trim().to_string(),
+                    count: 0,
+                    first_seen: seen,
+                }
+            });
+            tally.count += 1;
+        }
+    }
+
+    let mut tallies: Vec<Tally> = tallies.into_values().collect();
+    tallies.sort_by(|a, b| b.count.cmp(&a.count).then(a.first_seen.cmp(&b.first_seen)));
+    tallies
+        .into_iter()
+        .map(|t| RankedDistractor {
+            agreement: t.count as f64 / parsed.max(1) as f64,
+            text: t.text,
+            count: t.count,
+        })
+        .collect()
+}
+
+fn strings(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_array)
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| item.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Merges parsed candidates into one response with at most `top_k`
+/// distractors per list. The answer is taken from the first candidate.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let merged = merge_candidates(&[first, second, third], Some(3));
+/// assert_eq!(merged.candidates, 3);
+/// ```
+pub fn merge_candidates<T: Serialize>(candidates: &[T], top_k: Option<usize>) -> RankedResponse {
+    let objects: Vec<Map<String, Value>> = candidates
+        .iter()
+        .filter_map(|c| match serde_json::to_value(c) {
+            Ok(Value::Object(object)) => Some(object),
+            _ => None,
+        })
+        .collect();
+    let parsed = objects.len() as u32;
+
+    let mut response = Map::new();
+    let mut scores = BTreeMap::new();
+    let Some(first) = objects.first() else {
+        return RankedResponse {
+            response,
+            candidates: 0,
+            scores,
+            rejected: Vec::new(),
+        };
+    };
+
+    let answers = strings(first.get(ANSWER_FIELD));
+    for (field, value) in first {
+        if field == ANSWER_FIELD || !value.is_array() {
+            response.insert(field.clone(), value.clone());
+            continue;
+        }
+
+        let lists: Vec<Vec<String>> = objects.iter().map(|o| strings(o.get(field))).collect();
+        let mut ranked = rank_distractors(&lists, &answers, Folding::for_field(field), parsed);
+        if let Some(top_k) = top_k {
+            ranked.truncate(top_k);
+        }
+
+        let texts: Vec<Value> = ranked.iter().map(|r| Value::from(r.text.clone())).collect();
+        response.insert(field.clone(), Value::Array(texts));
+        scores.insert(field.clone(), ranked);
+    }
+
+    RankedResponse {
+        response,
+        candidates: parsed,
+        scores,
+        rejected: Vec::new(),
+    }
+}
+
+/// Adds distractors regenerated to fill a short `field` after the ranked
+/// ones. They were asked for once rather than suggested by the candidates,
+/// so they get no votes. Repeats of listed distractors or the answer are
+/// dropped.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let mut merged = merge_candidates(&candidates, None);
+/// append_regenerated(&mut merged, "thematic_distractors", &regenerated);
+/// ```
+pub fn append_regenerated(merged: &mut RankedResponse, field: &str, texts: &[String]) {
+    let folding = Folding::for_field(field);
+    let mut seen: HashSet<String> = strings(merged.response.get(ANSWER_FIELD))
+        .iter()
+        .chain(&strings(merged.response.get(field)))
+        .map(|text| dedup_key(text, folding))
+        .collect();
+
+    let ranked = merged.scores.entry(field.to_string()).or_default();
+    let mut list = strings(merged.response.get(field));
+    for text in texts {
+        let key = dedup_key(text, folding);
+        if key.is_empty() || !seen.insert(key) {
+            continue;
+        }
+        list.push(text.trim().to_string());
+        ranked.push(RankedDistractor {
+            text: text.trim().to_string(),
+            count: 0,
+            agreement: 0.0,
+        });
+    }
+    merged.response.insert(
+        field.to_string(),
+        Value::Array(list.into_iter().map(Value::from).collect()),
+    );
+}
+
+/// Keeps at most `top_k` distractors per list of a merged response.
+pub fn truncate_lists(merged: &mut RankedResponse, top_k: usize) {
+    for (field, ranked) in merged.scores.iter_mut() {
+        ranked.truncate(top_k);
+        if let Some(Value::Array(list)) = merged.response.get_mut(field) {
+            list.truncate(top_k);
+        }
+    }
+}
+
+/// The distractors of a generated response body: every string list but the
+/// answer, in field order, without repeats or texts already in `existing`.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let new_options = distractor_texts(&body, &question.options);
+/// ```
+pub fn distractor_texts(response: &Value, existing: &[String]) -> Vec<String> {
+    let Some(object) = response.as_object() else {
+        return Vec::new();
+    };
+    let mut seen: HashSet<String> = existing
+        .iter()
+        .map(|text| dedup_key(text, Folding::Spacing))
+        .collect();
+
+    object
+        .iter()
+        .filter(|(field, _)| field.as_str() != ANSWER_FIELD)
+        .flat_map(|(_, value)| strings(Some(value)))
+        .filter(|text| seen.insert(dedup_key(text, Folding::Spacing)))
+        .collect()
+}
@@ -0,0 +1,462 @@
+//! Conversion between exam sections and Moodle's GIFT text format.
+//!
+//! Sections map to `$CATEGORY:` lines. Multiple-choice, true/false and
+//! short-answer questions round-trip, and questions containing a `___` blank
+//! use GIFT's missing-word form. GIFT has no grades, so imported questions are
+//! worth one mark. A question's `description` is carried as general
+//! feedback (`####`).
+
+use crate::model::format::ImportReport;
+use crate::model::option::OptionRequestModel;
+use crate::model::question::QuestionRequest;
+use crate::model::section::SectionRequest;
+use crate::services::interchange::{self, QuestionKind, SectionCollector, BLANK};
+use crate::utils::html;
+use anyhow::{bail, Result};
+
+const SPECIAL: [char; 6] = ['~', '=', '#', '{', '}', ':'];
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if SPECIAL.contains(&c) => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some(next) => out.push(next),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+fn render_answers(question: &QuestionRequest) -> String {
+    let mut answers = match interchange::classify(question) {
+        QuestionKind::TrueFalse(value) => {
+            return if value {
+                "{TRUE".into()
+            } else {
+                "{FALSE".into()
+            }
+        }
+        QuestionKind::ShortAnswer(texts) => texts
+            .iter()
+            .map(|t| format!("\t={}\n", escape(t)))
+            .collect::<String>(),
+        QuestionKind::MultiChoice => {
+            let correct = question
+                .options
+                .iter()
+                .filter(|o| o.base.is_correct.unwrap_or_default())
+                .count();
+            let weight = interchange::correct_fraction(correct);
+
+            question
+                .options
+                .iter()
+                .map(|o| {
+                    let text = escape(&o.base.text);
+                    match (o.base.is_correct.unwrap_or_default(), correct > 1) {
+                        (true, false) => format!("\t={}\n", text),
+                        (true, true) => format!("\t~%{}%{}\n", weight, text),
+                        (false, false) => format!("\t~{}\n", text),
+                        (false, true) => format!("\t~%-100%{}\n", text),
+                    }
+                })
+                .collect()
+        }
+    };
+    answers.insert_str(0, "{\n");
+    answers
+}
+
+fn render_question(question: &QuestionRequest) -> String {
+    let mut answers = render_answers(question);
+    if let Some(description) = question
+        .base
+        .description
+        .as_deref()
+        .filter(|d| !d.is_empty())
+    {
+        answers.push_str(&format!("\t####{}\n", escape(description)));
+    }
+    answers.push('}');
+
+    let name = escape(&interchange::question_name(question));
+    let text = &question.base.text;
+
+    // GIFT's missing-word form needs text after the answer block; a blank
+    // at the very end is kept literally instead.
+    match text
+        .split_once(BLANK)
+        .filter(|(_, after)| !after.trim().is_empty())
+    {
+        Some((before, after)) => format!(
+            "::{}::{} {} {}\n",
+            name,
+            escape(before.trim_end()),
+            answers,
+            escape(after.trim_start())
+        ),
+        None => format!("::{}::{} {}\n", name, escape(text), answers),
+    }
+}
+
+/// Renders sections as GIFT, one `$CATEGORY:` per section.
+pub fn export_sections(sections: &[SectionRequest]) -> String {
+    let mut out = String::new();
+
+    for section in sections {
+        out.push_str(&format!(
+            "$CATEGORY: {}\n\n",
+            interchange::category_path(&section.base.title)
+        ));
+        for question in &section.questions {
+            out.push_str(&render_question(question));
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+/// Splits `text` at every unescaped occurrence of one of `delimiters`,
+/// keeping the delimiter at the start of each following piece.
+fn split_unescaped(text: &str, delimiters: &[char]) -> Vec<String> {
+    let mut pieces = vec![String::new()];
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            let last = pieces.last_mut().unwrap();
+            last.push(c);
+            if let Some(next) = chars.next() {
+                last.push(next);
+            }
+        } else if delimiters.contains(&c) {
+            pieces.push(c.to_string());
+        } else {
+            pieces.last_mut().unwrap().push(c);
+        }
+    }
+
+    pieces
+}
+
+/// Finds the byte index of the first unescaped occurrence of `needle`.
+fn find_unescaped(text: &str, needle: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if text[index..].starts_with(needle) {
+            return Some(index);
+        }
+    }
+    None
+}
+
+struct ParsedAnswer {
+    correct: bool,
+    weight: Option<f64>,
+    text: String,
+    feedback: bool,
+}
+
+fn parse_answer(piece: &str) -> ParsedAnswer {
+    let correct_marker = piece.starts_with('=');
+    let mut body = piece[1..].trim();
+
+    let mut weight = None;
+    if let Some(rest) = body.strip_prefix('%') {
+        if let Some((value, rest)) = rest.split_once('%') {
+            weight = value.trim().parse::<f64>().ok();
+            body = rest.trim();
+        }
+    }
+
+    let (text, feedback) = match find_unescaped(body, "#") {
+        Some(index) => (&body[..index], !body[index + 1..].trim().is_empty()),
+        None => (body, false),
+    };
+
+    ParsedAnswer {
+        correct: correct_marker || weight.is_some_and(|w| w > 0.0),
+        weight,
+        text: unescape(text.trim()),
+        feedback,
+    }
+}
+
+/// Strips a `[format]` marker from question text, converting HTML to plain text.
+fn question_text(raw: &str) -> (String, bool) {
+    let raw = raw.trim();
+    if let Some(rest) = raw.strip_prefix('[') {
+        if let Some((format, text)) = rest.split_once(']') {
+            let text = unescape(text.trim());
+            return match format {
+                "html" | "moodle" => (html::to_plain_text(&text), html::has_rich_content(&text)),
+                _ => (text, false),
+            };
+        }
+    }
+    (unescape(raw), false)
+}
+
+struct Block {
+    position: usize,
+    category: Option<String>,
+    text: String,
+}
+
+/// Splits a GIFT document into question blocks, tracking the category each
+/// block belongs to. Comments are dropped.
+fn blocks(text: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut category = None;
+    let mut current = String::new();
+
+    let flush = |current: &mut String, category: &Option<String>, blocks: &mut Vec<Block>| {
+        if !current.trim().is_empty() {
+            blocks.push(Block {
+                position: blocks.len() + 1,
+                category: category.clone(),
+                text: std::mem::take(current),
+            });
+        }
+        current.clear();
+    };
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("//") {
+            continue;
+        }
+        if let Some(path) = trimmed.strip_prefix("$CATEGORY:") {
+            flush(&mut current, &category, &mut blocks);
+            category = Some(interchange::category_title(path));
+            continue;
+        }
+        if trimmed.is_empty() {
+            flush(&mut current, &category, &mut blocks);
+            continue;
+        }
+        current.push_str(line);
+        current.push('\n');
+    }
+    flush(&mut current, &category, &mut blocks);
+
+    blocks
+}
+
+struct QuestionImport<'a> {
+    position: usize,
+    name: Option<String>,
+    question_type: &'static str,
+    report: &'a mut ImportReport,
+}
+
+impl QuestionImport<'_> {
+    fn warn(&mut self, message: impl Into<String>) {
+        self.report.warn(
+            self.position,
+            self.name.as_deref(),
+            self.question_type,
+            message,
+        );
+    }
+
+    fn skip(&mut self, message: impl Into<String>) {
+        self.report.skip(
+            self.position,
+            self.name.as_deref(),
+            self.question_type,
+            message,
+        );
+    }
+}
+
+fn import_block(block: &Block, report: &mut ImportReport) -> Option<QuestionRequest> {
+    let mut source = block.text.trim();
+
+    let mut name = None;
+    if let Some(rest) = source.strip_prefix("::") {
+        if let Some(end) = find_unescaped(rest, "::") {
+            name = Some(unescape(rest[..end].trim())).filter(|n| !n.is_empty());
+            source = rest[end + 2..].trim();
+        }
+    }
+
+    let mut import = QuestionImport {
+        position: block.position,
+        name,
+        question_type: "unknown",
+        report,
+    };
+
+    let (Some(open), Some(close)) = (find_unescaped(source, "{"), source.rfind('}')) else {
+        import.question_type = "description";
+        import.skip("Text without an answer block is not a question");
+        return None;
+    };
+    if close < open {
+        import.skip("Unbalanced answer block braces");
+        return None;
+    }
+
+    let before = &source[..open];
+    let mut answer_block = source[open + 1..close].trim().to_string();
+    let after = source[close + 1..].trim();
+
+    let (mut text, rich) = if after.is_empty() {
+        question_text(before)
+    } else {
+        let separator =
+            if after.starts_with(|c: char| c.is_ascii_punctuation() || "،؛؟".contains(c)) {
+                ""
+            } else {
+                " "
+            };
+        question_text(&format!(
+            "{} {}{}{}",
+            before.trim_end(),
+            BLANK,
+            separator,
+            after
+        ))
+    };
+    if rich {
+        import.warn("Images, media or tables in the question text were dropped");
+    }
+
+    let mut description = None;
+    if let Some(index) = find_unescaped(&answer_block, "####") {
+        description = Some(unescape(answer_block[index + 4..].trim())).filter(|d| !d.is_empty());
+        answer_block.truncate(index);
+    }
+
+    let answer_block = answer_block.trim();
+    let keyword = answer_block
+        .split(|c: char| c == '#' || c.is_whitespace())
+        .next()
+        .unwrap_or_default();
+
+    let options = if answer_block.is_empty() {
+        import.question_type = "essay";
+        import.skip("Essay questions are not supported");
+        return None;
+    } else if answer_block.starts_with('#') {
+        import.question_type = "numerical";
+        import.skip("Numerical questions are not supported");
+        return None;
+    } else if matches!(keyword, "T" | "TRUE" | "F" | "FALSE") {
+        import.question_type = "truefalse";
+        if answer_block.len() > keyword.len() {
+            import.warn("Answer feedback was dropped");
+        }
+        interchange::true_false_options(keyword.starts_with('T'))
+    } else {
+        let pieces: Vec<String> = split_unescaped(answer_block, &['=', '~'])
+            .into_iter()
+            .skip_while(|piece| !piece.starts_with(['=', '~']))
+            .filter(|piece| piece.len() > 1)
+            .collect();
+        let has_wrong = pieces.iter().any(|piece| piece.starts_with('~'));
+        let answers: Vec<ParsedAnswer> = pieces.iter().map(|piece| parse_answer(piece)).collect();
+
+        if answers
+            .iter()
+            .any(|a| find_unescaped(&a.text, "->").is_some())
+        {
+            import.question_type = "matching";
+            import.skip("Matching questions are not supported in GIFT import");
+            return None;
+        }
+
+        import.question_type = if has_wrong {
+            "multichoice"
+        } else {
+            "shortanswer"
+        };
+
+        if answers.iter().any(|a| a.feedback) {
+            import.warn("Answer feedback was dropped");
+        }
+        if answers
+            .iter()
+            .any(|a| a.weight.is_some_and(|w| w > 0.0 && w < 100.0))
+            && answers.iter().filter(|a| a.correct).count() == 1
+        {
+            import.warn("Partial-credit answers were imported as fully correct");
+        }
+
+        answers
+            .into_iter()
+            .filter(|a| !a.text.is_empty())
+            .map(|a| OptionRequestModel::new(a.text, a.correct))
+            .collect::<Vec<_>>()
+    };
+
+    if text.is_empty() {
+        import.skip("Question has no text");
+        return None;
+    }
+    if options.is_empty() {
+        import.skip("Question has no usable answers");
+        return None;
+    }
+    if !options.iter().any(|o| o.base.is_correct == Some(true)) {
+        import.warn("Question has no correct answer");
+    }
+
+    text = text.trim().to_string();
+    Some(QuestionRequest::new(text, description, 1, options))
+}
+
+/// Reads a GIFT document into sections, one per `$CATEGORY:`.
+///
+/// IDs in the returned sections are placeholders. Questions that cannot be
+/// represented are skipped; every skip or lossy conversion is listed in the
+/// returned report.
+///
+/// # Errors
+/// Returns an error if the document contains no question blocks.
+pub fn import_sections(text: &str) -> Result<(Vec<SectionRequest>, ImportReport)> {
+    let blocks = blocks(text);
+    if blocks.is_empty() {
+        bail!("GIFT document contains no questions");
+    }
+
+    let mut report = ImportReport::default();
+    let mut sections = SectionCollector::default();
+
+    for block in &blocks {
+        if let Some(question) = import_block(block, &mut report) {
+            sections.push(block.category.as_deref(), question);
+            report.imported += 1;
+        }
+    }
+
+    Ok((sections.into_sections(), report))
+}
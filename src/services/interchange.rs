@@ -0,0 +1,132 @@
+//! Helpers shared by the question-bank converters (Moodle XML and GIFT).
+
+use crate::model::option::OptionRequestModel;
+use crate::model::question::QuestionRequest;
+use crate::model::section::SectionRequest;
+
+/// Section title used for questions that appear before any category.
+pub const DEFAULT_SECTION_TITLE: &str = "Imported questions";
+
+/// Marker for the blank in fill-in-the-blank questions.
+pub const BLANK: &str = "___";
+
+/// How a question is represented in a question-bank format.
+pub enum QuestionKind<'a> {
+    /// Exactly two options, "True" and "False".
+    TrueFalse(bool),
+    /// Every option is correct: they are the accepted typed answers.
+    ShortAnswer(Vec<&'a str>),
+    /// Anything else: a choice between correct and incorrect options.
+    MultiChoice,
+}
+
+pub fn classify(question: &QuestionRequest) -> QuestionKind<'_> {
+    let options = &question.options;
+    let is_correct = |o: &OptionRequestModel| o.base.is_correct.unwrap_or_default();
+
+    if options.len() == 2 {
+        let texts: Vec<String> = options
+            .iter()
+            .map(|o| o.base.text.trim().to_lowercase())
+            .collect();
+        if texts.contains(&"true".to_string()) && texts.contains(&"false".to_string()) {
+            let answer = options
+                .iter()
+                .any(|o| is_correct(o) && o.base.text.trim().eq_ignore_ascii_case("true"));
+            return QuestionKind::TrueFalse(answer);
+        }
+    }
+
+    if !options.is_empty() && options.iter().all(is_correct) {
+        return QuestionKind::ShortAnswer(options.iter().map(|o| o.base.text.as_str()).collect());
+    }
+
+    QuestionKind::MultiChoice
+}
+
+pub fn true_false_options(answer: bool) -> Vec<OptionRequestModel> {
+    vec![
+        OptionRequestModel::new("True".to_string(), answer),
+        OptionRequestModel::new("False".to_string(), !answer),
+    ]
+}
+
+/// A short name for a question, taken from the start of its text.
+pub fn question_name(question: &QuestionRequest) -> String {
+    let first_line = question.base.text.lines().next().unwrap_or_default().trim();
+    let name: String = first_line.chars().take(60).collect();
+    if name.chars().count() < first_line.chars().count() {
+        format!("{}…", name.trim_end())
+    } else {
+        name
+    }
+}
+
+/// Formats the share of the grade each of `correct` correct options earns.
+pub fn correct_fraction(correct: usize) -> String {
+    let fraction = 100.0 / correct.max(1) as f64;
+    let formatted = format!("{:.5}", fraction);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+/// Builds a Moodle category path for a section title. Slashes inside the
+/// title are doubled, as Moodle does.
+pub fn category_path(title: &str) -> String {
+    format!("$course$/top/{}", title.replace('/', "//"))
+}
+
+/// Extracts a section title from a Moodle category path: the last category
+/// in the path, with doubled slashes restored.
+pub fn category_title(path: &str) -> String {
+    let path = path.trim();
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut chars = path.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '/' {
+            if chars.peek() == Some(&'/') {
+                chars.next();
+                current.push('/');
+            } else {
+                segments.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(c);
+        }
+    }
+    segments.push(current);
+
+    let is_placeholder = |s: &str| s.starts_with('$') && s.ends_with('$');
+    segments
+        .into_iter()
+        .rev()
+        .map(|s| s.trim().to_string())
+        .find(|s| !s.is_empty() && !is_placeholder(s) && s != "top")
+        .unwrap_or_else(|| DEFAULT_SECTION_TITLE.to_string())
+}
+
+/// Groups imported questions into sections, in order of first appearance.
+#[derive(Default)]
+pub struct SectionCollector {
+    sections: Vec<SectionRequest>,
+}
+
+impl SectionCollector {
+    pub fn push(&mut self, section_title: Option<&str>, question: QuestionRequest) {
+        let title = section_title.unwrap_or(DEFAULT_SECTION_TITLE);
+        match self.sections.iter_mut().find(|s| s.base.title == title) {
+            Some(section) => section.questions.push(question),
+            None => self
+                .sections
+                .push(SectionRequest::new(title.to_string(), vec![question])),
+        }
+    }
+
+    pub fn into_sections(self) -> Vec<SectionRequest> {
+        self.sections
+    }
+}
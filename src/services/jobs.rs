@@ -0,0 +1,451 @@
+//! Redis-backed queue of asynchronous generation jobs.
+//!
+//! A job is stored as JSON under `job:{id}` and its ID pushed onto the
+//! `jobs:queue` list. A worker takes an ID by moving it onto
+//! `jobs:processing`, and removes it from there only once the outcome of
+//! the attempt is stored, so a job whose worker died is not lost. Jobs and
+//! their results expire `JOB_TTL_SECS` after they were created (an hour by
+//! default); a job still queued by then is dropped.
+//!
+//! Failed attempts are retried with exponential backoff when the error is
+//! temporary. A job waiting to be retried sits in the `jobs:delayed` sorted
+//! set, scored by when it is due, and is moved back onto the queue once it
+//! is.
+//!
+//! When a job is taken, the time is stamped in the `jobs:taken` hash. A job
+//! still in `jobs:processing` `JOB_STALE_SECS` after it was taken is
+//! assumed to have been interrupted, e.g. by a crash or a deploy, and is
+//! queued again. This should be well above the time one attempt can take.
+//!
+//! | Variable            | Default |
+//! |---------------------|---------|
+//! | `JOB_WORKERS`       | 2       |
+//! | `JOB_TTL_SECS`      | 3600    |
+//! | `JOB_MAX_ATTEMPTS`  | 3       |
+//! | `JOB_RETRY_BASE_MS` | 1000    |
+//! | `JOB_STALE_SECS`    | 900     |
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use deadpool_redis::redis::{AsyncCommands, Direction};
+use log::{debug, info, warn};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use crate::conn::RedisClient;
+use crate::model::job::{Job, JobOutput, JobRequest, JobStatus, RetryPolicy, MAX_JOB_ATTEMPTS};
+use crate::utils;
+
+/// The list job IDs wait on, oldest at the tail.
+pub const QUEUE_KEY: &str = "jobs:queue";
+
+/// The list of job IDs taken by a worker and not yet finished with.
+pub const PROCESSING_KEY: &str = "jobs:processing";
+
+/// The hash of when each job in `jobs:processing` was taken, in
+/// milliseconds since the epoch.
+pub const TAKEN_KEY: &str = "jobs:taken";
+
+/// The sorted set of job IDs waiting to be retried, scored by when they are
+/// due in milliseconds since the epoch.
+pub const DELAYED_KEY: &str = "jobs:delayed";
+
+/// Prefix of the job records in Redis.
+const JOB_PREFIX: &str = "job:";
+
+/// How long a worker blocks on an empty queue before polling again.
+const POLL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How often due retries and interrupted jobs are looked for.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Settings of the queue and its workers, see the module docs.
+#[derive(Debug, Clone)]
+pub struct JobConfig {
+    pub workers: usize,
+    pub ttl: Duration,
+    pub retry: RetryPolicy,
+    /// How long a job may go without an update in `jobs:processing` before
+    /// it is queued again.
+    pub stale_after: Duration,
+}
+
+impl Default for JobConfig {
+    fn default() -> Self {
+        Self {
+            workers: 2,
+            ttl: Duration::from_secs(60 * 60),
+            retry: RetryPolicy {
+                max_attempts: 3,
+                backoff_ms: 1000,
+            },
+            stale_after: Duration::from_secs(15 * 60),
+        }
+    }
+}
+
+fn env_or<T: FromStr>(key: &str, default: T) -> Result<T> {
+    match utils::env::load_env_var(key) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Invalid {} `{}`", key, value)),
+        Err(_) => Ok(default),
+    }
+}
+
+impl JobConfig {
+    pub fn from_env() -> Result<Self> {
+        let defaults = Self::default();
+        Ok(Self {
+            workers: env_or("JOB_WORKERS", defaults.workers)?,
+            ttl: Duration::from_secs(env_or("JOB_TTL_SECS", defaults.ttl.as_secs())?),
+            retry: RetryPolicy {
+                max_attempts: env_or("JOB_MAX_ATTEMPTS", defaults.retry.max_attempts)?
+                    .clamp(1, MAX_JOB_ATTEMPTS),
+                backoff_ms: env_or("JOB_RETRY_BASE_MS", defaults.retry.backoff_ms)?,
+            },
+            stale_after: Duration::from_secs(env_or(
+                "JOB_STALE_SECS",
+                defaults.stale_after.as_secs(),
+            )?),
+        })
+    }
+}
+
+/// Why an attempt at a job failed.
+#[derive(Debug)]
+pub struct JobFailure {
+    pub message: String,
+    /// Whether running the job again might succeed.
+    pub retryable: bool,
+}
+
+fn job_key(id: Uuid) -> String {
+    format!("{}{}", JOB_PREFIX, id)
+}
+
+/// The job queue shared through `AppState`.
+#[derive(Clone)]
+pub struct JobQueue {
+    redis: RedisClient,
+    config: JobConfig,
+}
+
+impl JobQueue {
+    pub fn new(redis: RedisClient, config: JobConfig) -> Self {
+        Self { redis, config }
+    }
+
+    pub fn from_env(redis: RedisClient) -> Result<Self> {
+        Ok(Self::new(redis, JobConfig::from_env()?))
+    }
+
+    pub fn config(&self) -> &JobConfig {
+        &self.config
+    }
+
+    /// Stores a new job and queues it. `max_attempts` overrides the
+    /// configured retry policy.
+    pub async fn enqueue(&self, request: JobRequest, max_attempts: Option<u32>) -> Result<Job> {
+        let now = Utc::now();
+        let ttl = chrono::Duration::from_std(self.config.ttl).context("Invalid job TTL")?;
+        let mut retry = self.config.retry;
+        if let Some(max_attempts) = max_attempts {
+            retry.max_attempts = max_attempts.clamp(1, MAX_JOB_ATTEMPTS);
+        }
+
+        let job = Job {
+            id: Uuid::new_v4(),
+            status: JobStatus::Queued,
+            request,
+            attempts: 0,
+            retry,
+            result: None,
+            prompt_version: None,
+            generation_id: None,
+            error: None,
+            created_at: now,
+            updated_at: now,
+            expires_at: now + ttl,
+        };
+        self.save(&job).await?;
+        self.push(job.id).await?;
+        Ok(job)
+    }
+
+    /// Returns the job, unless it does not exist or has expired.
+    pub async fn get(&self, id: Uuid) -> Result<Option<Job>> {
+        let mut conn = self.redis.get_connection().await?;
+        let json: Option<String> = conn.get(job_key(id)).await.context("Failed to read job")?;
+        json.map(|json| serde_json::from_str(&json).context("Failed to parse stored job"))
+            .transpose()
+    }
+
+    /// Writes the job back, keeping its original expiry.
+    pub async fn save(&self, job: &Job) -> Result<()> {
+        // Rounded up, so a job saved right away keeps the full TTL.
+        let remaining_ms = (job.expires_at - Utc::now()).num_milliseconds();
+        let remaining = ((remaining_ms + 999) / 1000).max(1) as u64;
+        let json = serde_json::to_string(job).context("Failed to serialize job")?;
+        let mut conn = self.redis.get_connection().await?;
+        conn.set_ex::<_, _, ()>(job_key(job.id), json, remaining)
+            .await
+            .context("Failed to store job")
+    }
+
+    async fn push(&self, id: Uuid) -> Result<()> {
+        let mut conn = self.redis.get_connection().await?;
+        conn.lpush::<_, _, ()>(QUEUE_KEY, id.to_string())
+            .await
+            .context("Failed to queue job")
+    }
+
+    /// Waits up to [`POLL_TIMEOUT`] for the next queued job ID, moving it
+    /// onto `jobs:processing` until it is acknowledged and stamping when it
+    /// was taken.
+    async fn pop(&self) -> Result<Option<Uuid>> {
+        let mut conn = self.redis.get_connection().await?;
+        let popped: Option<String> = conn
+            .blmove(
+                QUEUE_KEY,
+                PROCESSING_KEY,
+                Direction::Right,
+                Direction::Left,
+                POLL_TIMEOUT.as_secs_f64(),
+            )
+            .await
+            .context("Failed to read the job queue")?;
+        let Some(id) = popped else {
+            return Ok(None);
+        };
+        conn.hset::<_, _, _, ()>(TAKEN_KEY, &id, Utc::now().timestamp_millis())
+            .await
+            .context("Failed to stamp taken job")?;
+        Ok(id.parse().ok())
+    }
+
+    /// Removes a job from `jobs:processing` once its outcome is stored.
+    async fn acknowledge(&self, id: Uuid) -> Result<()> {
+        let mut conn = self.redis.get_connection().await?;
+        conn.hdel::<_, _, ()>(TAKEN_KEY, id.to_string())
+            .await
+            .context("Failed to acknowledge job")?;
+        conn.lrem::<_, _, ()>(PROCESSING_KEY, 1, id.to_string())
+            .await
+            .context("Failed to acknowledge job")
+    }
+
+    /// Queues the job again once `delay` has passed.
+    async fn schedule(&self, id: Uuid, delay: Duration) -> Result<()> {
+        let due = Utc::now().timestamp_millis() + delay.as_millis() as i64;
+        let mut conn = self.redis.get_connection().await?;
+        conn.zadd::<_, _, _, ()>(DELAYED_KEY, id.to_string(), due)
+            .await
+            .context("Failed to schedule job retry")
+    }
+
+    /// Moves the retries that are due back onto the queue.
+    async fn promote_due(&self) -> Result<()> {
+        let now = Utc::now().timestamp_millis();
+        let mut conn = self.redis.get_connection().await?;
+        let due: Vec<String> = conn
+            .zrangebyscore(DELAYED_KEY, "-inf", now)
+            .await
+            .context("Failed to read delayed jobs")?;
+        for id in due {
+            // Only the caller that removed the ID queues it, so a retry is
+            // queued once however many instances promote at the same time.
+            let removed: usize = conn
+                .zrem(DELAYED_KEY, &id)
+                .await
+                .context("Failed to promote delayed job")?;
+            if removed > 0 {
+                conn.lpush::<_, _, ()>(QUEUE_KEY, &id)
+                    .await
+                    .context("Failed to queue delayed job")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Queues the jobs in `jobs:processing` that were taken more than
+    /// [`JobConfig::stale_after`] ago again, and drops the ones that
+    /// finished or expired before they were acknowledged.
+    ///
+    /// An entry not stamped yet, because it was moved onto the list a moment
+    /// ago, is stamped now, so it is only recovered once it has been there
+    /// for the whole stale time.
+    async fn recover_stale(&self) -> Result<()> {
+        let (entries, stamps): (Vec<String>, HashMap<String, i64>) = {
+            let mut conn = self.redis.get_connection().await?;
+            let entries = conn
+                .lrange(PROCESSING_KEY, 0, -1)
+                .await
+                .context("Failed to read processing jobs")?;
+            let stamps = conn
+                .hgetall(TAKEN_KEY)
+                .await
+                .context("Failed to read when jobs were taken")?;
+            (entries, stamps)
+        };
+        let now = Utc::now().timestamp_millis();
+        let stale_after = self.config.stale_after.as_millis() as i64;
+
+        for entry in entries {
+            let Some(&taken) = stamps.get(&entry) else {
+                let mut conn = self.redis.get_connection().await?;
+                conn.hset_nx::<_, _, _, ()>(TAKEN_KEY, &entry, now)
+                    .await
+                    .context("Failed to stamp taken job")?;
+                continue;
+            };
+            if now - taken < stale_after {
+                continue;
+            }
+
+            let job = match entry.parse() {
+                Ok(id) => self.get(id).await?,
+                Err(_) => None,
+            };
+            let requeue = job.is_some_and(|job| !job.status.is_finished());
+
+            let mut conn = self.redis.get_connection().await?;
+            let removed: usize = conn
+                .lrem(PROCESSING_KEY, 1, &entry)
+                .await
+                .context("Failed to recover processing job")?;
+            conn.hdel::<_, _, ()>(TAKEN_KEY, &entry)
+                .await
+                .context("Failed to recover processing job")?;
+            if requeue && removed > 0 {
+                warn!("Job {} was interrupted, queueing it again", entry);
+                conn.lpush::<_, _, ()>(QUEUE_KEY, &entry)
+                    .await
+                    .context("Failed to queue interrupted job")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Starts the configured number of workers, each running jobs with `run`
+/// until the runtime shuts down.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// spawn_workers(queue, move |request| run_job(state.clone(), request));
+/// ```
+pub fn spawn_workers<F, Fut>(queue: JobQueue, run: F) -> Vec<JoinHandle<()>>
+where
+    F: Fn(JobRequest) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = Result<JobOutput, JobFailure>> + Send + 'static,
+{
+    info!("Starting {} job workers.", queue.config.workers);
+    let mut handles: Vec<_> = (0..queue.config.workers)
+        .map(|_| tokio::spawn(work(queue.clone(), run.clone())))
+        .collect();
+    handles.push(tokio::spawn(maintain(queue)));
+    handles
+}
+
+/// Moves due retries and interrupted jobs back onto the queue, starting
+/// with whatever a previous run left behind.
+async fn maintain(queue: JobQueue) {
+    loop {
+        if let Err(e) = queue.recover_stale().await {
+            warn!("Failed to recover interrupted jobs: {:?}", e);
+        }
+        if let Err(e) = queue.promote_due().await {
+            warn!("Failed to queue due job retries: {:?}", e);
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+async fn work<F, Fut>(queue: JobQueue, run: F)
+where
+    F: Fn(JobRequest) -> Fut,
+    Fut: Future<Output = Result<JobOutput, JobFailure>>,
+{
+    loop {
+        let id = match queue.pop().await {
+            Ok(Some(id)) => id,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!("Job worker failed to poll the queue: {:?}", e);
+                tokio::time::sleep(POLL_TIMEOUT).await;
+                continue;
+            }
+        };
+        // A job that could not be processed stays in `jobs:processing`,
+        // to be queued again once it is stale.
+        let processed = process(&queue, id, &run).await;
+        if let Err(e) = processed.and(queue.acknowledge(id).await) {
+            warn!("Job {} could not be processed: {:?}", id, e);
+        }
+    }
+}
+
+/// Runs one attempt of a job and records the outcome.
+async fn process<F, Fut>(queue: &JobQueue, id: Uuid, run: &F) -> Result<()>
+where
+    F: Fn(JobRequest) -> Fut,
+    Fut: Future<Output = Result<JobOutput, JobFailure>>,
+{
+    let Some(mut job) = queue.get(id).await? else {
+        debug!("Job {} expired before it ran", id);
+        return Ok(());
+    };
+    if job.status.is_finished() {
+        return Ok(());
+    }
+    if job.attempts >= job.retry.max_attempts {
+        // Its last attempt was interrupted; running it again could go on
+        // forever if the job itself is what brings workers down.
+        warn!("Job {} was interrupted during its last attempt", id);
+        job.status = JobStatus::Failed;
+        job.error = Some("Interrupted during the last attempt".to_string());
+        job.updated_at = Utc::now();
+        return queue.save(&job).await;
+    }
+
+    job.status = JobStatus::Running;
+    job.attempts += 1;
+    job.updated_at = Utc::now();
+    queue.save(&job).await?;
+
+    let outcome = run(job.request.clone()).await;
+    job.updated_at = Utc::now();
+    match outcome {
+        Ok(output) => {
+            job.status = JobStatus::Succeeded;
+            job.result = Some(output.result);
+            job.prompt_version = Some(output.prompt_version);
+            job.generation_id = output.generation_id;
+            job.error = None;
+        }
+        Err(failure) if failure.retryable && job.attempts < job.retry.max_attempts => {
+            let delay = job.retry.delay(job.attempts);
+            warn!(
+                "Job {} failed attempt {}, retrying in {:?}: {}",
+                id, job.attempts, delay, failure.message
+            );
+            job.status = JobStatus::Retrying;
+            job.error = Some(failure.message);
+            queue.save(&job).await?;
+            return queue.schedule(id, delay).await;
+        }
+        Err(failure) => {
+            warn!("Job {} failed: {}", id, failure.message);
+            job.status = JobStatus::Failed;
+            job.error = Some(failure.message);
+        }
+    }
+    queue.save(&job).await
+}
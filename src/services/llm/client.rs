@@ -0,0 +1,539 @@
+//! The shared, failure-tolerant front of the configured [`LlmProvider`].
+//!
+//! Every call goes through three guards:
+//!
+//! 1. a semaphore capping the requests in flight,
+//! 2. retries of timeouts, 429s and 5xx answers with exponential backoff and
+//!    full jitter, waiting as long as a `Retry-After` header asks,
+//! 3. a circuit breaker that fails fast for a while once enough calls in a
+//!    row have failed, then lets a single call probe the provider again.
+//!
+//! With a [`UsageTracker`], calls also fail once the user of the current
+//! usage scope spent a token quota, and the tokens of every call are
+//! recorded (see `services::usage`).
+//!
+//! The limits come from the environment, each with a default:
+//!
+//! | Variable                    | Default | Meaning                                |
+//! |-----------------------------|---------|----------------------------------------|
+//! | `LLM_CONNECT_TIMEOUT_SECS`  | 5       | Timeout for opening a connection       |
+//! | `LLM_REQUEST_TIMEOUT_SECS`  | 60      | Timeout for a whole request            |
+//! | `LLM_MAX_RETRIES`           | 3       | Retries after the first attempt        |
+//! | `LLM_RETRY_BASE_MS`         | 500     | Backoff before the first retry         |
+//! | `LLM_MAX_CONCURRENT`        | 8       | Requests in flight at once             |
+//! | `LLM_BREAKER_THRESHOLD`     | 5       | Failed calls in a row that open it     |
+//! | `LLM_BREAKER_COOLDOWN_SECS` | 30      | How long it stays open                 |
+
+use super::{LlmError, LlmProvider, ProviderConfig, TextStream};
+use crate::model::llm::{GenerationRequest, TextDelta};
+use crate::model::usage::{TokenUsage, UsageScope};
+use crate::services::usage::{current_scope, UsageTracker};
+use crate::utils;
+use anyhow::{Context, Result};
+use futures_util::stream::{self, StreamExt};
+use log::warn;
+use rand::Rng;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Longest wait between two attempts, whatever the backoff or `Retry-After`.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Limits of an [`LlmClient`], see the module docs.
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    pub connect_timeout: Duration,
+    pub request_timeout: Duration,
+    pub max_retries: u32,
+    pub retry_base: Duration,
+    pub max_concurrent: usize,
+    pub breaker_threshold: u32,
+    pub breaker_cooldown: Duration,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(5),
+            request_timeout: Duration::from_secs(60),
+            max_retries: 3,
+            retry_base: Duration::from_millis(500),
+            max_concurrent: 8,
+            breaker_threshold: 5,
+            breaker_cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Reads an optional numeric variable, failing only if it is set but invalid.
+fn env_number<T: FromStr>(key: &str, default: T) -> Result<T>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match utils::env::load_env_var(key) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid value `{}` for {}", value, key)),
+        Err(_) => Ok(default),
+    }
+}
+
+impl ClientConfig {
+    /// Reads the limits from the environment, see the module docs.
+    pub fn from_env() -> Result<Self> {
+        let default = Self::default();
+        Ok(Self {
+            connect_timeout: Duration::from_secs(env_number(
+                "LLM_CONNECT_TIMEOUT_SECS",
+                default.connect_timeout.as_secs(),
+            )?),
+            request_timeout: Duration::from_secs(env_number(
+                "LLM_REQUEST_TIMEOUT_SECS",
+                default.request_timeout.as_secs(),
+            )?),
+            max_retries: env_number("LLM_MAX_RETRIES", default.max_retries)?,
+            retry_base: Duration::from_millis(env_number(
+                "LLM_RETRY_BASE_MS",
+                default.retry_base.as_millis() as u64,
+            )?),
+            max_concurrent: env_number("LLM_MAX_CONCURRENT", default.max_concurrent)?.max(1),
+            breaker_threshold: env_number("LLM_BREAKER_THRESHOLD", default.breaker_threshold)?
+                .max(1),
+            breaker_cooldown: Duration::from_secs(env_number(
+                "LLM_BREAKER_COOLDOWN_SECS",
+                default.breaker_cooldown.as_secs(),
+            )?),
+        })
+    }
+
+    /// Builds the HTTP client the providers share, with the configured timeouts.
+    pub fn http_client(&self) -> Result<reqwest::Client> {
+        reqwest::Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.request_timeout)
+            .build()
+            .context("Failed to build LLM HTTP client")
+    }
+
+    /// Backoff before retry number `attempt` (counting from 0): a random
+    /// duration up to `retry_base * 2^attempt`.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .retry_base
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_BACKOFF);
+        ceiling.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+    }
+}
+
+#[derive(Default)]
+struct BreakerState {
+    failures: u32,
+    open_until: Option<Instant>,
+    probing: bool,
+}
+
+/// Counts failed calls in a row and refuses calls while open.
+struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// Lets a call through, or returns how long until the next probe.
+    fn admit(&self) -> Result<Admission<'_>, Duration> {
+        let mut state = self.state.lock().unwrap();
+        let Some(open_until) = state.open_until else {
+            return Ok(Admission {
+                breaker: self,
+                probe: false,
+            });
+        };
+
+        let now = Instant::now();
+        if now < open_until {
+            return Err(open_until - now);
+        }
+        if state.probing {
+            // Another call is already probing the provider.
+            return Err(self.cooldown);
+        }
+        state.probing = true;
+        Ok(Admission {
+            breaker: self,
+            probe: true,
+        })
+    }
+}
+
+/// A call the breaker let through, which reports how it went. A probe
+/// dropped before reporting, e.g. because its caller went away, lets the
+/// next call probe instead of keeping the circuit open for good.
+struct Admission<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+}
+
+impl Admission<'_> {
+    fn record_success(&mut self) {
+        self.probe = false;
+        *self.breaker.state.lock().unwrap() = BreakerState::default();
+    }
+
+    fn record_failure(&mut self) {
+        let mut state = self.breaker.state.lock().unwrap();
+        state.failures += 1;
+        if self.probe || state.failures >= self.breaker.threshold {
+            state.open_until = Some(Instant::now() + self.breaker.cooldown);
+            state.probing = false;
+        }
+        self.probe = false;
+    }
+}
+
+impl Drop for Admission<'_> {
+    fn drop(&mut self) {
+        if self.probe {
+            self.breaker.state.lock().unwrap().probing = false;
+        }
+    }
+}
+
+/// Rough token count of `chars` characters, for usage a provider did not
+/// get to report.
+fn estimate_tokens(chars: usize) -> u64 {
+    chars.div_ceil(4) as u64
+}
+
+/// The tokens of a streamed completion so far. They are recorded once, when
+/// the stream ends or is dropped, so a stream abandoned part way, e.g.
+/// because its reader went away, still counts against the quotas.
+struct StreamUsage {
+    client: LlmClient,
+    /// `None` once recorded.
+    scope: Option<UsageScope>,
+    /// The last usage the provider reported, which covers the stream so far.
+    reported: Option<TokenUsage>,
+    prompt_chars: usize,
+    streamed_chars: usize,
+}
+
+impl StreamUsage {
+    fn observe(&mut self, delta: &TextDelta) {
+        if delta.usage.is_some() {
+            self.reported = delta.usage;
+        }
+        self.streamed_chars += delta.text.chars().count();
+    }
+
+    /// The scope and tokens to record, unless they already were. Without a
+    /// report from the provider, which some only send at the end, the tokens
+    /// are estimated from the prompt and the text streamed so far.
+    fn take(&mut self) -> Option<(UsageScope, TokenUsage)> {
+        let scope = self.scope.take()?;
+        let tokens = self.reported.unwrap_or(TokenUsage {
+            prompt_tokens: estimate_tokens(self.prompt_chars),
+            completion_tokens: estimate_tokens(self.streamed_chars),
+        });
+        Some((scope, tokens))
+    }
+}
+
+impl Drop for StreamUsage {
+    fn drop(&mut self) {
+        let Some((scope, tokens)) = self.take() else {
+            return;
+        };
+        let client = self.client.clone();
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(async move { client.record(&scope, &tokens).await });
+            }
+            Err(_) => warn!("Usage of a dropped LLM stream was not recorded"),
+        }
+    }
+}
+
+/// Whether an attempt that failed with `error` may succeed if repeated, and
+/// how long the provider asked us to wait.
+fn retry_hint(error: &anyhow::Error) -> Option<Option<Duration>> {
+    if let Some(LlmError::Status {
+        status,
+        retry_after,
+        ..
+    }) = error.downcast_ref::<LlmError>()
+    {
+        let transient =
+            status.as_u16() == 408 || status.as_u16() == 429 || status.is_server_error();
+        return transient.then_some(*retry_after);
+    }
+
+    error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        .any(|e| e.is_timeout() || e.is_connect())
+        .then_some(None)
+}
+
+struct Inner {
+    provider: Box<dyn LlmProvider>,
+    config: ClientConfig,
+    breaker: CircuitBreaker,
+    permits: Arc<Semaphore>,
+    usage: Option<UsageTracker>,
+}
+
+/// The LLM client shared through `AppState`. Cloning is cheap and every
+/// clone shares the same limits.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let llm_client = LlmClient::from_env(usage_tracker.clone())?;
+/// let text = llm_client.send_prompt(prompt, 1).await?;
+/// ```
+#[derive(Clone)]
+pub struct LlmClient {
+    inner: Arc<Inner>,
+}
+
+impl LlmClient {
+    /// A client that neither enforces quotas nor records usage.
+    pub fn new(provider: Box<dyn LlmProvider>, config: ClientConfig) -> Self {
+        Self::build(provider, config, None)
+    }
+
+    /// A client that enforces quotas and records usage with `usage`.
+    pub fn tracked(
+        provider: Box<dyn LlmProvider>,
+        config: ClientConfig,
+        usage: UsageTracker,
+    ) -> Self {
+        Self::build(provider, config, Some(usage))
+    }
+
+    fn build(
+        provider: Box<dyn LlmProvider>,
+        config: ClientConfig,
+        usage: Option<UsageTracker>,
+    ) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                breaker: CircuitBreaker::new(config.breaker_threshold, config.breaker_cooldown),
+                permits: Arc::new(Semaphore::new(config.max_concurrent)),
+                provider,
+                config,
+                usage,
+            }),
+        }
+    }
+
+    /// Builds the provider and limits configured in the environment, tracking
+    /// usage with `usage`.
+    ///
+    /// # Errors
+    /// Returns an error if the provider settings are incomplete or a limit is
+    /// not a number.
+    pub fn from_env(usage: UsageTracker) -> Result<Self> {
+        let config = ClientConfig::from_env()?;
+        let provider = ProviderConfig::from_env()?.build(config.http_client()?)?;
+        Ok(Self::tracked(provider, config, usage))
+    }
+
+    /// Name of the provider behind the client, for logs.
+    pub fn provider_name(&self) -> &'static str {
+        self.inner.provider.name()
+    }
+
+    /// The model the provider generates with.
+    pub fn model_name(&self) -> &str {
+        self.inner.provider.model()
+    }
+
+    /// Generates completions, retrying transient failures.
+    ///
+    /// # Errors
+    /// Returns [`LlmError::QuotaExceeded`] if the user spent a quota, and
+    /// [`LlmError::Unavailable`] if the circuit is open, no request slot
+    /// frees up in time, or every attempt failed transiently. Other failures
+    /// are returned as they are.
+    pub async fn generate(&self, request: &GenerationRequest) -> Result<Vec<String>> {
+        let scope = self.admit().await?;
+        let (completion, _permit) = self
+            .guarded(|| self.inner.provider.generate(request))
+            .await?;
+        self.record(&scope, &completion.usage).await;
+        Ok(completion.texts)
+    }
+
+    /// Opens a stream of the first completion's text, retrying transient
+    /// failures to open it. The request slot stays taken until the stream is
+    /// dropped; failures part way through are not retried. Usage is recorded
+    /// once the stream is read to the end, or else when it is dropped.
+    ///
+    /// # Errors
+    /// See [`LlmClient::generate`].
+    pub async fn generate_stream(&self, request: &GenerationRequest) -> Result<TextStream> {
+        let scope = self.admit().await?;
+        let (deltas, permit) = self
+            .guarded(|| self.inner.provider.generate_stream(request))
+            .await?;
+
+        let usage = Arc::new(Mutex::new(StreamUsage {
+            client: self.clone(),
+            scope: Some(scope),
+            reported: None,
+            prompt_chars: request.prompt.chars().count(),
+            streamed_chars: 0,
+        }));
+        let seen = usage.clone();
+        let texts = deltas.map(move |delta| {
+            let _held = &permit;
+            let delta = delta?;
+            seen.lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .observe(&delta);
+            Ok(delta.text)
+        });
+
+        let client = self.clone();
+        let finish = stream::once(async move {
+            let pending = usage.lock().unwrap_or_else(|e| e.into_inner()).take();
+            if let Some((scope, tokens)) = pending {
+                client.record(&scope, &tokens).await;
+            }
+        })
+        .filter_map(|()| async { None });
+        Ok(Box::pin(texts.chain(finish)))
+    }
+
+    /// Returns the usage scope of the call, failing if its user spent a quota.
+    async fn admit(&self) -> Result<UsageScope> {
+        let scope = current_scope();
+        if let Some(usage) = &self.inner.usage {
+            usage.check(&scope.user_id).await?;
+        }
+        Ok(scope)
+    }
+
+    /// Records the tokens of a call. Failures are logged, not returned: the
+    /// completion is already paid for.
+    async fn record(&self, scope: &UsageScope, tokens: &TokenUsage) {
+        let inner = &self.inner;
+        let Some(usage) = &inner.usage else {
+            return;
+        };
+        if let Err(e) = usage
+            .record(scope, inner.provider.name(), inner.provider.model(), tokens)
+            .await
+        {
+            warn!("Failed to record LLM usage: {:?}", e);
+        }
+    }
+
+    /// Runs `call` under the circuit breaker, the concurrency cap and the
+    /// retry policy, returning its output with the request slot it holds.
+    async fn guarded<T, F, Fut>(&self, call: F) -> Result<(T, OwnedSemaphorePermit)>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let inner = &self.inner;
+        let mut admission = match inner.breaker.admit() {
+            Ok(admission) => admission,
+            Err(wait) => {
+                return Err(LlmError::Unavailable {
+                    reason: format!("{} is failing, calls are paused", inner.provider.name()),
+                    retry_after: Some(wait),
+                }
+                .into())
+            }
+        };
+
+        let mut attempt = 0;
+        loop {
+            let permit = tokio::time::timeout(
+                inner.config.request_timeout,
+                inner.permits.clone().acquire_owned(),
+            )
+            .await
+            .map_err(|_| LlmError::Unavailable {
+                reason: "Too many LLM requests in flight".to_string(),
+                retry_after: None,
+            })?
+            .context("LLM request limiter closed")?;
+
+            let error = match call().await {
+                Ok(output) => {
+                    admission.record_success();
+                    return Ok((output, permit));
+                }
+                Err(error) => error,
+            };
+            drop(permit);
+
+            let Some(retry_after) = retry_hint(&error) else {
+                // The provider answered, so it is up; the request itself is bad.
+                admission.record_success();
+                return Err(error);
+            };
+
+            if attempt >= inner.config.max_retries {
+                admission.record_failure();
+                return Err(LlmError::Unavailable {
+                    reason: format!(
+                        "{} failed after {} attempts: {}",
+                        inner.provider.name(),
+                        attempt + 1,
+                        error
+                    ),
+                    retry_after,
+                }
+                .into());
+            }
+
+            let wait = retry_after
+                .unwrap_or_else(|| inner.config.backoff(attempt))
+                .min(MAX_BACKOFF);
+            warn!(
+                "{} attempt {} failed, retrying in {:?}: {}",
+                inner.provider.name(),
+                attempt + 1,
+                wait,
+                error
+            );
+            tokio::time::sleep(wait).await;
+            attempt += 1;
+        }
+    }
+
+    /// Sends a prompt and returns the first completion.
+    ///
+    /// # Arguments
+    /// * `prompt` - The prompt string to send.
+    /// * `n_guesses` - Number of guesses/options to request.
+    ///
+    /// # Errors
+    /// See [`LlmClient::generate`].
+    pub async fn send_prompt(&self, prompt: String, n_guesses: u32) -> Result<String> {
+        let request = GenerationRequest::new(prompt, n_guesses, 0.7);
+
+        self.generate(&request)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("No valid text in LLM response"))
+    }
+}
@@ -0,0 +1,141 @@
+use super::{check_status, read_body, sse_data, DeltaStream, LlmProvider};
+use crate::model::llm::{Completion, GenerationRequest, LLMRequest, LLMResponse, TextDelta};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use reqwest::Client;
+use serde_json::Value;
+
+/// Converts a JSON schema to the OpenAPI subset Gemini accepts: upper-case
+/// type names and no `additionalProperties`.
+pub fn gemini_schema(schema: &Value) -> Value {
+    match schema {
+        Value::Object(object) => Value::Object(
+            object
+                .iter()
+                .filter(|(key, _)| *key != "additionalProperties")
+                .map(|(key, value)| match (key.as_str(), value) {
+                    ("type", Value::String(name)) => (key.clone(), name.to_uppercase().into()),
+                    _ => (key.clone(), gemini_schema(value)),
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(gemini_schema).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Google Gemini's `generateContent` API, and `streamGenerateContent` for
+/// streaming.
+pub struct GeminiProvider {
+    client: Client,
+    base_url: String,
+    model_name: String,
+    api_key: String,
+}
+
+impl GeminiProvider {
+    pub fn new(client: Client, base_url: String, model_name: String, api_key: String) -> Self {
+        Self {
+            client,
+            base_url,
+            model_name,
+            api_key,
+        }
+    }
+
+    fn url(&self, method: &str) -> String {
+        format!(
+            "{}/{}:{}?key={}",
+            self.base_url.trim_end_matches('/'),
+            self.model_name,
+            method,
+            self.api_key
+        )
+    }
+
+    fn body(request: &GenerationRequest, candidates: u32) -> LLMRequest {
+        let mut body = LLMRequest::new(request.prompt.clone(), candidates, 0.0);
+        body.generation_config.temperature = request.temperature;
+        body.generation_config.seed = request.seed;
+        body.generation_config.max_output_tokens = request.max_output_tokens;
+        if let Some(schema) = &request.response_schema {
+            body.generation_config.response_mime_type = Some("application/json".to_string());
+            body.generation_config.response_schema = Some(gemini_schema(schema));
+        }
+        body
+    }
+}
+
+#[async_trait]
+impl LlmProvider for GeminiProvider {
+    fn name(&self) -> &'static str {
+        "Gemini"
+    }
+
+    fn model(&self) -> &str {
+        &self.model_name
+    }
+
+    async fn generate(&self, request: &GenerationRequest) -> Result<Completion> {
+        let body = Self::body(request, request.candidates);
+
+        let response = self
+            .client
+            .post(self.url("generateContent"))
+            .header("Content-Type", "application/json")
+            .json(&body)
+            .send()
+            .await?;
+        let body = read_body(response).await?;
+
+        let api_response: LLMResponse =
+            serde_json::from_str(&body).context("Failed to parse LLM API JSON")?;
+
+        let usage = api_response.usage_metadata.map(Into::into);
+        let texts: Vec<String> = api_response
+            .candidates
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|c| c.content)
+            .filter_map(|c| c.parts.into_iter().next())
+            .map(|part| part.text)
+            .collect();
+
+        if texts.is_empty() {
+            anyhow::bail!("No valid text in LLM response");
+        }
+        Ok(Completion {
+            texts,
+            usage: usage.unwrap_or_default(),
+        })
+    }
+
+    async fn generate_stream(&self, request: &GenerationRequest) -> Result<DeltaStream> {
+        let response = self
+            .client
+            .post(format!("{}&alt=sse", self.url("streamGenerateContent")))
+            .header("Content-Type", "application/json")
+            .json(&Self::body(request, 1))
+            .send()
+            .await?;
+        let response = check_status(response).await?;
+
+        // Each event holds the next piece of the text and the usage so far.
+        Ok(Box::pin(sse_data(response).map(|data| {
+            let chunk: LLMResponse =
+                serde_json::from_str(&data?).context("Failed to parse LLM stream JSON")?;
+            Ok(TextDelta {
+                text: chunk
+                    .candidates
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|c| c.content)
+                    .flat_map(|c| c.parts)
+                    .map(|part| part.text)
+                    .collect(),
+                usage: chunk.usage_metadata.map(Into::into),
+            })
+        })))
+    }
+}
@@ -0,0 +1,86 @@
+use super::LlmProvider;
+use crate::model::llm::{Completion, GenerationRequest};
+use crate::model::usage::TokenUsage;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Map, Value};
+
+/// Keys of every distractor list the MCQ routes parse.
+const DISTRACTOR_KEYS: [&str; 8] = [
+    "distractors",
+    "collocational_distractors",
+    "thematic_distractors",
+    "alternative_verse_distractors",
+    "grammatical_distractors",
+    "morphological_distractors",
+    "phonetic_orthographic_distractors",
+    "diacritic_distractors",
+];
+
+/// Distractors generated per list.
+const DISTRACTORS_PER_KEY: usize = 3;
+
+/// An offline provider for development and CI.
+///
+/// Answers every prompt with a JSON object that parses as any of the MCQ
+/// response types. The values depend only on the prompt, so the same prompt
+/// always gives the same text.
+pub struct MockProvider;
+
+/// 64-bit FNV-1a, stable across runs and platforms unlike `DefaultHasher`.
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// The mock completion of `prompt` for the given candidate.
+pub fn mock_completion(prompt: &str, candidate: u32) -> String {
+    let seed = fnv1a(prompt).wrapping_add(candidate as u64);
+
+    let mut object = Map::new();
+    object.insert(
+        "correct_answer".to_string(),
+        json!([format!("answer-{:x}", seed)]),
+    );
+    for (k, key) in DISTRACTOR_KEYS.iter().enumerate() {
+        let values: Vec<String> = (0..DISTRACTORS_PER_KEY)
+            .map(|i| {
+                format!(
+                    "{}-{:x}-{}",
+                    key,
+                    seed % 0x10000,
+                    k * DISTRACTORS_PER_KEY + i
+                )
+            })
+            .collect();
+        object.insert(key.to_string(), json!(values));
+    }
+    Value::Object(object).to_string()
+}
+
+#[async_trait]
+impl LlmProvider for MockProvider {
+    fn name(&self) -> &'static str {
+        "Mock"
+    }
+
+    fn model(&self) -> &str {
+        "mock"
+    }
+
+    async fn generate(&self, request: &GenerationRequest) -> Result<Completion> {
+        let texts: Vec<String> = (0..request.candidates.max(1))
+            .map(|candidate| mock_completion(&request.prompt, candidate))
+            .collect();
+        // Words stand in for tokens.
+        let words = |text: &str| text.split_whitespace().count() as u64;
+        Ok(Completion {
+            usage: TokenUsage {
+                prompt_tokens: words(&request.prompt),
+                completion_tokens: texts.iter().map(|text| words(text)).sum(),
+            },
+            texts,
+        })
+    }
+}
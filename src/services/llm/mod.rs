@@ -0,0 +1,286 @@
+//! Text generation through a configurable LLM provider.
+//!
+//! The provider is chosen with `LLM_PROVIDER`:
+//!
+//! | Value    | Backend                                             |
+//! |----------|-----------------------------------------------------|
+//! | `gemini` | Google Gemini `generateContent` (the default)       |
+//! | `openai` | Any OpenAI-compatible chat completions server       |
+//! | `ollama` | A local Ollama server                               |
+//! | `mock`   | Deterministic offline responses, no key needed      |
+//!
+//! `TEXT_GENERATION_URL` and `TEXT_GENERATION_MODEL` configure every backend
+//! but the mock. `TEXT_GENERATION_API_KEY` is required for Gemini, optional
+//! for OpenAI-compatible servers and unused by Ollama.
+//!
+//! Requests go through the shared [`LlmClient`] in `AppState`, which adds
+//! timeouts, retries, a circuit breaker and a concurrency cap.
+//!
+//! Completions can also be streamed as they are generated. Gemini, OpenAI
+//! and Ollama use their streaming APIs; the mock yields its text at once.
+
+pub mod client;
+pub mod gemini;
+pub mod mock;
+pub mod ollama;
+pub mod openai;
+pub mod schema;
+
+pub use client::{ClientConfig, LlmClient};
+
+use crate::model::llm::{Completion, GenerationRequest, TextDelta};
+use crate::model::usage::QuotaExceeded;
+use crate::utils;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use futures_util::stream::{self, Stream, StreamExt};
+use reqwest::StatusCode;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// The text of a completion, chunk by chunk as it is generated.
+pub type TextStream = Pin<Box<dyn Stream<Item = Result<String>> + Send>>;
+
+/// A streamed completion as a provider reports it, with its usage.
+pub type DeltaStream = Pin<Box<dyn Stream<Item = Result<TextDelta>> + Send>>;
+
+/// Failures callers may want to tell apart from other errors.
+#[derive(Debug, thiserror::Error)]
+pub enum LlmError {
+    /// The provider answered with an error status.
+    #[error("LLM API Error: {status} - {body}")]
+    Status {
+        status: StatusCode,
+        /// The wait the provider asked for in `Retry-After`, if any.
+        retry_after: Option<Duration>,
+        body: String,
+    },
+    /// The provider cannot take the request right now; try again later.
+    #[error("{reason}")]
+    Unavailable {
+        reason: String,
+        retry_after: Option<Duration>,
+    },
+    /// The user spent a token quota; see `services::usage`.
+    #[error("{}", .0.message)]
+    QuotaExceeded(Box<QuotaExceeded>),
+}
+
+/// A backend that turns a prompt into generated text.
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    /// Name of the backend, for logs.
+    fn name(&self) -> &'static str;
+
+    /// The model generating the text.
+    fn model(&self) -> &str;
+
+    /// Generates up to `request.candidates` completions of the prompt, with
+    /// the tokens they took.
+    ///
+    /// # Errors
+    /// Returns an error if the backend cannot be reached, answers with an
+    /// error status, or returns no text.
+    async fn generate(&self, request: &GenerationRequest) -> Result<Completion>;
+
+    /// Streams one completion of the prompt, whatever `request.candidates`
+    /// says. Backends without a streaming API yield the whole text at once.
+    ///
+    /// # Errors
+    /// Fails like [`LlmProvider::generate`] if the stream cannot be opened.
+    /// Errors after that are items of the stream.
+    async fn generate_stream(&self, request: &GenerationRequest) -> Result<DeltaStream> {
+        let completion = self.generate(request).await?;
+        let text = completion
+            .texts
+            .into_iter()
+            .next()
+            .context("No valid text in LLM response")?;
+        let delta = TextDelta {
+            text,
+            usage: Some(completion.usage),
+        };
+        Ok(Box::pin(stream::once(async move { Ok(delta) })))
+    }
+}
+
+/// The supported backends, as named in `LLM_PROVIDER`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProviderKind {
+    Gemini,
+    OpenAi,
+    Ollama,
+    Mock,
+}
+
+impl FromStr for ProviderKind {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "gemini" => Ok(Self::Gemini),
+            "openai" => Ok(Self::OpenAi),
+            "ollama" => Ok(Self::Ollama),
+            "mock" => Ok(Self::Mock),
+            other => bail!(
+                "Unknown LLM provider `{}`, expected gemini, openai, ollama or mock",
+                other
+            ),
+        }
+    }
+}
+
+/// Settings for building a provider.
+#[derive(Debug, Clone)]
+pub struct ProviderConfig {
+    pub kind: ProviderKind,
+    pub base_url: Option<String>,
+    pub model: Option<String>,
+    pub api_key: Option<String>,
+}
+
+impl ProviderConfig {
+    /// Reads the provider settings from the environment, see the module docs.
+    pub fn from_env() -> Result<Self> {
+        let kind = match utils::env::load_env_var("LLM_PROVIDER") {
+            Ok(name) => name.parse()?,
+            Err(_) => ProviderKind::Gemini,
+        };
+
+        Ok(Self {
+            kind,
+            base_url: utils::env::load_env_var("TEXT_GENERATION_URL").ok(),
+            model: utils::env::load_env_var("TEXT_GENERATION_MODEL").ok(),
+            api_key: utils::env::load_env_var("TEXT_GENERATION_API_KEY").ok(),
+        })
+    }
+
+    fn require(value: &Option<String>, name: &str, kind: ProviderKind) -> Result<String> {
+        value
+            .clone()
+            .filter(|v| !v.is_empty())
+            .with_context(|| format!("{} must be set for the {:?} LLM provider", name, kind))
+    }
+
+    /// Builds the configured provider on top of a shared HTTP client.
+    ///
+    /// # Errors
+    /// Returns an error if a setting the provider needs is missing.
+    pub fn build(&self, http: reqwest::Client) -> Result<Box<dyn LlmProvider>> {
+        let url = || Self::require(&self.base_url, "TEXT_GENERATION_URL", self.kind);
+        let model = || Self::require(&self.model, "TEXT_GENERATION_MODEL", self.kind);
+
+        Ok(match self.kind {
+            ProviderKind::Gemini => Box::new(gemini::GeminiProvider::new(
+                http,
+                url()?,
+                model()?,
+                Self::require(&self.api_key, "TEXT_GENERATION_API_KEY", self.kind)?,
+            )),
+            ProviderKind::OpenAi => Box::new(openai::OpenAiProvider::new(
+                http,
+                url()?,
+                model()?,
+                self.api_key.clone().filter(|k| !k.is_empty()),
+            )),
+            ProviderKind::Ollama => Box::new(ollama::OllamaProvider::new(http, url()?, model()?)),
+            ProviderKind::Mock => Box::new(mock::MockProvider),
+        })
+    }
+}
+
+/// Parses a `Retry-After` header given in seconds or as an HTTP date.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = date.signed_duration_since(chrono::Utc::now());
+    Some(wait.to_std().unwrap_or_default())
+}
+
+/// Fails with [`LlmError::Status`] on an unsuccessful response, or returns it.
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_retry_after);
+    let body = response
+        .text()
+        .await
+        .context("Failed to read LLM response body")?;
+    Err(LlmError::Status {
+        status,
+        retry_after,
+        body,
+    }
+    .into())
+}
+
+/// Fails with [`LlmError::Status`] on an unsuccessful response, or returns the body.
+async fn read_body(response: reqwest::Response) -> Result<String> {
+    check_status(response)
+        .await?
+        .text()
+        .await
+        .context("Failed to read LLM response body")
+}
+
+/// The lines of a streamed response body, without line endings.
+fn response_lines(response: reqwest::Response) -> impl Stream<Item = Result<String>> + Send {
+    let state = (response, Vec::new(), false);
+    stream::unfold(state, |(mut response, mut buffer, mut done)| async move {
+        loop {
+            if let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line)
+                    .trim_end_matches(['\r', '\n'])
+                    .to_string();
+                return Some((Ok(line), (response, buffer, done)));
+            }
+            if done {
+                if buffer.is_empty() {
+                    return None;
+                }
+                let line = String::from_utf8_lossy(&std::mem::take(&mut buffer)).into_owned();
+                return Some((Ok(line), (response, buffer, done)));
+            }
+
+            match response.chunk().await {
+                Ok(Some(chunk)) => buffer.extend_from_slice(&chunk),
+                Ok(None) => done = true,
+                Err(e) => {
+                    buffer.clear();
+                    let error = anyhow::Error::from(e).context("Failed to read LLM stream");
+                    return Some((Err(error), (response, buffer, true)));
+                }
+            }
+        }
+    })
+}
+
+/// The `data` of each server-sent event of a response, up to an OpenAI
+/// style `[DONE]`.
+fn sse_data(response: reqwest::Response) -> impl Stream<Item = Result<String>> + Send {
+    response_lines(response)
+        .filter_map(|line| async move {
+            match line {
+                Ok(line) => line
+                    .strip_prefix("data:")
+                    .map(|data| Ok(data.trim().to_string())),
+                Err(e) => Some(Err(e)),
+            }
+        })
+        .take_while(|data| {
+            let done = matches!(data, Ok(data) if data == "[DONE]");
+            async move { !done }
+        })
+}
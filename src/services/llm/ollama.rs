@@ -0,0 +1,96 @@
+use super::{check_status, read_body, response_lines, DeltaStream, LlmProvider};
+use crate::model::llm::{
+    Completion, GenerationRequest, OllamaGenerateRequest, OllamaGenerateResponse, OllamaOptions,
+    TextDelta,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use reqwest::Client;
+
+/// A local Ollama server, e.g. `http://localhost:11434`.
+///
+/// Ollama returns one completion per request, so several candidates are
+/// requested one after the other, each with its own seed, see
+/// [`GenerationRequest::candidate_seed`].
+pub struct OllamaProvider {
+    client: Client,
+    base_url: String,
+    model_name: String,
+}
+
+impl OllamaProvider {
+    pub fn new(client: Client, base_url: String, model_name: String) -> Self {
+        Self {
+            client,
+            base_url,
+            model_name,
+        }
+    }
+
+    fn url(&self) -> String {
+        format!("{}/api/generate", self.base_url.trim_end_matches('/'))
+    }
+
+    fn body(&self, request: &GenerationRequest, stream: bool) -> OllamaGenerateRequest {
+        OllamaGenerateRequest {
+            model: self.model_name.clone(),
+            prompt: request.prompt.clone(),
+            stream,
+            format: request.response_schema.clone(),
+            options: OllamaOptions {
+                temperature: request.temperature,
+                seed: request.seed,
+                num_predict: request.max_output_tokens,
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OllamaProvider {
+    fn name(&self) -> &'static str {
+        "Ollama"
+    }
+
+    fn model(&self) -> &str {
+        &self.model_name
+    }
+
+    async fn generate(&self, request: &GenerationRequest) -> Result<Completion> {
+        let url = self.url();
+        let mut body = self.body(request, false);
+
+        let mut completion = Completion::default();
+        for candidate in 0..request.candidates.max(1) {
+            body.options.seed = request.candidate_seed(candidate);
+            let response = self.client.post(&url).json(&body).send().await?;
+            let response: OllamaGenerateResponse =
+                serde_json::from_str(&read_body(response).await?)
+                    .context("Failed to parse Ollama JSON")?;
+            completion.usage += response.usage().unwrap_or_default();
+            completion.texts.push(response.response);
+        }
+        Ok(completion)
+    }
+
+    async fn generate_stream(&self, request: &GenerationRequest) -> Result<DeltaStream> {
+        let body = self.body(request, true);
+        let response = self.client.post(self.url()).json(&body).send().await?;
+        let response = check_status(response).await?;
+
+        // One JSON object per line, each with the next piece of the text.
+        let lines = response_lines(response).filter(|line| {
+            let blank = matches!(line, Ok(line) if line.trim().is_empty());
+            async move { !blank }
+        });
+        Ok(Box::pin(lines.map(|line| {
+            let chunk: OllamaGenerateResponse =
+                serde_json::from_str(&line?).context("Failed to parse Ollama stream JSON")?;
+            Ok(TextDelta {
+                usage: chunk.usage(),
+                text: chunk.response,
+            })
+        })))
+    }
+}
@@ -0,0 +1,130 @@
+use super::{check_status, read_body, sse_data, DeltaStream, LlmProvider};
+use crate::model::llm::{
+    ChatCompletionChunk, ChatCompletionRequest, ChatCompletionResponse, ChatMessage, Completion,
+    GenerationRequest, TextDelta,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use reqwest::Client;
+use serde_json::json;
+
+/// Any server with an OpenAI-compatible `chat/completions` endpoint, such as
+/// OpenAI itself, vLLM or the llama.cpp server. `base_url` includes the API
+/// version, e.g. `https://api.openai.com/v1`.
+pub struct OpenAiProvider {
+    client: Client,
+    base_url: String,
+    model_name: String,
+    api_key: Option<String>,
+}
+
+impl OpenAiProvider {
+    /// Local servers usually need no `api_key`.
+    pub fn new(
+        client: Client,
+        base_url: String,
+        model_name: String,
+        api_key: Option<String>,
+    ) -> Self {
+        Self {
+            client,
+            base_url,
+            model_name,
+            api_key,
+        }
+    }
+
+    fn body(
+        &self,
+        request: &GenerationRequest,
+        candidates: u32,
+        stream: bool,
+    ) -> ChatCompletionRequest {
+        ChatCompletionRequest {
+            model: self.model_name.clone(),
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: Some(request.prompt.clone()),
+            }],
+            n: candidates,
+            temperature: request.temperature,
+            seed: request.seed,
+            max_tokens: request.max_output_tokens,
+            response_format: request.response_schema.as_ref().map(|schema| {
+                json!({
+                    "type": "json_schema",
+                    "json_schema": { "name": "response", "strict": true, "schema": schema }
+                })
+            }),
+            stream,
+            stream_options: stream.then(|| json!({ "include_usage": true })),
+        }
+    }
+
+    fn post(&self, body: &ChatCompletionRequest) -> reqwest::RequestBuilder {
+        let http_request = self
+            .client
+            .post(format!(
+                "{}/chat/completions",
+                self.base_url.trim_end_matches('/')
+            ))
+            .json(body);
+        match &self.api_key {
+            Some(api_key) => http_request.bearer_auth(api_key),
+            None => http_request,
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAiProvider {
+    fn name(&self) -> &'static str {
+        "OpenAI-compatible"
+    }
+
+    fn model(&self) -> &str {
+        &self.model_name
+    }
+
+    async fn generate(&self, request: &GenerationRequest) -> Result<Completion> {
+        let body = self.body(request, request.candidates, false);
+        let body = read_body(self.post(&body).send().await?).await?;
+        let api_response: ChatCompletionResponse =
+            serde_json::from_str(&body).context("Failed to parse chat completion JSON")?;
+
+        let usage = api_response.usage.map(Into::into);
+        let texts: Vec<String> = api_response
+            .choices
+            .into_iter()
+            .filter_map(|choice| choice.message.content)
+            .collect();
+
+        if texts.is_empty() {
+            anyhow::bail!("No valid text in LLM response");
+        }
+        Ok(Completion {
+            texts,
+            usage: usage.unwrap_or_default(),
+        })
+    }
+
+    async fn generate_stream(&self, request: &GenerationRequest) -> Result<DeltaStream> {
+        let body = self.body(request, 1, true);
+        let response = check_status(self.post(&body).send().await?).await?;
+
+        Ok(Box::pin(sse_data(response).map(|data| {
+            let chunk: ChatCompletionChunk =
+                serde_json::from_str(&data?).context("Failed to parse chat completion chunk")?;
+            Ok(TextDelta {
+                text: chunk
+                    .choices
+                    .into_iter()
+                    .next()
+                    .and_then(|choice| choice.delta.content)
+                    .unwrap_or_default(),
+                usage: chunk.usage.map(Into::into),
+            })
+        })))
+    }
+}
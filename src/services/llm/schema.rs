@@ -0,0 +1,71 @@
+//! JSON schemas of the MCQ response types, sent to providers that can
+//! constrain their output to a schema.
+//!
+//! Every MCQ response is an object of string lists, so the schema is built
+//! from the field names serde knows for the type and cannot drift from it.
+
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use serde_json::{json, Map, Value};
+
+/// A deserializer that only records the fields of the struct asked for.
+struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+impl<'de> Deserializer<'de> for FieldNames<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(de::Error::custom("fields recorded"))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// Field names of a struct deriving `Deserialize`.
+pub fn struct_fields<T: DeserializeOwned>() -> &'static [&'static str] {
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+/// JSON schema of an MCQ response type: an object whose fields are all
+/// required lists of strings.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let schema = response_schema::<DiacriticDistractorResponse>();
+/// assert_eq!(schema["required"], json!(["correct_answer", "diacritic_distractors"]));
+/// ```
+pub fn response_schema<T: DeserializeOwned>() -> Value {
+    let fields = struct_fields::<T>();
+    let properties: Map<String, Value> = fields
+        .iter()
+        .map(|field| {
+            (
+                field.to_string(),
+                json!({ "type": "array", "items": { "type": "string" } }),
+            )
+        })
+        .collect();
+
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": fields,
+        "additionalProperties": false,
+    })
+}
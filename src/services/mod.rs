@@ -1 +1,14 @@
+pub mod cache;
+pub mod distractors;
+pub mod gift;
+pub mod interchange;
+pub mod jobs;
 pub mod llm;
+pub mod moodle;
+pub mod print;
+pub mod prompts;
+pub mod qti;
+pub mod quality;
+pub mod search;
+pub mod spreadsheet;
+pub mod usage;
@@ -0,0 +1,573 @@
+//! Conversion between exam sections and Moodle XML question banks.
+//!
+//! Sections map to Moodle categories. Multiple-choice, true/false and
+//! short-answer questions round-trip. Matching questions are split into one
+//! multiple-choice question per pair on import, and runs of questions shaped
+//! like such a split are exported as one matching question again. A
+//! question's `description` is carried as Moodle's general feedback.
+
+use crate::model::format::ImportReport;
+use crate::model::option::OptionRequestModel;
+use crate::model::question::QuestionRequest;
+use crate::model::section::SectionRequest;
+use crate::services::interchange::{self, QuestionKind, SectionCollector};
+use crate::utils::arabic::contains_rtl;
+use crate::utils::html;
+use crate::utils::xml::{self, escape, XmlElement};
+use anyhow::{bail, Context, Result};
+
+/// Wraps text as an HTML paragraph inside CDATA, the way Moodle stores it.
+fn html_text(text: &str) -> String {
+    let dir = if contains_rtl(text) {
+        r#" dir="rtl""#
+    } else {
+        ""
+    };
+    let body = text
+        .lines()
+        .map(html::escape)
+        .collect::<Vec<_>>()
+        .join("<br>");
+    format!("<text><![CDATA[<p{}>{}</p>]]></text>", dir, body)
+}
+
+fn answer(fraction: &str, text: &str) -> String {
+    format!(
+        "    <answer fraction=\"{}\" format=\"html\">\n      {}\n      <feedback format=\"html\"><text></text></feedback>\n    </answer>\n",
+        fraction,
+        html_text(text)
+    )
+}
+
+/// Opens a `<question>` with the fields every type shares. `question` gives
+/// the name and general feedback.
+fn question_header(
+    question_type: &str,
+    question: &QuestionRequest,
+    text: &str,
+    marks: i32,
+) -> String {
+    format!(
+        r#"  <question type="{}">
+    <name><text>{}</text></name>
+    <questiontext format="html">
+      {}
+    </questiontext>
+    <generalfeedback format="html">
+      {}
+    </generalfeedback>
+    <defaultgrade>{}</defaultgrade>
+    <penalty>0.3333333</penalty>
+    <hidden>0</hidden>
+"#,
+        question_type,
+        escape(&interchange::question_name(question)),
+        html_text(text),
+        html_text(question.base.description.as_deref().unwrap_or_default()),
+        marks
+    )
+}
+
+/// Splits a question into the prompt, its own item on the last line, and
+/// its only correct answer, as importing a matching question produces.
+fn matching_pair(question: &QuestionRequest) -> Option<(&str, &str, &str)> {
+    let (prompt, item) = question.base.text.rsplit_once('\n')?;
+    if prompt.trim().is_empty() || item.trim().is_empty() {
+        return None;
+    }
+
+    let mut correct = question
+        .options
+        .iter()
+        .filter(|o| o.base.is_correct.unwrap_or_default());
+    let answer = correct.next()?;
+    if correct.next().is_some() {
+        return None;
+    }
+    Some((prompt, item, answer.base.text.as_str()))
+}
+
+/// The number of questions at the start of `questions` that read as one
+/// split matching question: at least two, with the same prompt, description
+/// and distinct options, one correct each, and marks shared out the way
+/// importing splits them. Zero if there is no such run.
+fn matching_run(questions: &[QuestionRequest]) -> usize {
+    let Some(first) = questions.first() else {
+        return 0;
+    };
+    let Some((prompt, _, _)) = matching_pair(first) else {
+        return 0;
+    };
+
+    let answers: Vec<&str> = first.options.iter().map(|o| o.base.text.as_str()).collect();
+    let distinct = answers
+        .iter()
+        .enumerate()
+        .all(|(i, a)| !a.trim().is_empty() && !answers[..i].contains(a));
+    if !distinct {
+        return 0;
+    }
+
+    let len = questions
+        .iter()
+        .take_while(|q| {
+            q.base.description == first.base.description
+                && matching_pair(q).is_some_and(|(p, _, _)| p == prompt)
+                && q.options.len() == answers.len()
+                && q.options
+                    .iter()
+                    .all(|o| answers.contains(&o.base.text.as_str()))
+        })
+        .count();
+    if len < 2 {
+        return 0;
+    }
+
+    let marks: Vec<i32> = questions[..len].iter().map(|q| q.base.marks).collect();
+    if split_marks(marks.iter().sum(), len) != marks {
+        return 0;
+    }
+    len
+}
+
+/// Renders a run found by [`matching_run`] as one matching question. Options
+/// that answer none of the items become distractor subquestions without text.
+fn render_matching(questions: &[QuestionRequest]) -> String {
+    let first = &questions[0];
+    let pairs: Vec<_> = questions.iter().filter_map(matching_pair).collect();
+    let prompt = pairs[0].0;
+    let marks = questions.iter().map(|q| q.base.marks).sum();
+
+    let mut out = question_header("matching", first, prompt, marks);
+    out.push_str("    <shuffleanswers>true</shuffleanswers>\n");
+
+    let subquestion = |text: &str, answer: &str| {
+        format!(
+            "    <subquestion format=\"html\">\n      {}\n      <answer><text>{}</text></answer>\n    </subquestion>\n",
+            if text.is_empty() {
+                "<text></text>".to_string()
+            } else {
+                html_text(text)
+            },
+            escape(answer)
+        )
+    };
+    for (_, item, answer) in &pairs {
+        out.push_str(&subquestion(item, answer));
+    }
+    for option in &first.options {
+        let answer = option.base.text.as_str();
+        if !pairs.iter().any(|(_, _, a)| *a == answer) {
+            out.push_str(&subquestion("", answer));
+        }
+    }
+
+    out.push_str("  </question>\n");
+    out
+}
+
+fn render_question(question: &QuestionRequest) -> String {
+    let kind = interchange::classify(question);
+    let question_type = match kind {
+        QuestionKind::TrueFalse(_) => "truefalse",
+        QuestionKind::ShortAnswer(_) => "shortanswer",
+        QuestionKind::MultiChoice => "multichoice",
+    };
+
+    let mut out = question_header(
+        question_type,
+        question,
+        &question.base.text,
+        question.base.marks,
+    );
+
+    match kind {
+        QuestionKind::TrueFalse(value) => {
+            for (text, correct) in [("true", value), ("false", !value)] {
+                out.push_str(&format!(
+                    "    <answer fraction=\"{}\" format=\"moodle_auto_format\">\n      <text>{}</text>\n      <feedback format=\"html\"><text></text></feedback>\n    </answer>\n",
+                    if correct { 100 } else { 0 },
+                    text
+                ));
+            }
+        }
+        QuestionKind::ShortAnswer(answers) => {
+            out.push_str("    <usecase>0</usecase>\n");
+            for text in answers {
+                out.push_str(&answer("100", text));
+            }
+        }
+        QuestionKind::MultiChoice => {
+            let correct = question
+                .options
+                .iter()
+                .filter(|o| o.base.is_correct.unwrap_or_default())
+                .count();
+            out.push_str(&format!(
+                "    <single>{}</single>\n    <shuffleanswers>true</shuffleanswers>\n    <answernumbering>abc</answernumbering>\n",
+                correct <= 1
+            ));
+
+            let fraction = interchange::correct_fraction(correct);
+            for option in &question.options {
+                let fraction = if option.base.is_correct.unwrap_or_default() {
+                    fraction.as_str()
+                } else {
+                    "0"
+                };
+                out.push_str(&answer(fraction, &option.base.text));
+            }
+        }
+    }
+
+    out.push_str("  </question>\n");
+    out
+}
+
+/// Renders sections as a Moodle XML question bank, one category per section.
+pub fn export_sections(sections: &[SectionRequest]) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<quiz>\n");
+
+    for section in sections {
+        out.push_str(&format!(
+            "  <question type=\"category\">\n    <category><text>{}</text></category>\n  </question>\n",
+            escape(&interchange::category_path(&section.base.title))
+        ));
+        let mut questions = section.questions.as_slice();
+        while !questions.is_empty() {
+            let run = matching_run(questions);
+            if run > 0 {
+                out.push_str(&render_matching(&questions[..run]));
+                questions = &questions[run..];
+            } else {
+                out.push_str(&render_question(&questions[0]));
+                questions = &questions[1..];
+            }
+        }
+    }
+
+    out.push_str("</quiz>\n");
+    out
+}
+
+/// Reads the `<text>` of a Moodle text field as plain text, converting HTML
+/// according to the field's `format`.
+fn field_text(field: &XmlElement) -> String {
+    let raw = field.child("text").map(|t| t.text()).unwrap_or_default();
+    match field.attr("format").unwrap_or("html") {
+        "plain_text" | "markdown" => raw.trim().to_string(),
+        _ => html::to_plain_text(&raw),
+    }
+}
+
+fn child_text(element: &XmlElement, name: &str) -> Option<String> {
+    element
+        .child(name)
+        .map(field_text)
+        .filter(|t| !t.is_empty())
+}
+
+fn parse_fraction(answer: &XmlElement) -> f64 {
+    answer
+        .attr("fraction")
+        .and_then(|f| f.trim().parse().ok())
+        .unwrap_or_default()
+}
+
+/// Splits `marks` into `parts` whole shares that add up to it, the
+/// remainder going to the first shares.
+fn split_marks(marks: i32, parts: usize) -> Vec<i32> {
+    let parts = parts as i32;
+    let (share, remainder) = (marks.div_euclid(parts), marks.rem_euclid(parts));
+    (0..parts)
+        .map(|i| share + i32::from(i < remainder))
+        .collect()
+}
+
+/// Per-question context for reporting while converting one `<question>`.
+struct QuestionImport<'a> {
+    position: usize,
+    name: Option<String>,
+    question_type: String,
+    element: &'a XmlElement,
+    report: &'a mut ImportReport,
+}
+
+impl QuestionImport<'_> {
+    fn warn(&mut self, message: impl Into<String>) {
+        self.report.warn(
+            self.position,
+            self.name.as_deref(),
+            &self.question_type,
+            message,
+        );
+    }
+
+    fn skip(&mut self, message: impl Into<String>) {
+        self.report.skip(
+            self.position,
+            self.name.as_deref(),
+            &self.question_type,
+            message,
+        );
+    }
+
+    fn text(&mut self) -> Option<String> {
+        let field = self.element.child("questiontext")?;
+        let raw = field.child("text").map(|t| t.text()).unwrap_or_default();
+
+        if html::has_rich_content(&raw) {
+            self.warn("Images, media or tables in the question text were dropped");
+        }
+        if field.child("file").is_some() {
+            self.warn("Embedded files were dropped");
+        }
+
+        Some(field_text(field)).filter(|t| !t.is_empty())
+    }
+
+    fn marks(&mut self) -> i32 {
+        let grade: f64 = self
+            .element
+            .child("defaultgrade")
+            .and_then(|g| g.text().trim().parse().ok())
+            .unwrap_or(1.0);
+
+        let marks = grade.round() as i32;
+        if (grade - f64::from(marks)).abs() > f64::EPSILON {
+            self.warn(format!("Grade {} was rounded to {}", grade, marks));
+        }
+        marks
+    }
+
+    fn check_feedback(&mut self) {
+        let has_feedback = self
+            .element
+            .children_named("answer")
+            .filter_map(|a| a.child("feedback"))
+            .any(|f| !field_text(f).is_empty());
+        if has_feedback {
+            self.warn("Per-answer feedback was dropped");
+        }
+    }
+
+    /// Converts the `<answer>` elements into options. Answers with a positive
+    /// fraction are correct.
+    fn answers(&mut self, single: bool) -> Vec<OptionRequestModel> {
+        let mut options = Vec::new();
+        let mut partial = false;
+
+        for answer in self.element.children_named("answer") {
+            let text = field_text(answer);
+            if text.is_empty() {
+                self.warn("An empty answer was dropped");
+                continue;
+            }
+
+            let fraction = parse_fraction(answer);
+            partial |= single && fraction > 0.0 && fraction < 100.0;
+            options.push(OptionRequestModel::new(text, fraction > 0.0));
+        }
+
+        if partial {
+            self.warn("Partial-credit answers were imported as fully correct");
+        }
+        options
+    }
+
+    fn convert(&mut self) -> Vec<QuestionRequest> {
+        let Some(text) = self.text() else {
+            self.skip("Question has no text");
+            return Vec::new();
+        };
+
+        let description = child_text(self.element, "generalfeedback");
+        let marks = self.marks();
+
+        let options = match self.question_type.as_str() {
+            "multichoice" => {
+                let single = self
+                    .element
+                    .child("single")
+                    .is_none_or(|s| s.text().trim() != "false");
+                self.check_feedback();
+                self.answers(single)
+            }
+            "truefalse" => {
+                self.check_feedback();
+                let answer = self
+                    .element
+                    .children_named("answer")
+                    .find(|a| parse_fraction(a) > 0.0)
+                    .map(field_text);
+                match answer.as_deref().map(str::to_lowercase).as_deref() {
+                    Some("true") => interchange::true_false_options(true),
+                    Some("false") => interchange::true_false_options(false),
+                    _ => {
+                        self.skip("True/false question has no correct answer");
+                        return Vec::new();
+                    }
+                }
+            }
+            "shortanswer" => {
+                self.check_feedback();
+                let options: Vec<_> = self
+                    .answers(true)
+                    .into_iter()
+                    .filter(|o| o.base.is_correct == Some(true))
+                    .collect();
+                if options.len() < self.element.children_named("answer").count() {
+                    self.warn("Answers worth no credit were dropped");
+                }
+                options
+            }
+            "matching" => return self.convert_matching(text, description, marks),
+            _ => unreachable!("unsupported types are filtered before conversion"),
+        };
+
+        if options.is_empty() {
+            self.skip("Question has no usable answers");
+            return Vec::new();
+        }
+        if !options.iter().any(|o| o.base.is_correct == Some(true)) {
+            self.warn("Question has no correct answer");
+        }
+
+        vec![QuestionRequest::new(text, description, marks, options)]
+    }
+
+    /// Splits a matching question into one multiple-choice question per
+    /// pair, offering every answer of the set as an option.
+    fn convert_matching(
+        &mut self,
+        prompt: String,
+        description: Option<String>,
+        marks: i32,
+    ) -> Vec<QuestionRequest> {
+        let pairs: Vec<(String, String)> = self
+            .element
+            .children_named("subquestion")
+            .map(|s| {
+                let answer = s
+                    .child("answer")
+                    .and_then(|a| a.child("text"))
+                    .map(|t| t.text().trim().to_string())
+                    .unwrap_or_default();
+                (field_text(s), answer)
+            })
+            .filter(|(_, answer)| !answer.is_empty())
+            .collect();
+
+        let mut answers: Vec<&str> = Vec::new();
+        for (_, answer) in &pairs {
+            if !answers.contains(&answer.as_str()) {
+                answers.push(answer);
+            }
+        }
+
+        let questions: Vec<QuestionRequest> = pairs
+            .iter()
+            .filter(|(text, _)| !text.is_empty())
+            .map(|(text, correct)| {
+                let options = answers
+                    .iter()
+                    .map(|a| OptionRequestModel::new(a.to_string(), a == correct))
+                    .collect();
+                QuestionRequest::new(
+                    format!("{}\n{}", prompt, text),
+                    description.clone(),
+                    0,
+                    options,
+                )
+            })
+            .collect();
+
+        if questions.is_empty() {
+            self.skip("Matching question has no pairs");
+            return questions;
+        }
+
+        self.warn(format!(
+            "Matching question was split into {} multiple-choice questions sharing its {} marks",
+            questions.len(),
+            marks
+        ));
+
+        let shares = split_marks(marks, questions.len());
+        questions
+            .into_iter()
+            .zip(shares)
+            .map(|(mut q, share)| {
+                q.base.marks = share;
+                q
+            })
+            .collect()
+    }
+}
+
+const SUPPORTED_TYPES: [&str; 4] = ["multichoice", "truefalse", "shortanswer", "matching"];
+
+/// Reads a Moodle XML question bank into sections, one per category.
+///
+/// IDs in the returned sections are placeholders. Questions that cannot be
+/// represented are skipped; every skip or lossy conversion is listed in the
+/// returned report.
+///
+/// # Errors
+/// Returns an error if the document is not well-formed XML or has no `<quiz>` root.
+pub fn import_sections(xml_text: &str) -> Result<(Vec<SectionRequest>, ImportReport)> {
+    let root = xml::parse(xml_text).context("Moodle XML is not well-formed")?;
+    if root.name != "quiz" {
+        bail!(
+            "Moodle XML root element must be <quiz>, found <{}>",
+            root.name
+        );
+    }
+
+    let mut report = ImportReport::default();
+    let mut sections = SectionCollector::default();
+    let mut category: Option<String> = None;
+    let mut position = 0;
+
+    for element in root.children_named("question") {
+        let question_type = element.attr("type").unwrap_or_default().to_string();
+
+        if question_type == "category" {
+            category = element
+                .child("category")
+                .and_then(|c| c.child("text"))
+                .map(|t| interchange::category_title(&t.text()));
+            continue;
+        }
+
+        position += 1;
+        let name = element
+            .child("name")
+            .and_then(|n| n.child("text"))
+            .map(|t| t.text().trim().to_string())
+            .filter(|n| !n.is_empty());
+
+        let mut import = QuestionImport {
+            position,
+            name,
+            question_type: question_type.clone(),
+            element,
+            report: &mut report,
+        };
+
+        if !SUPPORTED_TYPES.contains(&question_type.as_str()) {
+            import.skip(format!(
+                "Question type '{}' is not supported",
+                question_type
+            ));
+            continue;
+        }
+
+        for question in import.convert() {
+            sections.push(category.as_deref(), question);
+            report.imported += 1;
+        }
+    }
+
+    Ok((sections.into_sections(), report))
+}
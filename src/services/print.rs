@@ -0,0 +1,510 @@
+//! Printable exams: self-contained HTML for paper sittings, and PDF through
+//! a Gotenberg-compatible HTML-to-PDF service.
+//!
+//! The page direction and header labels follow the language of the exam
+//! (English, Arabic or Urdu). Every text also carries `dir="auto"` so mixed
+//! content lays out correctly, and Quranic text is set in a Quranic font.
+
+use crate::model::exam::ExamResponse;
+use crate::model::print::PrintOptions;
+use crate::model::question::QuestionResponse;
+use crate::services::interchange::BLANK;
+use crate::utils::{self, arabic, html::escape};
+use anyhow::{bail, Context, Result};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use reqwest::multipart::{Form, Part};
+use reqwest::Client;
+use std::time::Duration;
+
+const STYLE: &str = r#"
+@page { size: A4; margin: 18mm 16mm; }
+* { box-sizing: border-box; }
+body { margin: 0; color: #111; font-size: 12pt; line-height: 1.6;
+  font-family: "Noto Naskh Arabic", "Amiri", "Segoe UI", "Noto Sans", Arial, sans-serif; }
+html[lang="ur"] body { font-family: "Noto Nastaliq Urdu", "Jameel Noori Nastaleeq", "Noto Naskh Arabic", serif; line-height: 2.2; }
+.quran { font-family: "KFGQPC Uthmanic Script HAFS", "Amiri Quran", "Scheherazade New", "Noto Naskh Arabic", serif;
+  font-size: 1.35em; line-height: 2.3; }
+.paper + .paper, .answer-key { break-before: page; page-break-before: always; }
+.exam-header { border-bottom: 2px solid #111; padding-bottom: 8pt; margin-bottom: 12pt; }
+.exam-header h1 { font-size: 18pt; margin: 0 0 4pt; }
+.variant { float: inline-end; font-weight: bold; border: 1.5px solid #111; padding: 2pt 8pt; }
+.exam-meta { display: flex; flex-wrap: wrap; gap: 4pt 18pt; margin: 6pt 0; }
+.exam-meta dt { display: inline; font-weight: bold; }
+.exam-meta dt::after { content: ":"; }
+.exam-meta dd { display: inline; margin: 0 4pt; }
+.candidate { display: flex; gap: 24pt; margin-top: 8pt; }
+.candidate span { flex: 1; border-bottom: 1px dotted #111; padding-bottom: 2pt; }
+h2 { font-size: 14pt; margin: 14pt 0 6pt; }
+.question { break-inside: avoid; page-break-inside: avoid; margin: 0 0 10pt; }
+.question-head { display: flex; gap: 6pt; align-items: baseline; }
+.number { font-weight: bold; }
+.marks { margin-inline-start: auto; white-space: nowrap; font-size: 10pt; }
+.question-description { margin: 2pt 0; font-size: 10.5pt; color: #333; }
+.options { list-style: none; margin: 4pt 0 0; padding-inline-start: 22pt; }
+.options li { margin: 2pt 0; }
+.label { display: inline-block; min-width: 18pt; font-weight: bold; }
+.blank { display: inline-block; min-width: 60pt; border-bottom: 1px solid #111; }
+table { border-collapse: collapse; width: 100%; }
+th, td { border: 1px solid #111; padding: 3pt 8pt; text-align: start; }
+"#;
+
+/// Arabic option letters in abjad order.
+const ARABIC_LETTERS: [&str; 28] = [
+    "أ", "ب", "ج", "د", "هـ", "و", "ز", "ح", "ط", "ي", "ك", "ل", "م", "ن", "س", "ع", "ف", "ص", "ق",
+    "ر", "ش", "ت", "ث", "خ", "ذ", "ض", "ظ", "غ",
+];
+
+struct Labels {
+    duration: &'static str,
+    minutes: &'static str,
+    passing_score: &'static str,
+    total_marks: &'static str,
+    marks: &'static str,
+    name: &'static str,
+    date: &'static str,
+    paper: &'static str,
+    answer_key: &'static str,
+    answer: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Language {
+    English,
+    Arabic,
+    Urdu,
+}
+
+impl Language {
+    /// Picks the language from the exam's titles and question texts.
+    fn detect(exam: &ExamResponse) -> Self {
+        let mut texts = vec![
+            exam.description.title.as_str(),
+            exam.description.description.as_str(),
+        ];
+        for section in &exam.sections {
+            texts.push(&section.base.title);
+            texts.extend(section.questions.iter().map(|q| q.base.text.as_str()));
+        }
+        texts.retain(|t| !t.trim().is_empty());
+
+        if texts.iter().any(|t| arabic::contains_urdu(t)) {
+            return Language::Urdu;
+        }
+        let rtl = texts.iter().filter(|t| arabic::contains_rtl(t)).count();
+        if rtl > 0 && rtl * 2 >= texts.len() {
+            Language::Arabic
+        } else {
+            Language::English
+        }
+    }
+
+    fn code(self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Arabic => "ar",
+            Language::Urdu => "ur",
+        }
+    }
+
+    fn dir(self) -> &'static str {
+        match self {
+            Language::English => "ltr",
+            Language::Arabic | Language::Urdu => "rtl",
+        }
+    }
+
+    fn labels(self) -> Labels {
+        match self {
+            Language::English => Labels {
+                duration: "Duration",
+                minutes: "minutes",
+                passing_score: "Passing score",
+                total_marks: "Total marks",
+                marks: "marks",
+                name: "Name",
+                date: "Date",
+                paper: "Paper",
+                answer_key: "Answer key",
+                answer: "Answer",
+            },
+            Language::Arabic => Labels {
+                duration: "المدة",
+                minutes: "دقيقة",
+                passing_score: "درجة النجاح",
+                total_marks: "مجموع الدرجات",
+                marks: "درجات",
+                name: "الاسم",
+                date: "التاريخ",
+                paper: "النموذج",
+                answer_key: "مفتاح الإجابة",
+                answer: "الإجابة",
+            },
+            Language::Urdu => Labels {
+                duration: "دورانیہ",
+                minutes: "منٹ",
+                passing_score: "کامیابی کے نمبر",
+                total_marks: "کل نمبر",
+                marks: "نمبر",
+                name: "نام",
+                date: "تاریخ",
+                paper: "پرچہ",
+                answer_key: "جوابی کلید",
+                answer: "جواب",
+            },
+        }
+    }
+
+    fn option_label(self, index: usize) -> String {
+        match self {
+            Language::English if index < 26 => ((b'A' + index as u8) as char).to_string(),
+            Language::Arabic | Language::Urdu if index < ARABIC_LETTERS.len() => {
+                ARABIC_LETTERS[index].to_string()
+            }
+            _ => (index + 1).to_string(),
+        }
+    }
+}
+
+/// Letter of the `index`-th printed variant: A, B, C, ...
+fn variant_name(index: usize) -> char {
+    (b'A' + index as u8) as char
+}
+
+/// Escapes `text` for the page, drawing blanks as a line to write on.
+fn text_html(text: &str) -> String {
+    escape(text.trim())
+        .replace(BLANK, r#"<span class="blank"></span>"#)
+        .replace('\n', "<br>")
+}
+
+/// Wraps text in `tag` with its own direction and, for Quranic text, the
+/// Quranic font.
+fn text_element(tag: &str, class: &str, text: &str) -> String {
+    let mut classes = class.to_string();
+    if arabic::is_quranic(text) {
+        if !classes.is_empty() {
+            classes.push(' ');
+        }
+        classes.push_str("quran");
+    }
+    let class_attr = if classes.is_empty() {
+        String::new()
+    } else {
+        format!(r#" class="{}""#, classes)
+    };
+    format!(
+        r#"<{tag}{class_attr} dir="auto">{}</{tag}>"#,
+        text_html(text)
+    )
+}
+
+/// One question as printed on a paper, with its options in print order.
+struct PrintedQuestion<'a> {
+    number: usize,
+    question: &'a QuestionResponse,
+    order: Vec<usize>,
+}
+
+impl PrintedQuestion<'_> {
+    fn correct_labels(&self, language: Language) -> Vec<String> {
+        self.order
+            .iter()
+            .enumerate()
+            .filter(|(_, &original)| {
+                self.question.options[original]
+                    .base
+                    .is_correct
+                    .unwrap_or_default()
+            })
+            .map(|(position, _)| language.option_label(position))
+            .collect()
+    }
+}
+
+/// Lays out the questions of one variant, numbering them across sections.
+fn layout_variant<'a>(
+    exam: &'a ExamResponse,
+    options: &PrintOptions,
+    variant: usize,
+) -> Vec<(&'a str, Vec<PrintedQuestion<'a>>)> {
+    // Seeded per exam and variant so a reprint gives the same papers.
+    let mut rng = StdRng::seed_from_u64(((exam.exam_id.id as u64) << 8) | variant as u64);
+    let mut number = 0;
+
+    exam.sections
+        .iter()
+        .map(|section| {
+            let questions = section
+                .questions
+                .iter()
+                .map(|question| {
+                    number += 1;
+                    let mut order: Vec<usize> = (0..question.options.len()).collect();
+                    if options.shuffle {
+                        order.shuffle(&mut rng);
+                    }
+                    PrintedQuestion {
+                        number,
+                        question,
+                        order,
+                    }
+                })
+                .collect();
+            (section.base.title.as_str(), questions)
+        })
+        .collect()
+}
+
+fn render_header(
+    out: &mut String,
+    exam: &ExamResponse,
+    labels: &Labels,
+    variant: Option<char>,
+    total_marks: i32,
+) {
+    out.push_str(r#"<header class="exam-header">"#);
+    if let Some(variant) = variant {
+        out.push_str(&format!(
+            r#"<div class="variant">{} {}</div>"#,
+            labels.paper, variant
+        ));
+    }
+    out.push_str(&text_element("h1", "", &exam.description.title));
+    if !exam.description.description.trim().is_empty() {
+        out.push_str(&text_element(
+            "p",
+            "exam-description",
+            &exam.description.description,
+        ));
+    }
+    out.push_str(&format!(
+        r#"<dl class="exam-meta"><div><dt>{}</dt><dd>{} {}</dd></div><div><dt>{}</dt><dd>{}</dd></div><div><dt>{}</dt><dd>{}</dd></div></dl>"#,
+        labels.duration,
+        exam.description.duration,
+        labels.minutes,
+        labels.passing_score,
+        exam.description.passing_score,
+        labels.total_marks,
+        total_marks
+    ));
+    out.push_str(&format!(
+        r#"<div class="candidate"><span>{}:</span><span>{}:</span></div>"#,
+        labels.name, labels.date
+    ));
+    out.push_str("</header>");
+}
+
+fn render_question(out: &mut String, printed: &PrintedQuestion, language: Language) {
+    let labels = language.labels();
+    let question = printed.question;
+
+    out.push_str(r#"<div class="question"><div class="question-head">"#);
+    out.push_str(&format!(
+        r#"<span class="number">{}.</span>"#,
+        printed.number
+    ));
+    out.push_str(&text_element("span", "question-text", &question.base.text));
+    out.push_str(&format!(
+        r#"<span class="marks">({} {})</span></div>"#,
+        question.base.marks, labels.marks
+    ));
+
+    if let Some(description) = question
+        .base
+        .description
+        .as_deref()
+        .filter(|d| !d.trim().is_empty())
+    {
+        out.push_str(&text_element("p", "question-description", description));
+    }
+
+    out.push_str(r#"<ol class="options">"#);
+    for (position, &original) in printed.order.iter().enumerate() {
+        out.push_str(&format!(
+            r#"<li><span class="label">{}</span> {}</li>"#,
+            language.option_label(position),
+            text_element("span", "option-text", &question.options[original].base.text)
+        ));
+    }
+    out.push_str("</ol></div>");
+}
+
+fn render_answer_key(
+    out: &mut String,
+    layout: &[(&str, Vec<PrintedQuestion>)],
+    language: Language,
+    variant: Option<char>,
+) {
+    let labels = language.labels();
+    let title = match variant {
+        Some(variant) => format!("{} — {} {}", labels.answer_key, labels.paper, variant),
+        None => labels.answer_key.to_string(),
+    };
+
+    out.push_str(&format!(
+        r#"<section class="answer-key"><h1>{}</h1><table><thead><tr><th>#</th><th>{}</th><th>{}</th></tr></thead><tbody>"#,
+        title, labels.answer, labels.marks
+    ));
+    for printed in layout.iter().flat_map(|(_, questions)| questions) {
+        let answers = printed.correct_labels(language);
+        let answer = if answers.is_empty() {
+            "—".to_string()
+        } else {
+            answers.join(", ")
+        };
+        out.push_str(&format!(
+            r#"<tr><td>{}</td><td class="answer">{}</td><td>{}</td></tr>"#,
+            printed.number, answer, printed.question.base.marks
+        ));
+    }
+    out.push_str("</tbody></table></section>");
+}
+
+/// Renders an exam as a self-contained HTML document ready for printing.
+///
+/// Each variant is printed as its own paper, starting on a new page. With
+/// `answer_key`, an answer-key page for every variant follows the papers.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let exam = read_exam_data(&pool, 1).await?;
+/// let html = render_html(&exam, &PrintOptions { answer_key: true, variants: 2, shuffle: true });
+/// ```
+pub fn render_html(exam: &ExamResponse, options: &PrintOptions) -> String {
+    let language = Language::detect(exam);
+    let labels = language.labels();
+    let total_marks: i32 = exam
+        .sections
+        .iter()
+        .flat_map(|s| &s.questions)
+        .map(|q| q.base.marks)
+        .sum();
+
+    let mut out = format!(
+        r#"<!DOCTYPE html><html lang="{}" dir="{}"><head><meta charset="utf-8"><title>{}</title><style>{}</style></head><body>"#,
+        language.code(),
+        language.dir(),
+        escape(&exam.description.title),
+        STYLE
+    );
+
+    let layouts: Vec<_> = (0..options.variants)
+        .map(|variant| layout_variant(exam, options, variant))
+        .collect();
+    let variant_label = |variant: usize| (options.variants > 1).then(|| variant_name(variant));
+
+    for (variant, layout) in layouts.iter().enumerate() {
+        out.push_str(r#"<section class="paper">"#);
+        render_header(&mut out, exam, &labels, variant_label(variant), total_marks);
+        for (title, questions) in layout {
+            out.push_str(&text_element("h2", "", title));
+            for printed in questions {
+                render_question(&mut out, printed, language);
+            }
+        }
+        out.push_str("</section>");
+    }
+
+    if options.answer_key {
+        for (variant, layout) in layouts.iter().enumerate() {
+            render_answer_key(&mut out, layout, language, variant_label(variant));
+        }
+    }
+
+    out.push_str("</body></html>");
+    out
+}
+
+/// Seconds a PDF conversion may take unless `PDF_RENDER_TIMEOUT_SECS` says
+/// otherwise.
+const DEFAULT_PDF_RENDER_TIMEOUT_SECS: u64 = 60;
+
+/// Client of the HTML-to-PDF service. The HTTP client is shared so
+/// connections are reused, and a renderer that hangs fails the request after
+/// the timeout instead of holding it.
+#[derive(Clone)]
+pub struct PdfRenderer {
+    client: Client,
+    base_url: Option<String>,
+}
+
+impl PdfRenderer {
+    pub fn new(base_url: Option<String>, timeout: Duration) -> Result<Self> {
+        let client = Client::builder()
+            .connect_timeout(Duration::from_secs(5))
+            .timeout(timeout)
+            .build()
+            .context("Failed to build PDF renderer HTTP client")?;
+        Ok(Self { client, base_url })
+    }
+
+    /// Reads the service from `PDF_RENDER_URL` and the timeout from
+    /// `PDF_RENDER_TIMEOUT_SECS`. PDF rendering is off without a URL.
+    pub fn from_env() -> Result<Self> {
+        let base_url = utils::env::load_env_var("PDF_RENDER_URL")
+            .ok()
+            .filter(|url| !url.trim().is_empty());
+        let timeout = match utils::env::load_env_var("PDF_RENDER_TIMEOUT_SECS") {
+            Ok(secs) => secs
+                .trim()
+                .parse()
+                .with_context(|| format!("Invalid PDF_RENDER_TIMEOUT_SECS `{}`", secs))?,
+            Err(_) => DEFAULT_PDF_RENDER_TIMEOUT_SECS,
+        };
+        Self::new(base_url, Duration::from_secs(timeout))
+    }
+
+    /// Whether a PDF service is configured.
+    pub fn enabled(&self) -> bool {
+        self.base_url.is_some()
+    }
+
+    /// Converts an HTML document to PDF with a Gotenberg-compatible service
+    /// running headless Chromium, which shapes Arabic and Urdu text properly.
+    ///
+    /// # Errors
+    /// Returns an error if no service is configured, or if it cannot be
+    /// reached, rejects the document or does not answer in time.
+    pub async fn render(&self, html: String) -> Result<Vec<u8>> {
+        let Some(base_url) = &self.base_url else {
+            bail!("PDF_RENDER_URL is not configured");
+        };
+        let url = format!(
+            "{}/forms/chromium/convert/html",
+            base_url.trim_end_matches('/')
+        );
+        let file = Part::text(html)
+            .file_name("index.html")
+            .mime_str("text/html")
+            .context("Failed to build PDF request")?;
+        let form = Form::new()
+            .part("files", file)
+            .text("preferCssPageSize", "true")
+            .text("printBackground", "true");
+
+        let response = self
+            .client
+            .post(url)
+            .multipart(form)
+            .send()
+            .await
+            .context("Failed to reach the PDF renderer")?;
+
+        let status = response.status();
+        let body = response
+            .bytes()
+            .await
+            .context("Failed to read the PDF renderer response")?;
+        if !status.is_success() {
+            bail!(
+                "PDF renderer error: {} - {}",
+                status,
+                String::from_utf8_lossy(&body)
+            );
+        }
+
+        Ok(body.to_vec())
+    }
+}
@@ -0,0 +1,396 @@
+//! Prompt templates, stored in Postgres with their versions.
+//!
+//! Each (task, language, distractor type) has a series of versions, one of
+//! which is active. [`PromptStore`] keeps the active versions in memory and
+//! renders prompts from them; it reloads them after every change made
+//! through it and every `PROMPT_RELOAD_SECS` (30 by default), so that
+//! changes made by other instances are picked up without a restart.
+//!
+//! Templates are rendered by the engine of `utils::prompts` and can include
+//! the partials of the `prompt_partials` table, which are loaded alongside.
+//!
+//! A series without any active version is seeded on startup from the JSON
+//! file at `PROMPT_TEMPLATE_PATH`, if set, under its legacy field names or
+//! its series name, e.g. `context/english`.
+//!
+//! While an experiment runs on a series, its variants replace the active
+//! version: each user is assigned one by a hash of the experiment name and
+//! their ID, in proportion to the weights, so that they keep seeing the
+//! same variant. Anonymous requests are assigned at random.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+use minijinja::Environment;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use thiserror::Error;
+use tokio::task::JoinHandle;
+
+use crate::database::queries;
+use crate::model::experiment::{Experiment, VariantAssignment};
+use crate::model::llm::{DistractorType, Language};
+use crate::model::prompt::{
+    PromptContext, PromptExample, PromptTask, PromptTemplate, PromptTemplateQuery, RenderedPrompt,
+    TemplateKey,
+};
+use crate::model::usage::ANONYMOUS_USER;
+use crate::utils;
+
+const DEFAULT_RELOAD_SECS: u64 = 30;
+
+/// The series a field of the template file seeds: a legacy field name or
+/// a series name.
+fn seed_key(name: &str) -> Option<TemplateKey> {
+    let distractor = |distractor_type| TemplateKey::distractor(distractor_type, Language::Arabic);
+    Some(match name {
+        "prompt_context_urdu" => TemplateKey::new(PromptTask::Context, Language::Urdu),
+        "prompt_quranic_verse" => TemplateKey::new(PromptTask::QuranicVerse, Language::Arabic),
+        "prompt_quranic_verse_distractor_collection" => distractor(DistractorType::Collection),
+        "prompt_quranic_verse_diacritic_distractor" => distractor(DistractorType::Diacritic),
+        "prompt_quranic_verse_phonetic_distractor" => distractor(DistractorType::Phonetic),
+        "prompt_quranic_verse_morfological_distractor" => distractor(DistractorType::Morphological),
+        "prompt_quranic_verse_grammatical_distractor" => distractor(DistractorType::Grammatical),
+        "prompt_quranic_verse_alternate_verse_distractor" => {
+            distractor(DistractorType::AlternateVerse)
+        }
+        "prompt_quranic_verse_thematic_distractor" => distractor(DistractorType::Thematic),
+        "prompt_quranic_verse_collocational_distractor" => {
+            distractor(DistractorType::Collocational)
+        }
+        _ => return name.parse().ok(),
+    })
+}
+
+/// Why a prompt could not be rendered.
+#[derive(Debug, Error)]
+pub enum PromptError {
+    #[error("No active prompt template for {0}")]
+    Missing(TemplateKey),
+    #[error("Failed to render the {0} prompt template: {1}")]
+    Render(String, minijinja::Error),
+}
+
+/// A running experiment with the template of each of its variants.
+struct RunningExperiment {
+    experiment: Experiment,
+    templates: Vec<PromptTemplate>,
+}
+
+impl RunningExperiment {
+    /// The variant of a user: a hash of the experiment name and the user ID
+    /// picks a point within the total weight.
+    fn assign(&self, user_id: &str) -> usize {
+        let variants = &self.experiment.variants;
+        let total: u64 = variants.iter().map(|v| v.weight.max(1) as u64).sum();
+        let point = if user_id == ANONYMOUS_USER {
+            rand::thread_rng().gen_range(0..total)
+        } else {
+            let digest = Sha256::digest(format!("{}:{}", self.experiment.name, user_id));
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&digest[..8]);
+            u64::from_be_bytes(bytes) % total
+        };
+
+        let mut reached = 0;
+        for (index, variant) in variants.iter().enumerate() {
+            reached += variant.weight.max(1) as u64;
+            if point < reached {
+                return index;
+            }
+        }
+        variants.len() - 1
+    }
+}
+
+/// What [`PromptStore::reload`] reads.
+struct Loaded {
+    templates: HashMap<TemplateKey, PromptTemplate>,
+    experiments: HashMap<TemplateKey, RunningExperiment>,
+    partials: HashMap<String, String>,
+    /// The partials, the active templates by series name and the variant
+    /// templates by version label.
+    env: Environment<'static>,
+}
+
+impl Loaded {
+    fn new(
+        templates: HashMap<TemplateKey, PromptTemplate>,
+        experiments: HashMap<TemplateKey, RunningExperiment>,
+        partials: HashMap<String, String>,
+    ) -> Self {
+        let mut env = utils::prompts::environment();
+        for (name, body) in &partials {
+            if let Err(e) = env.add_template_owned(name.clone(), body.clone()) {
+                warn!("Failed to load prompt partial {}: {:?}", name, e);
+            }
+        }
+        for (key, template) in &templates {
+            if let Err(e) = env.add_template_owned(key.to_string(), template.body.clone()) {
+                warn!("Failed to load prompt template {}: {:?}", template.label, e);
+            }
+        }
+        for template in experiments.values().flat_map(|running| &running.templates) {
+            if let Err(e) = env.add_template_owned(template.label.clone(), template.body.clone()) {
+                warn!("Failed to load prompt template {}: {:?}", template.label, e);
+            }
+        }
+        Self {
+            templates,
+            experiments,
+            partials,
+            env,
+        }
+    }
+}
+
+/// The active prompt templates, shared through `AppState`.
+#[derive(Clone)]
+pub struct PromptStore {
+    pool: PgPool,
+    loaded: Arc<RwLock<Arc<Loaded>>>,
+}
+
+impl PromptStore {
+    /// Seeds the series that have no active version from the legacy
+    /// template file, then loads the active versions.
+    pub async fn load(pool: PgPool) -> Result<Self> {
+        let store = Self {
+            pool,
+            loaded: Arc::new(RwLock::new(Arc::new(Loaded::new(
+                HashMap::new(),
+                HashMap::new(),
+                HashMap::new(),
+            )))),
+        };
+        store.reload().await?;
+        if store.seed().await? > 0 {
+            store.reload().await?;
+        }
+        Ok(store)
+    }
+
+    fn loaded(&self) -> Arc<Loaded> {
+        self.loaded
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    async fn seed(&self) -> Result<usize> {
+        let Ok(path) = utils::env::load_env_var("PROMPT_TEMPLATE_PATH") else {
+            return Ok(0);
+        };
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read prompt template file {}", path))?;
+        let templates: HashMap<String, String> =
+            serde_json::from_str(&content).context("Failed to parse prompt template JSON")?;
+
+        let mut seeded = 0;
+        for (name, body) in templates {
+            let Some(key) = seed_key(&name) else {
+                warn!("Ignoring unknown prompt template `{}`", name);
+                continue;
+            };
+            if self.template(&key).is_some() {
+                continue;
+            }
+            queries::prompts::insert_template(
+                &self.pool,
+                &key,
+                &utils::prompts::upgrade_legacy_placeholders(&body),
+                &[],
+                Some(&format!("Seeded from {}", name)),
+                true,
+            )
+            .await?;
+            seeded += 1;
+        }
+        if seeded > 0 {
+            info!("Seeded {} prompt templates from {}.", seeded, path);
+        }
+        Ok(seeded)
+    }
+
+    /// Reads the active versions, the running experiments and the partials
+    /// again.
+    pub async fn reload(&self) -> Result<()> {
+        let filter = PromptTemplateQuery {
+            active: true,
+            ..Default::default()
+        };
+        let templates = queries::prompts::list_templates(&self.pool, &filter)
+            .await?
+            .into_iter()
+            .map(|template| (template.key, template))
+            .collect();
+        let mut experiments = HashMap::new();
+        for experiment in queries::experiments::list_experiments(&self.pool, true).await? {
+            let mut templates = Vec::with_capacity(experiment.variants.len());
+            for variant in &experiment.variants {
+                let template = queries::prompts::get_template(&self.pool, variant.template_id)
+                    .await?
+                    .with_context(|| {
+                        format!("Prompt template {} not found", variant.template_id)
+                    })?;
+                templates.push(template);
+            }
+            if templates.is_empty() {
+                continue;
+            }
+            let running = RunningExperiment {
+                experiment,
+                templates,
+            };
+            experiments.insert(running.experiment.key, running);
+        }
+        let partials = queries::prompts::list_partials(&self.pool)
+            .await?
+            .into_iter()
+            .map(|partial| (partial.name, partial.body))
+            .collect();
+
+        let loaded = Arc::new(Loaded::new(templates, experiments, partials));
+        *self.loaded.write().unwrap_or_else(|e| e.into_inner()) = loaded;
+        Ok(())
+    }
+
+    /// Reloads every `PROMPT_RELOAD_SECS` seconds, logging failures.
+    pub fn spawn_reloader(&self) -> Result<JoinHandle<()>> {
+        let secs = match utils::env::load_env_var("PROMPT_RELOAD_SECS") {
+            Ok(value) => value
+                .trim()
+                .parse()
+                .with_context(|| format!("Invalid PROMPT_RELOAD_SECS `{}`", value))?,
+            Err(_) => DEFAULT_RELOAD_SECS,
+        };
+        let store = self.clone();
+        Ok(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(secs.max(1)));
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if let Err(e) = store.reload().await {
+                    warn!("Failed to reload prompt templates: {:?}", e);
+                }
+            }
+        }))
+    }
+
+    /// The active version of a series.
+    pub fn template(&self, key: &TemplateKey) -> Option<PromptTemplate> {
+        self.loaded().templates.get(key).cloned()
+    }
+
+    /// The languages a series has a template in, active or in a running
+    /// experiment, with `key.language` ignored.
+    pub fn languages(&self, key: &TemplateKey) -> Vec<Language> {
+        let loaded = self.loaded();
+        Language::ALL
+            .into_iter()
+            .filter(|&language| {
+                let key = TemplateKey { language, ..*key };
+                loaded.templates.contains_key(&key) || loaded.experiments.contains_key(&key)
+            })
+            .collect()
+    }
+
+    /// Renders the active version of a series, or the variant `user_id` is
+    /// assigned to while an experiment runs on it.
+    ///
+    /// # Example (non-runnable)
+    /// ```ignore
+    /// let key = TemplateKey::distractor(DistractorType::Thematic, Language::Arabic);
+    /// let prompt = prompts.render(&key, &PromptContext::new(question, answer), "user-1")?;
+    /// ```
+    pub fn render(
+        &self,
+        key: &TemplateKey,
+        context: &PromptContext,
+        user_id: &str,
+    ) -> Result<RenderedPrompt, PromptError> {
+        let loaded = self.loaded();
+        if let Some(running) = loaded.experiments.get(key) {
+            let index = running.assign(user_id);
+            let (variant, template) = (
+                &running.experiment.variants[index],
+                &running.templates[index],
+            );
+            let text = utils::prompts::render_named(
+                &loaded.env,
+                &template.label,
+                context,
+                &template.examples,
+            )
+            .map_err(|e| PromptError::Render(template.label.clone(), e))?;
+            return Ok(RenderedPrompt {
+                text,
+                version: template.label.clone(),
+                variant: Some(VariantAssignment {
+                    experiment_id: running.experiment.id,
+                    experiment: running.experiment.name.clone(),
+                    variant_id: variant.id,
+                    variant: variant.name.clone(),
+                }),
+            });
+        }
+
+        let template = loaded
+            .templates
+            .get(key)
+            .ok_or(PromptError::Missing(*key))?;
+        let text = utils::prompts::render(&loaded.env, key, context, &template.examples)
+            .map_err(|e| PromptError::Render(template.label.clone(), e))?;
+        Ok(RenderedPrompt {
+            text,
+            version: template.label.clone(),
+            variant: None,
+        })
+    }
+
+    /// Checks a new template version against the loaded partials, see
+    /// `utils::prompts::validate_template`.
+    pub fn validate(
+        &self,
+        key: &TemplateKey,
+        body: &str,
+        examples: &[PromptExample],
+    ) -> Result<(), String> {
+        utils::prompts::validate_template(&self.loaded().env, key, body, examples)
+    }
+
+    /// Checks that every active or variant template still renders once the
+    /// partial `name` is replaced by `body`, or removed with `None`.
+    pub fn validate_partial(&self, name: &str, body: Option<&str>) -> Result<(), String> {
+        let loaded = self.loaded();
+        let mut partials = loaded.partials.clone();
+        match body {
+            Some(body) => partials.insert(name.to_string(), body.to_string()),
+            None => partials.remove(name),
+        };
+
+        let mut env = utils::prompts::environment();
+        for (name, body) in partials {
+            env.add_template_owned(name, body)
+                .map_err(|e| format!("Invalid partial: {}", e))?;
+        }
+        let variants = loaded
+            .experiments
+            .values()
+            .flat_map(|running| &running.templates);
+        for template in loaded.templates.values().chain(variants) {
+            utils::prompts::validate_template(
+                &env,
+                &template.key,
+                &template.body,
+                &template.examples,
+            )
+            .map_err(|e| format!("{} would break: {}", template.label, e))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,518 @@
+//! Conversion between exams and IMS QTI 3.0 content packages.
+//!
+//! A package is a zip archive holding an `imsmanifest.xml`, one assessment
+//! test and one assessment item per question. Every exam section becomes a
+//! test part with a single assessment section, and options flagged
+//! `is_correct` make up the item's correct response.
+
+use crate::model::exam::{ExamRequest, ExamResponse};
+use crate::model::option::OptionRequestModel;
+use crate::model::question::{QuestionRequest, QuestionResponse};
+use crate::model::section::SectionRequest;
+use crate::utils::arabic::contains_rtl;
+use crate::utils::archive::LimitedArchive;
+use crate::utils::xml::{self, escape, XmlElement};
+use anyhow::{anyhow, bail, Context, Result};
+use std::io::{Cursor, Write};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const QTI_NAMESPACE: &str = "http://www.imsglobal.org/xsd/imsqtiasi_v3p0";
+const CP_NAMESPACE: &str = "http://www.imsglobal.org/xsd/qti/qtiv3p0/imscp_v1p1";
+const LOM_NAMESPACE: &str = "http://ltsc.ieee.org/xsd/LOM";
+const MATCH_CORRECT: &str =
+    "https://purl.imsglobal.org/spec/qti/v3p0/rptemplates/match_correct.xml";
+
+const MANIFEST_PATH: &str = "imsmanifest.xml";
+const TEST_PATH: &str = "assessment-test.xml";
+const TEST_RESOURCE_TYPE: &str = "imsqti_test_xmlv3p0";
+const ITEM_RESOURCE_TYPE: &str = "imsqti_item_xmlv3p0";
+
+const PASSING_SCORE: &str = "PASSING_SCORE";
+const MAX_SCORE: &str = "MAXSCORE";
+const RESPONSE: &str = "RESPONSE";
+
+fn item_identifier(question_id: i32) -> String {
+    format!("item-{}", question_id)
+}
+
+fn item_path(question_id: i32) -> String {
+    format!("items/{}.xml", item_identifier(question_id))
+}
+
+fn choice_identifier(option_id: i32) -> String {
+    format!("choice-{}", option_id)
+}
+
+/// `dir` attribute for elements holding `text`, so RTL content renders correctly.
+fn dir_attribute(text: &str) -> &'static str {
+    if contains_rtl(text) {
+        r#" dir="rtl""#
+    } else {
+        ""
+    }
+}
+
+fn outcome_declaration(identifier: &str, default: Option<String>) -> String {
+    match default {
+        Some(value) => format!(
+            r#"  <qti-outcome-declaration identifier="{}" cardinality="single" base-type="float">
+    <qti-default-value><qti-value>{}</qti-value></qti-default-value>
+  </qti-outcome-declaration>
+"#,
+            identifier, value
+        ),
+        None => format!(
+            r#"  <qti-outcome-declaration identifier="{}" cardinality="single" base-type="float"/>
+"#,
+            identifier
+        ),
+    }
+}
+
+fn render_item(index: usize, question: &QuestionResponse) -> String {
+    let correct: Vec<String> = question
+        .options
+        .iter()
+        .filter(|o| o.base.is_correct.unwrap_or_default())
+        .map(|o| choice_identifier(o.base.id))
+        .collect();
+
+    let (cardinality, max_choices) = if correct.len() > 1 {
+        ("multiple", 0)
+    } else {
+        ("single", 1)
+    };
+
+    let mut xml = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<qti-assessment-item xmlns="{}" identifier="{}" title="Question {}" adaptive="false" time-dependent="false">
+  <qti-response-declaration identifier="{}" cardinality="{}" base-type="identifier">
+"#,
+        QTI_NAMESPACE,
+        item_identifier(question.base.id),
+        index + 1,
+        RESPONSE,
+        cardinality
+    );
+
+    if !correct.is_empty() {
+        xml.push_str("    <qti-correct-response>\n");
+        for value in &correct {
+            xml.push_str(&format!("      <qti-value>{}</qti-value>\n", value));
+        }
+        xml.push_str("    </qti-correct-response>\n");
+    }
+    xml.push_str("  </qti-response-declaration>\n");
+
+    xml.push_str(&outcome_declaration("SCORE", Some("0".to_string())));
+    xml.push_str(&outcome_declaration(
+        MAX_SCORE,
+        Some(question.base.marks.to_string()),
+    ));
+
+    xml.push_str(&format!(
+        r#"  <qti-item-body{}>
+    <p>{}</p>
+    <qti-choice-interaction response-identifier="{}" shuffle="false" max-choices="{}">
+"#,
+        dir_attribute(&question.base.text),
+        escape(&question.base.text),
+        RESPONSE,
+        max_choices
+    ));
+
+    if let Some(description) = question
+        .base
+        .description
+        .as_deref()
+        .filter(|d| !d.is_empty())
+    {
+        xml.push_str(&format!(
+            "      <qti-prompt{}>{}</qti-prompt>\n",
+            dir_attribute(description),
+            escape(description)
+        ));
+    }
+
+    for option in &question.options {
+        xml.push_str(&format!(
+            "      <qti-simple-choice identifier=\"{}\"{}>{}</qti-simple-choice>\n",
+            choice_identifier(option.base.id),
+            dir_attribute(&option.base.text),
+            escape(&option.base.text)
+        ));
+    }
+
+    xml.push_str(&format!(
+        r#"    </qti-choice-interaction>
+  </qti-item-body>
+  <qti-response-processing template="{}"/>
+</qti-assessment-item>
+"#,
+        MATCH_CORRECT
+    ));
+
+    xml
+}
+
+fn render_test(exam: &ExamResponse) -> String {
+    let description = &exam.description;
+
+    let mut xml = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<qti-assessment-test xmlns="{}" identifier="exam-{}" title="{}">
+"#,
+        QTI_NAMESPACE,
+        exam.exam_id.id,
+        escape(&description.title)
+    );
+
+    xml.push_str(&outcome_declaration("SCORE", None));
+    xml.push_str(&outcome_declaration(
+        PASSING_SCORE,
+        Some(description.passing_score.to_string()),
+    ));
+    xml.push_str(&format!(
+        "  <qti-time-limits max-time=\"{}\"/>\n",
+        i64::from(description.duration) * 60
+    ));
+
+    for section in &exam.sections {
+        xml.push_str(&format!(
+            r#"  <qti-test-part identifier="part-{id}" navigation-mode="linear" submission-mode="individual">
+    <qti-assessment-section identifier="section-{id}" title="{title}" visible="true">
+"#,
+            id = section.base.id,
+            title = escape(&section.base.title)
+        ));
+
+        for question in &section.questions {
+            xml.push_str(&format!(
+                "      <qti-assessment-item-ref identifier=\"{}\" href=\"{}\"/>\n",
+                item_identifier(question.base.id),
+                item_path(question.base.id)
+            ));
+        }
+
+        xml.push_str("    </qti-assessment-section>\n  </qti-test-part>\n");
+    }
+
+    xml.push_str("</qti-assessment-test>\n");
+    xml
+}
+
+fn render_manifest(exam: &ExamResponse) -> String {
+    let description = &exam.description;
+    let questions: Vec<&QuestionResponse> =
+        exam.sections.iter().flat_map(|s| &s.questions).collect();
+
+    let mut xml = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<manifest xmlns="{}" xmlns:imsmd="{}" identifier="manifest-exam-{}">
+  <metadata>
+    <schema>QTI Package</schema>
+    <schemaversion>3.0.0</schemaversion>
+    <imsmd:lom>
+      <imsmd:general>
+        <imsmd:title><imsmd:string>{}</imsmd:string></imsmd:title>
+        <imsmd:description><imsmd:string>{}</imsmd:string></imsmd:description>
+      </imsmd:general>
+    </imsmd:lom>
+  </metadata>
+  <organizations/>
+  <resources>
+    <resource identifier="exam-{}" type="{}" href="{}">
+      <file href="{}"/>
+"#,
+        CP_NAMESPACE,
+        LOM_NAMESPACE,
+        exam.exam_id.id,
+        escape(&description.title),
+        escape(&description.description),
+        exam.exam_id.id,
+        TEST_RESOURCE_TYPE,
+        TEST_PATH,
+        TEST_PATH
+    );
+
+    for question in &questions {
+        xml.push_str(&format!(
+            "      <dependency identifierref=\"{}\"/>\n",
+            item_identifier(question.base.id)
+        ));
+    }
+    xml.push_str("    </resource>\n");
+
+    for question in &questions {
+        xml.push_str(&format!(
+            r#"    <resource identifier="{id}" type="{}" href="{path}">
+      <file href="{path}"/>
+    </resource>
+"#,
+            ITEM_RESOURCE_TYPE,
+            id = item_identifier(question.base.id),
+            path = item_path(question.base.id)
+        ));
+    }
+
+    xml.push_str("  </resources>\n</manifest>\n");
+    xml
+}
+
+/// Builds a QTI 3.0 content package (zip) for an exam.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let exam = read_exam_data(&pool, 1).await?;
+/// let zip_bytes = export_package(&exam)?;
+/// ```
+pub fn export_package(exam: &ExamResponse) -> Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let mut files = vec![
+        (MANIFEST_PATH.to_string(), render_manifest(exam)),
+        (TEST_PATH.to_string(), render_test(exam)),
+    ];
+    for (index, question) in exam.sections.iter().flat_map(|s| &s.questions).enumerate() {
+        files.push((item_path(question.base.id), render_item(index, question)));
+    }
+
+    for (path, content) in files {
+        zip.start_file(path.as_str(), options)
+            .with_context(|| format!("Failed to add {} to QTI package", path))?;
+        zip.write_all(content.as_bytes())
+            .with_context(|| format!("Failed to write {} to QTI package", path))?;
+    }
+
+    let cursor = zip.finish().context("Failed to finish QTI package")?;
+    Ok(cursor.into_inner())
+}
+
+struct Package {
+    archive: LimitedArchive,
+}
+
+impl Package {
+    fn open(bytes: &[u8]) -> Result<Self> {
+        let archive =
+            LimitedArchive::new(bytes).context("QTI package is not a valid zip archive")?;
+        Ok(Self { archive })
+    }
+
+    fn read_xml(&mut self, path: &str) -> Result<XmlElement> {
+        let content = self.archive.read_string(path)?;
+        xml::parse(&content).with_context(|| format!("Failed to parse {}", path))
+    }
+
+    fn file_names(&self) -> Vec<String> {
+        self.archive.file_names()
+    }
+}
+
+/// Resolves `href` relative to the directory of the file at `base`.
+fn resolve_href(base: &str, href: &str) -> String {
+    let mut parts: Vec<&str> = base.split('/').collect();
+    parts.pop();
+
+    for segment in href.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            segment => parts.push(segment),
+        }
+    }
+
+    parts.join("/")
+}
+
+struct ManifestInfo {
+    test_path: String,
+    title: Option<String>,
+    description: Option<String>,
+}
+
+fn lom_string(general: &XmlElement, field: &str) -> Option<String> {
+    general
+        .child(field)
+        .and_then(|f| f.find("string"))
+        .map(|s| s.text().trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+fn read_manifest(package: &mut Package) -> Result<ManifestInfo> {
+    let manifest = package.read_xml(MANIFEST_PATH).ok();
+
+    let test_path = manifest
+        .as_ref()
+        .and_then(|m| {
+            m.find_all("resource")
+                .into_iter()
+                .find(|r| {
+                    r.attr("type")
+                        .is_some_and(|t| t.starts_with(TEST_RESOURCE_TYPE))
+                })
+                .and_then(|r| r.attr("href"))
+                .map(|href| resolve_href(MANIFEST_PATH, href))
+        })
+        .or_else(|| {
+            package.file_names().into_iter().find(|name| {
+                name.ends_with(".xml")
+                    && package
+                        .read_xml(name)
+                        .is_ok_and(|root| root.name == "qti-assessment-test")
+            })
+        })
+        .ok_or_else(|| anyhow!("QTI package contains no assessment test"))?;
+
+    let general = manifest.as_ref().and_then(|m| m.find("general"));
+
+    Ok(ManifestInfo {
+        test_path,
+        title: general.and_then(|g| lom_string(g, "title")),
+        description: general.and_then(|g| lom_string(g, "description")),
+    })
+}
+
+fn default_value(root: &XmlElement, identifier: &str) -> Option<f64> {
+    root.children_named("qti-outcome-declaration")
+        .find(|d| d.attr("identifier") == Some(identifier))
+        .and_then(|d| d.find("qti-value"))
+        .and_then(|v| v.text().trim().parse().ok())
+}
+
+fn read_item(item: &XmlElement, path: &str) -> Result<QuestionRequest> {
+    let interaction_id = |i: &XmlElement| {
+        i.attr("response-identifier")
+            .unwrap_or(RESPONSE)
+            .to_string()
+    };
+
+    let body = item
+        .child("qti-item-body")
+        .ok_or_else(|| anyhow!("{} has no qti-item-body", path))?;
+    let interaction = body.find("qti-choice-interaction").ok_or_else(|| {
+        anyhow!(
+            "{} has no choice interaction; only choice items are supported",
+            path
+        )
+    })?;
+
+    let correct: Vec<String> = item
+        .children_named("qti-response-declaration")
+        .find(|d| d.attr("identifier") == Some(interaction_id(interaction).as_str()))
+        .and_then(|d| d.child("qti-correct-response"))
+        .map(|c| {
+            c.children_named("qti-value")
+                .map(|v| v.text().trim().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let text = body
+        .elements()
+        .filter(|e| {
+            e.name != "qti-choice-interaction" && e.find("qti-choice-interaction").is_none()
+        })
+        .map(|e| e.text().trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if text.is_empty() {
+        bail!("{} has no question text", path);
+    }
+
+    let description = interaction
+        .child("qti-prompt")
+        .map(|p| p.text().trim().to_string())
+        .filter(|p| !p.is_empty());
+
+    let options = interaction
+        .find_all("qti-simple-choice")
+        .into_iter()
+        .map(|choice| {
+            let is_correct = choice
+                .attr("identifier")
+                .is_some_and(|id| correct.iter().any(|c| c == id));
+            OptionRequestModel::new(choice.text().trim().to_string(), is_correct)
+        })
+        .collect();
+
+    let marks = default_value(item, MAX_SCORE)
+        .map(|m| m.round() as i32)
+        .unwrap_or(1);
+
+    Ok(QuestionRequest::new(text, description, marks, options))
+}
+
+/// Reads a QTI 3.0 content package into an `ExamRequest`.
+///
+/// IDs in the returned exam are placeholders; assign them with
+/// `database::queries::ids::assign_exam_ids` before inserting.
+///
+/// # Errors
+/// Returns an error if the archive or any XML document is malformed, or if an
+/// item uses an interaction other than choice.
+pub fn import_package(bytes: &[u8]) -> Result<ExamRequest> {
+    let mut package = Package::open(bytes)?;
+    let manifest = read_manifest(&mut package)?;
+    let test = package.read_xml(&manifest.test_path)?;
+
+    if test.name != "qti-assessment-test" {
+        bail!("{} is not a qti-assessment-test", manifest.test_path);
+    }
+
+    let mut sections = Vec::new();
+    for (index, part) in test.children_named("qti-test-part").enumerate() {
+        for section in part.find_all("qti-assessment-section") {
+            let mut questions = Vec::new();
+            for item_ref in section.children_named("qti-assessment-item-ref") {
+                let href = item_ref.attr("href").ok_or_else(|| {
+                    anyhow!("Item reference without href in {}", manifest.test_path)
+                })?;
+                let path = resolve_href(&manifest.test_path, href);
+                let item = package.read_xml(&path)?;
+                questions.push(read_item(&item, &path)?);
+            }
+
+            if questions.is_empty() {
+                continue;
+            }
+
+            let title = section
+                .attr("title")
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("Section {}", index + 1));
+            sections.push(SectionRequest::new(title, questions));
+        }
+    }
+
+    let title = test
+        .attr("title")
+        .map(str::to_string)
+        .or(manifest.title)
+        .unwrap_or_else(|| "Imported exam".to_string());
+
+    let duration = test
+        .child("qti-time-limits")
+        .and_then(|t| t.attr("max-time"))
+        .and_then(|t| t.parse::<f64>().ok())
+        .map(|seconds| (seconds / 60.0).ceil() as i32)
+        .unwrap_or_default();
+
+    let passing_score = default_value(&test, PASSING_SCORE)
+        .map(|s| s.round() as i32)
+        .unwrap_or_default();
+
+    Ok(ExamRequest::new(
+        title,
+        manifest.description,
+        duration,
+        passing_score,
+        sections,
+    ))
+}
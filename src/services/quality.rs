@@ -0,0 +1,192 @@
+//! The quality gate generated distractors pass before they are returned.
+//!
+//! Every distractor must be non-empty, differ from the answer and from the
+//! distractors before it (ignoring whitespace), keep to the answer's script
+//! and have as many words as the answer. Some types add their own rule:
+//!
+//! | Type            | Rule                                                   |
+//! |-----------------|--------------------------------------------------------|
+//! | Diacritic       | Same letters as the answer once tashkeel is stripped   |
+//! | Phonetic        | 1 to [`MAX_PHONETIC_EDITS`] letter edits from the answer |
+//! | Morphological   | At most [`MAX_MORPHOLOGICAL_EDITS`] letter edits away  |
+//!
+//! Folding of letter variants follows `services::distractors::Folding`, so a
+//! grammatical distractor may differ from the answer in its case ending only.
+
+use serde_json::{Map, Value};
+
+use crate::model::llm::{DistractorType, RejectedDistractor};
+use crate::services::distractors::{dedup_key, Folding};
+use crate::utils::arabic::{contains_rtl, fold_for_search};
+
+/// Distractors each list needs unless the request asks otherwise.
+pub const DEFAULT_MIN_DISTRACTORS: usize = 3;
+
+/// Extra requests made when too few distractors pass.
+pub const REGENERATION_BUDGET: u32 = 2;
+
+/// Most letter edits between a phonetic distractor and the answer.
+pub const MAX_PHONETIC_EDITS: usize = 2;
+
+/// Most letter edits between a morphological distractor and the answer.
+pub const MAX_MORPHOLOGICAL_EDITS: usize = 3;
+
+/// Field holding the answer rather than distractors.
+const ANSWER_FIELD: &str = "correct_answer";
+
+/// The distractor type whose rules apply to a list of a response, or `None`
+/// for the generic `distractors` list.
+pub fn distractor_type_of(field: &str) -> Option<DistractorType> {
+    match field {
+        "diacritic_distractors" => Some(DistractorType::Diacritic),
+        "phonetic_orthographic_distractors" => Some(DistractorType::Phonetic),
+        "morphological_distractors" => Some(DistractorType::Morphological),
+        "grammatical_distractors" => Some(DistractorType::Grammatical),
+        "alternative_verse_distractors" => Some(DistractorType::AlternateVerse),
+        "thematic_distractors" => Some(DistractorType::Thematic),
+        "collocational_distractors" => Some(DistractorType::Collocational),
+        _ => None,
+    }
+}
+
+/// The comparison key of a distractor: folded for its list, without spaces.
+fn compact_key(text: &str, folding: Folding) -> String {
+    dedup_key(text, folding).replace(' ', "")
+}
+
+/// Letters only: tashkeel, tatweel and whitespace dropped, variants kept.
+fn bare_letters(text: &str) -> Vec<char> {
+    text.chars()
+        .filter(|c| !c.is_whitespace() && fold_for_search(*c).is_some())
+        .collect()
+}
+
+/// Levenshtein distance between two letter sequences.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Which scripts the letters of `text` use: (right-to-left, other).
+fn scripts(text: &str) -> (bool, bool) {
+    text.chars()
+        .filter(|c| c.is_alphabetic())
+        .fold((false, false), |(rtl, other), c| {
+            if contains_rtl(c.encode_utf8(&mut [0; 4])) {
+                (true, other)
+            } else {
+                (rtl, true)
+            }
+        })
+}
+
+/// Checks one distractor of the list `field` against the answer and the
+/// distractors already accepted, returning why it fails.
+pub fn check_distractor(
+    text: &str,
+    answer: &str,
+    field: &str,
+    accepted: &[String],
+) -> Result<(), String> {
+    let folding = Folding::for_field(field);
+    let key = compact_key(text, folding);
+
+    if key.is_empty() {
+        return Err("is empty".to_string());
+    }
+    if key == compact_key(answer, folding) {
+        return Err("equals the correct answer".to_string());
+    }
+    if accepted.iter().any(|a| compact_key(a, folding) == key) {
+        return Err("repeats another distractor".to_string());
+    }
+
+    let (rtl, other) = scripts(text);
+    if rtl && other {
+        return Err("mixes scripts".to_string());
+    }
+    let (answer_rtl, answer_other) = scripts(answer);
+    if (rtl, other) != (answer_rtl, answer_other) {
+        return Err("is not in the script of the answer".to_string());
+    }
+
+    let words = text.split_whitespace().count();
+    let answer_words = answer.split_whitespace().count();
+    if words != answer_words {
+        return Err(format!(
+            "has {} words where the answer has {}",
+            words, answer_words
+        ));
+    }
+
+    let edits = || edit_distance(&bare_letters(text), &bare_letters(answer));
+    match distractor_type_of(field) {
+        Some(DistractorType::Diacritic) if edits() != 0 => {
+            Err("differs from the answer in more than tashkeel".to_string())
+        }
+        Some(DistractorType::Phonetic) => match edits() {
+            0 => Err("differs from the answer only in tashkeel".to_string()),
+            n if n > MAX_PHONETIC_EDITS => Err(format!(
+                "is {} letter edits from the answer, at most {} are allowed",
+                n, MAX_PHONETIC_EDITS
+            )),
+            _ => Ok(()),
+        },
+        Some(DistractorType::Morphological) if edits() > MAX_MORPHOLOGICAL_EDITS => Err(format!(
+            "is {} letter edits from the answer, at most {} are allowed",
+            edits(),
+            MAX_MORPHOLOGICAL_EDITS
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Drops the distractors of a parsed response that fail the gate, returning
+/// what was dropped and why.
+pub fn apply_gate(response: &mut Map<String, Value>, answer: &str) -> Vec<RejectedDistractor> {
+    let mut rejected = Vec::new();
+    for (field, value) in response.iter_mut() {
+        let Value::Array(items) = value else {
+            continue;
+        };
+        if field == ANSWER_FIELD {
+            continue;
+        }
+
+        let mut accepted: Vec<String> = Vec::new();
+        for item in items.iter() {
+            let text = item.as_str().unwrap_or_default();
+            match check_distractor(text, answer, field, &accepted) {
+                Ok(()) => accepted.push(text.trim().to_string()),
+                Err(reason) => rejected.push(RejectedDistractor {
+                    field: field.clone(),
+                    text: text.to_string(),
+                    reason,
+                }),
+            }
+        }
+        *items = accepted.into_iter().map(Value::from).collect();
+    }
+    rejected
+}
+
+/// Distractor lists of a response with fewer than `min` items, with how many
+/// more each needs.
+pub fn shortfall(response: &Map<String, Value>, min: usize) -> Vec<(String, usize)> {
+    response
+        .iter()
+        .filter(|(field, _)| *field != ANSWER_FIELD)
+        .filter_map(|(field, value)| {
+            let count = value.as_array()?.len();
+            (count < min).then(|| (field.clone(), min - count))
+        })
+        .collect()
+}
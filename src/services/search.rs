@@ -0,0 +1,200 @@
+//! Ranking and highlighting of question search results.
+//!
+//! Matching happens in the database on folded text (see
+//! `utils::arabic::fold_for_search`). Here the hits are grouped per question
+//! and the matched words are located again in the original text, so the
+//! snippets keep their tashkeel.
+
+use std::collections::HashSet;
+use std::ops::Range;
+
+use crate::database::schema::SearchRow;
+use crate::model::search::{SearchHit, SearchMatch};
+use crate::utils::arabic::fold_for_search;
+use crate::utils::html::escape;
+
+/// Longest snippet, in characters, before the text is cut around the match.
+pub const SNIPPET_CHARS: usize = 160;
+
+/// Characters of context kept before the first match in a cut snippet.
+const SNIPPET_LEAD: usize = 50;
+
+/// Smallest trigram similarity for a word to be highlighted as a near match.
+const FUZZY_HIGHLIGHT: f32 = 0.4;
+
+/// A folded character with the bytes of the original text it came from.
+/// Dropped marks are counted with the letter before them.
+struct Folded {
+    c: char,
+    source: Range<usize>,
+}
+
+fn fold_with_sources(text: &str) -> Vec<Folded> {
+    let mut folded: Vec<Folded> = Vec::new();
+    for (start, c) in text.char_indices() {
+        let end = start + c.len_utf8();
+        match fold_for_search(c) {
+            Some(f) => folded.extend(f.to_lowercase().map(|c| Folded {
+                c,
+                source: start..end,
+            })),
+            None => {
+                if let Some(last) = folded.last_mut() {
+                    last.source.end = end;
+                }
+            }
+        }
+    }
+    folded
+}
+
+fn trigrams(word: &str) -> HashSet<[char; 3]> {
+    let padded: Vec<char> = "  "
+        .chars()
+        .chain(word.chars())
+        .chain(" ".chars())
+        .collect();
+    padded.windows(3).map(|w| [w[0], w[1], w[2]]).collect()
+}
+
+/// Trigram similarity of two words, computed like `pg_trgm`'s `similarity`.
+fn similarity(a: &str, b: &str) -> f32 {
+    let a = trigrams(a);
+    let b = trigrams(b);
+    let common = a.intersection(&b).count();
+    let total = a.len() + b.len() - common;
+    if total == 0 {
+        0.0
+    } else {
+        common as f32 / total as f32
+    }
+}
+
+/// Byte ranges of `text` to highlight for `term`: every occurrence of the
+/// folded term, or failing that the words similar to one of its words.
+fn highlights(text: &str, term: &str) -> Vec<Range<usize>> {
+    let folded = fold_with_sources(text);
+    let needle: Vec<char> = fold_with_sources(term).into_iter().map(|f| f.c).collect();
+    if needle.is_empty() {
+        return Vec::new();
+    }
+
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i + needle.len() <= folded.len() {
+        if folded[i..i + needle.len()]
+            .iter()
+            .zip(&needle)
+            .all(|(f, n)| f.c == *n)
+        {
+            ranges.push(folded[i].source.start..folded[i + needle.len() - 1].source.end);
+            i += needle.len();
+        } else {
+            i += 1;
+        }
+    }
+    if !ranges.is_empty() {
+        return ranges;
+    }
+
+    let needle: String = needle.into_iter().collect();
+    let term_words: Vec<&str> = needle.split_whitespace().collect();
+    for word in folded
+        .split(|f| f.c.is_whitespace())
+        .filter(|w| !w.is_empty())
+    {
+        let text: String = word.iter().map(|f| f.c).collect();
+        if term_words
+            .iter()
+            .any(|t| similarity(t, &text) >= FUZZY_HIGHLIGHT)
+        {
+            ranges.push(word[0].source.start..word[word.len() - 1].source.end);
+        }
+    }
+    ranges
+}
+
+/// Byte offset of the `n`th character of `text`, or its length.
+fn char_offset(text: &str, n: usize) -> usize {
+    text.char_indices()
+        .nth(n)
+        .map(|(i, _)| i)
+        .unwrap_or(text.len())
+}
+
+/// Builds an HTML snippet of `text` with the matches of `term` wrapped in
+/// `<mark>`. Long texts are cut to about [`SNIPPET_CHARS`] characters around
+/// the first match.
+pub fn snippet(text: &str, term: &str) -> String {
+    let ranges = highlights(text, term);
+
+    let mut window = 0..text.len();
+    if text.chars().count() > SNIPPET_CHARS {
+        let first = ranges.first().map(|r| r.start).unwrap_or_default();
+        let lead = text[..first].chars().count().saturating_sub(SNIPPET_LEAD);
+        // Start at a word boundary unless that loses most of the lead.
+        let mut start = char_offset(text, lead);
+        if lead > 0 {
+            if let Some(space) = text[start..first].find(char::is_whitespace) {
+                start += space + 1;
+            }
+        }
+        let end = start + char_offset(&text[start..], SNIPPET_CHARS);
+        window = start..end;
+    }
+
+    let mut html = String::new();
+    if window.start > 0 {
+        html.push('…');
+    }
+    let mut at = window.start;
+    for range in &ranges {
+        let start = range.start.max(at);
+        let end = range.end.min(window.end);
+        if start >= end {
+            continue;
+        }
+        html.push_str(&escape(&text[at..start]));
+        html.push_str("<mark>");
+        html.push_str(&escape(&text[start..end]));
+        html.push_str("</mark>");
+        at = end;
+    }
+    html.push_str(&escape(&text[at..window.end]));
+    if window.end < text.len() {
+        html.push('…');
+    }
+    html
+}
+
+/// Groups the matching fields by question, best question first. Usage is
+/// left empty for the caller to fill in.
+pub fn rank(rows: Vec<SearchRow>, term: &str) -> Vec<SearchHit> {
+    let mut hits: Vec<SearchHit> = Vec::new();
+    for row in rows {
+        let found = SearchMatch {
+            field: row.field,
+            option_id: row.option_id,
+            snippet: snippet(&row.content, term),
+        };
+        match hits.last_mut() {
+            Some(hit) if hit.question_id == row.question_id => {
+                hit.score = hit.score.max(row.score);
+                hit.matches.push(found);
+            }
+            _ => hits.push(SearchHit {
+                question_id: row.question_id,
+                score: row.score,
+                matches: vec![found],
+                used_in: Vec::new(),
+            }),
+        }
+    }
+
+    hits.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(a.question_id.cmp(&b.question_id))
+    });
+    hits
+}
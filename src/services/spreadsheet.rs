@@ -0,0 +1,407 @@
+//! Reading exam questions from CSV and XLSX spreadsheets.
+//!
+//! The first non-empty row is a header. Each following row is one question:
+//!
+//! | Column                      | Content                                         |
+//! |-----------------------------|-------------------------------------------------|
+//! | `section`                   | Section title; blank cells repeat the one above |
+//! | `question`                  | Question text (required)                        |
+//! | `description`               | Optional question description                   |
+//! | `marks`                     | Positive whole number, 1 when blank             |
+//! | `option a`, `option 2`, ... | Any number of option columns, in order          |
+//! | `correct`                   | Correct options as letters, numbers or text     |
+//!
+//! Instead of a `correct` column, correct options can be marked by starting
+//! the option text with `*`.
+
+use std::io::Cursor;
+
+use crate::model::bulk::{RowError, SpreadsheetFormat};
+use crate::model::option::OptionRequestModel;
+use crate::model::question::QuestionRequest;
+use crate::model::section::SectionRequest;
+use crate::services::interchange::{SectionCollector, DEFAULT_SECTION_TITLE};
+use crate::utils::archive::LimitedArchive;
+use anyhow::{Context, Result};
+use calamine::{Reader, Xlsx};
+
+/// One row of a sheet with its 1-based row number.
+pub struct Row {
+    pub number: usize,
+    pub cells: Vec<String>,
+}
+
+impl Row {
+    fn cell(&self, index: usize) -> &str {
+        self.cells.get(index).map(|c| c.trim()).unwrap_or_default()
+    }
+
+    fn is_blank(&self) -> bool {
+        self.cells.iter().all(|c| c.trim().is_empty())
+    }
+}
+
+/// Picks the delimiter used most often in the first line: spreadsheet
+/// programs in many locales save CSV with semicolons.
+fn detect_delimiter(text: &str) -> u8 {
+    let first_line = text.lines().next().unwrap_or_default();
+    [b',', b';', b'\t']
+        .into_iter()
+        .max_by_key(|d| first_line.matches(*d as char).count())
+        .unwrap_or(b',')
+}
+
+fn read_csv(bytes: &[u8]) -> Result<Vec<Row>> {
+    let text = std::str::from_utf8(bytes).context("CSV file is not valid UTF-8")?;
+    let text = text.trim_start_matches('\u{FEFF}');
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(detect_delimiter(text))
+        .from_reader(text.as_bytes());
+
+    let mut rows = Vec::new();
+    // Line breaks before `counted`, kept between records so the text is
+    // scanned once.
+    let (mut counted, mut line_breaks) = (0, 0);
+    for record in reader.records() {
+        let record = record.context("Malformed CSV")?;
+        // The reader skips blank lines without counting them, and reports
+        // the offset of the first skipped line, so the row number is worked
+        // out from the text instead.
+        let offset = record
+            .position()
+            .map(|p| p.byte() as usize)
+            .unwrap_or_default();
+        let start = text.len() - text[offset..].trim_start_matches(['\r', '\n']).len();
+        line_breaks += text[counted..start].matches('\n').count();
+        counted = start;
+        let number = line_breaks + 1;
+        rows.push(Row {
+            number,
+            cells: record.iter().map(str::to_string).collect(),
+        });
+    }
+    Ok(rows)
+}
+
+fn read_xlsx(bytes: &[u8]) -> Result<Vec<Row>> {
+    // An XLSX file is a zip archive; calamine decompresses its parts
+    // without a limit.
+    LimitedArchive::new(bytes)
+        .and_then(|mut archive| archive.check_all())
+        .context("Not a valid XLSX file")?;
+    let mut workbook = Xlsx::new(Cursor::new(bytes)).context("Not a valid XLSX file")?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .context("Workbook has no sheets")?
+        .context("Failed to read the first sheet")?;
+
+    let first_row = range
+        .start()
+        .map(|(row, _)| row as usize)
+        .unwrap_or_default();
+    Ok(range
+        .rows()
+        .enumerate()
+        .map(|(index, cells)| Row {
+            number: first_row + index + 1,
+            cells: cells.iter().map(|c| c.to_string()).collect(),
+        })
+        .collect())
+}
+
+/// Reads every row of the upload; for XLSX only the first sheet is used.
+///
+/// # Errors
+/// Returns an error if the file cannot be decoded in the given format.
+pub fn read_rows(format: SpreadsheetFormat, bytes: &[u8]) -> Result<Vec<Row>> {
+    match format {
+        SpreadsheetFormat::Csv => read_csv(bytes),
+        SpreadsheetFormat::Xlsx => read_xlsx(bytes),
+    }
+}
+
+fn normalize_header(header: &str) -> String {
+    header
+        .trim()
+        .to_lowercase()
+        .replace(['_', '-'], " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Positions of the known columns in the header row.
+struct Columns {
+    headers: Vec<String>,
+    section: Option<usize>,
+    question: usize,
+    description: Option<usize>,
+    marks: Option<usize>,
+    options: Vec<usize>,
+    correct: Option<usize>,
+}
+
+impl Columns {
+    fn from_header(header: &Row) -> Result<Self, RowError> {
+        let mut section = None;
+        let mut question = None;
+        let mut description = None;
+        let mut marks = None;
+        let mut options = Vec::new();
+        let mut correct = None;
+
+        for (index, raw) in header.cells.iter().enumerate() {
+            let name = normalize_header(raw);
+            match name.as_str() {
+                "section" | "section title" => section = section.or(Some(index)),
+                "question" | "question text" | "text" => question = question.or(Some(index)),
+                "description" => description = description.or(Some(index)),
+                "marks" | "mark" | "points" => marks = marks.or(Some(index)),
+                "correct" | "correct option" | "correct options" | "answer" => {
+                    correct = correct.or(Some(index))
+                }
+                _ if name.starts_with("option") => options.push(index),
+                _ => {}
+            }
+        }
+
+        let error = |message: &str| RowError {
+            row: header.number,
+            column: None,
+            message: message.to_string(),
+        };
+        let question = question.ok_or_else(|| error("Header has no 'question' column"))?;
+        if options.len() < 2 {
+            return Err(error("Header needs at least two 'option' columns"));
+        }
+
+        Ok(Self {
+            headers: header.cells.iter().map(|h| h.trim().to_string()).collect(),
+            section,
+            question,
+            description,
+            marks,
+            options,
+            correct,
+        })
+    }
+
+    fn error(&self, row: &Row, column: Option<usize>, message: impl Into<String>) -> RowError {
+        RowError {
+            row: row.number,
+            column: column.map(|c| self.headers[c].clone()),
+            message: message.into(),
+        }
+    }
+}
+
+/// Resolves the `correct` cell to option positions. The cell may name an
+/// option by its text, or list letters (`A`, `B`) or 1-based numbers
+/// separated by commas, semicolons or spaces.
+fn correct_positions(cell: &str, options: &[Option<String>]) -> Result<Vec<usize>, String> {
+    if let Some(index) = options
+        .iter()
+        .position(|o| o.as_deref().is_some_and(|o| o == cell))
+    {
+        return Ok(vec![index]);
+    }
+
+    let mut positions = Vec::new();
+    for token in cell
+        .split([',', ';', ' ', '،'])
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+    {
+        let index = match token.parse::<usize>() {
+            Ok(number) if number >= 1 => number - 1,
+            Ok(_) => return Err(format!("'{}' is not a valid option number", token)),
+            Err(_) => {
+                let mut chars = token.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(letter), None) if letter.is_ascii_alphabetic() => {
+                        (letter.to_ascii_uppercase() as u8 - b'A') as usize
+                    }
+                    _ => return Err(format!("'{}' does not name an option", token)),
+                }
+            }
+        };
+
+        match options.get(index) {
+            Some(Some(_)) => positions.push(index),
+            Some(None) => return Err(format!("Option '{}' is empty", token)),
+            None => return Err(format!("There is no option '{}'", token)),
+        }
+    }
+    Ok(positions)
+}
+
+fn parse_marks(cell: &str) -> Option<i32> {
+    if cell.is_empty() {
+        return Some(1);
+    }
+    let marks: f64 = cell.parse().ok()?;
+    let whole = marks.round();
+    (whole == marks && whole >= 1.0 && whole <= f64::from(i32::MAX)).then_some(whole as i32)
+}
+
+/// Validates one question row, returning the question or every problem
+/// found in the row.
+fn parse_question(columns: &Columns, row: &Row) -> Result<QuestionRequest, Vec<RowError>> {
+    let mut errors = Vec::new();
+
+    let text = row.cell(columns.question).to_string();
+    if text.is_empty() {
+        errors.push(columns.error(row, Some(columns.question), "Question text is empty"));
+    }
+
+    let description = columns
+        .description
+        .map(|c| row.cell(c).to_string())
+        .filter(|d| !d.is_empty());
+
+    let marks = columns.marks.map(|c| row.cell(c)).unwrap_or_default();
+    let marks = parse_marks(marks).unwrap_or_else(|| {
+        errors.push(columns.error(
+            row,
+            columns.marks,
+            format!("Marks must be a positive whole number, found '{}'", marks),
+        ));
+        0
+    });
+
+    // Options keep their column position so `correct` letters line up
+    // with the sheet even when some option cells are blank.
+    let mut starred = Vec::new();
+    let options: Vec<Option<String>> = columns
+        .options
+        .iter()
+        .enumerate()
+        .map(|(position, &c)| {
+            let cell = row.cell(c);
+            let text = match cell.strip_prefix('*') {
+                Some(rest) => {
+                    starred.push(position);
+                    rest.trim()
+                }
+                None => cell,
+            };
+            Some(text.to_string()).filter(|t| !t.is_empty())
+        })
+        .collect();
+
+    let filled: Vec<&str> = options.iter().flatten().map(String::as_str).collect();
+    if filled.len() < 2 {
+        errors.push(columns.error(row, None, "A question needs at least two options"));
+    }
+    for (index, option) in filled.iter().enumerate() {
+        if filled[..index].contains(option) {
+            errors.push(columns.error(row, None, format!("Option '{}' is repeated", option)));
+        }
+    }
+
+    let mut correct = starred;
+    let mut unreadable_correct = false;
+    if let Some(c) = columns.correct {
+        match correct_positions(row.cell(c), &options) {
+            Ok(positions) => correct.extend(positions),
+            Err(message) => {
+                unreadable_correct = true;
+                errors.push(columns.error(row, Some(c), message));
+            }
+        }
+    }
+    if correct.is_empty() && !unreadable_correct {
+        errors.push(columns.error(row, columns.correct, "No correct option is marked"));
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let options = options
+        .into_iter()
+        .enumerate()
+        .filter_map(|(position, text)| {
+            text.map(|t| OptionRequestModel::new(t, correct.contains(&position)))
+        })
+        .collect();
+
+    Ok(QuestionRequest::new(text, description, marks, options))
+}
+
+/// The questions read from a sheet, grouped into sections.
+pub struct ParsedSheet {
+    pub sections: Vec<SectionRequest>,
+    /// Number of non-empty rows after the header.
+    pub rows: usize,
+    pub errors: Vec<RowError>,
+}
+
+/// Validates every row of a sheet. Rows with errors are reported and left
+/// out of the returned sections; blank rows are ignored.
+///
+/// IDs in the returned sections are placeholders.
+pub fn parse_rows(rows: &[Row]) -> ParsedSheet {
+    let mut rows = rows.iter().filter(|r| !r.is_blank());
+
+    let Some(header) = rows.next() else {
+        return ParsedSheet {
+            sections: Vec::new(),
+            rows: 0,
+            errors: vec![RowError {
+                row: 1,
+                column: None,
+                message: "The sheet is empty".to_string(),
+            }],
+        };
+    };
+
+    let columns = match Columns::from_header(header) {
+        Ok(columns) => columns,
+        Err(error) => {
+            return ParsedSheet {
+                sections: Vec::new(),
+                rows: rows.count(),
+                errors: vec![error],
+            }
+        }
+    };
+
+    let mut sections = SectionCollector::default();
+    let mut section = DEFAULT_SECTION_TITLE.to_string();
+    let mut errors = Vec::new();
+    let mut count = 0;
+
+    for row in rows {
+        count += 1;
+        if let Some(title) = columns
+            .section
+            .map(|c| row.cell(c))
+            .filter(|t| !t.is_empty())
+        {
+            section = title.to_string();
+        }
+
+        match parse_question(&columns, row) {
+            Ok(question) => sections.push(Some(&section), question),
+            Err(row_errors) => errors.extend(row_errors),
+        }
+    }
+
+    if count == 0 {
+        errors.push(RowError {
+            row: header.number,
+            column: None,
+            message: "The sheet has no question rows".to_string(),
+        });
+    }
+
+    ParsedSheet {
+        sections: sections.into_sections(),
+        rows: count,
+        errors,
+    }
+}
@@ -0,0 +1,181 @@
+//! Token accounting and per-user quotas of LLM calls.
+//!
+//! Handlers run their generation inside [`scoped`], naming the user (from
+//! `X-User-Id`, which a trusted gateway must set), the exam and the
+//! endpoint. `LlmClient` checks the user's quotas before every call and
+//! records the tokens the provider reports after it, in the `llm_usage`
+//! table. Calls outside a scope are accounted to the anonymous user; while
+//! quotas are set, requests without `X-User-Id` are refused.
+//!
+//! Quotas count prompt and completion tokens together, per UTC day and
+//! month, and are off unless set:
+//!
+//! | Variable                  | Meaning                           |
+//! |---------------------------|-----------------------------------|
+//! | `LLM_DAILY_TOKEN_QUOTA`   | Tokens a user may spend in a day  |
+//! | `LLM_MONTHLY_TOKEN_QUOTA` | Tokens a user may spend in a month |
+
+use std::future::Future;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Duration, TimeZone, Utc};
+use sqlx::PgPool;
+
+use crate::database::queries;
+use crate::model::usage::{QuotaBudget, QuotaExceeded, TokenUsage, UsageScope};
+use crate::services::llm::LlmError;
+use crate::utils;
+
+tokio::task_local! {
+    static SCOPE: UsageScope;
+}
+
+/// Runs `future` with LLM calls accounted to `scope`.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let body = usage::scoped(scope, generate_job(&app_state, &request)).await?;
+/// ```
+pub async fn scoped<F: Future>(scope: UsageScope, future: F) -> F::Output {
+    SCOPE.scope(scope, future).await
+}
+
+/// The scope LLM calls of the current task are accounted to, the default
+/// one outside [`scoped`].
+pub fn current_scope() -> UsageScope {
+    SCOPE.try_with(UsageScope::clone).unwrap_or_default()
+}
+
+/// Token quotas per user; `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Quotas {
+    pub daily: Option<u64>,
+    pub monthly: Option<u64>,
+}
+
+fn quota_from_env(key: &str) -> Result<Option<u64>> {
+    match utils::env::load_env_var(key) {
+        Ok(value) if !value.trim().is_empty() => value
+            .trim()
+            .parse()
+            .map(Some)
+            .with_context(|| format!("Invalid {} `{}`", key, value)),
+        _ => Ok(None),
+    }
+}
+
+impl Quotas {
+    pub fn from_env() -> Result<Self> {
+        Ok(Self {
+            daily: quota_from_env("LLM_DAILY_TOKEN_QUOTA")?,
+            monthly: quota_from_env("LLM_MONTHLY_TOKEN_QUOTA")?,
+        })
+    }
+
+    /// Whether any quota is set.
+    pub fn is_set(&self) -> bool {
+        self.daily.is_some() || self.monthly.is_some()
+    }
+}
+
+/// Start of the UTC day of `now`, and of the next one.
+fn day_window(now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+    let start = Utc.from_utc_datetime(&now.date_naive().and_hms_opt(0, 0, 0).unwrap_or_default());
+    (start, start + Duration::days(1))
+}
+
+/// Start of the UTC month of `now`, and of the next one.
+fn month_window(now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+    let start = Utc
+        .with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0)
+        .single()
+        .unwrap_or(now);
+    let (year, month) = match now.month() {
+        12 => (now.year() + 1, 1),
+        month => (now.year(), month + 1),
+    };
+    let end = Utc
+        .with_ymd_and_hms(year, month, 1, 0, 0, 0)
+        .single()
+        .unwrap_or(now);
+    (start, end)
+}
+
+/// Records usage and enforces quotas, shared through `AppState` and the
+/// LLM client.
+#[derive(Clone)]
+pub struct UsageTracker {
+    pool: PgPool,
+    quotas: Quotas,
+}
+
+impl UsageTracker {
+    pub fn new(pool: PgPool, quotas: Quotas) -> Self {
+        Self { pool, quotas }
+    }
+
+    /// Reads the quotas from the environment, see the module docs.
+    pub fn from_env(pool: PgPool) -> Result<Self> {
+        Ok(Self::new(pool, Quotas::from_env()?))
+    }
+
+    pub fn quotas(&self) -> Quotas {
+        self.quotas
+    }
+
+    async fn budget(
+        &self,
+        user_id: &str,
+        limit: u64,
+        (start, end): (DateTime<Utc>, DateTime<Utc>),
+    ) -> Result<QuotaBudget> {
+        let used = queries::usage::user_tokens_since(&self.pool, user_id, start).await?;
+        Ok(QuotaBudget {
+            limit,
+            used,
+            remaining: limit.saturating_sub(used),
+            resets_at: end,
+        })
+    }
+
+    /// Fails with [`LlmError::QuotaExceeded`] if the user has no tokens left
+    /// today or this month.
+    pub async fn check(&self, user_id: &str) -> Result<()> {
+        let now = Utc::now();
+        let daily = match self.quotas.daily {
+            Some(limit) => Some(self.budget(user_id, limit, day_window(now)).await?),
+            None => None,
+        };
+        let monthly = match self.quotas.monthly {
+            Some(limit) => Some(self.budget(user_id, limit, month_window(now)).await?),
+            None => None,
+        };
+
+        let spent = |budget: &Option<QuotaBudget>| {
+            budget.as_ref().is_some_and(|budget| budget.remaining == 0)
+        };
+        let period = match (spent(&daily), spent(&monthly)) {
+            (_, true) => "monthly",
+            (true, false) => "daily",
+            (false, false) => return Ok(()),
+        };
+        Err(LlmError::QuotaExceeded(Box::new(QuotaExceeded {
+            message: format!("The {} token quota of {} is spent", period, user_id),
+            user_id: user_id.to_string(),
+            daily,
+            monthly,
+        }))
+        .into())
+    }
+
+    /// Records the tokens of one call.
+    pub async fn record(
+        &self,
+        scope: &UsageScope,
+        provider: &str,
+        model: &str,
+        usage: &TokenUsage,
+    ) -> Result<()> {
+        queries::usage::insert_usage(&self.pool, scope, provider, model, usage).await
+    }
+}
@@ -0,0 +1,83 @@
+/// Returns true if `text` contains characters from a right-to-left script
+/// (Arabic, Urdu, Persian or Hebrew).
+pub fn contains_rtl(text: &str) -> bool {
+    text.chars().any(|c| {
+        matches!(c,
+            '\u{0590}'..='\u{05FF}'
+            | '\u{0600}'..='\u{06FF}'
+            | '\u{0750}'..='\u{077F}'
+            | '\u{08A0}'..='\u{08FF}'
+            | '\u{FB1D}'..='\u{FDFF}'
+            | '\u{FE70}'..='\u{FEFF}')
+    })
+}
+
+/// Returns true if `text` uses letters found in Urdu but not in Arabic.
+pub fn contains_urdu(text: &str) -> bool {
+    text.chars().any(|c| {
+        matches!(
+            c,
+            '\u{0679}'
+                | '\u{0688}'
+                | '\u{0691}'
+                | '\u{06BA}'
+                | '\u{06BE}'
+                | '\u{06D2}'
+                | '\u{06D3}'
+        )
+    })
+}
+
+fn is_harakah(c: char) -> bool {
+    matches!(c, '\u{064B}'..='\u{0652}' | '\u{0670}')
+}
+
+/// Returns true if `text` looks like Quranic script: it carries Quranic
+/// annotation marks or is fully vowelled.
+pub fn is_quranic(text: &str) -> bool {
+    if text
+        .chars()
+        .any(|c| matches!(c, '\u{0671}' | '\u{06D6}'..='\u{06ED}'))
+    {
+        return true;
+    }
+
+    let letters = text
+        .chars()
+        .filter(|c| matches!(c, '\u{0621}'..='\u{064A}'))
+        .count();
+    let harakat = text.chars().filter(|c| is_harakah(*c)).count();
+    letters >= 3 && harakat * 2 >= letters
+}
+
+/// Folds one character for diacritic-insensitive search: tashkeel, Quranic
+/// marks and tatweel are dropped (`None`), and the alef, ya, ta marbuta, heh
+/// and kaf variants of Arabic and Urdu map to one letter each.
+///
+/// Must stay in line with the `normalize_arabic` SQL function, which folds
+/// the stored text the same way.
+pub fn fold_for_search(c: char) -> Option<char> {
+    match c {
+        '\u{064B}'..='\u{065F}' | '\u{0670}' | '\u{0640}' | '\u{06D6}'..='\u{06ED}' => None,
+        'أ' | 'إ' | 'آ' | 'ٱ' => Some('ا'),
+        'ى' | 'ی' | 'ئ' => Some('ي'),
+        'ؤ' => Some('و'),
+        'ة' | 'ۃ' | 'ۀ' | 'ہ' | 'ھ' => Some('ه'),
+        'ک' => Some('ك'),
+        _ => Some(c),
+    }
+}
+
+/// Normalizes text for search, see [`fold_for_search`]. Latin letters are
+/// lowercased.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// assert_eq!(normalize_for_search("الْحَمْدُ لِلَّهِ"), normalize_for_search("الحمد لله"));
+/// ```
+pub fn normalize_for_search(text: &str) -> String {
+    text.chars()
+        .filter_map(fold_for_search)
+        .flat_map(char::to_lowercase)
+        .collect()
+}
@@ -0,0 +1,76 @@
+use anyhow::{bail, Context, Result};
+use std::io::{Cursor, Read};
+use zip::ZipArchive;
+
+/// Largest decompressed size of one entry of an uploaded archive.
+pub const MAX_ENTRY_SIZE: u64 = 32 * 1024 * 1024;
+
+/// Largest decompressed size of all the entries read from an uploaded
+/// archive together.
+pub const MAX_ARCHIVE_SIZE: u64 = 128 * 1024 * 1024;
+
+/// A zip archive that decompresses at most `MAX_ENTRY_SIZE` per entry and
+/// `MAX_ARCHIVE_SIZE` in all, so a small upload cannot expand without
+/// bound.
+///
+/// Sizes declared in the archive are checked first, and entries are read
+/// through a limit in case they lie.
+pub struct LimitedArchive {
+    archive: ZipArchive<Cursor<Vec<u8>>>,
+    read: u64,
+}
+
+impl LimitedArchive {
+    pub fn new(bytes: &[u8]) -> Result<Self> {
+        let archive = ZipArchive::new(Cursor::new(bytes.to_vec()))?;
+        Ok(Self { archive, read: 0 })
+    }
+
+    /// Decompresses the entry at `path`.
+    pub fn read(&mut self, path: &str) -> Result<Vec<u8>> {
+        let remaining = MAX_ARCHIVE_SIZE - self.read;
+        let file = self
+            .archive
+            .by_name(path)
+            .with_context(|| format!("Archive has no file {}", path))?;
+        let content = read_limited(file, remaining)?;
+        self.read += content.len() as u64;
+        Ok(content)
+    }
+
+    /// Decompresses the entry at `path` as text.
+    pub fn read_string(&mut self, path: &str) -> Result<String> {
+        String::from_utf8(self.read(path)?).with_context(|| format!("{} is not valid UTF-8", path))
+    }
+
+    /// Decompresses every entry once, failing if any is over the limits.
+    pub fn check_all(&mut self) -> Result<()> {
+        for index in 0..self.archive.len() {
+            let remaining = MAX_ARCHIVE_SIZE - self.read;
+            let file = self.archive.by_index(index)?;
+            self.read += read_limited(file, remaining)?.len() as u64;
+        }
+        Ok(())
+    }
+
+    pub fn file_names(&self) -> Vec<String> {
+        self.archive.file_names().map(str::to_string).collect()
+    }
+}
+
+fn read_limited<R: Read>(file: zip::read::ZipFile<'_, R>, remaining: u64) -> Result<Vec<u8>> {
+    let name = file.name().to_string();
+    let limit = MAX_ENTRY_SIZE.min(remaining);
+    if file.size() > limit {
+        bail!("{} is too large once decompressed", name);
+    }
+
+    let mut content = Vec::new();
+    file.take(limit + 1)
+        .read_to_end(&mut content)
+        .with_context(|| format!("Failed to decompress {}", name))?;
+    if content.len() as u64 > limit {
+        bail!("{} is too large once decompressed", name);
+    }
+    Ok(content)
+}
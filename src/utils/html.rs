@@ -0,0 +1,74 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+static BREAK_TAG: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)<br\s*/?>|</p\s*>|</div\s*>|</li\s*>").unwrap());
+static ANY_TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[^>]*>").unwrap());
+static ENTITY: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-zA-Z]+);").unwrap());
+
+/// Escapes text for use in HTML content or attribute values.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    if let Some(hex) = entity
+        .strip_prefix("#x")
+        .or_else(|| entity.strip_prefix("#X"))
+    {
+        return u32::from_str_radix(hex, 16).ok().and_then(char::from_u32);
+    }
+    if let Some(decimal) = entity.strip_prefix('#') {
+        return decimal.parse().ok().and_then(char::from_u32);
+    }
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{00A0}'),
+        _ => None,
+    }
+}
+
+/// Returns true if `text` contains markup that `to_plain_text` cannot keep,
+/// such as images, media or tables.
+pub fn has_rich_content(text: &str) -> bool {
+    let lower = text.to_lowercase();
+    [
+        "<img", "<video", "<audio", "<table", "<object", "<iframe", "<math",
+    ]
+    .iter()
+    .any(|tag| lower.contains(tag))
+}
+
+/// Converts an HTML fragment to plain text: line-breaking tags become
+/// newlines, every other tag is dropped and character entities are decoded.
+pub fn to_plain_text(html: &str) -> String {
+    let text = BREAK_TAG.replace_all(html, "\n");
+    let text = ANY_TAG.replace_all(&text, "");
+    let text = ENTITY.replace_all(&text, |caps: &regex::Captures| {
+        decode_entity(&caps[1])
+            .map(String::from)
+            .unwrap_or_else(|| caps[0].to_string())
+    });
+
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,266 @@
+//! Tolerant extraction of JSON objects from LLM output.
+//!
+//! Models wrap their JSON in code fences or prose, leave trailing commas,
+//! break lines inside strings and stop mid-object when they run out of
+//! tokens. [`extract_json_object`] finds the first object and repairs those
+//! defects so that `serde_json` can read it.
+
+use anyhow::{anyhow, bail, Result};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// A point of the repaired text where every open value is complete, so the
+/// text can be cut there and closed.
+struct CutPoint {
+    len: usize,
+    stack: Vec<char>,
+}
+
+fn drop_trailing_comma(out: &mut String) {
+    let trimmed = out.trim_end().len();
+    out.truncate(trimmed);
+    if out.ends_with(',') {
+        out.pop();
+    }
+}
+
+fn closer(open: char) -> char {
+    if open == '{' {
+        '}'
+    } else {
+        ']'
+    }
+}
+
+/// Returns the balanced JSON object starting at the first `{` of `text`,
+/// repaired:
+///
+/// - trailing commas before `}` and `]` are removed,
+/// - raw line breaks and tabs inside strings are escaped,
+/// - truncated output is cut back to its last complete value and closed.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let text = "Sure! ```json\n{\"a\": [\"x\", \"y\",], \"b\": [\"z";
+/// assert_eq!(extract_json_object(text)?, r#"{"a": ["x", "y"], "b": []}"#);
+/// ```
+pub fn extract_json_object(text: &str) -> Result<String> {
+    let start = text
+        .find('{')
+        .ok_or_else(|| anyhow!("No JSON object found in the output"))?;
+
+    let mut out = String::new();
+    let mut stack: Vec<char> = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut expecting_value = false;
+    let mut cut = CutPoint {
+        len: 0,
+        stack: Vec::new(),
+    };
+
+    for c in text[start..].chars() {
+        if in_string {
+            if escaped {
+                escaped = false;
+                out.push(c);
+            } else {
+                match c {
+                    '\\' => {
+                        escaped = true;
+                        out.push(c);
+                    }
+                    '"' => {
+                        in_string = false;
+                        out.push(c);
+                        // A string closes a value unless it is an object key.
+                        if stack.last() == Some(&'[') || expecting_value {
+                            expecting_value = false;
+                            cut = CutPoint {
+                                len: out.len(),
+                                stack: stack.clone(),
+                            };
+                        }
+                    }
+                    '\n' => out.push_str("\\n"),
+                    '\r' => out.push_str("\\r"),
+                    '\t' => out.push_str("\\t"),
+                    _ => out.push(c),
+                }
+            }
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            '{' | '[' => {
+                stack.push(c);
+                expecting_value = false;
+                out.push(c);
+                cut = CutPoint {
+                    len: out.len(),
+                    stack: stack.clone(),
+                };
+            }
+            '}' | ']' => {
+                drop_trailing_comma(&mut out);
+                stack.pop();
+                expecting_value = false;
+                out.push(c);
+                if stack.is_empty() {
+                    return Ok(out);
+                }
+                cut = CutPoint {
+                    len: out.len(),
+                    stack: stack.clone(),
+                };
+            }
+            ':' => {
+                expecting_value = true;
+                out.push(c);
+            }
+            ',' => {
+                expecting_value = false;
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+
+    // The output stopped before the object closed.
+    if cut.len == 0 {
+        bail!("Truncated JSON object");
+    }
+    out.truncate(cut.len);
+    drop_trailing_comma(&mut out);
+    for open in cut.stack.iter().rev() {
+        out.push(closer(*open));
+    }
+    Ok(out)
+}
+
+/// Parses the first object of `text` that reads as `T`, repairing it as
+/// [`extract_json_object`] does. Prose before the object may contain braces,
+/// so every `{` is tried in turn.
+///
+/// # Errors
+/// Returns the error of the first attempt if no object reads as `T`.
+pub fn parse_llm_json<T: DeserializeOwned>(text: &str) -> Result<T> {
+    let mut first_error = None;
+    for (start, _) in text.match_indices('{') {
+        let attempt = extract_json_object(&text[start..])
+            .and_then(|json| serde_json::from_str(&json).map_err(anyhow::Error::from));
+        match attempt {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    Err(first_error.unwrap_or_else(|| anyhow!("No JSON object found in the output")))
+}
+
+/// Reads a JSON object as it streams in, reporting each top-level field as
+/// soon as its value is complete. Text before the first `{` is skipped, and
+/// values are repaired as [`extract_json_object`] does.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// let mut fields = FieldStream::default();
+/// assert!(fields.push(r#"{"a": ["x", "#).is_empty());
+/// assert_eq!(fields.push(r#""y"], "b"#), vec![("a".into(), json!(["x", "y"]))]);
+/// ```
+#[derive(Debug, Default)]
+pub struct FieldStream {
+    buffer: String,
+    /// Bytes of `buffer` scanned so far.
+    scanned: usize,
+    /// Nesting depth, 1 inside the top-level object.
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    key_start: Option<usize>,
+    key: Option<String>,
+    value_start: Option<usize>,
+    finished: bool,
+}
+
+impl FieldStream {
+    /// Adds a chunk of text and returns the fields it completed, in order.
+    pub fn push(&mut self, chunk: &str) -> Vec<(String, Value)> {
+        self.buffer.push_str(chunk);
+        let mut fields = Vec::new();
+
+        // Taken out so the fields can be cut from it while scanning.
+        let buffer = std::mem::take(&mut self.buffer);
+        let start = self.scanned;
+        self.scanned = buffer.len();
+        for (offset, c) in buffer[start..].char_indices() {
+            let index = start + offset;
+            if self.finished {
+                break;
+            }
+            if self.depth == 0 {
+                if c == '{' {
+                    self.depth = 1;
+                }
+                continue;
+            }
+            if self.in_string {
+                match (self.escaped, c) {
+                    (true, _) => self.escaped = false,
+                    (false, '\\') => self.escaped = true,
+                    (false, '"') => {
+                        self.in_string = false;
+                        if let (Some(key_start), None) = (self.key_start, self.value_start) {
+                            self.key = serde_json::from_str(&buffer[key_start..=index]).ok();
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            match c {
+                '"' => {
+                    self.in_string = true;
+                    if self.depth == 1 && self.key.is_none() && self.value_start.is_none() {
+                        self.key_start = Some(index);
+                    }
+                }
+                ':' if self.depth == 1 && self.key.is_some() && self.value_start.is_none() => {
+                    self.value_start = Some(index + 1);
+                }
+                '{' | '[' => self.depth += 1,
+                ',' if self.depth == 1 => fields.extend(self.finish_field(&buffer, index)),
+                '}' if self.depth == 1 => {
+                    fields.extend(self.finish_field(&buffer, index));
+                    self.finished = true;
+                }
+                '}' | ']' => self.depth -= 1,
+                _ => {}
+            }
+        }
+        self.buffer = buffer;
+        fields
+    }
+
+    /// Parses the value of the current field, which ends at `end`.
+    fn finish_field(&mut self, buffer: &str, end: usize) -> Option<(String, Value)> {
+        let key = self.key.take();
+        self.key_start = None;
+        let start = self.value_start.take()?;
+
+        let text = buffer[start..end].trim();
+        let value = serde_json::from_str(text).ok().or_else(|| {
+            let wrapped = extract_json_object(&format!("{{\"value\": {}}}", text)).ok()?;
+            serde_json::from_str::<Value>(&wrapped)
+                .ok()
+                .map(|mut object| object["value"].take())
+        })?;
+        Some((key?, value))
+    }
+}
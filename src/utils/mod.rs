@@ -1,3 +1,8 @@
+pub mod arabic;
+pub mod archive;
 pub mod env;
+pub mod html;
+pub mod json;
 pub mod parse;
 pub mod prompts;
+pub mod xml;
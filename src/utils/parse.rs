@@ -1,13 +1,14 @@
 use crate::database::schema;
 use crate::model;
-use crate::model::llm::PromptLanguage;
 use crate::model::option::OptionResponseModel;
 use crate::model::question::QuestionResponse;
 use crate::model::section::SectionResponse;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use std::collections::HashMap;
 
 /// Maps the raw rows from the database query to structured `SectionResponse` objects.
+/// Sections without questions and questions without options come through as
+/// rows with the missing columns empty.
 /// Returns a Result with either the mapped data or an error.
 pub fn map_to_section_response(
     rows: Vec<schema::SectionRow>,
@@ -30,50 +31,50 @@ pub fn map_to_section_response(
                 questions: Vec::new(),
             });
 
-        // Build the question base model
-        let question_model = schema::QuestionsModel {
-            id: row.question_id,
-            section_id: row.section_id,
-            text: row.question_text.clone(),
-            description: row.question_description.clone(),
-            marks: row.question_marks,
+        let (Some(question_id), Some(question_text), Some(question_marks)) =
+            (row.question_id, row.question_text, row.question_marks)
+        else {
+            continue;
         };
 
-        let question_id = question_model.id;
-
-        if let Some(existing_question) = section
+        let question = match section
             .questions
-            .iter_mut()
-            .find(|q| q.base.id == question_id)
+            .iter()
+            .position(|q| q.base.id == question_id)
         {
-            let option_model = schema::OptionsModel {
-                id: row.option_id,
-                question_id,
-                text: row.option_text.clone(),
-                is_correct: row.option_is_correct,
-            };
-
-            existing_question
-                .options
-                .push(OptionResponseModel { base: option_model });
-        } else {
-            let mut question = QuestionResponse {
-                base: question_model,
-                options: Vec::new(),
-            };
+            Some(index) => &mut section.questions[index],
+            None => {
+                // Build the question base model
+                let question_model = schema::QuestionsModel {
+                    id: question_id,
+                    section_id: row.section_id,
+                    text: question_text,
+                    description: row.question_description,
+                    marks: question_marks,
+                };
+
+                section.questions.push(QuestionResponse {
+                    base: question_model,
+                    options: Vec::new(),
+                });
+                section
+                    .questions
+                    .last_mut()
+                    .context("Question was just added")?
+            }
+        };
 
+        if let (Some(option_id), Some(option_text)) = (row.option_id, row.option_text) {
             let option_model = schema::OptionsModel {
-                id: row.option_id,
+                id: option_id,
                 question_id,
-                text: row.option_text.clone(),
+                text: option_text,
                 is_correct: row.option_is_correct,
             };
 
             question
                 .options
                 .push(OptionResponseModel { base: option_model });
-
-            section.questions.push(question);
         }
     }
 
@@ -81,28 +82,33 @@ pub fn map_to_section_response(
     Ok(sections_map)
 }
 
-pub fn map_to_prompt_language(language: &model::llm::Language) -> PromptLanguage {
-    match language {
-        model::llm::Language::Arabic => PromptLanguage::Arabic,
-        model::llm::Language::Urdu => PromptLanguage::Urdu,
+/// Nests a flat list of tags under their parents. Tags whose parent is not
+/// in the list become roots.
+pub fn map_to_tag_tree(tags: Vec<model::tag::Tag>) -> Vec<model::tag::TagNode> {
+    let ids: Vec<i32> = tags.iter().map(|t| t.id).collect();
+    let mut children: HashMap<Option<i32>, Vec<model::tag::Tag>> = HashMap::new();
+    for tag in tags {
+        let parent = tag.parent_id.filter(|p| ids.contains(p));
+        children.entry(parent).or_default().push(tag);
     }
-}
-
-pub fn clean_llm_json_output(json_text: &str) -> Result<String, anyhow::Error> {
-    let mut clean_text = json_text.trim();
 
-    // Remove the code block markers and possible 'json' after the first ```
-    if clean_text.starts_with("```") {
-        clean_text = clean_text.trim_start_matches("```").trim_start();
-
-        if clean_text.starts_with("json") {
-            clean_text = clean_text.trim_start_matches("json").trim_start();
-        }
-
-        if clean_text.ends_with("```") {
-            clean_text = clean_text.trim_end_matches("```").trim();
-        }
+    fn build(
+        parent: Option<i32>,
+        children: &mut HashMap<Option<i32>, Vec<model::tag::Tag>>,
+    ) -> Vec<model::tag::TagNode> {
+        children
+            .remove(&parent)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|tag| {
+                let nested = build(Some(tag.id), children);
+                model::tag::TagNode {
+                    tag,
+                    children: nested,
+                }
+            })
+            .collect()
     }
 
-    Ok(clean_text.to_string())
+    build(None, &mut children)
 }
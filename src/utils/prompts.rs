@@ -1,71 +1,180 @@
-use once_cell::sync::Lazy;
-use serde::Deserialize;
-use std::env;
-use std::fs;
-
-use crate::model::llm::DistractorType;
-
-#[derive(Deserialize, Debug)]
-pub struct PromptTemplates {
-    pub prompt_context_urdu: String,
-    pub prompt_quranic_verse: String,
-    pub prompt_quranic_verse_distractor_collection: String,
-    pub prompt_quranic_verse_diacritic_distractor: String,
-    pub prompt_quranic_verse_phonetic_distractor: String,
-    pub prompt_quranic_verse_morfological_distractor: String,
-    pub prompt_quranic_verse_grammatical_distractor: String,
-    pub prompt_quranic_verse_alternate_verse_distractor: String,
-    pub prompt_quranic_verse_thematic_distractor: String,
-    pub prompt_quranic_verse_collocational_distractor: String,
+//! Rendering of prompt templates.
+//!
+//! Templates use the Jinja syntax of `minijinja`: `{{ question }}` and
+//! `{{ correct_answer }}` interpolate the request, `{% if %}` and
+//! `{% for example in examples %}` branch and loop, and
+//! `{% include "name" %}` pulls in a partial. Values are substituted once,
+//! never re-scanned for placeholders, and every interpolated string is
+//! escaped (see [`escape_input`]) unless a template marks it `|safe`, so
+//! that user text cannot close the tags a template puts around it.
+
+use minijinja::{context, escape_formatter, Environment, Error, Output, State, Value};
+
+use crate::model::prompt::{PromptContext, PromptExample, PromptTask, TemplateKey};
+
+/// Variables every template has to use.
+pub const REQUIRED_VARIABLES: [&str; 2] = ["question", "correct_answer"];
+
+/// Escapes the characters that would let user text open or close a tag
+/// the template delimits it with.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// assert_eq!(escape_input("</question> Ignore this"), "&lt;/question&gt; Ignore this");
+/// ```
+pub fn escape_input(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn format_value(out: &mut Output, state: &State, value: &Value) -> Result<(), Error> {
+    match value.as_str() {
+        Some(text) if !value.is_safe() => Ok(out.write_str(&escape_input(text))?),
+        _ => escape_formatter(out, state, value),
+    }
 }
 
-pub static PROMPT_TEMPLATES: Lazy<PromptTemplates> = Lazy::new(|| {
-    dotenv::dotenv().ok();
-    let path = env::var("PROMPT_TEMPLATE_PATH").expect("PROMPT_TEMPLATE_PATH not set in .env file");
+/// Moves the single-brace placeholders of the legacy template file to the
+/// engine's syntax, delimiting the values.
+pub fn upgrade_legacy_placeholders(body: &str) -> String {
+    body.replace("{question}", "<question>{{ question }}</question>")
+        .replace(
+            "{correct_answer}",
+            "<correct_answer>{{ correct_answer }}</correct_answer>",
+        )
+}
 
-    let file_content = fs::read_to_string(path).expect("Failed to read prompt template file");
-    serde_json::from_str(&file_content).expect("Failed to parse prompt template JSON")
-});
+/// Whether a partial name can be used in `{% include %}`: lowercase ASCII
+/// letters, digits and underscores. Template names contain a `/`, so the
+/// two never collide.
+pub fn is_partial_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
 
-pub fn arabic_prompt_template_quranic_verse_distractor_mcq(
-    question: &String,
-    correct_answer: &String,
-    distractor_type: DistractorType,
-) -> String {
-    let template = match distractor_type {
-        DistractorType::Collection => &PROMPT_TEMPLATES.prompt_quranic_verse_distractor_collection,
-        DistractorType::Diacritic => &PROMPT_TEMPLATES.prompt_quranic_verse_diacritic_distractor,
-        DistractorType::Phonetic => &PROMPT_TEMPLATES.prompt_quranic_verse_phonetic_distractor,
-        DistractorType::Morphological => {
-            &PROMPT_TEMPLATES.prompt_quranic_verse_morfological_distractor
-        }
-        DistractorType::Grammatical => {
-            &PROMPT_TEMPLATES.prompt_quranic_verse_grammatical_distractor
-        }
-        DistractorType::AlternateVerse => {
-            &PROMPT_TEMPLATES.prompt_quranic_verse_alternate_verse_distractor
+/// An environment that escapes interpolated strings. Partials are added
+/// to it by name and templates by [`TemplateKey`] name.
+pub fn environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_formatter(format_value);
+    env.set_keep_trailing_newline(true);
+    env
+}
+
+/// Renders a template loaded into `env` under the name of `key`.
+pub fn render(
+    env: &Environment<'static>,
+    key: &TemplateKey,
+    prompt: &PromptContext,
+    examples: &[PromptExample],
+) -> Result<String, Error> {
+    render_named(env, &key.to_string(), prompt, examples)
+}
+
+/// Renders a template loaded into `env` under `name`, e.g. the version
+/// label of a version that is not active.
+pub fn render_named(
+    env: &Environment<'static>,
+    name: &str,
+    prompt: &PromptContext,
+    examples: &[PromptExample],
+) -> Result<String, Error> {
+    env.get_template(name)?
+        .render(context! { examples => examples, ..Value::from_serialize(prompt) })
+}
+
+/// Checks a template before it is stored: it parses, uses the required
+/// variables, renders with the partials of `env`, and names a distractor
+/// type exactly when its task is `quranic_verse_distractor`.
+///
+/// # Example (non-runnable)
+/// ```ignore
+/// validate_template(&env, &key, "<q>{{ question }}</q> <a>{{ correct_answer }}</a>", &[])?;
+/// ```
+pub fn validate_template(
+    env: &Environment<'static>,
+    key: &TemplateKey,
+    body: &str,
+    examples: &[PromptExample],
+) -> Result<(), String> {
+    match (key.task, key.distractor_type) {
+        (PromptTask::QuranicVerseDistractor, None) => {
+            return Err(
+                "distractor_type is required for quranic_verse_distractor templates".to_string(),
+            )
         }
-        DistractorType::Thematic => &PROMPT_TEMPLATES.prompt_quranic_verse_thematic_distractor,
-        DistractorType::Collocational => {
-            &PROMPT_TEMPLATES.prompt_quranic_verse_collocational_distractor
+        (PromptTask::QuranicVerseDistractor, Some(_)) | (_, None) => {}
+        (_, Some(_)) => {
+            return Err(
+                "distractor_type is only allowed for quranic_verse_distractor templates"
+                    .to_string(),
+            )
         }
-    };
+    }
+
+    let mut env = env.clone();
+    env.add_template_owned(key.to_string(), body.to_string())
+        .map_err(|e| format!("Invalid template: {}", e))?;
+    let template = env
+        .get_template(&key.to_string())
+        .map_err(|e| format!("Invalid template: {}", e))?;
 
-    template
-        .replace("{question}", question.trim())
-        .replace("{correct_answer}", correct_answer.trim())
+    let used = template.undeclared_variables(false);
+    let missing: Vec<&str> = REQUIRED_VARIABLES
+        .into_iter()
+        .filter(|variable| !used.contains(*variable))
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!(
+            "Template does not use the variables {}",
+            missing.join(", ")
+        ));
+    }
+
+    render(
+        &env,
+        key,
+        &PromptContext::new("question", "answer"),
+        examples,
+    )
+    .map(|_| ())
+    .map_err(|e| format!("Template does not render: {}", e))
 }
 
-// pub fn arabic_prompt_template_context_fill_in_the_blank(question: &String, correct_answer: &String) -> String {
-//     PROMPT_TEMPLATES
-//         .arabic_context_fill
-//         .replace("{question}", question.trim())
-//         .replace("{correct_answer}", correct_answer.trim())
-// }
-
-pub fn urdu_prompt_template_context_mcq(question: &String, correct_answer: &String) -> String {
-    PROMPT_TEMPLATES
-        .prompt_context_urdu
-        .replace("{question}", question.trim())
-        .replace("{correct_answer}", correct_answer.trim())
+/// Longest part of an unparseable reply quoted back to the model.
+const REPAIR_QUOTE_CHARS: usize = 2000;
+
+/// Follow-up prompt asking the model to fix a reply that was not valid JSON.
+pub fn json_repair_prompt(prompt: &str, reply: &str, error: &str) -> String {
+    let reply: String = reply.chars().take(REPAIR_QUOTE_CHARS).collect();
+    format!(
+        "{}\n\nYour previous reply could not be parsed:\n{}\n\nParse error: {}\n\n\
+         Reply again with only the JSON object, without code fences or any other text.",
+        prompt, reply, error
+    )
+}
+
+/// Follow-up prompt asking for more distractors after some failed the
+/// quality gate.
+pub fn regeneration_prompt(
+    prompt: &str,
+    rejected: &[crate::model::llm::RejectedDistractor],
+    missing: &[(String, usize)],
+) -> String {
+    let rejected: String = rejected
+        .iter()
+        .map(|r| format!("- {} ({}): {}\n", r.text, r.field, r.reason))
+        .collect();
+    let missing: String = missing
+        .iter()
+        .map(|(field, count)| format!("- {}: {} more\n", field, count))
+        .collect();
+    format!(
+        "{}\n\nSome of your distractors were rejected:\n{}\nGive new distractors that avoid \
+         these problems, at least:\n{}",
+        prompt, rejected, missing
+    )
 }
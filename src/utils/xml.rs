@@ -0,0 +1,169 @@
+use anyhow::{anyhow, bail, Context, Result};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::borrow::Cow;
+
+/// Deepest element nesting `parse` accepts. Walking the tree recurses once
+/// per level, so deeper documents could overflow the stack.
+pub const MAX_DEPTH: usize = 128;
+
+/// A parsed XML element.
+///
+/// Element and attribute names are stored without their namespace prefix, so
+/// `imsmd:title` and `title` are looked up the same way.
+#[derive(Debug, Clone, Default)]
+pub struct XmlElement {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<XmlNode>,
+}
+
+#[derive(Debug, Clone)]
+pub enum XmlNode {
+    Element(XmlElement),
+    Text(String),
+}
+
+impl XmlElement {
+    /// Returns the value of the attribute `name`, if present.
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Iterates over the direct child elements.
+    pub fn elements(&self) -> impl Iterator<Item = &XmlElement> {
+        self.children.iter().filter_map(|node| match node {
+            XmlNode::Element(element) => Some(element),
+            XmlNode::Text(_) => None,
+        })
+    }
+
+    /// Iterates over the direct child elements called `name`.
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a XmlElement> {
+        self.elements().filter(move |element| element.name == name)
+    }
+
+    /// Returns the first direct child element called `name`.
+    pub fn child(&self, name: &str) -> Option<&XmlElement> {
+        self.elements().find(|element| element.name == name)
+    }
+
+    /// Returns the first element called `name` anywhere below this one, depth first.
+    pub fn find(&self, name: &str) -> Option<&XmlElement> {
+        self.elements().find_map(|element| {
+            (element.name == name)
+                .then_some(element)
+                .or_else(|| element.find(name))
+        })
+    }
+
+    /// Returns every element called `name` below this one, in document order.
+    pub fn find_all<'a>(&'a self, name: &str) -> Vec<&'a XmlElement> {
+        let mut found = Vec::new();
+        for element in self.elements() {
+            if element.name == name {
+                found.push(element);
+            }
+            found.extend(element.find_all(name));
+        }
+        found
+    }
+
+    /// Concatenates all text below this element, exactly as written.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for node in &self.children {
+            match node {
+                XmlNode::Text(t) => text.push_str(t),
+                XmlNode::Element(element) => text.push_str(&element.text()),
+            }
+        }
+        text
+    }
+}
+
+fn local_name(name: &[u8]) -> String {
+    let name = String::from_utf8_lossy(name);
+    match name.rsplit_once(':') {
+        Some((_, local)) => local.to_string(),
+        None => name.into_owned(),
+    }
+}
+
+fn start_element(start: &BytesStart) -> Result<XmlElement> {
+    let mut attributes = Vec::new();
+    for attr in start.attributes() {
+        let attr = attr.context("Malformed XML attribute")?;
+        let value = attr
+            .unescape_value()
+            .context("Malformed XML attribute value")?;
+        attributes.push((local_name(attr.key.as_ref()), value.into_owned()));
+    }
+
+    Ok(XmlElement {
+        name: local_name(start.name().as_ref()),
+        attributes,
+        children: Vec::new(),
+    })
+}
+
+/// Parses an XML document into a tree and returns its root element.
+///
+/// Text is kept verbatim (no trimming or normalization), so right-to-left
+/// scripts and combining marks survive a round trip unchanged. Fails on
+/// elements nested deeper than `MAX_DEPTH`.
+pub fn parse(xml: &str) -> Result<XmlElement> {
+    let mut reader = Reader::from_str(xml);
+    let mut stack: Vec<XmlElement> = Vec::new();
+
+    loop {
+        let event = reader
+            .read_event()
+            .with_context(|| format!("Malformed XML at position {}", reader.buffer_position()))?;
+
+        match event {
+            Event::Start(_) | Event::Empty(_) if stack.len() >= MAX_DEPTH => {
+                bail!("XML elements are nested deeper than {} levels", MAX_DEPTH)
+            }
+            Event::Start(start) => stack.push(start_element(&start)?),
+            Event::Empty(start) => {
+                let element = start_element(&start)?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(XmlNode::Element(element)),
+                    None => return Ok(element),
+                }
+            }
+            Event::End(_) => {
+                let element = stack
+                    .pop()
+                    .ok_or_else(|| anyhow!("Unbalanced XML end tag"))?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(XmlNode::Element(element)),
+                    None => return Ok(element),
+                }
+            }
+            Event::Text(text) => {
+                if let Some(parent) = stack.last_mut() {
+                    let text = text.unescape().context("Malformed XML text")?;
+                    parent.children.push(XmlNode::Text(text.into_owned()));
+                }
+            }
+            Event::CData(data) => {
+                if let Some(parent) = stack.last_mut() {
+                    let text = String::from_utf8_lossy(&data).into_owned();
+                    parent.children.push(XmlNode::Text(text));
+                }
+            }
+            Event::Eof => bail!("XML document has no root element"),
+            _ => {}
+        }
+    }
+}
+
+/// Escapes text for use in XML content or attribute values.
+pub fn escape(text: &str) -> Cow<'_, str> {
+    quick_xml::escape::escape(text)
+}
@@ -0,0 +1,376 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use common::{sample_exam, TestContext};
+
+fn bank_question(text: &str) -> Value {
+    json!({
+        "text": text,
+        "description": "Choose the correct word",
+        "marks": 2,
+        "options": [
+            { "text": "الرَّحْمَٰنِ", "is_correct": true },
+            { "text": "الرَّحِيمِ", "is_correct": false }
+        ]
+    })
+}
+
+macro_rules! create_question {
+    ($app:expr, $body:expr) => {{
+        let req = test::TestRequest::post()
+            .uri("/bank/questions")
+            .set_json($body)
+            .to_request();
+        let resp = test::call_service(&$app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let created: Value = test::read_body_json(resp).await;
+        created["id"].as_i64().unwrap()
+    }};
+}
+
+macro_rules! create_exam {
+    ($app:expr, $exam_id:expr) => {{
+        let req = test::TestRequest::post()
+            .uri("/exam/create")
+            .set_json(sample_exam($exam_id))
+            .to_request();
+        assert!(test::call_service(&$app, req).await.status().is_success());
+    }};
+}
+
+#[sqlx::test]
+async fn bank_questions_can_be_created_updated_and_deleted(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let id = create_question!(app, bank_question("بِسْمِ اللَّهِ ___"));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/bank/questions/{}", id))
+        .to_request();
+    let fetched: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(fetched["text"], "بِسْمِ اللَّهِ ___");
+    assert_eq!(fetched["marks"], 2);
+    assert_eq!(fetched["options"].as_array().unwrap().len(), 2);
+    assert_eq!(fetched["used_in"], json!([]));
+    assert!(fetched["created_at"].is_string());
+
+    let mut changed = bank_question("بِسْمِ اللَّهِ الرَّحْمَٰنِ ___");
+    changed["marks"] = json!(4);
+    changed["options"]
+        .as_array_mut()
+        .unwrap()
+        .push(json!({ "text": "الْعَظِيمِ", "is_correct": false }));
+    let req = test::TestRequest::put()
+        .uri(&format!("/bank/questions/{}", id))
+        .set_json(&changed)
+        .to_request();
+    let updated: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(updated["text"], "بِسْمِ اللَّهِ الرَّحْمَٰنِ ___");
+    assert_eq!(updated["marks"], 4);
+    assert_eq!(updated["options"].as_array().unwrap().len(), 3);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/bank/questions/{}", id))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/bank/questions/{}", id))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[sqlx::test]
+async fn invalid_bank_questions_are_rejected(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let mut no_correct = bank_question("سؤال");
+    no_correct["options"][0]["is_correct"] = json!(false);
+    let mut one_option = bank_question("سؤال");
+    one_option["options"].as_array_mut().unwrap().pop();
+
+    for body in [no_correct, one_option, bank_question("  ")] {
+        let req = test::TestRequest::post()
+            .uri("/bank/questions")
+            .set_json(&body)
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
+
+#[sqlx::test]
+async fn search_matches_text_and_options_and_pages(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    create_question!(app, bank_question("Which word completes the verse?"));
+    create_question!(app, bank_question("Name the surah"));
+    let mut third = bank_question("Pick the meaning");
+    third["options"][1]["text"] = json!("100% sure");
+    create_question!(app, third);
+
+    let req = test::TestRequest::get()
+        .uri("/bank/questions?q=VERSE")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["total"], 1);
+    assert_eq!(
+        page["questions"][0]["text"],
+        "Which word completes the verse?"
+    );
+
+    // Wildcards in the search term are matched literally.
+    let req = test::TestRequest::get()
+        .uri("/bank/questions?q=100%25")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["total"], 1);
+    assert_eq!(page["questions"][0]["text"], "Pick the meaning");
+
+    // Matching on option text.
+    let req = test::TestRequest::get()
+        .uri("/bank/questions?q=%D8%A7%D9%84%D8%B1%D9%8E%D9%91%D8%AD%D9%90%D9%8A%D9%85%D9%90")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["total"], 2);
+
+    let req = test::TestRequest::get()
+        .uri("/bank/questions?limit=2&offset=2")
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(page["total"], 3);
+    assert_eq!(page["questions"].as_array().unwrap().len(), 1);
+    assert_eq!(
+        page["questions"][0]["text"],
+        "Which word completes the verse?"
+    );
+}
+
+#[sqlx::test]
+async fn one_question_can_be_used_by_two_exams(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    create_exam!(app, 1);
+    create_exam!(app, 2);
+    let id = create_question!(app, bank_question("بِسْمِ اللَّهِ ___"));
+
+    for exam_id in [1, 2] {
+        let req = test::TestRequest::post()
+            .uri(&format!(
+                "/exam/{}/sections/{}/questions",
+                exam_id,
+                exam_id * 100
+            ))
+            .set_json(json!({ "question_ids": [id] }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/exam/{}", exam_id))
+            .to_request();
+        let exam: Value = test::call_and_read_body_json(&app, req).await;
+        let questions = exam["sections"][0]["questions"].as_array().unwrap();
+        assert_eq!(questions.len(), 3);
+        // Linked questions come after the ones the section already had.
+        assert_eq!(questions[2]["id"], id);
+        assert_eq!(questions[2]["section_id"], exam_id * 100);
+        assert_eq!(questions[2]["options"].as_array().unwrap().len(), 2);
+    }
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/bank/questions/{}", id))
+        .to_request();
+    let fetched: Value = test::call_and_read_body_json(&app, req).await;
+    let used_in: Vec<i64> = fetched["used_in"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|u| u["exam_id"].as_i64().unwrap())
+        .collect();
+    assert_eq!(used_in, vec![1, 2]);
+    assert_eq!(fetched["used_in"][0]["section_title"], "القسم الأول");
+
+    // Deleting one exam leaves the question in the bank and in the other exam.
+    let req = test::TestRequest::delete()
+        .uri("/exam/delete/1")
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/bank/questions/{}", id))
+        .to_request();
+    let fetched: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(fetched["used_in"].as_array().unwrap().len(), 1);
+    assert_eq!(fetched["used_in"][0]["exam_id"], 2);
+
+    // Unlinking removes it from the exam but keeps it in the bank.
+    let req = test::TestRequest::delete()
+        .uri(&format!("/exam/2/sections/200/questions/{}", id))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get().uri("/exam/2").to_request();
+    let exam: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        exam["sections"][0]["questions"].as_array().unwrap().len(),
+        2
+    );
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/bank/questions/{}", id))
+        .to_request();
+    let fetched: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(fetched["used_in"], json!([]));
+}
+
+#[sqlx::test]
+async fn editing_one_exam_leaves_shared_questions_and_other_exams_alone(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    create_exam!(app, 1);
+    create_exam!(app, 2);
+    let id = create_question!(app, bank_question("بِسْمِ اللَّهِ ___"));
+    for exam_id in [1, 2] {
+        let req = test::TestRequest::post()
+            .uri(&format!(
+                "/exam/{}/sections/{}/questions",
+                exam_id,
+                exam_id * 100
+            ))
+            .set_json(json!({ "question_ids": [id] }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+    let req = test::TestRequest::get()
+        .uri(&format!("/bank/questions/{}", id))
+        .to_request();
+    let fetched: Value = test::call_and_read_body_json(&app, req).await;
+    let option_ids: Vec<&Value> = fetched["options"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|o| &o["id"])
+        .collect();
+
+    // Exam 2 names a section of exam 1 and the options of the shared question.
+    let mut edit = sample_exam(2);
+    edit["delete"] = json!({
+        "section_ids": [100],
+        "question_ids": [],
+        "option_ids": option_ids
+    });
+    let req = test::TestRequest::put()
+        .uri("/exam/edit")
+        .set_json(&edit)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get().uri("/exam/1").to_request();
+    let exam: Value = test::call_and_read_body_json(&app, req).await;
+    let questions = exam["sections"][0]["questions"].as_array().unwrap();
+    assert_eq!(exam["sections"][0]["id"], 100);
+    assert_eq!(questions.len(), 3);
+    assert_eq!(questions[2]["options"].as_array().unwrap().len(), 2);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/bank/questions/{}", id))
+        .to_request();
+    let fetched: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(fetched["options"].as_array().unwrap().len(), 2);
+}
+
+#[sqlx::test]
+async fn linking_checks_the_section_and_questions(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    create_exam!(app, 1);
+    create_exam!(app, 2);
+    let id = create_question!(app, bank_question("بِسْمِ اللَّهِ ___"));
+
+    // Section 200 belongs to exam 2.
+    let req = test::TestRequest::post()
+        .uri("/exam/1/sections/200/questions")
+        .set_json(json!({ "question_ids": [id] }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+
+    let req = test::TestRequest::post()
+        .uri("/exam/1/sections/100/questions")
+        .set_json(json!({ "question_ids": [id, 999999] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["question_ids"], json!([999999]));
+
+    let req = test::TestRequest::get().uri("/exam/1").to_request();
+    let exam: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        exam["sections"][0]["questions"].as_array().unwrap().len(),
+        2
+    );
+}
+
+#[sqlx::test]
+async fn deleting_a_used_question_needs_force(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    create_exam!(app, 1);
+
+    let req = test::TestRequest::delete()
+        .uri("/bank/questions/1001")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["used_in"][0]["exam_id"], 1);
+
+    let req = test::TestRequest::delete()
+        .uri("/bank/questions/1001?force=true")
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get().uri("/exam/1").to_request();
+    let exam: Value = test::call_and_read_body_json(&app, req).await;
+    let questions = exam["sections"][0]["questions"].as_array().unwrap();
+    assert_eq!(questions.len(), 1);
+    assert_eq!(questions[0]["id"], 1002);
+}
+
+#[sqlx::test]
+async fn exam_with_an_empty_section_can_be_fetched(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    create_exam!(app, 1);
+    for id in [1001, 1002] {
+        let req = test::TestRequest::delete()
+            .uri(&format!("/exam/1/sections/100/questions/{}", id))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+
+    let req = test::TestRequest::get().uri("/exam/1").to_request();
+    let exam: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(exam["sections"][0]["title"], "القسم الأول");
+    assert_eq!(exam["sections"][0]["questions"], json!([]));
+}
@@ -0,0 +1,152 @@
+//! A local mock of Gemini's `generateContent` and `streamGenerateContent`
+//! endpoints.
+//!
+//! `services::llm` builds its provider once per process, so every test binary
+//! shares a single mock server. Tests keep their expectations apart by
+//! matching on a marker that only appears in their own prompt.
+
+use serde_json::{json, Value};
+use tokio::sync::OnceCell;
+use wiremock::matchers::{body_string_contains, method, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+pub const MODEL_NAME: &str = "gemini-test";
+
+static SERVER: OnceCell<MockServer> = OnceCell::const_new();
+
+pub async fn server() -> &'static MockServer {
+    SERVER.get_or_init(MockServer::start).await
+}
+
+/// A body for the `/mcq/quran` routes whose prompt contains `marker`.
+pub fn quran_request(marker: &str) -> Value {
+    json!({
+        "question": format!("{} بِسْمِ اللَّهِ الرَّحْمَٰنِ ___", marker),
+        "correct_answer": "الرَّحِيمِ"
+    })
+}
+
+/// A valid reply to a [`quran_request`] on the thematic route.
+pub fn thematic_output() -> Value {
+    json!({
+        "correct_answer": ["الرَّحِيمِ"],
+        "thematic_distractors": ["الرَّحْمَنِ", "الْكَرِيمِ", "الْعَظِيمِ"]
+    })
+}
+
+/// Builds a body shaped like a successful Gemini `generateContent` response.
+pub fn generate_content_body(texts: &[&str]) -> Value {
+    let candidates: Vec<Value> = texts
+        .iter()
+        .enumerate()
+        .map(|(index, text)| {
+            json!({
+                "content": { "parts": [{ "text": text }], "role": "model" },
+                "finishReason": "STOP",
+                "index": index
+            })
+        })
+        .collect();
+
+    json!({
+        "candidates": candidates,
+        "usageMetadata": {
+            "promptTokenCount": 42,
+            "candidatesTokenCount": 17,
+            "totalTokenCount": 59
+        },
+        "modelVersion": MODEL_NAME
+    })
+}
+
+/// Replies with `text` to any prompt that contains `marker`.
+pub async fn respond_with_text(marker: &str, text: &str) {
+    respond_with(
+        marker,
+        ResponseTemplate::new(200).set_body_json(generate_content_body(&[text])),
+    )
+    .await;
+}
+
+/// Replies with an arbitrary response to any prompt that contains `marker`.
+pub async fn respond_with(marker: &str, response: ResponseTemplate) {
+    Mock::given(method("POST"))
+        .and(path_regex(r":generateContent$"))
+        .and(body_string_contains(marker))
+        .respond_with(response)
+        .mount(server().await)
+        .await;
+}
+
+/// Streams `chunks` as server-sent events, like Gemini's
+/// `streamGenerateContent`, to any prompt that contains `marker`.
+pub async fn respond_with_stream(marker: &str, chunks: &[&str]) {
+    let events: String = chunks
+        .iter()
+        .map(|chunk| format!("data: {}\r\n\r\n", generate_content_body(&[chunk])))
+        .collect();
+    respond_to_stream_with(
+        marker,
+        ResponseTemplate::new(200).set_body_raw(events, "text/event-stream"),
+    )
+    .await;
+}
+
+/// Replies to streaming requests whose prompt contains `marker`.
+pub async fn respond_to_stream_with(marker: &str, response: ResponseTemplate) {
+    Mock::given(method("POST"))
+        .and(path_regex(r":streamGenerateContent$"))
+        .and(body_string_contains(marker))
+        .respond_with(response)
+        .mount(server().await)
+        .await;
+}
+
+/// Replies with `text` to follow-up prompts, e.g. after a reply that could
+/// not be parsed: prompts that contain both `marker` and `phrase`. Takes
+/// precedence over [`respond_with`].
+pub async fn respond_to_follow_up_with_text(marker: &str, phrase: &str, text: &str) {
+    Mock::given(method("POST"))
+        .and(path_regex(r":generateContent$"))
+        .and(body_string_contains(marker))
+        .and(body_string_contains(phrase))
+        .respond_with(ResponseTemplate::new(200).set_body_json(generate_content_body(&[text])))
+        .with_priority(1)
+        .mount(server().await)
+        .await;
+}
+
+/// Returns the request bodies received so far whose prompt contains `marker`.
+pub async fn received_bodies(marker: &str) -> Vec<Value> {
+    server()
+        .await
+        .received_requests()
+        .await
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|request| serde_json::from_slice::<Value>(&request.body).ok())
+        .filter(|body| {
+            body["contents"][0]["parts"][0]["text"]
+                .as_str()
+                .is_some_and(|prompt| prompt.contains(marker))
+        })
+        .collect()
+}
+
+/// Returns the prompts received so far that contain `marker`.
+pub async fn received_prompts(marker: &str) -> Vec<String> {
+    server()
+        .await
+        .received_requests()
+        .await
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|request| serde_json::from_slice::<Value>(&request.body).ok())
+        .filter_map(|body| {
+            body["contents"][0]["parts"][0]["text"]
+                .as_str()
+                .map(str::to_string)
+        })
+        .filter(|prompt| prompt.contains(marker))
+        .collect()
+}
@@ -0,0 +1,216 @@
+//! Shared harness for the integration tests.
+//!
+//! Every test gets its own Postgres database from `#[sqlx::test]` and its own
+//! Redis stand-in. The LLM mock and the environment the services read their
+//! configuration from are process-wide, see [`llm`].
+
+#![allow(dead_code)]
+
+pub mod llm;
+pub mod redis;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::{web, App};
+use ilmiya::conn::{DbClient, RedisClient};
+use ilmiya::model::state::AppState;
+use ilmiya::routes;
+use ilmiya::services::cache::LlmCache;
+use ilmiya::services::jobs::JobQueue;
+use ilmiya::services::llm::LlmClient;
+use ilmiya::services::print::PdfRenderer;
+use ilmiya::services::prompts::PromptStore;
+use ilmiya::services::usage::{Quotas, UsageTracker};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use tokio::sync::OnceCell;
+
+use self::redis::RedisStub;
+
+static ENV: OnceCell<()> = OnceCell::const_new();
+
+/// Bearer token of the admin routes.
+pub const ADMIN_TOKEN: &str = "test-admin-token";
+
+/// Prompt templates used by the tests. Each one names its task so tests can
+/// assert which template a route rendered.
+fn prompt_templates() -> Value {
+    let template = |task: &str| {
+        format!(
+            "TASK={} QUESTION={{question}} ANSWER={{correct_answer}}",
+            task
+        )
+    };
+
+    json!({
+        "prompt_context_urdu": template("context_urdu"),
+        "prompt_quranic_verse": template("quranic_verse"),
+        "prompt_quranic_verse_distractor_collection": template("collection"),
+        "prompt_quranic_verse_diacritic_distractor": template("diacritic"),
+        "prompt_quranic_verse_phonetic_distractor": template("phonetic"),
+        "prompt_quranic_verse_morfological_distractor": template("morphological"),
+        "prompt_quranic_verse_grammatical_distractor": template("grammatical"),
+        "prompt_quranic_verse_alternate_verse_distractor": template("alternate_verse"),
+        "prompt_quranic_verse_thematic_distractor": template("thematic"),
+        "prompt_quranic_verse_collocational_distractor": template("collocational"),
+    })
+}
+
+/// Points the services at the mock LLM server and the test prompt templates.
+///
+/// The services read their configuration lazily and only once, so this has
+/// to run before the first request of every test binary.
+async fn init_env() {
+    ENV.get_or_init(|| async {
+        let server = llm::server().await;
+        let dir = std::env::temp_dir().join(format!("ilmiya-tests-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("Failed to create test directory");
+
+        let prompts_path = dir.join("prompts.json");
+        std::fs::write(&prompts_path, prompt_templates().to_string())
+            .expect("Failed to write prompt templates");
+
+        let vars = [
+            ("TEXT_GENERATION_URL", server.uri()),
+            ("TEXT_GENERATION_MODEL", llm::MODEL_NAME.to_string()),
+            ("TEXT_GENERATION_API_KEY", "test-key".to_string()),
+            ("PROMPT_TEMPLATE_PATH", prompts_path.display().to_string()),
+            // The same mock server stands in for the HTML-to-PDF service.
+            ("PDF_RENDER_URL", server.uri()),
+            ("PDF_RENDER_TIMEOUT_SECS", "1".to_string()),
+            // Keep retries of failing mock responses quick.
+            ("LLM_RETRY_BASE_MS", "1".to_string()),
+            ("JOB_RETRY_BASE_MS", "1".to_string()),
+            ("ADMIN_API_TOKEN", ADMIN_TOKEN.to_string()),
+        ];
+
+        let env_path = dir.join(".env");
+        let env_file: String = vars
+            .iter()
+            .map(|(key, value)| format!("{}={}\n", key, value))
+            .collect();
+        std::fs::write(&env_path, env_file).expect("Failed to write .env file");
+
+        std::env::set_var("ENV_PATH", &env_path);
+        for (key, value) in vars {
+            std::env::set_var(key, value);
+        }
+    })
+    .await;
+}
+
+pub struct TestContext {
+    pub state: web::Data<AppState>,
+    pub redis: RedisStub,
+}
+
+impl TestContext {
+    pub async fn new(pool: PgPool) -> Self {
+        init_env().await;
+        let quotas = Quotas::from_env().expect("Failed to read quotas");
+        Self::with_quotas(pool, quotas).await
+    }
+
+    /// Like [`TestContext::new`], with token quotas instead of none.
+    pub async fn with_quotas(pool: PgPool, quotas: Quotas) -> Self {
+        init_env().await;
+
+        let redis = RedisStub::start();
+        let redis_client =
+            RedisClient::from_url(&redis.url).expect("Failed to create Redis client");
+
+        let usage_tracker = UsageTracker::new(pool.clone(), quotas);
+        let llm_client =
+            LlmClient::from_env(usage_tracker.clone()).expect("Failed to create LLM client");
+
+        let llm_cache =
+            LlmCache::from_env(redis_client.clone()).expect("Failed to create LLM cache");
+        let job_queue =
+            JobQueue::from_env(redis_client.clone()).expect("Failed to create job queue");
+        let pdf_renderer = PdfRenderer::from_env().expect("Failed to create PDF renderer");
+
+        // Seeded from the test template file into this test's database.
+        let prompts = PromptStore::load(pool.clone())
+            .await
+            .expect("Failed to load prompt templates");
+
+        let state = web::Data::new(AppState {
+            db_client: DbClient { pool },
+            redis_client,
+            llm_client,
+            llm_cache,
+            job_queue,
+            usage_tracker,
+            prompts,
+            pdf_renderer,
+        });
+
+        Self { state, redis }
+    }
+
+    /// Builds the application the same way `main` does, minus the server.
+    pub fn app(
+        &self,
+    ) -> App<
+        impl ServiceFactory<
+            ServiceRequest,
+            Config = (),
+            Response = ServiceResponse<impl MessageBody>,
+            Error = actix_web::Error,
+            InitError = (),
+        >,
+    > {
+        App::new()
+            .app_data(self.state.clone())
+            .configure(routes::config_routes)
+    }
+}
+
+/// A complete exam payload as accepted by `/exam/create`.
+pub fn sample_exam(exam_id: i32) -> Value {
+    let description_id = exam_id * 10;
+    let section_id = exam_id * 100;
+    let question_ids = [exam_id * 1000 + 1, exam_id * 1000 + 2];
+
+    json!({
+        "exam_id": { "id": exam_id },
+        "description": {
+            "id": description_id,
+            "exam_id": exam_id,
+            "title": "Surah Al-Fatiha",
+            "description": "Fill in the missing word",
+            "duration": 30,
+            "passing_score": 60
+        },
+        "sections": [{
+            "id": section_id,
+            "exam_description_id": description_id,
+            "title": "القسم الأول",
+            "questions": [
+                {
+                    "id": question_ids[0],
+                    "section_id": section_id,
+                    "text": "الْحَمْدُ لِلَّهِ رَبِّ ___",
+                    "description": "Complete the verse",
+                    "marks": 5,
+                    "options": [
+                        { "id": question_ids[0] * 10 + 1, "question_id": question_ids[0], "text": "الْعَالَمِينَ", "is_correct": true },
+                        { "id": question_ids[0] * 10 + 2, "question_id": question_ids[0], "text": "الْعَالِمِينَ", "is_correct": false }
+                    ]
+                },
+                {
+                    "id": question_ids[1],
+                    "section_id": section_id,
+                    "text": "مَالِكِ يَوْمِ ___",
+                    "description": null,
+                    "marks": 3,
+                    "options": [
+                        { "id": question_ids[1] * 10 + 1, "question_id": question_ids[1], "text": "الدِّينِ", "is_correct": true },
+                        { "id": question_ids[1] * 10 + 2, "question_id": question_ids[1], "text": "الدَّيْنِ", "is_correct": false },
+                        { "id": question_ids[1] * 10 + 3, "question_id": question_ids[1], "text": "الْقِيَامَةِ", "is_correct": false }
+                    ]
+                }
+            ]
+        }]
+    })
+}
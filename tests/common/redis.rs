@@ -0,0 +1,453 @@
+//! A minimal in-process Redis stand-in speaking RESP2 over TCP.
+//!
+//! It implements just enough of the protocol for `deadpool-redis` and the
+//! commands the application issues. Unknown commands are acknowledged with
+//! `+OK` so connection handshakes (e.g. `CLIENT SETINFO`) succeed.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+type Store = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+/// Expiry in seconds given with `SET ... EX`, per key. Keys never expire.
+type Ttls = Arc<Mutex<HashMap<String, u64>>>;
+
+/// Lists, head first.
+type Lists = Arc<Mutex<HashMap<String, VecDeque<Vec<u8>>>>>;
+
+/// Sorted sets, as members and their scores.
+type SortedSets = Arc<Mutex<HashMap<String, HashMap<Vec<u8>, f64>>>>;
+
+/// Hashes, as fields and their values.
+type Hashes = Arc<Mutex<HashMap<String, HashMap<Vec<u8>, Vec<u8>>>>>;
+
+/// Everything the stand-in stores, shared by its connections.
+#[derive(Clone, Default)]
+struct Data {
+    store: Store,
+    ttls: Ttls,
+    lists: Lists,
+    sorted_sets: SortedSets,
+    hashes: Hashes,
+}
+
+pub struct RedisStub {
+    pub url: String,
+    data: Data,
+}
+
+impl RedisStub {
+    /// Starts the stand-in on an ephemeral port in a dedicated thread, so it
+    /// outlives the runtime of the test that created it.
+    pub fn start() -> Self {
+        let data = Data::default();
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        let server_data = data.clone();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("Failed to build Redis stub runtime");
+
+            runtime.block_on(async move {
+                let listener = TcpListener::bind("127.0.0.1:0")
+                    .await
+                    .expect("Failed to bind Redis stub");
+                tx.send(listener.local_addr().unwrap()).unwrap();
+
+                loop {
+                    let Ok((socket, _)) = listener.accept().await else {
+                        break;
+                    };
+                    tokio::spawn(serve(socket, server_data.clone()));
+                }
+            });
+        });
+
+        let addr = rx.recv().expect("Redis stub failed to start");
+        Self {
+            url: format!("redis://{}", addr),
+            data,
+        }
+    }
+
+    pub fn set(&self, key: &str, value: &str) {
+        self.data
+            .store
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), value.as_bytes().to_vec());
+    }
+
+    /// Keys whose name starts with `prefix`.
+    pub fn keys(&self, prefix: &str) -> Vec<String> {
+        self.data
+            .store
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect()
+    }
+
+    /// The expiry the key was last set with, in seconds.
+    pub fn ttl(&self, key: &str) -> Option<u64> {
+        self.data.ttls.lock().unwrap().get(key).copied()
+    }
+
+    /// Removes a key, as if it had expired.
+    pub fn remove(&self, key: &str) {
+        self.data.store.lock().unwrap().remove(key);
+        self.data.ttls.lock().unwrap().remove(key);
+        self.data.lists.lock().unwrap().remove(key);
+        self.data.sorted_sets.lock().unwrap().remove(key);
+        self.data.hashes.lock().unwrap().remove(key);
+    }
+
+    /// Sets a field of a hash.
+    pub fn hash_set(&self, key: &str, field: &str, value: &str) {
+        self.data
+            .hashes
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_default()
+            .insert(field.as_bytes().to_vec(), value.as_bytes().to_vec());
+    }
+
+    /// A field of a hash.
+    pub fn hash_get(&self, key: &str, field: &str) -> Option<String> {
+        self.data
+            .hashes
+            .lock()
+            .unwrap()
+            .get(key)
+            .and_then(|hash| hash.get(field.as_bytes()))
+            .map(|value| String::from_utf8_lossy(value).into_owned())
+    }
+
+    /// Pushes an item onto the head of a list.
+    pub fn push(&self, key: &str, value: &str) {
+        self.data
+            .lists
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_default()
+            .push_front(value.as_bytes().to_vec());
+    }
+
+    /// The items of a list, head first.
+    pub fn list(&self, key: &str) -> Vec<String> {
+        self.data
+            .lists
+            .lock()
+            .unwrap()
+            .get(key)
+            .map(|items| {
+                items
+                    .iter()
+                    .map(|item| String::from_utf8_lossy(item).into_owned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The members of a sorted set, lowest score first.
+    pub fn sorted_set(&self, key: &str) -> Vec<String> {
+        sorted_members(&self.data.sorted_sets, key, f64::INFINITY)
+            .iter()
+            .map(|member| String::from_utf8_lossy(member).into_owned())
+            .collect()
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.data
+            .store
+            .lock()
+            .unwrap()
+            .get(key)
+            .map(|v| String::from_utf8_lossy(v).into_owned())
+    }
+}
+
+async fn serve(socket: TcpStream, data: Data) {
+    let (reader, mut writer) = socket.into_split();
+    let mut reader = BufReader::new(reader);
+
+    while let Some(args) = read_command(&mut reader).await {
+        let name = args.first().map(|name| name.to_ascii_uppercase());
+        let reply = match name.as_deref() {
+            Some(b"BRPOP") => blocking_pop(&args, &data.lists).await,
+            Some(b"BLMOVE") => blocking_move(&args, &data.lists).await,
+            _ => execute(&args, &data),
+        };
+        if writer.write_all(&reply).await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn read_line<R: AsyncBufReadExt + Unpin>(reader: &mut R) -> Option<String> {
+    let mut line = String::new();
+    match reader.read_line(&mut line).await {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line.trim_end_matches("\r\n").to_string()),
+    }
+}
+
+async fn read_command<R: AsyncBufReadExt + Unpin>(reader: &mut R) -> Option<Vec<Vec<u8>>> {
+    let header = read_line(reader).await?;
+    let count: usize = header.strip_prefix('*')?.parse().ok()?;
+
+    let mut args = Vec::with_capacity(count);
+    for _ in 0..count {
+        let len: usize = read_line(reader).await?.strip_prefix('$')?.parse().ok()?;
+        let mut buf = vec![0; len + 2];
+        reader.read_exact(&mut buf).await.ok()?;
+        buf.truncate(len);
+        args.push(buf);
+    }
+    Some(args)
+}
+
+fn bulk(value: &[u8]) -> Vec<u8> {
+    let mut out = format!("${}\r\n", value.len()).into_bytes();
+    out.extend_from_slice(value);
+    out.extend_from_slice(b"\r\n");
+    out
+}
+
+/// `BRPOP key... timeout`, polling the lists until one has an item.
+async fn blocking_pop(args: &[Vec<u8>], lists: &Lists) -> Vec<u8> {
+    let Some((timeout, keys)) = args[1..].split_last() else {
+        return b"-ERR wrong number of arguments\r\n".to_vec();
+    };
+    let timeout: f64 = String::from_utf8_lossy(timeout).parse().unwrap_or(0.0);
+    let deadline = Instant::now() + Duration::from_secs_f64(timeout);
+
+    loop {
+        {
+            let mut lists = lists.lock().unwrap();
+            for key in keys {
+                let key = String::from_utf8_lossy(key).into_owned();
+                if let Some(value) = lists.get_mut(&key).and_then(VecDeque::pop_back) {
+                    let mut reply = b"*2\r\n".to_vec();
+                    reply.extend(bulk(key.as_bytes()));
+                    reply.extend(bulk(&value));
+                    return reply;
+                }
+            }
+        }
+        // A zero timeout blocks forever.
+        if timeout > 0.0 && Instant::now() >= deadline {
+            return b"*-1\r\n".to_vec();
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+/// `BLMOVE source destination RIGHT LEFT timeout`, the only directions the
+/// application uses.
+async fn blocking_move(args: &[Vec<u8>], lists: &Lists) -> Vec<u8> {
+    if args.len() != 6 {
+        return b"-ERR wrong number of arguments\r\n".to_vec();
+    }
+    let source = String::from_utf8_lossy(&args[1]).into_owned();
+    let destination = String::from_utf8_lossy(&args[2]).into_owned();
+    let timeout: f64 = String::from_utf8_lossy(&args[5]).parse().unwrap_or(0.0);
+    let deadline = Instant::now() + Duration::from_secs_f64(timeout);
+
+    loop {
+        {
+            let mut lists = lists.lock().unwrap();
+            if let Some(value) = lists.get_mut(&source).and_then(VecDeque::pop_back) {
+                lists
+                    .entry(destination)
+                    .or_default()
+                    .push_front(value.clone());
+                return bulk(&value);
+            }
+        }
+        if timeout > 0.0 && Instant::now() >= deadline {
+            return b"$-1\r\n".to_vec();
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+/// Members of a sorted set scored at most `max`, lowest score first.
+fn sorted_members(sorted_sets: &SortedSets, key: &str, max: f64) -> Vec<Vec<u8>> {
+    let sorted_sets = sorted_sets.lock().unwrap();
+    let mut members: Vec<_> = sorted_sets
+        .get(key)
+        .into_iter()
+        .flatten()
+        .filter(|(_, score)| **score <= max)
+        .collect();
+    members.sort_by(|a, b| a.1.total_cmp(b.1));
+    members
+        .into_iter()
+        .map(|(member, _)| member.clone())
+        .collect()
+}
+
+fn array(items: &[Vec<u8>]) -> Vec<u8> {
+    let mut out = format!("*{}\r\n", items.len()).into_bytes();
+    for item in items {
+        out.extend(bulk(item));
+    }
+    out
+}
+
+fn execute(args: &[Vec<u8>], data: &Data) -> Vec<u8> {
+    let Data {
+        store,
+        ttls,
+        lists,
+        sorted_sets,
+        hashes,
+    } = data;
+    let Some(name) = args.first() else {
+        return b"-ERR empty command\r\n".to_vec();
+    };
+    let key = || String::from_utf8_lossy(&args[1]).into_owned();
+
+    match name.to_ascii_uppercase().as_slice() {
+        b"PING" => match args.get(1) {
+            Some(message) => bulk(message),
+            None => b"+PONG\r\n".to_vec(),
+        },
+        b"GET" => match store.lock().unwrap().get(&key()) {
+            Some(value) => bulk(value),
+            None => b"$-1\r\n".to_vec(),
+        },
+        b"SET" => {
+            store.lock().unwrap().insert(key(), args[2].clone());
+            let expiry = args[3..]
+                .windows(2)
+                .find(|pair| pair[0].eq_ignore_ascii_case(b"EX"))
+                .and_then(|pair| String::from_utf8_lossy(&pair[1]).parse().ok());
+            match expiry {
+                Some(seconds) => ttls.lock().unwrap().insert(key(), seconds),
+                None => ttls.lock().unwrap().remove(&key()),
+            };
+            b"+OK\r\n".to_vec()
+        }
+        b"SETEX" => {
+            store.lock().unwrap().insert(key(), args[3].clone());
+            if let Ok(seconds) = String::from_utf8_lossy(&args[2]).parse() {
+                ttls.lock().unwrap().insert(key(), seconds);
+            }
+            b"+OK\r\n".to_vec()
+        }
+        b"LPUSH" => {
+            let mut lists = lists.lock().unwrap();
+            let list = lists.entry(key()).or_default();
+            for value in &args[2..] {
+                list.push_front(value.clone());
+            }
+            format!(":{}\r\n", list.len()).into_bytes()
+        }
+        // Only the whole list, `LRANGE key 0 -1`, is supported.
+        b"LRANGE" => {
+            let lists = lists.lock().unwrap();
+            let items: Vec<_> = lists.get(&key()).into_iter().flatten().cloned().collect();
+            array(&items)
+        }
+        // Only a positive count, removing from the head, is supported.
+        b"LREM" => {
+            let count: usize = String::from_utf8_lossy(&args[2]).parse().unwrap_or(0);
+            let mut lists = lists.lock().unwrap();
+            let mut removed = 0;
+            if let Some(list) = lists.get_mut(&key()) {
+                while removed < count {
+                    let Some(index) = list.iter().position(|item| *item == args[3]) else {
+                        break;
+                    };
+                    list.remove(index);
+                    removed += 1;
+                }
+            }
+            format!(":{}\r\n", removed).into_bytes()
+        }
+        b"ZADD" => {
+            let mut sorted_sets = sorted_sets.lock().unwrap();
+            let set = sorted_sets.entry(key()).or_default();
+            let mut added = 0;
+            for pair in args[2..].chunks(2) {
+                let score = String::from_utf8_lossy(&pair[0]).parse().unwrap_or(0.0);
+                if set.insert(pair[1].clone(), score).is_none() {
+                    added += 1;
+                }
+            }
+            format!(":{}\r\n", added).into_bytes()
+        }
+        // Only `ZRANGEBYSCORE key -inf max` is supported.
+        b"ZRANGEBYSCORE" => {
+            let max = String::from_utf8_lossy(&args[3])
+                .parse()
+                .unwrap_or(f64::INFINITY);
+            array(&sorted_members(sorted_sets, &key(), max))
+        }
+        b"ZREM" => {
+            let mut sorted_sets = sorted_sets.lock().unwrap();
+            let removed = match sorted_sets.get_mut(&key()) {
+                Some(set) => args[2..]
+                    .iter()
+                    .filter(|member| set.remove(*member).is_some())
+                    .count(),
+                None => 0,
+            };
+            format!(":{}\r\n", removed).into_bytes()
+        }
+        // `HSET key field value` and `HSETNX key field value`.
+        b"HSET" | b"HSETNX" => {
+            let only_new = name.eq_ignore_ascii_case(b"HSETNX");
+            let mut hashes = hashes.lock().unwrap();
+            let hash = hashes.entry(key()).or_default();
+            let added = if only_new && hash.contains_key(&args[2]) {
+                0
+            } else {
+                usize::from(hash.insert(args[2].clone(), args[3].clone()).is_none())
+            };
+            format!(":{}\r\n", added).into_bytes()
+        }
+        b"HGETALL" => {
+            let hashes = hashes.lock().unwrap();
+            let items: Vec<_> = hashes
+                .get(&key())
+                .into_iter()
+                .flatten()
+                .flat_map(|(field, value)| [field.clone(), value.clone()])
+                .collect();
+            array(&items)
+        }
+        b"HDEL" => {
+            let mut hashes = hashes.lock().unwrap();
+            let removed = match hashes.get_mut(&key()) {
+                Some(hash) => args[2..]
+                    .iter()
+                    .filter(|field| hash.remove(*field).is_some())
+                    .count(),
+                None => 0,
+            };
+            format!(":{}\r\n", removed).into_bytes()
+        }
+        b"DEL" => {
+            let mut store = store.lock().unwrap();
+            let removed = args[1..]
+                .iter()
+                .filter(|k| store.remove(&*String::from_utf8_lossy(k)).is_some())
+                .count();
+            format!(":{}\r\n", removed).into_bytes()
+        }
+        _ => b"+OK\r\n".to_vec(),
+    }
+}
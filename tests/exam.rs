@@ -0,0 +1,154 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use common::{sample_exam, TestContext};
+
+fn sorted_by_id(values: &Value) -> Vec<Value> {
+    let mut values = values.as_array().cloned().unwrap_or_default();
+    values.sort_by_key(|v| v["id"].as_i64());
+    values
+}
+
+#[sqlx::test]
+async fn create_then_fetch_returns_the_same_exam(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let exam = sample_exam(1);
+
+    let req = test::TestRequest::post()
+        .uri("/exam/create")
+        .set_json(&exam)
+        .to_request();
+    let created: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(created, json!(1));
+
+    let req = test::TestRequest::get().uri("/exam/1").to_request();
+    let fetched: Value = test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(fetched["exam_id"], json!({ "id": 1 }));
+    assert_eq!(fetched["description"], exam["description"]);
+
+    let sections = fetched["sections"].as_array().unwrap();
+    assert_eq!(sections.len(), 1);
+    assert_eq!(sections[0]["title"], "القسم الأول");
+
+    let questions = sorted_by_id(&sections[0]["questions"]);
+    let expected = exam["sections"][0]["questions"].as_array().unwrap();
+    assert_eq!(questions.len(), expected.len());
+
+    for (question, expected) in questions.iter().zip(expected) {
+        assert_eq!(question["text"], expected["text"]);
+        assert_eq!(question["marks"], expected["marks"]);
+        assert_eq!(
+            sorted_by_id(&question["options"]),
+            sorted_by_id(&expected["options"])
+        );
+    }
+
+    // Missing descriptions are stored as empty strings.
+    assert_eq!(questions[1]["description"], "");
+}
+
+#[sqlx::test]
+async fn create_rejects_duplicate_exam_id(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    for expected in [StatusCode::OK, StatusCode::INTERNAL_SERVER_ERROR] {
+        let req = test::TestRequest::post()
+            .uri("/exam/create")
+            .set_json(sample_exam(2))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), expected);
+    }
+}
+
+#[sqlx::test]
+async fn edit_deletes_requested_questions_and_options(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let exam = sample_exam(3);
+
+    let req = test::TestRequest::post()
+        .uri("/exam/create")
+        .set_json(&exam)
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let mut edit = exam.clone();
+    edit["delete"] = json!({
+        "section_ids": [],
+        "question_ids": [3002],
+        "option_ids": [30012]
+    });
+
+    let req = test::TestRequest::put()
+        .uri("/exam/edit")
+        .set_json(&edit)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get().uri("/exam/3").to_request();
+    let fetched: Value = test::call_and_read_body_json(&app, req).await;
+
+    let questions = fetched["sections"][0]["questions"].as_array().unwrap();
+    assert_eq!(questions.len(), 1);
+    assert_eq!(questions[0]["id"], 3001);
+
+    let options = questions[0]["options"].as_array().unwrap();
+    assert_eq!(options.len(), 1);
+    assert_eq!(options[0]["id"], 30011);
+}
+
+#[sqlx::test]
+async fn delete_removes_the_exam(pool: PgPool) {
+    let ctx = TestContext::new(pool.clone()).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/exam/create")
+        .set_json(sample_exam(4))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let req = test::TestRequest::delete()
+        .uri("/exam/delete/4")
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body, "Exam deleted successfully");
+
+    let req = test::TestRequest::get().uri("/exam/4").to_request();
+    assert!(!test::call_service(&app, req).await.status().is_success());
+
+    // The questions stay in the question bank, only their links are gone.
+    let links: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM section_questions")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(links, 0);
+
+    let options: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM options")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(options, 5);
+}
+
+#[sqlx::test]
+async fn fetch_and_delete_reject_non_numeric_ids(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::get().uri("/exam/abc").to_request();
+    assert!(!test::call_service(&app, req).await.status().is_success());
+
+    let req = test::TestRequest::delete()
+        .uri("/exam/delete/abc")
+        .to_request();
+    assert!(!test::call_service(&app, req).await.status().is_success());
+}
@@ -0,0 +1,197 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use common::{llm, TestContext};
+
+/// Adds the controls to a request body.
+fn with_controls(mut body: Value, controls: &Value) -> Value {
+    body.as_object_mut()
+        .unwrap()
+        .extend(controls.as_object().unwrap().clone());
+    body
+}
+
+fn thematic_request(marker: &str, controls: Value) -> Value {
+    with_controls(llm::quran_request(marker), &controls)
+}
+
+#[sqlx::test]
+async fn controls_reach_the_template_and_the_provider(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/prompts/templates")
+        .set_json(json!({
+            "task": "quranic_verse_distractor",
+            "language": "arabic",
+            "distractor_type": "thematic",
+            "body": "DIFFICULTY={{ difficulty or \"any\" }} COUNT={{ num_distractors or \"any\" }} \
+                     {{ question }} {{ correct_answer }}",
+            "activate": true
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let marker = "generation-controls";
+    llm::respond_with_text(marker, &llm::thematic_output().to_string()).await;
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/thematic")
+        .set_json(thematic_request(
+            marker,
+            json!({
+                "num_distractors": 2,
+                "difficulty": "hard",
+                "temperature": 0.25,
+                "seed": 42,
+                "max_output_tokens": 512
+            }),
+        ))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["thematic_distractors"], json!(["الرَّحْمَنِ", "الْكَرِيمِ"]));
+
+    let bodies = llm::received_bodies(marker).await;
+    assert_eq!(bodies.len(), 1);
+    let prompt = bodies[0]["contents"][0]["parts"][0]["text"]
+        .as_str()
+        .unwrap();
+    assert!(prompt.starts_with("DIFFICULTY=hard COUNT=2 "), "{}", prompt);
+    let config = &bodies[0]["generationConfig"];
+    assert_eq!(config["temperature"], 0.25);
+    assert_eq!(config["seed"], 42);
+    assert_eq!(config["maxOutputTokens"], 512);
+
+    // Without controls the template falls back and the defaults apply.
+    let marker = "generation-controls-defaults";
+    llm::respond_with_text(marker, &llm::thematic_output().to_string()).await;
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/thematic")
+        .set_json(thematic_request(marker, json!({})))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let bodies = llm::received_bodies(marker).await;
+    let prompt = bodies[0]["contents"][0]["parts"][0]["text"]
+        .as_str()
+        .unwrap();
+    assert!(
+        prompt.starts_with("DIFFICULTY=any COUNT=any "),
+        "{}",
+        prompt
+    );
+    let config = &bodies[0]["generationConfig"];
+    assert_eq!(config["temperature"], 0.7);
+    assert!(config.get("seed").is_none());
+    assert!(config.get("maxOutputTokens").is_none());
+}
+
+#[sqlx::test]
+async fn sequential_samples_each_get_their_own_seed(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "seeded-sequential-sampling";
+    llm::respond_with_text(marker, &llm::thematic_output().to_string()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/thematic?candidates=3&sampling=sequential")
+        .set_json(thematic_request(
+            marker,
+            json!({ "seed": 2_147_483_646u64 }),
+        ))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Counted up from the given seed, wrapping around past the largest one.
+    let seeds: Vec<Value> = llm::received_bodies(marker)
+        .await
+        .iter()
+        .map(|body| body["generationConfig"]["seed"].clone())
+        .collect();
+    assert_eq!(
+        seeds,
+        [json!(2_147_483_646u64), json!(2_147_483_647u64), json!(0)]
+    );
+}
+
+#[sqlx::test]
+async fn controls_outside_the_limits_are_rejected(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "generation-controls-limits";
+
+    let invalid = [
+        json!({ "num_distractors": 0 }),
+        json!({ "num_distractors": 11 }),
+        json!({ "difficulty": "impossible" }),
+        json!({ "temperature": 2.5 }),
+        json!({ "temperature": -0.1 }),
+        json!({ "seed": 2_147_483_648u64 }),
+        json!({ "max_output_tokens": 0 }),
+        json!({ "max_output_tokens": 100_000 }),
+    ];
+    for controls in invalid {
+        let req = test::TestRequest::post()
+            .uri("/mcq/quran/thematic")
+            .set_json(thematic_request(marker, controls.clone()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(
+            resp.status(),
+            StatusCode::BAD_REQUEST,
+            "accepted {}",
+            controls
+        );
+
+        let context = json!({
+            "question": format!("{} لاہور پاکستان کا ___ ہے", marker),
+            "correct_answer": "شہر",
+            "language": "urdu"
+        });
+        let context = with_controls(context, &controls);
+        let req = test::TestRequest::post()
+            .uri("/mcq/options/context")
+            .set_json(&context)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(
+            resp.status(),
+            StatusCode::BAD_REQUEST,
+            "accepted {}",
+            controls
+        );
+    }
+    assert!(llm::received_prompts(marker).await.is_empty());
+}
+
+#[sqlx::test]
+async fn too_few_distractors_fail_the_generation(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    // The model keeps returning the same three distractors.
+    let marker = "generation-controls-short";
+    llm::respond_with_text(marker, &llm::thematic_output().to_string()).await;
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/thematic")
+        .set_json(thematic_request(marker, json!({ "num_distractors": 5 })))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+    let body = test::read_body(resp).await;
+    assert_eq!(
+        std::str::from_utf8(&body).unwrap(),
+        "The model returned 3 of the 5 thematic_distractors asked for"
+    );
+    // The model was asked for the missing ones before giving up.
+    assert!(llm::received_prompts(marker).await.len() > 1);
+}
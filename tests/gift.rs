@@ -0,0 +1,198 @@
+mod common;
+
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+use serde_json::Value;
+use sqlx::PgPool;
+
+use common::{sample_exam, TestContext};
+
+#[sqlx::test]
+async fn export_produces_gift_text(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/exam/create")
+        .set_json(sample_exam(1))
+        .to_request();
+    test::call_service(&app, req).await;
+
+    let req = test::TestRequest::get()
+        .uri("/exam/1/export?format=gift")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get(header::CONTENT_TYPE).unwrap(),
+        "text/plain; charset=utf-8"
+    );
+
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(body.contains("$CATEGORY: $course$/top/القسم الأول"));
+    assert!(body.contains("=الْعَالَمِينَ"));
+    assert!(body.contains("~الْعَالِمِينَ"));
+    assert!(body.contains("####Complete the verse"));
+}
+
+#[sqlx::test]
+async fn exported_gift_imports_back(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/exam/create")
+        .set_json(sample_exam(1))
+        .to_request();
+    test::call_service(&app, req).await;
+
+    let req = test::TestRequest::get()
+        .uri("/exam/1/export?format=gift")
+        .to_request();
+    let gift = test::call_and_read_body(&app, req).await;
+
+    let req = test::TestRequest::post()
+        .uri("/exam/import?format=gift&title=Al-Fatiha")
+        .set_payload(gift)
+        .to_request();
+    let imported: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(imported["report"]["imported"], 2);
+    assert_eq!(imported["report"]["skipped"], 0);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/exam/{}", imported["exam_id"]))
+        .to_request();
+    let exam: Value = test::call_and_read_body_json(&app, req).await;
+
+    let req = test::TestRequest::get().uri("/exam/1").to_request();
+    let original: Value = test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(exam["description"]["title"], "Al-Fatiha");
+    assert_eq!(
+        exam["sections"][0]["title"],
+        original["sections"][0]["title"]
+    );
+
+    for (question, expected) in exam["sections"][0]["questions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .zip(original["sections"][0]["questions"].as_array().unwrap())
+    {
+        assert_eq!(question["text"], expected["text"]);
+        assert_eq!(question["description"], expected["description"]);
+
+        let options: Vec<(&Value, &Value)> = question["options"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|o| (&o["text"], &o["is_correct"]))
+            .collect();
+        let expected: Vec<(&Value, &Value)> = expected["options"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|o| (&o["text"], &o["is_correct"]))
+            .collect();
+        assert_eq!(options, expected);
+    }
+}
+
+#[sqlx::test]
+async fn import_reports_skipped_questions(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let gift = r#"// Vocabulary drill
+$CATEGORY: $course$/top/Vocabulary
+
+::Capital::The capital of Egypt is {=Cairo ~Alexandria#Close, but no ~Giza}.
+
+::Sun::The sun rises in the east.{T}
+
+::Essay::Describe your favourite surah.{}
+
+::Year::In which year was the Hijra? {#622}
+
+::Pairs::Match the words {
+  =book -> كتاب
+  =pen -> قلم
+}
+
+::Colour::Name a primary colour. {=red =blue =yellow}
+"#;
+
+    let req = test::TestRequest::post()
+        .uri("/exam/import?format=gift")
+        .set_payload(gift)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let imported: Value = test::read_body_json(resp).await;
+
+    let report = &imported["report"];
+    assert_eq!(report["imported"], 3);
+    assert_eq!(report["skipped"], 3);
+
+    let skipped: Vec<(&str, &str)> = report["issues"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|issue| issue["skipped"] == true)
+        .map(|issue| {
+            (
+                issue["name"].as_str().unwrap(),
+                issue["question_type"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        skipped,
+        [
+            ("Essay", "essay"),
+            ("Year", "numerical"),
+            ("Pairs", "matching")
+        ]
+    );
+    assert!(report["issues"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|issue| issue["name"] == "Capital" && issue["skipped"] == false));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/exam/{}", imported["exam_id"]))
+        .to_request();
+    let exam: Value = test::call_and_read_body_json(&app, req).await;
+
+    let section = &exam["sections"][0];
+    assert_eq!(section["title"], "Vocabulary");
+    let questions = section["questions"].as_array().unwrap();
+    assert_eq!(questions.len(), 3);
+
+    assert_eq!(questions[0]["text"], "The capital of Egypt is ___.");
+    assert_eq!(questions[1]["options"][0]["text"], "True");
+    assert_eq!(questions[1]["options"][0]["is_correct"], true);
+    assert!(questions[2]["options"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|o| o["is_correct"] == true));
+}
+
+#[sqlx::test]
+async fn import_with_nothing_usable_is_rejected(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/exam/import?format=gift")
+        .set_payload("Write an essay about patience.{}")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let body: Value = test::read_body_json(resp).await;
+    assert!(body["exam_id"].is_null());
+    assert_eq!(body["report"]["issues"][0]["question_type"], "essay");
+}
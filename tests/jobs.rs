@@ -0,0 +1,264 @@
+mod common;
+
+use std::time::Duration;
+
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+use chrono::Utc;
+use ilmiya::routes::jobs::start_workers;
+use ilmiya::services::jobs::{DELAYED_KEY, PROCESSING_KEY, QUEUE_KEY, TAKEN_KEY};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use wiremock::ResponseTemplate;
+
+use common::{llm, TestContext};
+
+async fn enqueue<S, B>(app: &S, uri: &str, body: Value) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let req = test::TestRequest::post()
+        .uri(uri)
+        .set_json(body)
+        .to_request();
+    let resp = test::call_service(app, req).await;
+    let status = resp.status();
+    if status == StatusCode::ACCEPTED {
+        let location = resp.headers().get(header::LOCATION).unwrap().clone();
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(location, body["location"].as_str().unwrap());
+        (status, body)
+    } else {
+        (status, Value::Null)
+    }
+}
+
+/// Polls the job until it has finished.
+async fn wait_for_job<S, B>(app: &S, id: &str) -> Value
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    for _ in 0..200 {
+        let req = test::TestRequest::get()
+            .uri(&format!("/jobs/{}", id))
+            .to_request();
+        let job: Value = test::call_and_read_body_json(app, req).await;
+        if job["status"] == "succeeded" || job["status"] == "failed" {
+            return job;
+        }
+        tokio::time::sleep(Duration::from_millis(25)).await;
+    }
+    panic!("Job {} did not finish", id);
+}
+
+/// Waits until the finished job has been taken off `jobs:processing`.
+async fn wait_for_acknowledgement(ctx: &TestContext, id: &str) {
+    for _ in 0..200 {
+        if !ctx.redis.list(PROCESSING_KEY).contains(&id.to_string()) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("Job {} was not acknowledged", id);
+}
+
+#[sqlx::test]
+async fn jobs_run_in_the_background(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "job-background";
+    llm::respond_with_text(marker, &llm::thematic_output().to_string()).await;
+
+    let (status, accepted) =
+        enqueue(&app, "/jobs/mcq/quran/thematic", llm::quran_request(marker)).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!(accepted["status"], "queued");
+    let id = accepted["id"].as_str().unwrap();
+    assert_eq!(accepted["location"], format!("/jobs/{}", id));
+    assert_eq!(ctx.redis.list(QUEUE_KEY), vec![id.to_string()]);
+    assert_eq!(ctx.redis.ttl(&format!("job:{}", id)), Some(60 * 60));
+    assert!(llm::received_prompts(marker).await.is_empty());
+
+    start_workers(ctx.state.clone());
+    let job = wait_for_job(&app, id).await;
+    assert_eq!(job["status"], "succeeded");
+    assert_eq!(job["result"], llm::thematic_output());
+    assert_eq!(job["attempts"], 1);
+    assert_eq!(job["request"]["kind"], "thematic");
+    assert!(job.get("error").is_none());
+    assert_eq!(llm::received_prompts(marker).await.len(), 1);
+    wait_for_acknowledgement(&ctx, id).await;
+    assert!(ctx.redis.hash_get(TAKEN_KEY, id).is_none());
+}
+
+#[sqlx::test]
+async fn failing_jobs_are_retried_then_fail(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "job-retries";
+    llm::respond_with(marker, ResponseTemplate::new(500)).await;
+
+    let (_, accepted) = enqueue(
+        &app,
+        "/jobs/mcq/quran/thematic?max_attempts=2",
+        llm::quran_request(marker),
+    )
+    .await;
+    start_workers(ctx.state.clone());
+    let job = wait_for_job(&app, accepted["id"].as_str().unwrap()).await;
+
+    assert_eq!(job["status"], "failed");
+    assert_eq!(job["attempts"], 2);
+    assert_eq!(job["retry"]["max_attempts"], 2);
+    assert!(job["error"].as_str().unwrap().contains("500"));
+    assert!(job.get("result").is_none());
+    wait_for_acknowledgement(&ctx, accepted["id"].as_str().unwrap()).await;
+    assert!(ctx.redis.sorted_set(DELAYED_KEY).is_empty());
+}
+
+/// Moves the queued job onto the processing list, as a worker does when it
+/// takes the job, before stamping when it did.
+fn take(ctx: &TestContext, id: &str) {
+    ctx.redis.remove(QUEUE_KEY);
+    ctx.redis.push(PROCESSING_KEY, id);
+}
+
+/// Rewrites the stored job as last updated `minutes_ago`.
+fn backdate(ctx: &TestContext, id: &str, minutes_ago: i64, change: impl FnOnce(&mut Value)) {
+    let key = format!("job:{}", id);
+    let mut job: Value = serde_json::from_str(&ctx.redis.get(&key).unwrap()).unwrap();
+    job["updated_at"] = json!(Utc::now() - chrono::Duration::minutes(minutes_ago));
+    change(&mut job);
+    ctx.redis.set(&key, &job.to_string());
+}
+
+/// Takes the queued job as a worker would, then leaves it running as of
+/// `minutes_ago`, as if the worker had died.
+fn interrupt(ctx: &TestContext, id: &str, minutes_ago: i64) {
+    take(ctx, id);
+    let taken = Utc::now() - chrono::Duration::minutes(minutes_ago);
+    ctx.redis
+        .hash_set(TAKEN_KEY, id, &taken.timestamp_millis().to_string());
+    backdate(ctx, id, minutes_ago, |job| {
+        job["status"] = json!("running");
+        job["attempts"] = json!(1);
+    });
+}
+
+#[sqlx::test]
+async fn interrupted_jobs_are_run_again_once_stale(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "job-interrupted";
+    llm::respond_with_text(marker, &llm::thematic_output().to_string()).await;
+
+    let (_, stale) = enqueue(&app, "/jobs/mcq/quran/thematic", llm::quran_request(marker)).await;
+    let stale = stale["id"].as_str().unwrap();
+    interrupt(&ctx, stale, 30);
+    let (_, running) = enqueue(&app, "/jobs/mcq/quran/thematic", llm::quran_request(marker)).await;
+    let running = running["id"].as_str().unwrap();
+    interrupt(&ctx, running, 1);
+
+    start_workers(ctx.state.clone());
+    let job = wait_for_job(&app, stale).await;
+    assert_eq!(job["status"], "succeeded");
+    assert_eq!(job["attempts"], 2);
+    wait_for_acknowledgement(&ctx, stale).await;
+
+    // A job taken recently is assumed to still be running elsewhere.
+    assert_eq!(ctx.redis.list(PROCESSING_KEY), vec![running.to_string()]);
+    let req = test::TestRequest::get()
+        .uri(&format!("/jobs/{}", running))
+        .to_request();
+    let job: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(job["status"], "running");
+    assert_eq!(llm::received_prompts(marker).await.len(), 1);
+}
+
+#[sqlx::test]
+async fn jobs_that_waited_long_are_not_recovered_as_soon_as_taken(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "job-long-wait";
+    llm::respond_with_text(marker, &llm::thematic_output().to_string()).await;
+
+    // Queued half an hour ago, and taken by a worker that has not stamped
+    // it or saved it as running yet.
+    let (_, accepted) = enqueue(&app, "/jobs/mcq/quran/thematic", llm::quran_request(marker)).await;
+    let id = accepted["id"].as_str().unwrap();
+    take(&ctx, id);
+    backdate(&ctx, id, 30, |job| {
+        job["created_at"] = job["updated_at"].clone();
+    });
+
+    start_workers(ctx.state.clone());
+    for _ in 0..300 {
+        if ctx.redis.hash_get(TAKEN_KEY, id).is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(ctx.redis.list(PROCESSING_KEY), vec![id.to_string()]);
+    assert!(ctx.redis.list(QUEUE_KEY).is_empty());
+    assert!(ctx.redis.hash_get(TAKEN_KEY, id).is_some());
+    assert!(llm::received_prompts(marker).await.is_empty());
+}
+
+#[sqlx::test]
+async fn expired_jobs_are_not_run(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "job-expired";
+    llm::respond_with_text(marker, &llm::thematic_output().to_string()).await;
+
+    let (_, accepted) = enqueue(&app, "/jobs/mcq/quran/thematic", llm::quran_request(marker)).await;
+    let id = accepted["id"].as_str().unwrap();
+    ctx.redis.remove(&format!("job:{}", id));
+
+    start_workers(ctx.state.clone());
+    while !ctx.redis.list(QUEUE_KEY).is_empty() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(llm::received_prompts(marker).await.is_empty());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/jobs/{}", id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn invalid_jobs_are_rejected(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let body = llm::quran_request("job-invalid");
+
+    for uri in ["/jobs/mcq/quran/unknown", "/jobs/mcq/quran/context"] {
+        let (status, _) = enqueue(&app, uri, body.clone()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{}", uri);
+    }
+    let (status, _) = enqueue(&app, "/jobs/mcq/quran/thematic?candidates=20", body.clone()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let mut context = body.clone();
+    context["language"] = json!("arabic");
+    let (status, _) = enqueue(&app, "/jobs/mcq/options/context", context).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(ctx.redis.list(QUEUE_KEY).is_empty());
+
+    for uri in [
+        "/jobs/00000000-0000-0000-0000-000000000000",
+        "/jobs/not-a-job",
+    ] {
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND, "{}", uri);
+    }
+}
@@ -0,0 +1,175 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use common::{llm, TestContext};
+use ilmiya::model::llm::{DistractorType, Language};
+use ilmiya::model::prompt::{PromptTask, TemplateKey};
+
+/// The languages of a route in `GET /mcq/capabilities`.
+fn languages(capabilities: &Value, kind: &str) -> Vec<String> {
+    let task = capabilities["tasks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|task| task["kind"] == kind)
+        .unwrap_or_else(|| panic!("No {} task", kind));
+    task["languages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|language| language.as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn series_names_parse_back_into_keys() {
+    for key in [
+        TemplateKey::new(PromptTask::Context, Language::English),
+        TemplateKey::distractor(DistractorType::AlternateVerse, Language::Persian),
+    ] {
+        assert_eq!(key.to_string().parse::<TemplateKey>(), Ok(key));
+    }
+    for name in [
+        "context",
+        "context/klingon",
+        "context/urdu/unknown",
+        "a/b/c/d",
+    ] {
+        assert!(name.parse::<TemplateKey>().is_err(), "parsed {}", name);
+    }
+}
+
+#[sqlx::test]
+async fn capabilities_list_the_languages_with_a_template(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/mcq/capabilities")
+        .to_request();
+    let capabilities: Value = test::call_and_read_body_json(&app, req).await;
+
+    let codes: Vec<&str> = capabilities["languages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|language| language["code"].as_str().unwrap())
+        .collect();
+    assert_eq!(codes, ["ar", "ur", "en", "id", "tr", "fa"]);
+    assert_eq!(capabilities["tasks"].as_array().unwrap().len(), 9);
+    assert_eq!(languages(&capabilities, "context"), ["urdu"]);
+    assert_eq!(languages(&capabilities, "thematic"), ["arabic"]);
+
+    let thematic = capabilities["tasks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|task| task["kind"] == "thematic")
+        .unwrap();
+    assert_eq!(thematic["task"], "quranic_verse_distractor");
+    assert_eq!(thematic["distractor_type"], "thematic");
+    assert_eq!(thematic["endpoint"], "/mcq/quran/thematic");
+}
+
+#[sqlx::test]
+async fn templates_are_looked_up_by_task_and_language(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "language-matrix";
+
+    let context = |language: &str| {
+        test::TestRequest::post()
+            .uri("/mcq/options/context")
+            .set_json(json!({
+                "question": format!("{} The capital of Egypt is ___", marker),
+                "correct_answer": "Cairo",
+                "language": language
+            }))
+            .to_request()
+    };
+
+    // Without a template the request names the languages that have one.
+    let resp = test::call_service(&app, context("en")).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["task"], "context");
+    assert_eq!(body["language"], "english");
+    assert_eq!(body["supported_languages"], json!(["urdu"]));
+    assert!(llm::received_prompts(marker).await.is_empty());
+
+    for (task, language, distractor_type) in [
+        ("context", "english", None),
+        ("quranic_verse_distractor", "turkish", Some("thematic")),
+    ] {
+        let req = test::TestRequest::post()
+            .uri("/prompts/templates")
+            .set_json(json!({
+                "task": task,
+                "language": language,
+                "distractor_type": distractor_type,
+                "body": format!("TASK={} LANG={} {{{{ question }}}} {{{{ correct_answer }}}}", task, language),
+                "activate": true
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/mcq/capabilities")
+        .to_request();
+    let capabilities: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(languages(&capabilities, "context"), ["urdu", "english"]);
+    assert_eq!(languages(&capabilities, "thematic"), ["arabic", "turkish"]);
+
+    let output = json!({
+        "correct_answer": ["Cairo"],
+        "distractors": ["Alexandria", "Giza", "Luxor"],
+        "thematic_distractors": ["الْغَفُورِ", "الْكَرِيمِ", "الْعَظِيمِ"]
+    });
+    llm::respond_with_text(marker, &output.to_string()).await;
+
+    let resp = test::call_service(&app, context("en")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get("X-Prompt-Version").unwrap(),
+        "context/english@1"
+    );
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/thematic")
+        .set_json(json!({
+            "question": format!("{} بِسْمِ اللَّهِ الرَّحْمَٰنِ ___", marker),
+            "correct_answer": "الرَّحِيمِ",
+            "language": "tr"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get("X-Prompt-Version").unwrap(),
+        "quranic_verse_distractor/turkish/thematic@1"
+    );
+
+    let prompts = llm::received_prompts(marker).await;
+    assert!(prompts[0].starts_with("TASK=context LANG=english "));
+    assert!(prompts[1].starts_with("TASK=quranic_verse_distractor LANG=turkish "));
+
+    // The Quran routes stay Arabic by default.
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/thematic")
+        .set_json(json!({
+            "question": format!("{} بِسْمِ اللَّهِ الرَّحْمَٰنِ ___", marker),
+            "correct_answer": "الرَّحِيمِ"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(
+        resp.headers().get("X-Prompt-Version").unwrap(),
+        "quranic_verse_distractor/arabic/thematic@1"
+    );
+}
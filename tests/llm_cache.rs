@@ -0,0 +1,94 @@
+mod common;
+
+use actix_web::http::header;
+use actix_web::test;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use common::{llm, TestContext};
+
+/// Posts to the thematic route and returns the `X-Cache` header and body.
+macro_rules! generate {
+    ($app:expr, $uri:expr, $marker:expr) => {
+        generate!($app, $uri, $marker, None::<&str>)
+    };
+    ($app:expr, $uri:expr, $marker:expr, $cache_control:expr) => {{
+        let mut req = test::TestRequest::post()
+            .uri($uri)
+            .set_json(llm::quran_request($marker));
+        if let Some(value) = $cache_control {
+            req = req.insert_header((header::CACHE_CONTROL, value));
+        }
+        let resp = test::call_service(&$app, req.to_request()).await;
+        assert!(resp.status().is_success());
+        let status = resp
+            .headers()
+            .get("X-Cache")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body: Value = test::read_body_json(resp).await;
+        (status, body)
+    }};
+}
+
+#[sqlx::test]
+async fn repeated_requests_are_served_from_the_cache(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "cache-repeat";
+    llm::respond_with_text(marker, &llm::thematic_output().to_string()).await;
+
+    let (status, first) = generate!(app, "/mcq/quran/thematic", marker);
+    assert_eq!(status, "MISS");
+    let (status, second) = generate!(app, "/mcq/quran/thematic", marker);
+    assert_eq!(status, "HIT");
+    assert_eq!(first, llm::thematic_output());
+    assert_eq!(second, first);
+    assert_eq!(llm::received_prompts(marker).await.len(), 1);
+
+    let keys = ctx.redis.keys("llm:response:");
+    assert_eq!(keys.len(), 1);
+    assert_eq!(ctx.redis.ttl(&keys[0]), Some(24 * 60 * 60));
+
+    // Other generation options are another entry.
+    let (status, _) = generate!(app, "/mcq/quran/thematic?min_distractors=2", marker);
+    assert_eq!(status, "MISS");
+    assert_eq!(ctx.redis.keys("llm:response:").len(), 2);
+}
+
+#[sqlx::test]
+async fn cache_control_bypasses_the_cache(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "cache-bypass";
+    llm::respond_with_text(marker, &llm::thematic_output().to_string()).await;
+
+    // no-store neither reads nor writes.
+    let (status, _) = generate!(app, "/mcq/quran/thematic", marker, Some("no-store"));
+    assert_eq!(status, "BYPASS");
+    assert!(ctx.redis.keys("llm:response:").is_empty());
+
+    generate!(app, "/mcq/quran/thematic", marker);
+    let key = ctx.redis.keys("llm:response:").remove(0);
+    ctx.redis.set(&key, r#"{"stale":true}"#);
+    let (status, body) = generate!(app, "/mcq/quran/thematic", marker);
+    assert_eq!(status, "HIT");
+    assert_eq!(body, json!({ "stale": true }));
+
+    // no-cache asks the model again and refreshes the entry.
+    let (status, body) = generate!(
+        app,
+        "/mcq/quran/thematic",
+        marker,
+        Some("max-age=0, No-Cache")
+    );
+    assert_eq!(status, "BYPASS");
+    assert_eq!(body, llm::thematic_output());
+    assert_eq!(
+        ctx.redis.get(&key).unwrap(),
+        llm::thematic_output().to_string()
+    );
+    assert_eq!(llm::received_prompts(marker).await.len(), 3);
+}
@@ -0,0 +1,224 @@
+use std::time::{Duration, Instant};
+
+use ilmiya::model::llm::GenerationRequest;
+use ilmiya::services::llm::openai::OpenAiProvider;
+use ilmiya::services::llm::{ClientConfig, LlmClient, LlmError};
+use serde_json::json;
+use wiremock::matchers::method;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn config() -> ClientConfig {
+    ClientConfig {
+        retry_base: Duration::from_millis(1),
+        ..ClientConfig::default()
+    }
+}
+
+fn client(server: &MockServer, config: ClientConfig) -> LlmClient {
+    let provider = OpenAiProvider::new(
+        config.http_client().unwrap(),
+        server.uri(),
+        "llama".into(),
+        None,
+    );
+    LlmClient::new(Box::new(provider), config)
+}
+
+fn completion(text: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        "choices": [{ "message": { "role": "assistant", "content": text } }]
+    }))
+}
+
+fn request() -> GenerationRequest {
+    GenerationRequest::new("prompt".to_string(), 1, 0.7)
+}
+
+async fn received(server: &MockServer) -> usize {
+    server.received_requests().await.unwrap_or_default().len()
+}
+
+fn unavailable_wait(error: &anyhow::Error) -> Option<Duration> {
+    match error.downcast_ref::<LlmError>() {
+        Some(LlmError::Unavailable { retry_after, .. }) => *retry_after,
+        other => panic!("expected an unavailable error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn transient_failures_are_retried() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(2)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(completion("done"))
+        .mount(&server)
+        .await;
+
+    let texts = client(&server, config())
+        .generate(&request())
+        .await
+        .unwrap();
+    assert_eq!(texts, ["done"]);
+    assert_eq!(received(&server).await, 3);
+}
+
+#[tokio::test]
+async fn retry_after_is_honoured() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(completion("done"))
+        .mount(&server)
+        .await;
+
+    let started = Instant::now();
+    client(&server, config())
+        .generate(&request())
+        .await
+        .unwrap();
+    assert!(started.elapsed() >= Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn slow_responses_time_out_and_are_retried() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(completion("late").set_delay(Duration::from_secs(2)))
+        .mount(&server)
+        .await;
+
+    let config = ClientConfig {
+        request_timeout: Duration::from_millis(100),
+        max_retries: 1,
+        ..config()
+    };
+    let error = client(&server, config)
+        .generate(&request())
+        .await
+        .unwrap_err();
+    unavailable_wait(&error);
+    assert_eq!(received(&server).await, 2);
+}
+
+#[tokio::test]
+async fn open_circuit_fails_fast_until_a_probe_succeeds() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+
+    let config = ClientConfig {
+        max_retries: 0,
+        breaker_threshold: 2,
+        breaker_cooldown: Duration::from_millis(300),
+        ..config()
+    };
+    let client = client(&server, config);
+
+    for _ in 0..2 {
+        client.generate(&request()).await.unwrap_err();
+    }
+    let error = client.generate(&request()).await.unwrap_err();
+    assert!(unavailable_wait(&error).is_some());
+    assert_eq!(received(&server).await, 2);
+
+    server.reset().await;
+    Mock::given(method("POST"))
+        .respond_with(completion("back"))
+        .mount(&server)
+        .await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    assert_eq!(client.generate(&request()).await.unwrap(), ["back"]);
+    assert_eq!(client.generate(&request()).await.unwrap(), ["back"]);
+}
+
+#[tokio::test]
+async fn a_dropped_probe_lets_the_next_call_probe() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+
+    let config = ClientConfig {
+        max_retries: 0,
+        breaker_threshold: 1,
+        breaker_cooldown: Duration::from_millis(300),
+        ..config()
+    };
+    let client = client(&server, config);
+    client.generate(&request()).await.unwrap_err();
+
+    server.reset().await;
+    Mock::given(method("POST"))
+        .respond_with(completion("slow").set_delay(Duration::from_secs(5)))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(completion("back"))
+        .mount(&server)
+        .await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    // The probe is abandoned half way, as when its caller disconnects.
+    let probe = request();
+    let abandoned = tokio::time::timeout(Duration::from_millis(100), client.generate(&probe));
+    assert!(abandoned.await.is_err());
+
+    assert_eq!(client.generate(&request()).await.unwrap(), ["back"]);
+}
+
+#[tokio::test]
+async fn client_errors_are_returned_without_retrying() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(401).set_body_string("bad key"))
+        .mount(&server)
+        .await;
+
+    let error = client(&server, config())
+        .generate(&request())
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<LlmError>(),
+        Some(LlmError::Status { .. })
+    ));
+    assert_eq!(received(&server).await, 1);
+}
+
+#[tokio::test]
+async fn requests_in_flight_are_capped() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(completion("done").set_delay(Duration::from_millis(200)))
+        .mount(&server)
+        .await;
+
+    let config = ClientConfig {
+        max_concurrent: 1,
+        ..config()
+    };
+    let client = client(&server, config);
+
+    let started = Instant::now();
+    let request = request();
+    let (first, second) = tokio::join!(client.generate(&request), client.generate(&request));
+    first.unwrap();
+    second.unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(400));
+}
@@ -0,0 +1,103 @@
+use ilmiya::model::llm::{DiacriticDistractorResponse, GuessFillInTheBlankResponse};
+use ilmiya::services::llm::gemini::gemini_schema;
+use ilmiya::services::llm::schema::response_schema;
+use ilmiya::utils::json::{extract_json_object, parse_llm_json, FieldStream};
+use serde_json::{json, Value};
+
+fn repaired(text: &str) -> Value {
+    serde_json::from_str(&extract_json_object(text).unwrap()).unwrap()
+}
+
+#[test]
+fn objects_are_found_in_fences_and_prose() {
+    let text =
+        "Here you go:\n```json\n{\"a\": [\"x\"], \"b\": {\"c\": \"}\"}}\n```\nHope it helps {:}";
+    assert_eq!(repaired(text), json!({ "a": ["x"], "b": { "c": "}" } }));
+
+    assert!(extract_json_object("no json here").is_err());
+}
+
+#[test]
+fn common_defects_are_repaired() {
+    assert_eq!(
+        repaired("{\"a\": [\"x\", \"y\",], \"b\": [\"z\"],}"),
+        json!({ "a": ["x", "y"], "b": ["z"] })
+    );
+    assert_eq!(
+        repaired("{\"a\": [\"first\nsecond\"]}"),
+        json!({ "a": ["first\nsecond"] })
+    );
+    assert_eq!(
+        repaired(r#"{"a": ["say \"hi\""]}"#),
+        json!({ "a": ["say \"hi\""] })
+    );
+}
+
+#[test]
+fn truncated_output_keeps_its_complete_values() {
+    assert_eq!(
+        repaired("{\"a\": [\"x\", \"y\"], \"b\": [\"z\", \"trunc"),
+        json!({ "a": ["x", "y"], "b": ["z"] })
+    );
+    assert_eq!(repaired("{\"a\": [\"x\"], \"b"), json!({ "a": ["x"] }));
+    assert_eq!(repaired("{\"a\": "), json!({}));
+}
+
+#[test]
+fn typed_parsing_skips_braces_in_prose() {
+    let text = "Using {question} as given: {\"correct_answer\": [\"a\"], \"distractors\": [\"b\", \"c\",]}";
+    let response: GuessFillInTheBlankResponse = parse_llm_json(text).unwrap();
+    assert_eq!(response.distractors, ["b", "c"]);
+
+    assert!(parse_llm_json::<GuessFillInTheBlankResponse>("{\"other\": 1}").is_err());
+}
+
+#[test]
+fn schemas_follow_the_response_types() {
+    let schema = response_schema::<DiacriticDistractorResponse>();
+    assert_eq!(
+        schema,
+        json!({
+            "type": "object",
+            "properties": {
+                "correct_answer": { "type": "array", "items": { "type": "string" } },
+                "diacritic_distractors": { "type": "array", "items": { "type": "string" } }
+            },
+            "required": ["correct_answer", "diacritic_distractors"],
+            "additionalProperties": false
+        })
+    );
+
+    let gemini = gemini_schema(&schema);
+    assert_eq!(gemini["type"], "OBJECT");
+    assert_eq!(
+        gemini["properties"]["correct_answer"]["items"]["type"],
+        "STRING"
+    );
+    assert!(gemini.get("additionalProperties").is_none());
+}
+
+#[test]
+fn fields_are_emitted_as_they_complete() {
+    let mut fields = FieldStream::default();
+    let mut emitted = Vec::new();
+    let text = "Here you go:\n```json\n{\"a\": [\"x, }\", \"\\\"y\\\"\"], \"b\": {\"c\": [1]}, \"d\": 2}\n```";
+    for c in text.chars() {
+        let before = emitted.len();
+        emitted.extend(fields.push(&c.to_string()));
+        if emitted.len() > before {
+            // A field is complete as soon as the text after it starts.
+            assert!(matches!(c, ',' | '}'));
+        }
+    }
+
+    assert_eq!(
+        emitted,
+        vec![
+            ("a".to_string(), json!(["x, }", "\"y\""])),
+            ("b".to_string(), json!({ "c": [1] })),
+            ("d".to_string(), json!(2)),
+        ]
+    );
+    assert!(fields.push("{\"e\": 3}").is_empty());
+}
@@ -0,0 +1,290 @@
+use futures_util::TryStreamExt;
+use ilmiya::model::llm::{
+    GenerationRequest, GuessFillInTheBlankQuranDistractorCollectionResponse,
+    GuessFillInTheBlankResponse, TextDelta,
+};
+use ilmiya::model::usage::TokenUsage;
+use ilmiya::services::llm::gemini::GeminiProvider;
+use ilmiya::services::llm::mock::MockProvider;
+use ilmiya::services::llm::ollama::OllamaProvider;
+use ilmiya::services::llm::openai::OpenAiProvider;
+use ilmiya::services::llm::{LlmProvider, ProviderConfig, ProviderKind};
+use reqwest::Client;
+use serde_json::json;
+use wiremock::matchers::{body_partial_json, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn request(prompt: &str, candidates: u32) -> GenerationRequest {
+    GenerationRequest::new(prompt.to_string(), candidates, 0.2)
+}
+
+#[tokio::test]
+async fn gemini_returns_every_candidate() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/gemini-test:generateContent"))
+        .and(body_partial_json(
+            json!({ "generationConfig": { "candidateCount": 2 } }),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "candidates": [
+                { "content": { "parts": [{ "text": "first" }] } },
+                { "content": { "parts": [{ "text": "second" }] } }
+            ],
+            "usageMetadata": { "promptTokenCount": 12, "candidatesTokenCount": 30 }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let provider = GeminiProvider::new(
+        Client::new(),
+        server.uri(),
+        "gemini-test".into(),
+        "key".into(),
+    );
+    let completion = provider.generate(&request("prompt", 2)).await.unwrap();
+    assert_eq!(completion.texts, ["first", "second"]);
+    assert_eq!(
+        completion.usage,
+        TokenUsage {
+            prompt_tokens: 12,
+            completion_tokens: 30
+        }
+    );
+}
+
+#[tokio::test]
+async fn openai_compatible_servers_use_chat_completions() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(header("authorization", "Bearer secret"))
+        .and(body_partial_json(json!({
+            "model": "llama",
+            "n": 2,
+            "messages": [{ "role": "user", "content": "prompt" }]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [
+                { "index": 0, "message": { "role": "assistant", "content": "one" } },
+                { "index": 1, "message": { "role": "assistant", "content": "two" } }
+            ],
+            "usage": { "prompt_tokens": 5, "completion_tokens": 9, "total_tokens": 14 }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let provider = OpenAiProvider::new(
+        Client::new(),
+        format!("{}/v1/", server.uri()),
+        "llama".into(),
+        Some("secret".into()),
+    );
+    let completion = provider.generate(&request("prompt", 2)).await.unwrap();
+    assert_eq!(completion.texts, ["one", "two"]);
+    assert_eq!(completion.usage.total(), 14);
+}
+
+#[tokio::test]
+async fn ollama_is_asked_once_per_candidate() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/generate"))
+        .and(body_partial_json(json!({
+            "model": "qwen",
+            "prompt": "prompt",
+            "stream": false
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "model": "qwen",
+            "response": "text",
+            "done": true,
+            "prompt_eval_count": 4,
+            "eval_count": 2
+        })))
+        .expect(3)
+        .mount(&server)
+        .await;
+
+    let provider = OllamaProvider::new(Client::new(), server.uri(), "qwen".into());
+    let completion = provider.generate(&request("prompt", 3)).await.unwrap();
+    assert_eq!(completion.texts, ["text", "text", "text"]);
+    assert_eq!(
+        completion.usage,
+        TokenUsage {
+            prompt_tokens: 12,
+            completion_tokens: 6
+        }
+    );
+}
+
+#[tokio::test]
+async fn ollama_samples_each_candidate_with_its_own_seed() {
+    let server = MockServer::start().await;
+    for seed in [7, 8, 9] {
+        Mock::given(method("POST"))
+            .and(path("/api/generate"))
+            .and(body_partial_json(json!({ "options": { "seed": seed } })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "model": "qwen",
+                "response": format!("text {}", seed),
+                "done": true
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+    }
+
+    let provider = OllamaProvider::new(Client::new(), server.uri(), "qwen".into());
+    let mut seeded = request("prompt", 3);
+    seeded.seed = Some(7);
+    let completion = provider.generate(&seeded).await.unwrap();
+    assert_eq!(completion.texts, ["text 7", "text 8", "text 9"]);
+}
+
+#[tokio::test]
+async fn streams_are_read_piece_by_piece() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(body_partial_json(json!({
+            "stream": true,
+            "stream_options": { "include_usage": true },
+            "n": 1
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            concat!(
+                "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n",
+                "data: {\"choices\":[{\"delta\":{\"content\":\"{\\\"a\\\"\"}}]}\n\n",
+                "data: {\"choices\":[{\"delta\":{\"content\":\": 1}\"}}]}\n\n",
+                "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":3,\"completion_tokens\":4}}\n\n",
+                "data: [DONE]\n\n",
+            ),
+            "text/event-stream",
+        ))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/generate"))
+        .and(body_partial_json(json!({ "stream": true })))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            concat!(
+                "{\"response\":\"{\\\"a\\\"\",\"done\":false}\n",
+                "{\"response\":\": 1}\",\"done\":false}\n",
+                "{\"response\":\"\",\"done\":true,\"prompt_eval_count\":3,\"eval_count\":4}\n",
+            ),
+            "application/x-ndjson",
+        ))
+        .mount(&server)
+        .await;
+
+    let providers: [Box<dyn LlmProvider>; 2] = [
+        Box::new(OpenAiProvider::new(
+            Client::new(),
+            format!("{}/v1", server.uri()),
+            "llama".into(),
+            None,
+        )),
+        Box::new(OllamaProvider::new(
+            Client::new(),
+            server.uri(),
+            "llama".into(),
+        )),
+    ];
+    for provider in providers {
+        let deltas: Vec<TextDelta> = provider
+            .generate_stream(&request("prompt", 3))
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+
+        let text: String = deltas.iter().map(|delta| delta.text.as_str()).collect();
+        assert_eq!(text, r#"{"a": 1}"#);
+        let usage = deltas.iter().rev().find_map(|delta| delta.usage);
+        assert_eq!(usage.map(|usage| usage.total()), Some(7));
+    }
+}
+
+#[tokio::test]
+async fn error_statuses_are_reported() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(503).set_body_string("overloaded"))
+        .mount(&server)
+        .await;
+
+    let provider = OpenAiProvider::new(Client::new(), server.uri(), "llama".into(), None);
+    let error = provider.generate(&request("prompt", 1)).await.unwrap_err();
+    assert!(error.to_string().contains("503"));
+    assert!(error.to_string().contains("overloaded"));
+}
+
+#[tokio::test]
+async fn mock_is_deterministic_and_parses_as_every_response() {
+    let first = MockProvider
+        .generate(&request("a prompt", 2))
+        .await
+        .unwrap()
+        .texts;
+    let again = MockProvider
+        .generate(&request("a prompt", 2))
+        .await
+        .unwrap()
+        .texts;
+    let other = MockProvider
+        .generate(&request("another", 1))
+        .await
+        .unwrap()
+        .texts;
+
+    assert_eq!(first, again);
+    assert_eq!(first.len(), 2);
+    assert_ne!(first[0], first[1]);
+    assert_ne!(first[0], other[0]);
+
+    let simple: GuessFillInTheBlankResponse = serde_json::from_str(&first[0]).unwrap();
+    assert_eq!(simple.correct_answer.len(), 1);
+    assert_eq!(simple.distractors.len(), 3);
+    let quran: GuessFillInTheBlankQuranDistractorCollectionResponse =
+        serde_json::from_str(&first[0]).unwrap();
+    assert_eq!(quran.diacritic_distractors.len(), 3);
+}
+
+#[test]
+fn provider_config_checks_required_settings() {
+    assert_eq!(
+        "OpenAI".parse::<ProviderKind>().unwrap(),
+        ProviderKind::OpenAi
+    );
+    assert!("claude".parse::<ProviderKind>().is_err());
+
+    let mock = ProviderConfig {
+        kind: ProviderKind::Mock,
+        base_url: None,
+        model: None,
+        api_key: None,
+    };
+    assert_eq!(mock.build(Client::new()).unwrap().name(), "Mock");
+
+    let gemini = ProviderConfig {
+        kind: ProviderKind::Gemini,
+        base_url: Some("http://localhost".into()),
+        model: Some("gemini".into()),
+        api_key: None,
+    };
+    let error = gemini.build(Client::new()).err().unwrap();
+    assert!(error.to_string().contains("TEXT_GENERATION_API_KEY"));
+
+    let openai = ProviderConfig {
+        kind: ProviderKind::OpenAi,
+        ..gemini
+    };
+    assert_eq!(
+        openai.build(Client::new()).unwrap().name(),
+        "OpenAI-compatible"
+    );
+}
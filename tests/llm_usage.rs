@@ -0,0 +1,108 @@
+mod common;
+
+use actix_web::http::header;
+use actix_web::test;
+use ilmiya::database::queries;
+use ilmiya::model::usage::{TokenUsage, UsageReport, UsageScope};
+use ilmiya::services::usage::Quotas;
+use serde_json::Value;
+use sqlx::PgPool;
+
+use common::{llm, TestContext, ADMIN_TOKEN};
+
+#[sqlx::test]
+async fn tokens_are_reported_per_user_exam_and_endpoint(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "usage-report";
+    llm::respond_with_text(marker, &llm::thematic_output().to_string()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/thematic")
+        .insert_header(("X-User-Id", "teacher-1"))
+        .insert_header(("X-Exam-Id", "7"))
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+
+    let req = test::TestRequest::get().uri("/admin/usage").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    let req = test::TestRequest::get()
+        .uri("/admin/usage?user_id=teacher-1")
+        .insert_header((header::AUTHORIZATION, format!("Bearer {}", ADMIN_TOKEN)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let report: UsageReport = test::read_body_json(resp).await;
+
+    // The mock reports 42 prompt and 17 completion tokens per call.
+    assert_eq!(report.calls, 1);
+    assert_eq!(report.total_tokens, 59);
+    assert_eq!(report.daily_quota, None);
+    let row = &report.rows[0];
+    assert_eq!(row.user_id, "teacher-1");
+    assert_eq!(row.exam_id, Some(7));
+    assert_eq!(row.endpoint, "/mcq/quran/thematic");
+    assert_eq!((row.prompt_tokens, row.completion_tokens), (42, 17));
+}
+
+#[sqlx::test]
+async fn spent_quotas_answer_429_with_the_remaining_budget(pool: PgPool) {
+    let quotas = Quotas {
+        daily: Some(100),
+        monthly: Some(1000),
+    };
+    let ctx = TestContext::with_quotas(pool.clone(), quotas).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "usage-quota";
+    llm::respond_with_text(marker, &llm::thematic_output().to_string()).await;
+
+    let scope = UsageScope {
+        user_id: "teacher-2".to_string(),
+        exam_id: None,
+        endpoint: "/mcq/quran/thematic".to_string(),
+    };
+    let spent = TokenUsage {
+        prompt_tokens: 60,
+        completion_tokens: 40,
+    };
+    queries::usage::insert_usage(&pool, &scope, "Gemini", llm::MODEL_NAME, &spent)
+        .await
+        .unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/thematic")
+        .insert_header(("X-User-Id", "teacher-2"))
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 429);
+    assert!(resp.headers().get(header::RETRY_AFTER).is_some());
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["user_id"], "teacher-2");
+    assert_eq!(body["daily"]["used"], 100);
+    assert_eq!(body["daily"]["remaining"], 0);
+    assert_eq!(body["monthly"]["remaining"], 900);
+    assert!(llm::received_prompts(marker).await.is_empty());
+
+    // With quotas, a request must name its user.
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/thematic")
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 401);
+    assert!(llm::received_prompts(marker).await.is_empty());
+
+    // Other users keep their own budget.
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/thematic")
+        .insert_header(("X-User-Id", "teacher-3"))
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+}
@@ -0,0 +1,534 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use wiremock::ResponseTemplate;
+
+use common::{llm, TestContext};
+
+/// Every single-type `/mcq/quran/*` route with the template task it renders
+/// and the distractor field of its response.
+const QURAN_ROUTES: &[(&str, &str, &str)] = &[
+    ("diacritic", "diacritic", "diacritic_distractors"),
+    ("phonetic", "phonetic", "phonetic_orthographic_distractors"),
+    (
+        "morphological",
+        "morphological",
+        "morphological_distractors",
+    ),
+    ("grammatical", "grammatical", "grammatical_distractors"),
+    (
+        "alternate_verse",
+        "alternate_verse",
+        "alternative_verse_distractors",
+    ),
+    ("thematic", "thematic", "thematic_distractors"),
+    (
+        "collocational",
+        "collocational",
+        "collocational_distractors",
+    ),
+];
+
+/// Three distractors of the answer in [`quran_request`] that pass the
+/// quality gate for the route's distractor type.
+fn valid_distractors(route: &str) -> [&'static str; 3] {
+    match route {
+        "diacritic" => ["الرَّحِيمُ", "الرَّحِيمَ", "الرَّحْيمِ"],
+        "phonetic" => ["الرَّهِيمِ", "الرَّخِيمِ", "الرَّحِيبِ"],
+        "morphological" | "grammatical" => ["الرَّاحِمِ", "الرَّحِيمُ", "الرُّحَمَاءِ"],
+        _ => ["الرَّحْمَنِ", "الْكَرِيمِ", "الْعَظِيمِ"],
+    }
+}
+
+#[sqlx::test]
+async fn quran_routes_render_their_template_and_parse_the_response(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    for (route, task, field) in QURAN_ROUTES {
+        let marker = format!("quran-route-{}", route);
+        let output = json!({
+            "correct_answer": ["الرَّحِيمِ"],
+            *field: valid_distractors(route)
+        });
+        llm::respond_with_text(&marker, &output.to_string()).await;
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/mcq/quran/{}", route))
+            .set_json(llm::quran_request(&marker))
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body, output, "unexpected response for /mcq/quran/{}", route);
+
+        let prompts = llm::received_prompts(&marker).await;
+        assert_eq!(prompts.len(), 1);
+        assert!(
+            prompts[0].starts_with(&format!("TASK={} ", task)),
+            "/mcq/quran/{} rendered {:?}",
+            route,
+            prompts[0]
+        );
+        assert!(prompts[0].ends_with("ANSWER=<correct_answer>الرَّحِيمِ</correct_answer>"));
+    }
+}
+
+#[sqlx::test]
+async fn collection_route_parses_fenced_json(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "quran-route-collection-fenced";
+
+    let output = json!({
+        "correct_answer": ["الرَّحِيمِ"],
+        "collocational_distractors": ["الْكَرِيمِ"],
+        "thematic_distractors": ["الْغَفُورِ"],
+        "alternative_verse_distractors": ["الْعَظِيمِ"],
+        "grammatical_distractors": ["الرَّحِيمُ"],
+        "morphological_distractors": ["الرَّاحِمِ"],
+        "phonetic_orthographic_distractors": ["الرَّهِيمِ"],
+        "diacritic_distractors": ["الرَّحَيمِ"]
+    });
+    let fenced = format!(
+        "```json\n{}\n```",
+        serde_json::to_string_pretty(&output).unwrap()
+    );
+    llm::respond_with_text(marker, &fenced).await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/collection")
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(body, output);
+    assert!(llm::received_prompts(marker).await[0].starts_with("TASK=collection "));
+}
+
+#[sqlx::test]
+async fn fenced_output_without_language_tag_is_accepted(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "quran-route-bare-fence";
+
+    let output = json!({
+        "correct_answer": ["الرَّحِيمِ"],
+        "diacritic_distractors": ["الرَّحَيمِ"]
+    });
+    llm::respond_with_text(marker, &format!("```\n{}\n```", output)).await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/diacritic")
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(body, output);
+}
+
+#[sqlx::test]
+async fn unparseable_output_is_a_server_error(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "quran-route-unparseable";
+
+    llm::respond_with_text(marker, "Sorry, I cannot help with that.").await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/thematic")
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    // The reply was sent back once for repair.
+    assert_eq!(llm::received_prompts(marker).await.len(), 2);
+}
+
+#[sqlx::test]
+async fn malformed_output_is_repaired(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "repair-trailing-comma";
+
+    llm::respond_with_text(
+        marker,
+        "Here are the options:\n{\"correct_answer\": [\"الرَّحِيمِ\"], \"thematic_distractors\": [\"الْعَظِيمِ\", \"الْكَرِيمِ\",]}\nThanks!",
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/thematic")
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["thematic_distractors"], json!(["الْعَظِيمِ", "الْكَرِيمِ"]));
+
+    // The response schema was part of the request.
+    let sent = &llm::received_bodies(marker).await[0]["generationConfig"];
+    assert_eq!(sent["responseMimeType"], "application/json");
+    assert_eq!(
+        sent["responseSchema"]["required"],
+        json!(["correct_answer", "thematic_distractors"])
+    );
+}
+
+#[sqlx::test]
+async fn unparseable_output_is_sent_back_once(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "repair-reprompt";
+
+    let output = json!({
+        "correct_answer": ["الرَّحِيمِ"],
+        "collocational_distractors": ["الْعَلِيمِ"]
+    });
+    llm::respond_with_text(marker, "I would suggest الْعَلِيمِ.").await;
+    llm::respond_to_follow_up_with_text(marker, "could not be parsed", &output.to_string()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/collocational?min_distractors=1")
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body, output);
+
+    let prompts = llm::received_prompts(marker).await;
+    assert_eq!(prompts.len(), 2);
+    assert!(prompts[1].contains("I would suggest"));
+}
+
+#[sqlx::test]
+async fn failing_distractors_are_dropped_and_regenerated(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "quality-regenerate";
+
+    let first = json!({
+        "correct_answer": ["الرَّحِيمِ"],
+        "diacritic_distractors": [
+            "الرَّحِيمُ",
+            "الرَّحِيمِ ",
+            "الرَّحِيمُ",
+            "الرَّحْمَنِ",
+            "الرَّحِيمَ rahim",
+            "الرَّحِيمَ الرَّحِيمَ"
+        ]
+    });
+    let second = json!({
+        "correct_answer": ["الرَّحِيمِ"],
+        "diacritic_distractors": ["الرَّحِيمَ", "الرَّحْيمِ"]
+    });
+    llm::respond_with_text(marker, &first.to_string()).await;
+    llm::respond_to_follow_up_with_text(marker, "were rejected", &second.to_string()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/diacritic")
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        body["diacritic_distractors"],
+        json!(["الرَّحِيمُ", "الرَّحِيمَ", "الرَّحْيمِ"])
+    );
+
+    let prompts = llm::received_prompts(marker).await;
+    assert_eq!(prompts.len(), 2);
+    for reason in [
+        "equals the correct answer",
+        "repeats another distractor",
+        "differs from the answer in more than tashkeel",
+        "mixes scripts",
+        "has 2 words where the answer has 1",
+        "diacritic_distractors: 2 more",
+    ] {
+        assert!(prompts[1].contains(reason), "missing {:?}", reason);
+    }
+}
+
+#[sqlx::test]
+async fn regeneration_stops_when_the_budget_runs_out(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "quality-budget";
+
+    let output = json!({
+        "correct_answer": ["الرَّحِيمِ"],
+        // Too far from the answer to be a phonetic distractor.
+        "phonetic_orthographic_distractors": ["الرَّهِيمِ", "الْمُسْتَقِيمِ"]
+    });
+    llm::respond_with_text(marker, &output.to_string()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/phonetic?candidates=2")
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+
+    // Two candidates, then one request per regeneration round.
+    assert_eq!(llm::received_prompts(marker).await.len(), 3);
+    assert_eq!(body["phonetic_orthographic_distractors"], json!(["الرَّهِيمِ"]));
+    let rejected = body["rejected"].as_array().unwrap();
+    assert!(rejected.iter().all(|r| r["text"] == "الْمُسْتَقِيمِ"));
+    assert_eq!(
+        rejected[0]["reason"],
+        "is 4 letter edits from the answer, at most 2 are allowed"
+    );
+}
+
+#[sqlx::test]
+async fn regenerated_distractors_fill_only_short_lists_without_votes(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "regeneration-without-votes";
+
+    let first = json!({
+        "correct_answer": ["الرَّحِيمِ"],
+        "thematic_distractors": ["الْغَفُورِ", "الْكَرِيمِ"]
+    });
+    // Repeats a distractor the candidates gave, and one new one.
+    let second = json!({
+        "correct_answer": ["الرَّحِيمِ"],
+        "thematic_distractors": ["الْغَفُورِ", "الْعَظِيمِ"]
+    });
+    let first = first.to_string();
+    llm::respond_with(
+        marker,
+        ResponseTemplate::new(200).set_body_json(llm::generate_content_body(&[&first, &first])),
+    )
+    .await;
+    llm::respond_to_follow_up_with_text(marker, "were rejected", &second.to_string()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/thematic?candidates=2")
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        body["thematic_distractors"],
+        json!(["الْغَفُورِ", "الْكَرِيمِ", "الْعَظِيمِ"])
+    );
+    assert_eq!(body["candidates"], 2);
+    let counts: Vec<(&str, u64)> = body["scores"]["thematic_distractors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| (s["text"].as_str().unwrap(), s["count"].as_u64().unwrap()))
+        .collect();
+    assert_eq!(counts, [("الْغَفُورِ", 2), ("الْكَرِيمِ", 2), ("الْعَظِيمِ", 0)]);
+
+    // A single candidate stays unmerged after regeneration.
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/thematic")
+        .insert_header(("Cache-Control", "no-store"))
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["thematic_distractors"].as_array().unwrap().len(), 3);
+    assert!(body.get("scores").is_none());
+}
+
+#[sqlx::test]
+async fn llm_outage_is_service_unavailable(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "quran-route-upstream-failure";
+
+    llm::respond_with(
+        marker,
+        ResponseTemplate::new(503).set_body_string("model overloaded"),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/phonetic")
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    // The first attempt and every retry reached the provider.
+    assert_eq!(llm::received_prompts(marker).await.len(), 4);
+}
+
+#[sqlx::test]
+async fn rejected_llm_requests_are_not_retried(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "quran-route-bad-request";
+
+    llm::respond_with(
+        marker,
+        ResponseTemplate::new(400).set_body_string("invalid argument"),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/phonetic")
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(llm::received_prompts(marker).await.len(), 1);
+}
+
+#[sqlx::test]
+async fn candidates_are_merged_and_ranked_by_agreement(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "merge-ranked-candidates";
+
+    let candidates = [
+        json!({
+            "correct_answer": ["الرَّحِيمِ"],
+            "thematic_distractors": ["الرَّحْمَنِ", "الْعَظِيمِ", "الكريم"]
+        }),
+        json!({
+            "correct_answer": ["الرَّحِيمِ"],
+            "thematic_distractors": ["الرحمن", "الكريم", "الكريم"]
+        }),
+        json!({
+            "correct_answer": ["الرَّحِيمِ"],
+            "thematic_distractors": ["الرَّحْمٰنِ", "الرحيم"]
+        }),
+    ]
+    .map(|c| c.to_string());
+    let texts: Vec<&str> = candidates.iter().map(String::as_str).collect();
+    llm::respond_with(
+        marker,
+        ResponseTemplate::new(200).set_body_json(llm::generate_content_body(&texts)),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/thematic?candidates=3&top_k=2")
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(body["candidates"], 3);
+    assert_eq!(body["correct_answer"], json!(["الرَّحِيمِ"]));
+    // Spellings of one word count once; the answer itself is dropped.
+    assert_eq!(body["thematic_distractors"], json!(["الرَّحْمَنِ", "الكريم"]));
+    assert_eq!(
+        body["scores"]["thematic_distractors"],
+        json!([
+            { "text": "الرَّحْمَنِ", "count": 3, "agreement": 1.0 },
+            { "text": "الكريم", "count": 2, "agreement": 2.0 / 3.0 }
+        ])
+    );
+}
+
+#[sqlx::test]
+async fn diacritic_candidates_keep_their_tashkeel_apart(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "merge-diacritic-candidates";
+
+    let first = json!({
+        "correct_answer": ["الرَّحِيمِ"],
+        "diacritic_distractors": ["الرَّحِيمُ", "الرَّحِيمِ", "الرَّحِيمَ"]
+    })
+    .to_string();
+    let second = json!({
+        "correct_answer": ["الرَّحِيمِ"],
+        "diacritic_distractors": ["الرَّحِيمَ"]
+    })
+    .to_string();
+    llm::respond_with(
+        marker,
+        ResponseTemplate::new(200)
+            .set_body_json(llm::generate_content_body(&[&first, "not json", &second])),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/diacritic?candidates=3&min_distractors=2")
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+
+    // The unparseable candidate is skipped.
+    assert_eq!(body["candidates"], 2);
+    assert_eq!(body["diacritic_distractors"], json!(["الرَّحِيمَ", "الرَّحِيمُ"]));
+}
+
+#[sqlx::test]
+async fn sequential_sampling_sends_one_request_per_candidate(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "merge-sequential-candidates";
+
+    let output = json!({
+        "correct_answer": ["الرَّحِيمِ"],
+        "grammatical_distractors": ["الرَّحِيمُ"]
+    });
+    llm::respond_with_text(marker, &output.to_string()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/grammatical?candidates=2&sampling=sequential&min_distractors=1")
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["candidates"], 2);
+    assert_eq!(body["scores"]["grammatical_distractors"][0]["count"], 2);
+    assert_eq!(llm::received_prompts(marker).await.len(), 2);
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/grammatical?candidates=9")
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[sqlx::test]
+async fn context_route_generates_urdu_options(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "context-route-urdu";
+
+    let output = json!({
+        "correct_answer": ["لاہور"],
+        "distractors": ["کراچی", "ملتان", "پشاور"]
+    });
+    llm::respond_with_text(marker, &format!("```json\n{}\n```", output)).await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/options/context")
+        .set_json(json!({
+            "question": format!("{} پنجاب کا دارالحکومت ___ ہے", marker),
+            "correct_answer": "لاہور",
+            "language": "urdu"
+        }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(body, output);
+    assert!(llm::received_prompts(marker).await[0].starts_with("TASK=context_urdu "));
+}
+
+#[sqlx::test]
+async fn context_route_rejects_arabic_without_calling_the_llm(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "context-route-arabic";
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/options/context")
+        .set_json(json!({
+            "question": format!("{} عاصمة مصر هي ___", marker),
+            "correct_answer": "القاهرة",
+            "language": "arabic"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert!(llm::received_prompts(marker).await.is_empty());
+}
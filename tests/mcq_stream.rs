@@ -0,0 +1,208 @@
+mod common;
+
+use std::time::Duration;
+
+use actix_web::test;
+use chrono::DateTime;
+use ilmiya::database::queries;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use wiremock::ResponseTemplate;
+
+use common::{llm, TestContext};
+
+/// Splits `text` into pieces of `size` characters, cutting through keys,
+/// strings and escapes alike.
+fn pieces(text: &str, size: usize) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    chars
+        .chunks(size)
+        .map(|piece| piece.iter().collect())
+        .collect()
+}
+
+/// Reads a `text/event-stream` body into `(event, data)` pairs.
+fn events(body: &[u8]) -> Vec<(String, Value)> {
+    std::str::from_utf8(body)
+        .unwrap()
+        .split("\n\n")
+        .filter(|event| !event.is_empty())
+        .map(|event| {
+            let mut name = String::new();
+            let mut data = Value::Null;
+            for line in event.lines() {
+                if let Some(value) = line.strip_prefix("event: ") {
+                    name = value.to_string();
+                } else if let Some(value) = line.strip_prefix("data: ") {
+                    data = serde_json::from_str(value).unwrap();
+                }
+            }
+            (name, data)
+        })
+        .collect()
+}
+
+#[sqlx::test]
+async fn each_completed_list_is_pushed_before_the_result(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "stream-collection";
+    let text = r#"```json
+{
+  "correct_answer": ["الرَّحِيمِ"],
+  "collocational_distractors": ["الْكَرِيمِ", "الْعَلِيمِ"],
+  "thematic_distractors": ["الرَّحْمَنِ", "الْغَفُورِ"],
+  "alternative_verse_distractors": ["الْحَكِيمِ"],
+  "grammatical_distractors": ["رَحِيمٌ"],
+  "morphological_distractors": ["الرَّاحِمِ"],
+  "phonetic_orthographic_distractors": ["الرَّهِيمِ"],
+  "diacritic_distractors": ["الرَّحَيمِ"]
+}
+```"#;
+    let chunks = pieces(text, 7);
+    let chunks: Vec<&str> = chunks.iter().map(String::as_str).collect();
+    llm::respond_with_stream(marker, &chunks).await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/collection/stream?min_distractors=0")
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    assert_eq!(
+        resp.headers().get("content-type").unwrap(),
+        "text/event-stream"
+    );
+    let events = events(&test::read_body(resp).await);
+
+    let fields: Vec<&str> = events[..7]
+        .iter()
+        .map(|(name, data)| {
+            assert_eq!(name, "distractors");
+            data["field"].as_str().unwrap()
+        })
+        .collect();
+    assert_eq!(
+        fields,
+        [
+            "collocational_distractors",
+            "thematic_distractors",
+            "alternative_verse_distractors",
+            "grammatical_distractors",
+            "morphological_distractors",
+            "phonetic_orthographic_distractors",
+            "diacritic_distractors"
+        ]
+    );
+
+    // The pushed lists are gated like the final payload.
+    assert_eq!(events.len(), 8);
+    let (name, result) = &events[7];
+    assert_eq!(name, "result");
+    assert_eq!(result["correct_answer"], json!(["الرَّحِيمِ"]));
+    for (_, data) in &events[..7] {
+        assert_eq!(data["distractors"], result[data["field"].as_str().unwrap()]);
+    }
+    assert!(llm::received_prompts(marker).await[0].starts_with("TASK=collection "));
+}
+
+#[sqlx::test]
+async fn streams_answer_with_a_single_candidate(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "stream-candidates";
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/thematic/stream?candidates=2")
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/context/stream")
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 404);
+    assert!(llm::received_prompts(marker).await.is_empty());
+}
+
+#[sqlx::test]
+async fn an_unavailable_provider_fails_before_streaming(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "stream-unavailable";
+    llm::respond_to_stream_with(marker, ResponseTemplate::new(500)).await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/thematic/stream")
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), 503);
+}
+
+#[sqlx::test]
+async fn unparseable_output_ends_with_an_error_event(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "stream-garbage";
+    llm::respond_with_stream(marker, &["no json ", "here"]).await;
+    llm::respond_with_text(marker, "still no json").await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/thematic/stream")
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let events = events(&test::read_body(resp).await);
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].0, "error");
+    assert!(events[0].1["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("Parsing error"));
+}
+
+#[sqlx::test]
+async fn streams_failing_part_way_still_count_their_tokens(pool: PgPool) {
+    let ctx = TestContext::new(pool.clone()).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "stream-cut-short";
+    let events = format!(
+        "data: {}\r\n\r\ndata: not json\r\n\r\n",
+        llm::generate_content_body(&[r#"{"correct_answer": ["#])
+    );
+    llm::respond_to_stream_with(
+        marker,
+        ResponseTemplate::new(200).set_body_raw(events, "text/event-stream"),
+    )
+    .await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran/thematic/stream")
+        .insert_header(("X-User-Id", "stream-reader"))
+        .set_json(llm::quran_request(marker))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_success());
+    let body = test::read_body(resp).await;
+    assert_eq!(self::events(&body)[0].0, "error");
+
+    // Recorded in the background once the stream is dropped, with the usage
+    // the provider reported so far.
+    for _ in 0..100 {
+        let used = queries::usage::user_tokens_since(&pool, "stream-reader", DateTime::UNIX_EPOCH)
+            .await
+            .unwrap();
+        if used > 0 {
+            assert_eq!(used, 59);
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("The usage of the failed stream was not recorded");
+}
@@ -0,0 +1,343 @@
+mod common;
+
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use common::{sample_exam, TestContext};
+
+fn issues_for(report: &Value, question: u64) -> Vec<&Value> {
+    report["issues"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|issue| issue["question"] == question)
+        .collect()
+}
+
+#[sqlx::test]
+async fn export_produces_moodle_xml(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/exam/create")
+        .set_json(sample_exam(1))
+        .to_request();
+    test::call_service(&app, req).await;
+
+    let req = test::TestRequest::get()
+        .uri("/exam/1/export?format=moodle")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("application/xml"));
+
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(body.contains("<category><text>$course$/top/القسم الأول</text></category>"));
+    assert_eq!(body.matches(r#"<question type="multichoice">"#).count(), 2);
+    assert!(body.contains("<defaultgrade>5</defaultgrade>"));
+    assert!(body.contains("<p>Complete the verse</p>"));
+    assert!(body.contains(r#"<p dir="rtl">الدِّينِ</p>"#));
+    assert!(body.contains(r#"<answer fraction="100" format="html">"#));
+}
+
+#[sqlx::test]
+async fn exported_moodle_xml_imports_back(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/exam/create")
+        .set_json(sample_exam(1))
+        .to_request();
+    test::call_service(&app, req).await;
+
+    let req = test::TestRequest::get()
+        .uri("/exam/1/export?format=moodle")
+        .to_request();
+    let xml = test::call_and_read_body(&app, req).await;
+
+    let req = test::TestRequest::post()
+        .uri("/exam/import?format=moodle&title=Round%20trip&duration=45&passing_score=70")
+        .set_payload(xml)
+        .to_request();
+    let imported: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(imported["report"]["imported"], 2);
+    assert_eq!(imported["report"]["skipped"], 0);
+    let imported_id = imported["exam_id"].as_i64().unwrap();
+    assert_ne!(imported_id, 1);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/exam/{}", imported_id))
+        .to_request();
+    let exam: Value = test::call_and_read_body_json(&app, req).await;
+
+    let req = test::TestRequest::get().uri("/exam/1").to_request();
+    let original: Value = test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(exam["description"]["title"], "Round trip");
+    assert_eq!(exam["description"]["duration"], 45);
+    assert_eq!(exam["description"]["passing_score"], 70);
+    assert_eq!(
+        exam["sections"][0]["title"],
+        original["sections"][0]["title"]
+    );
+
+    for (question, expected) in exam["sections"][0]["questions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .zip(original["sections"][0]["questions"].as_array().unwrap())
+    {
+        assert_eq!(question["text"], expected["text"]);
+        assert_eq!(question["description"], expected["description"]);
+        assert_eq!(question["marks"], expected["marks"]);
+
+        let options: Vec<(&Value, &Value)> = question["options"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|o| (&o["text"], &o["is_correct"]))
+            .collect();
+        let expected: Vec<(&Value, &Value)> = expected["options"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|o| (&o["text"], &o["is_correct"]))
+            .collect();
+        assert_eq!(options, expected);
+    }
+}
+
+#[sqlx::test]
+async fn import_reports_unsupported_and_lossy_questions(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<quiz>
+  <question type="category">
+    <category><text>$course$/top/Vocabulary</text></category>
+  </question>
+  <question type="multichoice">
+    <name><text>Nouns</text></name>
+    <questiontext format="html"><text><![CDATA[<p>Which of these are <b>nouns</b>?</p>]]></text></questiontext>
+    <defaultgrade>2</defaultgrade>
+    <single>false</single>
+    <answer fraction="50"><text>كتاب</text></answer>
+    <answer fraction="50"><text>قلم</text></answer>
+    <answer fraction="-50"><text>ذهب</text></answer>
+  </question>
+  <question type="essay">
+    <name><text>Essay</text></name>
+    <questiontext format="html"><text>Write about your day.</text></questiontext>
+  </question>
+  <question type="matching">
+    <name><text>Pairs</text></name>
+    <questiontext format="html"><text>Match the words</text></questiontext>
+    <defaultgrade>4</defaultgrade>
+    <subquestion format="html"><text>book</text><answer><text>كتاب</text></answer></subquestion>
+    <subquestion format="html"><text>pen</text><answer><text>قلم</text></answer></subquestion>
+  </question>
+</quiz>"#;
+
+    let req = test::TestRequest::post()
+        .uri("/exam/import?format=moodle")
+        .set_payload(xml)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let imported: Value = test::read_body_json(resp).await;
+
+    let report = &imported["report"];
+    assert_eq!(report["imported"], 3);
+    assert_eq!(report["skipped"], 1);
+
+    let essay = issues_for(report, 2);
+    assert_eq!(essay.len(), 1);
+    assert_eq!(essay[0]["skipped"], true);
+    assert_eq!(essay[0]["question_type"], "essay");
+    assert_eq!(essay[0]["name"], "Essay");
+
+    let matching = issues_for(report, 3);
+    assert!(matching
+        .iter()
+        .any(|issue| issue["skipped"] == false
+            && issue["message"].as_str().unwrap().contains("split")));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/exam/{}", imported["exam_id"]))
+        .to_request();
+    let exam: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(exam["description"]["title"], "Imported exam");
+
+    let section = &exam["sections"][0];
+    assert_eq!(section["title"], "Vocabulary");
+    let questions = section["questions"].as_array().unwrap();
+    assert_eq!(questions.len(), 3);
+
+    assert_eq!(questions[0]["text"], "Which of these are nouns?");
+    assert_eq!(questions[0]["marks"], 2);
+    let correct: Vec<&str> = questions[0]["options"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|o| o["is_correct"] == true)
+        .map(|o| o["text"].as_str().unwrap())
+        .collect();
+    assert_eq!(correct, ["كتاب", "قلم"]);
+
+    assert_eq!(questions[1]["text"], "Match the words\nbook");
+    assert_eq!(questions[1]["marks"], 2);
+    assert_eq!(questions[1]["options"].as_array().unwrap().len(), 2);
+}
+
+#[sqlx::test]
+async fn matching_marks_are_shared_out_in_full(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let xml = r#"<quiz>
+  <question type="matching">
+    <name><text>Plurals</text></name>
+    <questiontext format="html"><text>Match each word with its plural</text></questiontext>
+    <defaultgrade>5</defaultgrade>
+    <subquestion format="html"><text>كتاب</text><answer><text>كتب</text></answer></subquestion>
+    <subquestion format="html"><text>قلم</text><answer><text>أقلام</text></answer></subquestion>
+    <subquestion format="html"><text>باب</text><answer><text>أبواب</text></answer></subquestion>
+  </question>
+</quiz>"#;
+
+    let req = test::TestRequest::post()
+        .uri("/exam/import?format=moodle")
+        .set_payload(xml)
+        .to_request();
+    let imported: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(imported["report"]["imported"], 3);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/exam/{}", imported["exam_id"]))
+        .to_request();
+    let exam: Value = test::call_and_read_body_json(&app, req).await;
+    let marks: Vec<i64> = exam["sections"][0]["questions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|q| q["marks"].as_i64().unwrap())
+        .collect();
+    assert_eq!(marks, [2, 2, 1]);
+}
+
+/// The text, marks and options of each question of an exam's first section.
+fn question_summary(exam: &Value) -> Vec<Value> {
+    exam["sections"][0]["questions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|q| {
+            let options: Vec<Value> = q["options"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|o| json!([o["text"], o["is_correct"]]))
+                .collect();
+            json!([q["text"], q["marks"], options])
+        })
+        .collect()
+}
+
+#[sqlx::test]
+async fn matching_questions_survive_a_round_trip(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let xml = r#"<quiz>
+  <question type="matching">
+    <name><text>Plurals</text></name>
+    <questiontext format="html"><text>Match each word with its plural</text></questiontext>
+    <defaultgrade>5</defaultgrade>
+    <subquestion format="html"><text>كتاب</text><answer><text>كتب</text></answer></subquestion>
+    <subquestion format="html"><text>قلم</text><answer><text>أقلام</text></answer></subquestion>
+    <subquestion format="html"><text>باب</text><answer><text>أبواب</text></answer></subquestion>
+    <subquestion format="html"><text></text><answer><text>كتابات</text></answer></subquestion>
+  </question>
+</quiz>"#;
+
+    let req = test::TestRequest::post()
+        .uri("/exam/import?format=moodle")
+        .set_payload(xml)
+        .to_request();
+    let imported: Value = test::call_and_read_body_json(&app, req).await;
+    let req = test::TestRequest::get()
+        .uri(&format!("/exam/{}", imported["exam_id"]))
+        .to_request();
+    let original: Value = test::call_and_read_body_json(&app, req).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!(
+            "/exam/{}/export?format=moodle",
+            imported["exam_id"]
+        ))
+        .to_request();
+    let exported = test::call_and_read_body(&app, req).await;
+    let body = std::str::from_utf8(&exported).unwrap();
+    assert_eq!(body.matches(r#"<question type="matching">"#).count(), 1);
+    assert!(!body.contains(r#"<question type="multichoice">"#));
+    assert!(body.contains("<defaultgrade>5</defaultgrade>"));
+    assert_eq!(body.matches("<subquestion ").count(), 4);
+
+    let req = test::TestRequest::post()
+        .uri("/exam/import?format=moodle")
+        .set_payload(exported)
+        .to_request();
+    let reimported: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(reimported["report"]["imported"], 3);
+    let req = test::TestRequest::get()
+        .uri(&format!("/exam/{}", reimported["exam_id"]))
+        .to_request();
+    let exam: Value = test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(question_summary(&exam), question_summary(&original));
+    assert_eq!(
+        exam["sections"][0]["questions"][0]["text"],
+        "Match each word with its plural\nكتاب"
+    );
+}
+
+#[sqlx::test]
+async fn import_with_nothing_usable_is_rejected(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let xml = r#"<quiz>
+  <question type="essay">
+    <questiontext format="html"><text>Explain tajweed.</text></questiontext>
+  </question>
+</quiz>"#;
+
+    let req = test::TestRequest::post()
+        .uri("/exam/import?format=moodle")
+        .set_payload(xml)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let body: Value = test::read_body_json(resp).await;
+    assert!(body["exam_id"].is_null());
+    assert_eq!(body["report"]["skipped"], 1);
+
+    let req = test::TestRequest::post()
+        .uri("/exam/import?format=moodle")
+        .set_payload("<not-xml")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,260 @@
+mod common;
+
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+use regex::Regex;
+use serde_json::Value;
+use sqlx::PgPool;
+use std::time::{Duration, Instant};
+use wiremock::matchers::{body_string_contains, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use common::{llm, sample_exam, TestContext};
+
+/// Texts of the correct options of the sample exam's two questions.
+const CORRECT: [&str; 2] = ["الْعَالَمِينَ", "الدِّينِ"];
+
+/// Replies to PDF conversions of documents that contain `marker`.
+async fn pdf_renderer_responds(marker: &str, response: ResponseTemplate) {
+    Mock::given(method("POST"))
+        .and(path("/forms/chromium/convert/html"))
+        .and(body_string_contains(marker))
+        .respond_with(response)
+        .mount(llm::server().await)
+        .await;
+}
+
+/// Fetches a printed exam as text.
+macro_rules! print {
+    ($app:expr, $uri:expr) => {{
+        let req = test::TestRequest::get().uri($uri).to_request();
+        String::from_utf8(test::call_and_read_body($app, req).await.to_vec()).unwrap()
+    }};
+}
+
+/// Returns the (label, text) pairs of every printed option, in order.
+fn printed_options(paper: &str) -> Vec<(String, String)> {
+    let option = Regex::new(
+        r#"<span class="label">([^<]+)</span> <span class="option-text[^"]*" dir="auto">([^<]+)</span>"#,
+    )
+    .unwrap();
+    option
+        .captures_iter(paper)
+        .map(|c| (c[1].to_string(), c[2].to_string()))
+        .collect()
+}
+
+#[sqlx::test]
+async fn html_is_right_to_left_with_exam_header(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/exam/create")
+        .set_json(sample_exam(1))
+        .to_request();
+    test::call_service(&app, req).await;
+
+    let req = test::TestRequest::get().uri("/exam/1/print").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get(header::CONTENT_TYPE).unwrap(),
+        "text/html; charset=utf-8"
+    );
+    let html = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+
+    assert!(html.starts_with(r#"<!DOCTYPE html><html lang="ar" dir="rtl">"#));
+    assert!(!html.contains("<link") && !html.contains("<script"));
+    assert!(html.contains("<dt>المدة</dt><dd>30 دقيقة</dd>"));
+    assert!(html.contains("<dt>درجة النجاح</dt><dd>60</dd>"));
+    assert!(html.contains("<dt>مجموع الدرجات</dt><dd>8</dd>"));
+    assert!(html.contains(r#"<h1 dir="auto">Surah Al-Fatiha</h1>"#));
+    assert!(html.contains(
+        r#"<span class="question-text quran" dir="auto">الْحَمْدُ لِلَّهِ رَبِّ <span class="blank"></span></span>"#
+    ));
+    assert_eq!(html.matches(r#"<section class="paper">"#).count(), 1);
+    assert!(!html.contains(r#"<section class="answer-key">"#));
+    assert!(!html.contains(r#"class="variant""#));
+
+    let options: Vec<String> = printed_options(&html).into_iter().map(|(_, t)| t).collect();
+    assert_eq!(
+        options,
+        ["الْعَالَمِينَ", "الْعَالِمِينَ", "الدِّينِ", "الدَّيْنِ", "الْقِيَامَةِ"]
+    );
+}
+
+#[sqlx::test]
+async fn variants_have_their_own_answer_keys(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/exam/create")
+        .set_json(sample_exam(1))
+        .to_request();
+    test::call_service(&app, req).await;
+
+    let uri = "/exam/1/print?variants=3&answer_key=true";
+    let html = print!(&app, uri);
+    assert_eq!(html, print!(&app, uri), "reprints must match");
+
+    let (papers, _) = html.split_once(r#"<section class="answer-key">"#).unwrap();
+    let papers: Vec<&str> = papers.split(r#"<section class="paper">"#).skip(1).collect();
+    let keys: Vec<&str> = html
+        .split(r#"<section class="answer-key">"#)
+        .skip(1)
+        .collect();
+    assert_eq!(papers.len(), 3);
+    assert_eq!(keys.len(), 3);
+
+    let answer = Regex::new(r#"<td class="answer">([^<]+)</td>"#).unwrap();
+    let mut orders = Vec::new();
+    for (index, (paper, key)) in papers.iter().zip(&keys).enumerate() {
+        let variant = (b'A' + index as u8) as char;
+        assert!(paper.contains(&format!(
+            r#"<div class="variant">النموذج {}</div>"#,
+            variant
+        )));
+        assert!(key.contains(&format!("<h1>مفتاح الإجابة — النموذج {}</h1>", variant)));
+
+        let options = printed_options(paper);
+        let (first, second) = options.split_at(2);
+        let expected: Vec<String> = [first, second]
+            .iter()
+            .zip(CORRECT)
+            .map(|(question, correct)| {
+                question
+                    .iter()
+                    .find(|(_, text)| text == correct)
+                    .unwrap()
+                    .0
+                    .clone()
+            })
+            .collect();
+        let printed: Vec<String> = answer
+            .captures_iter(key)
+            .map(|c| c[1].to_string())
+            .collect();
+        assert_eq!(printed, expected, "answer key of paper {}", variant);
+
+        orders.push(options.into_iter().map(|(_, t)| t).collect::<Vec<_>>());
+    }
+    assert!(
+        orders.windows(2).any(|pair| pair[0] != pair[1]),
+        "variants should not all share one option order"
+    );
+
+    let html = print!(&app, "/exam/1/print?variants=2&shuffle=false");
+    let papers: Vec<&str> = html.split(r#"<section class="paper">"#).skip(1).collect();
+    assert_eq!(printed_options(papers[0]), printed_options(papers[1]));
+}
+
+#[sqlx::test]
+async fn out_of_range_variants_are_rejected(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    for variants in [0, 27] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/exam/1/print?variants={}", variants))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+}
+
+#[sqlx::test]
+async fn pdf_is_rendered_by_the_pdf_service(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let mut exam = sample_exam(1);
+    exam["description"]["title"] = Value::from("print-pdf-ok");
+    let req = test::TestRequest::post()
+        .uri("/exam/create")
+        .set_json(&exam)
+        .to_request();
+    test::call_service(&app, req).await;
+
+    pdf_renderer_responds(
+        "print-pdf-ok",
+        ResponseTemplate::new(200).set_body_bytes(b"%PDF-1.7 test".to_vec()),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/exam/1/print?format=pdf&answer_key=true")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get(header::CONTENT_TYPE).unwrap(),
+        "application/pdf"
+    );
+    assert_eq!(
+        resp.headers().get(header::CONTENT_DISPOSITION).unwrap(),
+        "attachment; filename=\"exam-1.pdf\""
+    );
+    assert_eq!(test::read_body(resp).await, "%PDF-1.7 test");
+
+    let requests = llm::server().await.received_requests().await.unwrap();
+    let sent = requests
+        .iter()
+        .map(|r| String::from_utf8_lossy(&r.body))
+        .find(|body| body.contains("print-pdf-ok"))
+        .unwrap();
+    assert!(sent.contains(r#"filename="index.html""#));
+    assert!(sent.contains("مفتاح الإجابة"));
+}
+
+#[sqlx::test]
+async fn pdf_service_failure_is_a_bad_gateway(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let mut exam = sample_exam(1);
+    exam["description"]["title"] = Value::from("print-pdf-down");
+    let req = test::TestRequest::post()
+        .uri("/exam/create")
+        .set_json(&exam)
+        .to_request();
+    test::call_service(&app, req).await;
+
+    pdf_renderer_responds("print-pdf-down", ResponseTemplate::new(503)).await;
+
+    let req = test::TestRequest::get()
+        .uri("/exam/1/print?format=pdf")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+}
+
+#[sqlx::test]
+async fn hanging_pdf_service_times_out_as_a_bad_gateway(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let mut exam = sample_exam(1);
+    exam["description"]["title"] = Value::from("print-pdf-hangs");
+    let req = test::TestRequest::post()
+        .uri("/exam/create")
+        .set_json(&exam)
+        .to_request();
+    test::call_service(&app, req).await;
+
+    // Longer than the test timeout of PDF_RENDER_TIMEOUT_SECS.
+    pdf_renderer_responds(
+        "print-pdf-hangs",
+        ResponseTemplate::new(200).set_delay(Duration::from_secs(5)),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/exam/1/print?format=pdf")
+        .to_request();
+    let started = Instant::now();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_GATEWAY);
+    assert!(started.elapsed() < Duration::from_secs(4));
+}
@@ -0,0 +1,254 @@
+mod common;
+
+use std::collections::HashMap;
+
+use actix_web::http::header::HeaderMap;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use common::{llm, TestContext};
+
+const THEMATIC: &str = "/prompts/templates?task=quranic_verse_distractor&distractor_type=thematic";
+
+fn header(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .map(|value| value.to_str().unwrap().to_string())
+}
+
+fn experiment(name: &str, variants: Value) -> Value {
+    json!({
+        "name": name,
+        "task": "quranic_verse_distractor",
+        "language": "arabic",
+        "distractor_type": "thematic",
+        "variants": variants
+    })
+}
+
+#[sqlx::test]
+async fn experiments_assign_users_stickily_and_report_outcomes(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::get().uri(THEMATIC).to_request();
+    let versions: Vec<Value> = test::call_and_read_body_json(&app, req).await;
+    let control = versions[0]["id"].as_i64().unwrap();
+    let req = test::TestRequest::put()
+        .uri(&format!("/prompts/templates/{}", control))
+        .set_json(json!({
+            "body": "TASK=thematic_v2 QUESTION={{ question }} ANSWER={{ correct_answer }}"
+        }))
+        .to_request();
+    let shorter: Value = test::call_and_read_body_json(&app, req).await;
+
+    let req = test::TestRequest::post()
+        .uri("/prompts/experiments")
+        .set_json(experiment(
+            "thematic-shorter",
+            json!([
+                { "name": "control", "template_id": control },
+                { "name": "shorter", "template_id": shorter["id"], "weight": 1 }
+            ]),
+        ))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let created: Value = test::read_body_json(resp).await;
+    assert_eq!(created["active"], true);
+    assert_eq!(
+        created["variants"][1]["prompt_version"],
+        "quranic_verse_distractor/arabic/thematic@2"
+    );
+    let experiment_id = created["id"].as_i64().unwrap();
+
+    let marker = "prompt-experiment-outcomes";
+    let output = json!({
+        "correct_answer": ["الرَّحِيمِ"],
+        "thematic_distractors": ["الْغَفُورِ", "الْكَرِيمِ", "الْعَظِيمِ"]
+    });
+    llm::respond_with_text(marker, &output.to_string()).await;
+    let generate = |user: &str| {
+        test::TestRequest::post()
+            .uri("/mcq/quran/thematic")
+            .insert_header(("Cache-Control", "no-store"))
+            .insert_header(("X-User-Id", user.to_string()))
+            .set_json(json!({
+                "question": format!("{} بِسْمِ اللَّهِ الرَّحْمَٰنِ ___", marker),
+                "correct_answer": "الرَّحِيمِ"
+            }))
+            .to_request()
+    };
+
+    // A user keeps the variant they were assigned.
+    let mut first_user = Vec::new();
+    for _ in 0..3 {
+        let resp = test::call_service(&app, generate("teacher-0")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        first_user.push(header(resp.headers(), "X-Prompt-Version").unwrap());
+    }
+    assert!(first_user.iter().all(|version| *version == first_user[0]));
+
+    // Across users, both variants are served, each generation recorded.
+    let mut generations: HashMap<String, Vec<String>> = HashMap::new();
+    for user in 0..16 {
+        let resp = test::call_service(&app, generate(&format!("teacher-{}", user))).await;
+        let version = header(resp.headers(), "X-Prompt-Version").unwrap();
+        let id = header(resp.headers(), "X-Generation-Id").expect("No generation ID");
+        generations.entry(version).or_default().push(id);
+    }
+    assert_eq!(generations.len(), 2);
+
+    let (version, ids) = generations.iter().next().unwrap();
+    let req = test::TestRequest::post()
+        .uri(&format!("/generations/{}/feedback", ids[0]))
+        .set_json(json!({ "accepted": ["الْغَفُورِ"], "rejected": ["الْكَرِيمِ"] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    for _ in 0..2 {
+        let req = test::TestRequest::post()
+            .uri(&format!("/generations/{}/selections", ids[0]))
+            .set_json(json!({
+                "responses": 10,
+                "selections": { "الْغَفُورِ": 3, "الْكَرِيمِ": 1 }
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/generations/{}/feedback", uuid::Uuid::new_v4()))
+        .set_json(json!({ "accepted": ["الْغَفُورِ"] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/prompts/experiments/{}/report", experiment_id))
+        .to_request();
+    let report: Value = test::call_and_read_body_json(&app, req).await;
+    let variants = report["variants"].as_array().unwrap();
+    assert_eq!(variants.len(), 2);
+    let total: i64 = variants
+        .iter()
+        .map(|v| v["generations"].as_i64().unwrap())
+        .sum();
+    assert_eq!(total, 19);
+    assert!(variants.iter().all(|v| v["parse_success_rate"] == 1.0));
+
+    let reviewed = variants
+        .iter()
+        .find(|v| v["prompt_version"] == version.as_str())
+        .unwrap();
+    let earlier = if *version == first_user[0] { 3 } else { 0 };
+    assert_eq!(reviewed["generations"], ids.len() + earlier);
+    assert_eq!(reviewed["acceptance_rate"], 0.5);
+    assert_eq!(reviewed["responses"], 40);
+    assert_eq!(reviewed["selections"], 8);
+    assert_eq!(reviewed["selection_rate"], 0.2);
+    let other = variants
+        .iter()
+        .find(|v| v["prompt_version"] != version.as_str())
+        .unwrap();
+    assert_eq!(other["acceptance_rate"], Value::Null);
+
+    // Once stopped, the active version is served and nothing is recorded.
+    let req = test::TestRequest::put()
+        .uri(&format!("/prompts/experiments/{}/stop", experiment_id))
+        .to_request();
+    let stopped: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(stopped["active"], false);
+    assert!(stopped["stopped_at"].is_string());
+
+    let resp = test::call_service(&app, generate("teacher-0")).await;
+    assert_eq!(
+        header(resp.headers(), "X-Prompt-Version").unwrap(),
+        "quranic_verse_distractor/arabic/thematic@1"
+    );
+    assert!(header(resp.headers(), "X-Generation-Id").is_none());
+}
+
+#[sqlx::test]
+async fn experiments_are_validated(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::get().uri(THEMATIC).to_request();
+    let versions: Vec<Value> = test::call_and_read_body_json(&app, req).await;
+    let control = versions[0]["id"].as_i64().unwrap();
+    let req = test::TestRequest::put()
+        .uri(&format!("/prompts/templates/{}", control))
+        .set_json(json!({ "body": "v2 {{ question }} {{ correct_answer }}" }))
+        .to_request();
+    let second: Value = test::call_and_read_body_json(&app, req).await;
+    let req = test::TestRequest::get()
+        .uri("/prompts/templates?task=context&language=urdu")
+        .to_request();
+    let context: Vec<Value> = test::call_and_read_body_json(&app, req).await;
+
+    let invalid = [
+        json!([{ "name": "control", "template_id": control }]),
+        json!([
+            { "name": "control", "template_id": control },
+            { "name": "control", "template_id": second["id"] }
+        ]),
+        json!([
+            { "name": "control", "template_id": control },
+            { "name": "other", "template_id": second["id"], "weight": 0 }
+        ]),
+        json!([
+            { "name": "control", "template_id": control },
+            { "name": "context", "template_id": context[0]["id"] }
+        ]),
+    ];
+    for variants in invalid {
+        let req = test::TestRequest::post()
+            .uri("/prompts/experiments")
+            .set_json(experiment("invalid", variants.clone()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(
+            resp.status(),
+            StatusCode::BAD_REQUEST,
+            "accepted {}",
+            variants
+        );
+    }
+
+    let variants = json!([
+        { "name": "control", "template_id": control },
+        { "name": "second", "template_id": second["id"] }
+    ]);
+    let req = test::TestRequest::post()
+        .uri("/prompts/experiments")
+        .set_json(experiment("first", variants.clone()))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    // One experiment runs per series at a time.
+    let req = test::TestRequest::post()
+        .uri("/prompts/experiments")
+        .set_json(experiment("second", variants))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    // A variant keeps its template version from being deleted.
+    let req = test::TestRequest::delete()
+        .uri(&format!("/prompts/templates/{}", second["id"]))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let req = test::TestRequest::get()
+        .uri("/prompts/experiments")
+        .to_request();
+    let experiments: Vec<Value> = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(experiments.len(), 1);
+    assert_eq!(experiments[0]["name"], "first");
+}
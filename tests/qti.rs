@@ -0,0 +1,286 @@
+mod common;
+
+use std::io::{Cursor, Read, Write};
+
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+use serde_json::Value;
+use sqlx::PgPool;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use common::{sample_exam, TestContext};
+
+fn read_entry(archive: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> String {
+    let mut content = String::new();
+    archive
+        .by_name(name)
+        .unwrap_or_else(|_| panic!("missing {}", name))
+        .read_to_string(&mut content)
+        .unwrap();
+    content
+}
+
+fn zip_files(files: &[(&str, &str)]) -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, content) in files {
+        zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+#[sqlx::test]
+async fn export_produces_a_qti_package(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/exam/create")
+        .set_json(sample_exam(1))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let req = test::TestRequest::get()
+        .uri("/exam/1/export?format=qti")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get(header::CONTENT_TYPE).unwrap(),
+        "application/zip"
+    );
+    assert!(resp
+        .headers()
+        .get(header::CONTENT_DISPOSITION)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .contains("exam-1-qti.zip"));
+
+    let bytes = test::read_body(resp).await.to_vec();
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+
+    let manifest = read_entry(&mut archive, "imsmanifest.xml");
+    assert!(manifest.contains(r#"type="imsqti_test_xmlv3p0""#));
+    assert_eq!(manifest.matches(r#"type="imsqti_item_xmlv3p0""#).count(), 2);
+
+    let test_xml = read_entry(&mut archive, "assessment-test.xml");
+    assert!(test_xml.contains(r#"<qti-time-limits max-time="1800"/>"#));
+    assert!(test_xml.contains(r#"<qti-test-part identifier="part-100""#));
+    assert!(test_xml.contains(r#"title="القسم الأول""#));
+
+    let item = read_entry(&mut archive, "items/item-1002.xml");
+    assert!(item.contains(r#"<qti-item-body dir="rtl">"#));
+    assert!(item.contains("<p>مَالِكِ يَوْمِ ___</p>"));
+    assert!(item.contains(
+        "<qti-correct-response>\n      <qti-value>choice-10021</qti-value>\n    </qti-correct-response>"
+    ));
+    assert!(item.contains(
+        r#"<qti-simple-choice identifier="choice-10023" dir="rtl">الْقِيَامَةِ</qti-simple-choice>"#
+    ));
+}
+
+#[sqlx::test]
+async fn exported_package_imports_as_an_identical_exam(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let original = sample_exam(1);
+
+    let req = test::TestRequest::post()
+        .uri("/exam/create")
+        .set_json(&original)
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let req = test::TestRequest::get()
+        .uri("/exam/1/export?format=qti")
+        .to_request();
+    let package = test::call_and_read_body(&app, req).await;
+
+    let req = test::TestRequest::post()
+        .uri("/exam/import?format=qti")
+        .set_payload(package)
+        .to_request();
+    let imported: Value = test::call_and_read_body_json(&app, req).await;
+    let imported_id = imported["exam_id"].as_i64().unwrap();
+    assert_ne!(imported_id, 1);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/exam/{}", imported_id))
+        .to_request();
+    let imported: Value = test::call_and_read_body_json(&app, req).await;
+
+    let description = &imported["description"];
+    assert_eq!(description["title"], original["description"]["title"]);
+    assert_eq!(
+        description["description"],
+        original["description"]["description"]
+    );
+    assert_eq!(description["duration"], 30);
+    assert_eq!(description["passing_score"], 60);
+
+    let section = &imported["sections"][0];
+    assert_eq!(section["title"], original["sections"][0]["title"]);
+
+    let questions = section["questions"].as_array().unwrap();
+    let expected = original["sections"][0]["questions"].as_array().unwrap();
+    assert_eq!(questions.len(), expected.len());
+
+    for (question, expected) in questions.iter().zip(expected) {
+        assert_eq!(question["text"], expected["text"]);
+        assert_eq!(question["marks"], expected["marks"]);
+        assert_eq!(
+            question["description"],
+            expected["description"].as_str().unwrap_or_default()
+        );
+
+        let options: Vec<(&Value, &Value)> = question["options"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|o| (&o["text"], &o["is_correct"]))
+            .collect();
+        let expected_options: Vec<(&Value, &Value)> = expected["options"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|o| (&o["text"], &o["is_correct"]))
+            .collect();
+        assert_eq!(options, expected_options);
+    }
+}
+
+#[sqlx::test]
+async fn import_resolves_items_through_the_manifest(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let manifest = r#"<?xml version="1.0" encoding="UTF-8"?>
+<manifest xmlns="http://www.imsglobal.org/xsd/qti/qtiv3p0/imscp_v1p1" identifier="m">
+  <resources>
+    <resource identifier="t" type="imsqti_test_xmlv3p0" href="tests/test.xml"/>
+  </resources>
+</manifest>"#;
+    let test_xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<qti-assessment-test xmlns="http://www.imsglobal.org/xsd/imsqtiasi_v3p0" identifier="t" title="Vocabulary &amp; grammar">
+  <qti-test-part identifier="p1" navigation-mode="linear" submission-mode="individual">
+    <qti-assessment-section identifier="s1" title="Nouns" visible="true">
+      <qti-assessment-item-ref identifier="i1" href="../items/noun.xml"/>
+    </qti-assessment-section>
+  </qti-test-part>
+</qti-assessment-test>"#;
+    let item = r#"<?xml version="1.0" encoding="UTF-8"?>
+<qti-assessment-item xmlns="http://www.imsglobal.org/xsd/imsqtiasi_v3p0" identifier="i1" title="Noun">
+  <qti-response-declaration identifier="RESPONSE" cardinality="multiple" base-type="identifier">
+    <qti-correct-response><qti-value>a</qti-value><qti-value>c</qti-value></qti-correct-response>
+  </qti-response-declaration>
+  <qti-item-body>
+    <p>Which of these are nouns?</p>
+    <qti-choice-interaction response-identifier="RESPONSE" max-choices="0">
+      <qti-simple-choice identifier="a">كِتَابٌ</qti-simple-choice>
+      <qti-simple-choice identifier="b">ذَهَبَ</qti-simple-choice>
+      <qti-simple-choice identifier="c">قَلَمٌ</qti-simple-choice>
+    </qti-choice-interaction>
+  </qti-item-body>
+</qti-assessment-item>"#;
+
+    let package = zip_files(&[
+        ("imsmanifest.xml", manifest),
+        ("tests/test.xml", test_xml),
+        ("items/noun.xml", item),
+    ]);
+
+    let req = test::TestRequest::post()
+        .uri("/exam/import?format=qti")
+        .set_payload(package)
+        .to_request();
+    let imported: Value = test::call_and_read_body_json(&app, req).await;
+    let imported_id = imported["exam_id"].as_i64().unwrap();
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/exam/{}", imported_id))
+        .to_request();
+    let imported: Value = test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(imported["description"]["title"], "Vocabulary & grammar");
+
+    let question = &imported["sections"][0]["questions"][0];
+    assert_eq!(question["text"], "Which of these are nouns?");
+    assert_eq!(question["marks"], 1);
+
+    let correct: Vec<&str> = question["options"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|o| o["is_correct"] == true)
+        .map(|o| o["text"].as_str().unwrap())
+        .collect();
+    assert_eq!(correct, ["كِتَابٌ", "قَلَمٌ"]);
+}
+
+#[sqlx::test]
+async fn import_rejects_invalid_packages(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let not_a_zip = b"definitely not a zip".to_vec();
+    let no_test = zip_files(&[("readme.txt", "nothing here")]);
+
+    for package in [not_a_zip, no_test] {
+        let req = test::TestRequest::post()
+            .uri("/exam/import?format=qti")
+            .set_payload(package)
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
+
+#[sqlx::test]
+async fn import_bounds_decompressed_size_and_nesting(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let manifest = r#"<manifest identifier="m">
+  <resources><resource identifier="t" type="imsqti_test_xmlv3p0" href="test.xml"/></resources>
+</manifest>"#;
+    // Compresses to a few kilobytes, decompresses past the entry limit.
+    let huge = format!(
+        "<qti-assessment-test>{}</qti-assessment-test>",
+        " ".repeat(40 * 1024 * 1024)
+    );
+    let deep = format!(
+        "<qti-assessment-test>{}{}</qti-assessment-test>",
+        "<div>".repeat(10_000),
+        "</div>".repeat(10_000)
+    );
+
+    for (test_xml, error) in [(huge, "too large"), (deep, "nested deeper")] {
+        let package = zip_files(&[("imsmanifest.xml", manifest), ("test.xml", &test_xml)]);
+        let req = test::TestRequest::post()
+            .uri("/exam/import?format=qti")
+            .set_payload(package)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body = test::read_body(resp).await;
+        assert!(String::from_utf8_lossy(&body).contains(error), "{:?}", body);
+    }
+}
+
+#[sqlx::test]
+async fn unknown_format_is_rejected(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::get()
+        .uri("/exam/1/export?format=docx")
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+}
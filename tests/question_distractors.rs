@@ -0,0 +1,133 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use common::{llm, TestContext};
+
+fn bank_question(text: &str) -> Value {
+    json!({
+        "text": text,
+        "description": null,
+        "marks": 1,
+        "options": [
+            { "text": "الرَّحِيمِ", "is_correct": true },
+            { "text": "الْكَرِيمِ", "is_correct": false }
+        ]
+    })
+}
+
+macro_rules! create_question {
+    ($app:expr, $body:expr) => {{
+        let req = test::TestRequest::post()
+            .uri("/bank/questions")
+            .set_json($body)
+            .to_request();
+        let resp = test::call_service(&$app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let created: Value = test::read_body_json(resp).await;
+        created["id"].as_i64().unwrap()
+    }};
+}
+
+macro_rules! attach {
+    ($app:expr, $question_id:expr, $body:expr) => {{
+        let req = test::TestRequest::post()
+            .uri(&format!("/bank/questions/{}/distractors", $question_id))
+            .set_json($body)
+            .to_request();
+        test::call_service(&$app, req).await
+    }};
+}
+
+#[sqlx::test]
+async fn generated_distractors_record_their_provenance(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "attach-provenance";
+    llm::respond_with_text(
+        marker,
+        &json!({
+            "correct_answer": ["الرَّحِيمِ"],
+            "thematic_distractors": ["الْكَرِيمِ", "الْعَظِيمِ", "الْحَكِيمِ", "الْعَلِيمِ"]
+        })
+        .to_string(),
+    )
+    .await;
+    let id = create_question!(
+        app,
+        bank_question(&format!("{} بِسْمِ اللَّهِ الرَّحْمَٰنِ ___", marker))
+    );
+
+    let resp = attach!(
+        app,
+        id,
+        json!({ "distractor_type": "thematic", "max_options": 2 })
+    );
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let question: Value = test::read_body_json(resp).await;
+
+    let options = question["options"].as_array().unwrap();
+    let texts: Vec<&str> = options
+        .iter()
+        .map(|o| o["text"].as_str().unwrap())
+        .collect();
+    // The option the question already had is not added twice.
+    assert_eq!(texts, ["الرَّحِيمِ", "الْكَرِيمِ", "الْعَظِيمِ", "الْحَكِيمِ"]);
+    assert!(options[..2].iter().all(|o| o.get("provenance").is_none()));
+    for option in &options[2..] {
+        assert_eq!(option["is_correct"], false);
+        let provenance = &option["provenance"];
+        assert_eq!(provenance["distractor_type"], "thematic");
+        assert_eq!(provenance["provider"], "Gemini");
+        assert_eq!(provenance["model"], llm::MODEL_NAME);
+        assert_eq!(
+            provenance["prompt_version"],
+            "quranic_verse_distractor/arabic/thematic@1"
+        );
+    }
+
+    // Saving the question back as read keeps the provenance.
+    let req = test::TestRequest::put()
+        .uri(&format!("/bank/questions/{}", id))
+        .set_json(json!({
+            "text": question["text"],
+            "description": question["description"],
+            "marks": question["marks"],
+            "options": question["options"],
+        }))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+    let req = test::TestRequest::get()
+        .uri(&format!("/bank/questions/{}", id))
+        .to_request();
+    let updated: Value = test::call_and_read_body_json(&app, req).await;
+    let provenance: Vec<&Value> = updated["options"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|o| &o["provenance"])
+        .collect();
+    assert_eq!(provenance[0], &Value::Null);
+    assert_eq!(provenance[2], &options[2]["provenance"]);
+    assert_eq!(provenance[3], &options[3]["provenance"]);
+}
+
+#[sqlx::test]
+async fn invalid_attach_requests_are_rejected(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let no_blank = create_question!(app, bank_question("attach-invalid بِسْمِ اللَّهِ"));
+    let thematic = json!({ "distractor_type": "thematic" });
+
+    let resp = attach!(app, 999_999, thematic.clone());
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let resp = attach!(app, "abc", thematic.clone());
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = attach!(app, no_blank, thematic);
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = attach!(app, no_blank, json!({ "distractor_type": "context" }));
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
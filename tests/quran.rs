@@ -0,0 +1,62 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use common::TestContext;
+
+#[sqlx::test]
+async fn verse_hit_returns_words(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    ctx.redis
+        .set("quran:1:2", r#"["ٱلْحَمْدُ","لِلَّهِ","رَبِّ","ٱلْعَٰلَمِينَ"]"#);
+
+    let req = test::TestRequest::post()
+        .uri("/quran/verse")
+        .set_json(json!({ "surah": 1, "verse": 2 }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(
+        body,
+        json!({
+            "text": ["ٱلْحَمْدُ", "لِلَّهِ", "رَبِّ", "ٱلْعَٰلَمِينَ"],
+            "mode": "indopak"
+        })
+    );
+}
+
+#[sqlx::test]
+async fn verse_miss_returns_not_found(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/quran/verse")
+        .set_json(json!({ "surah": 114, "verse": 7 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(test::read_body(resp).await, "Verse not found");
+}
+
+#[sqlx::test]
+async fn verse_with_corrupt_cache_entry_is_a_server_error(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    ctx.redis.set("quran:2:255", "not json");
+
+    let req = test::TestRequest::post()
+        .uri("/quran/verse")
+        .set_json(json!({ "surah": 2, "verse": 255 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
@@ -0,0 +1,157 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use wiremock::ResponseTemplate;
+
+use common::{llm, TestContext};
+
+fn request(marker: &str, distractor_types: Value) -> Value {
+    json!({
+        "question": format!("{} بِسْمِ اللَّهِ الرَّحْمَٰنِ ___", marker),
+        "correct_answer": "الرَّحِيمِ",
+        "distractor_types": distractor_types
+    })
+}
+
+#[sqlx::test]
+async fn types_are_generated_together_and_keyed_by_type(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let marker = "quran-distractors-together";
+    let output = json!({
+        "correct_answer": ["الرَّحِيمِ"],
+        "thematic_distractors": ["الْغَفُورِ", "الْكَرِيمِ", "الْعَظِيمِ"],
+        "diacritic_distractors": ["الرَّحِيمُ", "الرَّحِيمَ", "الرَّحْيمِ"]
+    });
+    llm::respond_with_text(marker, &output.to_string()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran")
+        .set_json(request(marker, json!(["thematic", "diacritic"])))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["failed"], 0);
+
+    let distractors = body["distractors"].as_object().unwrap();
+    assert_eq!(distractors.len(), 2);
+    let thematic = &distractors["thematic"];
+    assert_eq!(thematic["status"], 200);
+    assert_eq!(
+        thematic["prompt_version"],
+        "quranic_verse_distractor/arabic/thematic@1"
+    );
+    assert_eq!(
+        thematic["result"]["thematic_distractors"],
+        output["thematic_distractors"]
+    );
+    let diacritic = &distractors["diacritic"];
+    assert_eq!(
+        diacritic["result"]["diacritic_distractors"],
+        output["diacritic_distractors"]
+    );
+    assert!(diacritic.get("error").is_none());
+
+    let mut prompts = llm::received_prompts(marker).await;
+    prompts.sort();
+    assert_eq!(prompts.len(), 2);
+    assert!(prompts[0].starts_with("TASK=diacritic "));
+    assert!(prompts[1].starts_with("TASK=thematic "));
+}
+
+#[sqlx::test]
+async fn a_failing_type_does_not_fail_the_others(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    // Only thematic distractors can be generated in Turkish.
+    let req = test::TestRequest::post()
+        .uri("/prompts/templates")
+        .set_json(json!({
+            "task": "quranic_verse_distractor",
+            "language": "turkish",
+            "distractor_type": "thematic",
+            "body": "TASK=thematic_tr QUESTION={{ question }} ANSWER={{ correct_answer }}",
+            "activate": true
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let marker = "quran-distractors-partial";
+    let output = json!({
+        "correct_answer": ["الرَّحِيمِ"],
+        "thematic_distractors": ["الْغَفُورِ", "الْكَرِيمِ", "الْعَظِيمِ"]
+    });
+    llm::respond_with_text(marker, &output.to_string()).await;
+
+    let mut body = request(marker, json!(["diacritic", "thematic"]));
+    body["language"] = json!("tr");
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran")
+        .set_json(body)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["failed"], 1);
+    assert_eq!(body["distractors"]["thematic"]["status"], 200);
+    assert_eq!(
+        body["distractors"]["thematic"]["prompt_version"],
+        "quranic_verse_distractor/turkish/thematic@1"
+    );
+    let diacritic = &body["distractors"]["diacritic"];
+    assert_eq!(diacritic["status"], 400);
+    assert!(diacritic["error"].is_string());
+    assert!(diacritic.get("result").is_none());
+
+    assert_eq!(llm::received_prompts(marker).await.len(), 1);
+}
+
+#[sqlx::test]
+async fn failing_every_type_fails_the_request(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "quran-distractors-outage";
+    llm::respond_with(marker, ResponseTemplate::new(500)).await;
+
+    let req = test::TestRequest::post()
+        .uri("/mcq/quran")
+        .set_json(request(marker, json!(["thematic", "phonetic"])))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert!(resp.status().is_server_error());
+}
+
+#[sqlx::test]
+async fn invalid_type_lists_are_rejected(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let invalid = [
+        json!([]),
+        json!(["thematic", "thematic"]),
+        json!(["thematic", "unknown"]),
+    ];
+    for distractor_types in invalid {
+        let req = test::TestRequest::post()
+            .uri("/mcq/quran")
+            .set_json(request(
+                "quran-distractors-invalid",
+                distractor_types.clone(),
+            ))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(
+            resp.status(),
+            StatusCode::BAD_REQUEST,
+            "accepted {}",
+            distractor_types
+        );
+    }
+}
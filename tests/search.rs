@@ -0,0 +1,181 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use common::{sample_exam, TestContext};
+
+macro_rules! create_question {
+    ($app:expr, $text:expr, $options:expr) => {{
+        let options: Vec<Value> = $options
+            .iter()
+            .enumerate()
+            .map(|(i, text)| json!({ "text": text, "is_correct": i == 0 }))
+            .collect();
+        let req = test::TestRequest::post()
+            .uri("/bank/questions")
+            .set_json(json!({
+                "text": $text,
+                "description": null,
+                "marks": 1,
+                "options": options
+            }))
+            .to_request();
+        let created: Value = test::call_and_read_body_json(&$app, req).await;
+        created["id"].as_i64().unwrap()
+    }};
+}
+
+macro_rules! search {
+    ($app:expr, $q:expr) => {
+        search!($app, $q, "")
+    };
+    ($app:expr, $q:expr, $params:expr) => {{
+        let uri = format!("/search?q={}{}", urlencode($q), $params);
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        let body: Value = test::call_and_read_body_json(&$app, req).await;
+        body
+    }};
+}
+
+fn urlencode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn question_ids(results: &Value) -> Vec<i64> {
+    results["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["question_id"].as_i64().unwrap())
+        .collect()
+}
+
+#[sqlx::test]
+async fn search_ignores_tashkeel_and_shows_exam_context(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/exam/create")
+        .set_json(sample_exam(1))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let found = search!(app, "الحمد لله");
+    assert_eq!(found["total"], 1);
+    let hit = &found["results"][0];
+    assert_eq!(hit["question_id"], 1001);
+    assert_eq!(hit["score"], 1.0);
+    assert_eq!(hit["matches"][0]["field"], "text");
+    assert_eq!(
+        hit["matches"][0]["snippet"],
+        "<mark>الْحَمْدُ لِلَّهِ</mark> رَبِّ ___"
+    );
+    assert_eq!(
+        hit["used_in"],
+        json!([{
+            "exam_id": 1,
+            "exam_title": "Surah Al-Fatiha",
+            "section_id": 100,
+            "section_title": "القسم الأول"
+        }])
+    );
+
+    // Option text is searched too.
+    let found = search!(app, "العالمين");
+    assert_eq!(question_ids(&found), [1001]);
+    let matched = &found["results"][0]["matches"][0];
+    assert_eq!(matched["field"], "option");
+    assert_eq!(matched["option_id"], 10011);
+    assert_eq!(matched["snippet"], "<mark>الْعَالَمِينَ</mark>");
+}
+
+#[sqlx::test]
+async fn search_folds_letter_variants(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let iyyaka = create_question!(app, "إِيَّاكَ نَعْبُدُ ___", ["وَإِيَّاكَ", "نَسْتَعِينُ"]);
+    let salah = create_question!(app, "أقيموا الصلاة على وقتها", ["نعم", "لا"]);
+    let urdu = create_question!(app, "یہ کتاب کس کی ہے؟", ["قرآن", "حدیث"]);
+    let stretched = create_question!(app, "بســـــم الله", ["نعم", "لا"]);
+
+    assert_eq!(question_ids(&search!(app, "اياك نعبد")), [iyyaka]);
+    assert_eq!(question_ids(&search!(app, "الصلاه علي")), [salah]);
+    assert_eq!(question_ids(&search!(app, "كتاب")), [urdu]);
+    assert_eq!(question_ids(&search!(app, "بسم")), [stretched]);
+
+    let found = search!(app, "اياك");
+    assert_eq!(
+        found["results"][0]["matches"][0]["snippet"],
+        "<mark>إِيَّاكَ</mark> نَعْبُدُ ___"
+    );
+}
+
+#[sqlx::test]
+async fn fuzzy_matching_can_be_turned_off(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let id = create_question!(app, "اهْدِنَا الصِّرَاطَ ___", ["الْمُسْتَقِيمَ", "الْعَظِيمَ"]);
+
+    // Misspelt: ت instead of ط.
+    let found = search!(app, "الصرات");
+    assert_eq!(question_ids(&found), [id]);
+    let score = found["results"][0]["score"].as_f64().unwrap();
+    assert!(score > 0.0 && score < 1.0);
+    assert_eq!(
+        found["results"][0]["matches"][0]["snippet"],
+        "اهْدِنَا <mark>الصِّرَاطَ</mark> ___"
+    );
+
+    let found = search!(app, "الصرات", "&fuzzy=false");
+    assert_eq!(found["total"], 0);
+}
+
+#[sqlx::test]
+async fn long_texts_are_cut_around_the_match(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let filler = "كلمة ".repeat(60);
+    let text = format!("{}<b>الرحمن</b> {}", filler, filler);
+    create_question!(app, text, ["نعم", "لا"]);
+
+    let found = search!(app, "الرحمن");
+    let snippet = found["results"][0]["matches"][0]["snippet"]
+        .as_str()
+        .unwrap();
+    assert!(snippet.starts_with('…'));
+    assert!(snippet.ends_with('…'));
+    assert!(snippet.contains("&lt;b&gt;<mark>الرحمن</mark>&lt;/b&gt;"));
+    assert!(snippet.chars().count() < 200);
+}
+
+#[sqlx::test]
+async fn search_pages_and_rejects_empty_terms(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    for i in 0..3 {
+        create_question!(app, format!("سؤال رقم {}", i), ["نعم", "لا"]);
+    }
+
+    let found = search!(app, "سؤال", "&limit=2&offset=2");
+    assert_eq!(found["total"], 3);
+    assert_eq!(found["results"].as_array().unwrap().len(), 1);
+
+    let req = test::TestRequest::get().uri("/search?q=%20").to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+}
@@ -0,0 +1,203 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use wiremock::ResponseTemplate;
+
+use common::{llm, sample_exam, TestContext};
+
+const EXAM_ID: i32 = 4;
+const SECTION_ID: i32 = EXAM_ID * 100;
+const FIRST_QUESTION: i32 = EXAM_ID * 1000 + 1;
+const SECOND_QUESTION: i32 = EXAM_ID * 1000 + 2;
+
+/// The sample exam with `marker` in front of each question, so the mock
+/// LLM can tell the questions of different tests apart.
+fn exam(marker: &str) -> Value {
+    let mut exam = sample_exam(EXAM_ID);
+    for (index, question) in exam["sections"][0]["questions"]
+        .as_array_mut()
+        .unwrap()
+        .iter_mut()
+        .enumerate()
+    {
+        let text = question["text"].as_str().unwrap().to_string();
+        question["text"] = json!(format!("{}-q{} {}", marker, index + 1, text));
+    }
+    exam
+}
+
+fn thematic(answer: &str, distractors: &[&str]) -> String {
+    json!({
+        "correct_answer": [answer],
+        "thematic_distractors": distractors
+    })
+    .to_string()
+}
+
+/// Mocks both questions of the sample exam. The second one's distractors
+/// include an option the question already has.
+async fn mock_section(marker: &str) {
+    llm::respond_with_text(
+        &format!("{}-q1 ", marker),
+        &thematic("الْعَالَمِينَ", &["الْمُؤْمِنِينَ", "الصَّالِحِينَ", "الْمُتَّقِينَ"]),
+    )
+    .await;
+    llm::respond_with_text(
+        &format!("{}-q2 ", marker),
+        &thematic("الدِّينِ", &["الْقِيَامَةِ", "الْحِسَابِ", "الْجَزَاءِ"]),
+    )
+    .await;
+}
+
+macro_rules! create_exam {
+    ($app:expr, $exam:expr) => {{
+        let req = test::TestRequest::post()
+            .uri("/exam/create")
+            .set_json($exam)
+            .to_request();
+        assert!(test::call_service(&$app, req).await.status().is_success());
+    }};
+}
+
+macro_rules! generate {
+    ($app:expr, $query:expr, $body:expr) => {{
+        let req = test::TestRequest::post()
+            .uri(&format!(
+                "/exam/{}/sections/{}/generate-options{}",
+                EXAM_ID, SECTION_ID, $query
+            ))
+            .set_json($body)
+            .to_request();
+        test::call_service(&$app, req).await
+    }};
+}
+
+async fn options_of(pool: &PgPool, question_id: i32) -> Vec<(String, bool)> {
+    sqlx::query_as("SELECT text, is_correct FROM options WHERE question_id = $1 ORDER BY id")
+        .bind(question_id)
+        .fetch_all(pool)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn generated_options_are_added_to_every_question(pool: PgPool) {
+    let ctx = TestContext::new(pool.clone()).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "section-save";
+    create_exam!(app, exam(marker));
+    mock_section(marker).await;
+
+    let resp = generate!(app, "", json!({ "kind": "thematic", "max_options": 2 }));
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(
+        body,
+        json!({
+            "preview": false,
+            "inserted": 4,
+            "questions": [
+                { "question_id": FIRST_QUESTION, "options": ["الْمُؤْمِنِينَ", "الصَّالِحِينَ"] },
+                { "question_id": SECOND_QUESTION, "options": ["الْحِسَابِ", "الْجَزَاءِ"] }
+            ]
+        })
+    );
+
+    let second = options_of(&pool, SECOND_QUESTION).await;
+    assert_eq!(
+        second,
+        vec![
+            ("الدِّينِ".to_string(), true),
+            ("الدَّيْنِ".to_string(), false),
+            ("الْقِيَامَةِ".to_string(), false),
+            ("الْحِسَابِ".to_string(), false),
+            ("الْجَزَاءِ".to_string(), false),
+        ]
+    );
+    assert_eq!(options_of(&pool, FIRST_QUESTION).await.len(), 4);
+
+    let types: Vec<String> =
+        sqlx::query_scalar("SELECT distractor_type FROM option_provenance ORDER BY option_id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(types, vec!["thematic"; 4]);
+}
+
+#[sqlx::test]
+async fn preview_does_not_save(pool: PgPool) {
+    let ctx = TestContext::new(pool.clone()).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "section-preview";
+    create_exam!(app, exam(marker));
+    mock_section(marker).await;
+
+    let resp = generate!(app, "?preview=true", json!({ "kind": "thematic" }));
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["preview"], true);
+    assert_eq!(body["inserted"], 0);
+    assert_eq!(body["questions"][0]["options"].as_array().unwrap().len(), 3);
+    assert_eq!(body["questions"][1]["options"].as_array().unwrap().len(), 2);
+
+    assert_eq!(options_of(&pool, FIRST_QUESTION).await.len(), 2);
+    assert_eq!(options_of(&pool, SECOND_QUESTION).await.len(), 3);
+}
+
+#[sqlx::test]
+async fn failing_questions_are_reported(pool: PgPool) {
+    let ctx = TestContext::new(pool.clone()).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "section-partial";
+    let mut exam = exam(marker);
+    exam["sections"][0]["questions"][1]["text"] = json!(format!("{}-q2 مَالِكِ يَوْمِ", marker));
+    create_exam!(app, exam);
+    mock_section(marker).await;
+
+    let resp = generate!(app, "", json!({ "kind": "thematic" }));
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["inserted"], 3);
+    assert_eq!(body["questions"][1]["options"], json!([]));
+    assert_eq!(body["questions"][1]["error"], "Question has no ___ blank");
+    assert_eq!(options_of(&pool, SECOND_QUESTION).await.len(), 3);
+}
+
+#[sqlx::test]
+async fn llm_failure_fails_the_section(pool: PgPool) {
+    let ctx = TestContext::new(pool.clone()).await;
+    let app = test::init_service(ctx.app()).await;
+    let marker = "section-outage";
+    create_exam!(app, exam(marker));
+    llm::respond_with(marker, ResponseTemplate::new(500)).await;
+
+    let resp = generate!(app, "", json!({ "kind": "thematic" }));
+    assert!(resp.status().is_server_error());
+    assert_eq!(options_of(&pool, FIRST_QUESTION).await.len(), 2);
+}
+
+#[sqlx::test]
+async fn invalid_requests_are_rejected(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+    create_exam!(app, exam("section-invalid"));
+
+    let resp = generate!(app, "", json!({ "kind": "context" }));
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = generate!(app, "?candidates=0", json!({ "kind": "thematic" }));
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::post()
+        .uri(&format!(
+            "/exam/{}/sections/{}/generate-options",
+            EXAM_ID + 1,
+            SECTION_ID
+        ))
+        .set_json(json!({ "kind": "thematic" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
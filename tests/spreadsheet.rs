@@ -0,0 +1,360 @@
+mod common;
+
+use std::io::{Cursor, Write};
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::Value;
+use sqlx::PgPool;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use common::{sample_exam, TestContext};
+
+const QUESTIONS_CSV: &str = "\
+Section,Question,Description,Marks,Option A,Option B,Option C,Correct
+Vocabulary,What does كتاب mean?,,2,Book,Pen,House,A
+,What does قلم mean?,Pick one,,Book,Pen,House,2
+Grammar,Which are nouns?,,3,*رجل,*بيت,ذهب,
+Grammar,\"Complete: بِسْمِ اللَّهِ ___\",,1,الرَّحْمَٰنِ,الرَّحِيمِ,,الرَّحْمَٰنِ
+";
+
+async fn exam_count(pool: &PgPool) -> i64 {
+    sqlx::query_scalar("SELECT COUNT(*) FROM exams")
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+fn correct_options(question: &Value) -> Vec<&str> {
+    question["options"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|o| o["is_correct"] == true)
+        .map(|o| o["text"].as_str().unwrap())
+        .collect()
+}
+
+/// Builds a minimal XLSX workbook with one sheet of inline-string and
+/// numeric cells.
+fn xlsx(rows: &[&[&str]]) -> Vec<u8> {
+    let mut sheet = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8"?><worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>"#,
+    );
+    for (r, row) in rows.iter().enumerate() {
+        sheet.push_str(&format!(r#"<row r="{}">"#, r + 1));
+        for (c, cell) in row.iter().enumerate() {
+            let reference = format!("{}{}", (b'A' + c as u8) as char, r + 1);
+            if cell.is_empty() {
+                continue;
+            }
+            if cell.parse::<f64>().is_ok() {
+                sheet.push_str(&format!(r#"<c r="{}"><v>{}</v></c>"#, reference, cell));
+            } else {
+                sheet.push_str(&format!(
+                    r#"<c r="{}" t="inlineStr"><is><t>{}</t></is></c>"#,
+                    reference, cell
+                ));
+            }
+        }
+        sheet.push_str("</row>");
+    }
+    sheet.push_str("</sheetData></worksheet>");
+
+    let files = [
+        (
+            "[Content_Types].xml",
+            r#"<?xml version="1.0" encoding="UTF-8"?><Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/><Override PartName="/xl/worksheets/sheet1.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/></Types>"#.to_string(),
+        ),
+        (
+            "_rels/.rels",
+            r#"<?xml version="1.0" encoding="UTF-8"?><Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/></Relationships>"#.to_string(),
+        ),
+        (
+            "xl/workbook.xml",
+            r#"<?xml version="1.0" encoding="UTF-8"?><workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets><sheet name="Questions" sheetId="1" r:id="rId1"/></sheets></workbook>"#.to_string(),
+        ),
+        (
+            "xl/_rels/workbook.xml.rels",
+            r#"<?xml version="1.0" encoding="UTF-8"?><Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml"/></Relationships>"#.to_string(),
+        ),
+        ("xl/worksheets/sheet1.xml", sheet),
+    ];
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, content) in files {
+        zip.start_file(name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+#[sqlx::test]
+async fn csv_upload_creates_an_exam(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/exam/import/spreadsheet?format=csv&title=Week%201&duration=20&passing_score=50")
+        .set_payload(QUESTIONS_CSV)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let imported: Value = test::read_body_json(resp).await;
+
+    assert_eq!(imported["dry_run"], false);
+    assert_eq!(imported["rows"], 4);
+    assert_eq!(imported["questions"], 4);
+    assert!(imported["errors"].as_array().unwrap().is_empty());
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/exam/{}", imported["exam_id"]))
+        .to_request();
+    let exam: Value = test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(exam["description"]["title"], "Week 1");
+    assert_eq!(exam["description"]["duration"], 20);
+    assert_eq!(exam["description"]["passing_score"], 50);
+
+    let sections = exam["sections"].as_array().unwrap();
+    assert_eq!(sections.len(), 2);
+    assert_eq!(sections[0]["title"], "Vocabulary");
+    assert_eq!(sections[1]["title"], "Grammar");
+
+    let vocabulary = sections[0]["questions"].as_array().unwrap();
+    assert_eq!(vocabulary.len(), 2);
+    assert_eq!(vocabulary[0]["marks"], 2);
+    assert_eq!(correct_options(&vocabulary[0]), ["Book"]);
+    assert_eq!(vocabulary[1]["marks"], 1);
+    assert_eq!(vocabulary[1]["description"], "Pick one");
+    assert_eq!(correct_options(&vocabulary[1]), ["Pen"]);
+
+    let grammar = sections[1]["questions"].as_array().unwrap();
+    assert_eq!(correct_options(&grammar[0]), ["رجل", "بيت"]);
+    assert_eq!(grammar[1]["text"], "Complete: بِسْمِ اللَّهِ ___");
+    assert_eq!(grammar[1]["options"].as_array().unwrap().len(), 2);
+    assert_eq!(correct_options(&grammar[1]), ["الرَّحْمَٰنِ"]);
+}
+
+#[sqlx::test]
+async fn dry_run_validates_without_writing(pool: PgPool) {
+    let ctx = TestContext::new(pool.clone()).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let csv = "question;option 1;option 2;correct\nWhat is 2+2?;3;4;2\nWhat is 3+3?;6;7;1\n";
+    let req = test::TestRequest::post()
+        .uri("/exam/import/spreadsheet?format=csv&dry_run=true")
+        .set_payload(csv)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let preview: Value = test::read_body_json(resp).await;
+
+    assert_eq!(preview["dry_run"], true);
+    assert!(preview["exam_id"].is_null());
+    assert_eq!(preview["questions"], 2);
+    assert_eq!(preview["sections"][0]["title"], "Imported questions");
+    assert_eq!(preview["sections"][0]["questions"], 2);
+    assert_eq!(exam_count(&pool).await, 0);
+}
+
+#[sqlx::test]
+async fn invalid_rows_are_reported_and_nothing_is_written(pool: PgPool) {
+    let ctx = TestContext::new(pool.clone()).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let csv = "\
+Question,Marks,Option A,Option B,Option C,Correct
+Fine question,1,Yes,No,,A
+
+,2,Yes,No,,A
+Bad marks,two,Yes,No,,B
+One option,1,Only,,,A
+Bad marker,1,Yes,No,,D
+Nothing marked,1,Yes,No,Maybe,
+";
+    let req = test::TestRequest::post()
+        .uri("/exam/import/spreadsheet?format=csv")
+        .set_payload(csv)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+
+    assert!(body["exam_id"].is_null());
+    assert_eq!(body["rows"], 6);
+
+    let errors: Vec<(u64, Option<&str>)> = body["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| (e["row"].as_u64().unwrap(), e["column"].as_str()))
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            (4, Some("Question")),
+            (5, Some("Marks")),
+            (6, None),
+            (7, Some("Correct")),
+            (8, Some("Correct")),
+        ]
+    );
+    assert_eq!(exam_count(&pool).await, 0);
+
+    let req = test::TestRequest::post()
+        .uri("/exam/import/spreadsheet?format=csv")
+        .set_payload("Title,Body\nfoo,bar\n")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["errors"][0]["row"], 1);
+}
+
+#[sqlx::test]
+async fn large_uploads_keep_row_numbers_and_bound_decompression(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let mut csv = String::from("Question,Option A,Option B,Correct\n");
+    for index in 0..20_000 {
+        csv.push_str(&format!("Question {},Yes,No,A\n\n", index));
+    }
+    csv.push_str("Last question,Yes,No,C\n");
+    let req = test::TestRequest::post()
+        .uri("/exam/import/spreadsheet?format=csv&dry_run=true")
+        .set_payload(csv)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["rows"], 20_001);
+    assert_eq!(body["errors"][0]["row"], 40_002);
+
+    // Compresses to a few kilobytes, decompresses past the entry limit.
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("xl/worksheets/sheet1.xml", SimpleFileOptions::default())
+        .unwrap();
+    zip.write_all(" ".repeat(40 * 1024 * 1024).as_bytes())
+        .unwrap();
+    let workbook = zip.finish().unwrap().into_inner();
+
+    let req = test::TestRequest::post()
+        .uri("/exam/import/spreadsheet?format=xlsx")
+        .set_payload(workbook)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body = test::read_body(resp).await;
+    assert!(String::from_utf8_lossy(&body).contains("too large"));
+}
+
+#[sqlx::test]
+async fn xlsx_upload_creates_an_exam(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let workbook = xlsx(&[
+        &[
+            "Section", "Question", "Marks", "Option 1", "Option 2", "Correct",
+        ],
+        &["الفاتحة", "مَالِكِ يَوْمِ ___", "4", "الدِّينِ", "الدَّيْنِ", "1"],
+        &[
+            "",
+            "Which surah opens the Quran?",
+            "",
+            "Al-Fatiha",
+            "Al-Baqarah",
+            "A",
+        ],
+    ]);
+
+    let req = test::TestRequest::post()
+        .uri("/exam/import/spreadsheet?format=xlsx")
+        .set_payload(workbook)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let imported: Value = test::read_body_json(resp).await;
+    assert_eq!(imported["questions"], 2);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/exam/{}", imported["exam_id"]))
+        .to_request();
+    let exam: Value = test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(exam["description"]["title"], "Imported exam");
+    let section = &exam["sections"][0];
+    assert_eq!(section["title"], "الفاتحة");
+    assert_eq!(section["questions"][0]["marks"], 4);
+    assert_eq!(correct_options(&section["questions"][0]), ["الدِّينِ"]);
+    assert_eq!(correct_options(&section["questions"][1]), ["Al-Fatiha"]);
+
+    let req = test::TestRequest::post()
+        .uri("/exam/import/spreadsheet?format=xlsx")
+        .set_payload("not a workbook")
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[sqlx::test]
+async fn rows_are_appended_to_an_existing_exam(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/exam/create")
+        .set_json(sample_exam(1))
+        .to_request();
+    test::call_service(&app, req).await;
+
+    let csv = "\
+section,question,marks,option a,option b,correct
+القسم الأول,إِيَّاكَ نَعْبُدُ وَإِيَّاكَ ___,2,نَسْتَعِينُ,نَسْتَغْفِرُ,a
+Part two,How many verses are in Al-Fatiha?,1,7,6,7
+";
+
+    let req = test::TestRequest::post()
+        .uri("/exam/1/import/spreadsheet?format=csv&dry_run=true")
+        .set_payload(csv)
+        .to_request();
+    let preview: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(preview["exam_id"], 1);
+    assert_eq!(preview["sections"][0]["existing"], true);
+    assert_eq!(preview["sections"][1]["existing"], false);
+
+    let req = test::TestRequest::get().uri("/exam/1").to_request();
+    let exam: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(exam["sections"].as_array().unwrap().len(), 1);
+
+    let req = test::TestRequest::post()
+        .uri("/exam/1/import/spreadsheet?format=csv")
+        .set_payload(csv)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get().uri("/exam/1").to_request();
+    let exam: Value = test::call_and_read_body_json(&app, req).await;
+    let sections = exam["sections"].as_array().unwrap();
+    assert_eq!(sections.len(), 2);
+    assert_eq!(exam["description"]["title"], "Surah Al-Fatiha");
+
+    let first = sections[0]["questions"].as_array().unwrap();
+    assert_eq!(first.len(), 3);
+    assert_eq!(first[2]["text"], "إِيَّاكَ نَعْبُدُ وَإِيَّاكَ ___");
+    assert_eq!(correct_options(&first[2]), ["نَسْتَعِينُ"]);
+
+    assert_eq!(sections[1]["title"], "Part two");
+    assert_eq!(correct_options(&sections[1]["questions"][0]), ["7"]);
+
+    let req = test::TestRequest::post()
+        .uri("/exam/999/import/spreadsheet?format=csv")
+        .set_payload(csv)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,246 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use common::{sample_exam, TestContext};
+
+macro_rules! create_tag {
+    ($app:expr, $body:expr) => {{
+        let req = test::TestRequest::post()
+            .uri("/tags")
+            .set_json($body)
+            .to_request();
+        let resp = test::call_service(&$app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let tag: Value = test::read_body_json(resp).await;
+        tag["id"].as_i64().unwrap()
+    }};
+}
+
+macro_rules! create_question {
+    ($app:expr, $text:expr, $tags:expr) => {{
+        let req = test::TestRequest::post()
+            .uri("/bank/questions")
+            .set_json(json!({
+                "text": $text,
+                "description": null,
+                "marks": 1,
+                "options": [
+                    { "text": "أ", "is_correct": true },
+                    { "text": "ب", "is_correct": false }
+                ]
+            }))
+            .to_request();
+        let created: Value = test::call_and_read_body_json(&$app, req).await;
+        let id = created["id"].as_i64().unwrap();
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/bank/questions/{}/tags", id))
+            .set_json(json!({ "tag_ids": $tags }))
+            .to_request();
+        assert_eq!(test::call_service(&$app, req).await.status(), StatusCode::OK);
+        id
+    }};
+}
+
+macro_rules! search {
+    ($app:expr, $uri:expr) => {{
+        let req = test::TestRequest::get().uri($uri).to_request();
+        let body: Value = test::call_and_read_body_json(&$app, req).await;
+        body
+    }};
+}
+
+fn texts(page: &Value) -> Vec<String> {
+    let mut texts: Vec<String> = page["questions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|q| q["text"].as_str().unwrap().to_string())
+        .collect();
+    texts.sort();
+    texts
+}
+
+fn facet_count(page: &Value, tag_id: i64) -> Option<(i64, i64)> {
+    page["facets"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|f| f["tag_id"] == tag_id)
+        .map(|f| {
+            (
+                f["questions"].as_i64().unwrap(),
+                f["exams"].as_i64().unwrap(),
+            )
+        })
+}
+
+/// Looks up a tag that comes with the schema.
+macro_rules! seeded_tag {
+    ($app:expr, $facet:expr, $name:expr) => {{
+        let tags = search!($app, &format!("/tags?facet={}", $facet));
+        tags.as_array()
+            .unwrap()
+            .iter()
+            .find(|t| t["name"] == $name)
+            .unwrap()["id"]
+            .as_i64()
+            .unwrap()
+    }};
+}
+
+#[sqlx::test]
+async fn tags_form_a_tree_per_facet(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let baqarah = create_tag!(app, json!({ "facet": "quran", "name": "Al-Baqarah" }));
+    create_tag!(
+        app,
+        json!({ "facet": "quran", "name": "2:255", "parent_id": baqarah })
+    );
+
+    let tree = search!(app, "/tags?facet=quran");
+    assert_eq!(tree.as_array().unwrap().len(), 1);
+    assert_eq!(tree[0]["name"], "Al-Baqarah");
+    assert_eq!(tree[0]["facet"], "quran");
+    assert_eq!(tree[0]["children"][0]["name"], "2:255");
+    assert_eq!(tree[0]["children"][0]["parent_id"], baqarah);
+
+    // Difficulty and distractor types come with the schema.
+    let tree = search!(app, "/tags?facet=distractor_type");
+    assert_eq!(tree.as_array().unwrap().len(), 8);
+
+    let req = test::TestRequest::post()
+        .uri("/tags")
+        .set_json(json!({ "facet": "quran", "name": "Al-Baqarah" }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::CONFLICT
+    );
+
+    let req = test::TestRequest::post()
+        .uri("/tags")
+        .set_json(json!({ "facet": "topic", "name": "Ayat al-Kursi", "parent_id": baqarah }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    // Deleting a tag removes the tags nested under it.
+    let req = test::TestRequest::delete()
+        .uri(&format!("/tags/{}", baqarah))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    assert_eq!(search!(app, "/tags?facet=quran"), json!([]));
+}
+
+#[sqlx::test]
+async fn questions_are_filtered_by_facets(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let baqarah = create_tag!(app, json!({ "facet": "quran", "name": "Al-Baqarah" }));
+    let kursi = create_tag!(
+        app,
+        json!({ "facet": "quran", "name": "2:255", "parent_id": baqarah })
+    );
+    let fatiha = create_tag!(app, json!({ "facet": "quran", "name": "Al-Fatiha" }));
+    let diacritic = seeded_tag!(app, "distractor_type", "diacritic");
+    let phonetic = seeded_tag!(app, "distractor_type", "phonetic");
+
+    create_question!(app, "kursi diacritic", [kursi, diacritic]);
+    create_question!(app, "baqarah phonetic", [baqarah, phonetic]);
+    create_question!(app, "fatiha diacritic", [fatiha, diacritic]);
+
+    // A tag matches its descendants.
+    let page = search!(app, &format!("/bank/questions?tags={}", baqarah));
+    assert_eq!(texts(&page), ["baqarah phonetic", "kursi diacritic"]);
+
+    // Facets are AND-ed.
+    let page = search!(
+        app,
+        &format!("/bank/questions?tags={},{}", baqarah, diacritic)
+    );
+    assert_eq!(texts(&page), ["kursi diacritic"]);
+
+    // Tags of one facet are OR-ed.
+    let page = search!(app, &format!("/bank/questions?tags={},{}", baqarah, fatiha));
+    assert_eq!(page["total"], 3);
+
+    // Counts cover the matching questions, with parents counting children.
+    let page = search!(app, "/bank/questions");
+    assert_eq!(facet_count(&page, baqarah), Some((2, 0)));
+    assert_eq!(facet_count(&page, kursi), Some((1, 0)));
+    assert_eq!(facet_count(&page, diacritic), Some((2, 0)));
+
+    let page = search!(app, &format!("/bank/questions?tags={}", diacritic));
+    assert_eq!(facet_count(&page, baqarah), Some((1, 0)));
+    assert_eq!(facet_count(&page, phonetic), None);
+
+    let req = test::TestRequest::get()
+        .uri("/bank/questions?tags=999999")
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+    let req = test::TestRequest::get()
+        .uri("/bank/questions?tags=abc")
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[sqlx::test]
+async fn exams_are_found_through_question_tags(pool: PgPool) {
+    let ctx = TestContext::new(pool).await;
+    let app = test::init_service(ctx.app()).await;
+
+    let req = test::TestRequest::post()
+        .uri("/exam/create")
+        .set_json(sample_exam(1))
+        .to_request();
+    assert!(test::call_service(&app, req).await.status().is_success());
+
+    let baqarah = create_tag!(app, json!({ "facet": "quran", "name": "Al-Baqarah" }));
+    let hard = seeded_tag!(app, "difficulty", "hard");
+    let id = create_question!(app, "baqarah hard", [baqarah, hard]);
+    create_question!(app, "unused", [baqarah]);
+
+    let req = test::TestRequest::post()
+        .uri("/exam/1/sections/100/questions")
+        .set_json(json!({ "question_ids": [id] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let found = search!(app, &format!("/exam/search?tags={},{}", baqarah, hard));
+    assert_eq!(
+        found["exams"],
+        json!([{ "exam_id": 1, "title": "Surah Al-Fatiha", "questions": 1 }])
+    );
+    assert_eq!(facet_count(&found, baqarah), Some((1, 1)));
+
+    let found = search!(app, &format!("/exam/search?tags={}", baqarah));
+    assert_eq!(facet_count(&found, baqarah), Some((2, 1)));
+
+    // The bank question shows its tags; removing one updates the search.
+    let question = search!(app, &format!("/bank/questions/{}", id));
+    assert_eq!(question["tags"].as_array().unwrap().len(), 2);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/bank/questions/{}/tags/{}", id, hard))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let found = search!(app, &format!("/exam/search?tags={}", hard));
+    assert_eq!(found["exams"], json!([]));
+}